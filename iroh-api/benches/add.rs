@@ -10,7 +10,7 @@ use iroh_unixfs::{
 };
 use tokio::runtime::Runtime;
 
-use iroh_api::{Api, UnixfsConfig, UnixfsEntry};
+use iroh_api::{Api, CidVersion, MultihashCode, UnixfsConfig, UnixfsEntry};
 
 fn add_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("unixfs_add_file");
@@ -69,6 +69,13 @@ fn add_benchmark(c: &mut Criterion) {
                             UnixfsConfig {
                                 wrap: false,
                                 chunker: Some(ChunkerConfig::Fixed(DEFAULT_CHUNKS_SIZE)),
+                                layout: None,
+                                preserve_mode: false,
+                                preserve_mtime: false,
+                                cid_version: CidVersion::V1,
+                                hash: MultihashCode::Sha2_256,
+                                raw_leaves: true,
+                                inline_limit: None,
                             },
                         )
                         .await
@@ -1,25 +1,34 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use crate::config::{Config, CONFIG_FILE_NAME, ENV_PREFIX};
+use crate::error::map_service_error;
 use crate::IpfsPath;
 use crate::P2pApi;
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+use bytes::Bytes;
 use cid::Cid;
 use futures::stream::BoxStream;
-use futures::{StreamExt, TryStreamExt};
-use iroh_resolver::resolver::Resolver;
-use iroh_rpc_client::{Client, ClientStatus};
+use futures::{StreamExt, TryFutureExt, TryStreamExt};
+use iroh_car::{CarHeader, CarReader, CarWriter};
+use iroh_resolver::resolver::{OutContent, OutRaw, Resolver};
+use iroh_rpc_client::{Client, ClientStatus, StoreClient};
+use iroh_rpc_types::store::{GcResponse, PinType};
 use iroh_unixfs::{
     builder::Entry as UnixfsEntry,
-    content_loader::{FullLoader, FullLoaderConfig},
+    content_loader::{ContentLoader, FullLoader, FullLoaderConfig, StoreLoader},
+    parse_links, Block, Source,
 };
 use iroh_util::{iroh_config_path, make_config};
 use relative_path::RelativePathBuf;
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
 
-use crate::store::add_blocks_to_store;
+use crate::store::{add_blocks_to_store, Store};
+
+/// How many blocks of a CAR file are verified in parallel during an import.
+const IMPORT_PAR: usize = 8;
 
 /// API to interact with an iroh system.
 ///
@@ -40,6 +49,35 @@ pub enum OutType {
     Symlink(PathBuf),
 }
 
+/// The UnixFS metadata of an entry yielded by [`Api::get`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OutMetadata {
+    /// Unix permission bits.
+    pub mode: Option<u32>,
+    /// Modification time.
+    pub mtime: Option<SystemTime>,
+}
+
+/// Options for [`Api::export_car`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CarExportOptions {
+    /// Write the blocks in depth-first order and every block only once, so exporting the
+    /// same DAG always produces the same CAR file.
+    pub deterministic: bool,
+    /// Only use blocks from the local store, failing if any block of the DAG is missing
+    /// instead of fetching it from the network.
+    pub offline: bool,
+}
+
+/// Progress of [`Api::import_car`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarImportEvent {
+    /// A block was verified and added to the store.
+    Block(Cid),
+    /// A root of the CAR file was pinned recursively, after all blocks were added.
+    Pinned(Cid),
+}
+
 impl fmt::Debug for OutType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -109,6 +147,115 @@ impl Api {
         self.client.try_p2p()?.start_providing(&cid).await
     }
 
+    /// Pins the given [`Cid`], protecting it from garbage collection in the store.
+    ///
+    /// A recursive pin protects the entire DAG below the [`Cid`] and first fetches all of
+    /// its blocks, so they are available locally. A direct pin only fetches the root block.
+    /// Garbage collection is held off until the pin is in place.
+    pub async fn pin(&self, cid: Cid, recursive: bool) -> Result<()> {
+        let store = self.client.try_store()?;
+        let _guard = store
+            .gc_guard()
+            .await
+            .map_err(|e| map_service_error("store", e))?;
+        let root = IpfsPath::from_cid(cid);
+        // the loader only stores fetched blocks in the background, they are written here
+        // as well so they are all in the store before the root is pinned
+        if recursive {
+            let blocks = self.resolver.resolve_recursive_raw(root, None);
+            tokio::pin!(blocks);
+            while let Some(block) = blocks.next().await {
+                let block = block?;
+                if is_fetched(block.source()) {
+                    put_fetched(&store, *block.cid(), block.content().clone()).await?;
+                }
+            }
+        } else {
+            let out = self.resolver.resolve_raw(root).await?;
+            if is_fetched(&out.metadata().source) {
+                match out.content {
+                    OutContent::DagPb(_, data)
+                    | OutContent::DagCbor(_, data)
+                    | OutContent::DagJson(_, data)
+                    | OutContent::Raw(_, data) => put_fetched(&store, cid, data).await?,
+                    // not returned when resolving raw
+                    OutContent::Unixfs(_) => {}
+                }
+            }
+        }
+        store
+            .pin(cid, recursive)
+            .await
+            .map_err(|e| map_service_error("store", e))
+    }
+
+    /// Removes the direct or recursive pin for the given [`Cid`].
+    pub async fn unpin(&self, cid: Cid) -> Result<()> {
+        self.client
+            .try_store()?
+            .unpin(cid)
+            .await
+            .map_err(|e| map_service_error("store", e))
+    }
+
+    /// Lists the pinned [`Cid`]s, optionally restricted to a single [`PinType`].
+    pub async fn pins(&self, pin_type: Option<PinType>) -> Result<Vec<(Cid, PinType)>> {
+        self.client
+            .try_store()?
+            .list_pins(pin_type)
+            .await
+            .map_err(|e| map_service_error("store", e))
+    }
+
+    /// Deletes a single block from the store.
+    ///
+    /// Returns the size of the deleted block, or `None` if it was not stored.  Pinned blocks
+    /// can not be deleted.
+    pub async fn delete_block(&self, cid: Cid) -> Result<Option<u64>> {
+        Store::delete(&self.client, cid)
+            .await
+            .map_err(|e| map_service_error("store", e))
+    }
+
+    /// Deletes several blocks from the store in one batch.
+    ///
+    /// Returns the blocks which were actually deleted and their sizes.  If any of the blocks
+    /// is pinned nothing is deleted.
+    pub async fn delete_blocks(&self, cids: Vec<Cid>) -> Result<Vec<(Cid, u64)>> {
+        Store::delete_many(&self.client, cids)
+            .await
+            .map_err(|e| map_service_error("store", e))
+    }
+
+    /// Lists the blocks in the store, ordered by multihash, together with their sizes.
+    ///
+    /// Blocks can be filtered by `codec` and by a prefix of their multihash bytes.  Listing
+    /// resumes after the block `after` if given, which can be used as a pagination cursor.
+    pub async fn list_blocks(
+        &self,
+        codec: Option<u64>,
+        hash_prefix: Option<Vec<u8>>,
+        after: Option<Cid>,
+        limit: Option<u64>,
+    ) -> Result<BoxStream<'static, Result<(Cid, u64)>>> {
+        let blocks = self
+            .client
+            .try_store()?
+            .list_blocks(codec, hash_prefix.map(Into::into), after, limit)
+            .await
+            .map_err(|e| map_service_error("store", e))?;
+        Ok(blocks.map_err(|e| map_service_error("store", e)).boxed())
+    }
+
+    /// Runs garbage collection on the store, removing all blocks which are not pinned.
+    pub async fn gc(&self) -> Result<GcResponse> {
+        self.client
+            .try_store()?
+            .gc()
+            .await
+            .map_err(|e| map_service_error("store", e))
+    }
+
     pub fn p2p(&self) -> Result<P2pApi> {
         let p2p_client = self.client.try_p2p()?;
         Ok(P2pApi::new(p2p_client))
@@ -121,7 +268,7 @@ impl Api {
     pub fn get(
         &self,
         ipfs_path: &IpfsPath,
-    ) -> Result<BoxStream<'static, Result<(RelativePathBuf, OutType)>>> {
+    ) -> Result<BoxStream<'static, Result<(RelativePathBuf, OutType, OutMetadata)>>> {
         ensure!(
             ipfs_path.cid().is_some(),
             "IPFS path does not refer to a CID"
@@ -145,17 +292,21 @@ impl Api {
                     continue;
                 }
                 let relative_path = relative_path.strip_prefix(&sub_path).expect("should be a prefix").to_owned();
+                let metadata = OutMetadata {
+                    mode: out.metadata().mode,
+                    mtime: out.metadata().mtime,
+                };
                 if out.is_dir() {
-                    yield (relative_path, OutType::Dir);
+                    yield (relative_path, OutType::Dir, metadata);
                 } else if out.is_symlink() {
                     let mut reader = out.pretty(resolver.clone(), Default::default(), None)?;
                     let mut target = String::new();
                     reader.read_to_string(&mut target).await?;
                     let target = PathBuf::from(target);
-                    yield (relative_path, OutType::Symlink(target));
+                    yield (relative_path, OutType::Symlink(target), metadata);
                 } else {
                     let reader = out.pretty(resolver.clone(), Default::default(), None)?;
-                    yield (relative_path, OutType::Reader(Box::new(reader)));
+                    yield (relative_path, OutType::Reader(Box::new(reader)), metadata);
                 }
             }
         };
@@ -186,6 +337,7 @@ impl Api {
             UnixfsEntry::Symlink(s) => Box::pin(async_stream::try_stream! {
                 yield s.encode()?
             }),
+            UnixfsEntry::Link(_) => futures::stream::empty().boxed(),
         };
 
         Ok(Box::pin(
@@ -193,6 +345,146 @@ impl Api {
         ))
     }
 
+    /// Like [`Api::add_stream`], but also pins the root of the DAG recursively.
+    ///
+    /// Garbage collection is held off from before the first block is written until the
+    /// root is pinned, so none of the blocks can be removed in between. The root is pinned
+    /// after the last item, so the stream must be driven to completion.
+    pub async fn add_stream_pinned(
+        &self,
+        entry: UnixfsEntry,
+    ) -> Result<BoxStream<'static, Result<(Cid, u64)>>> {
+        let store = self.client.try_store()?;
+        let guard = store
+            .gc_guard()
+            .await
+            .map_err(|e| map_service_error("store", e))?;
+        let mut added = self.add_stream(entry).await?;
+        let stream = async_stream::try_stream! {
+            let _guard = guard;
+            let mut root = None;
+            while let Some(res) = added.next().await {
+                let (cid, size) = res?;
+                root = Some(cid);
+                yield (cid, size);
+            }
+            let root = root.context("No cid found")?;
+            store
+                .pin(root, true)
+                .await
+                .map_err(|e| map_service_error("store", e))?;
+        };
+
+        Ok(stream.boxed())
+    }
+
+    /// Imports the blocks of a CAR file into the store.
+    ///
+    /// The file is read as a stream, the hash of every block is verified against its
+    /// [`Cid`] and its links are extracted, before it is written to the store in batches.
+    /// With `pin_roots` the roots listed in the header of the CAR file are pinned
+    /// recursively once all blocks are imported, garbage collection is held off until then.
+    /// The returned stream reports the progress and must be driven to completion.
+    pub async fn import_car<R>(
+        &self,
+        reader: R,
+        pin_roots: bool,
+    ) -> Result<BoxStream<'static, Result<CarImportEvent>>>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let car = CarReader::new(reader).await?;
+        let roots = car.header().roots().to_vec();
+        let blocks = car
+            .stream()
+            .err_into::<anyhow::Error>()
+            .map(|block| {
+                tokio::task::spawn_blocking(move || {
+                    let (cid, data) = block?;
+                    verify_car_block(cid, Bytes::from(data))
+                })
+                .err_into::<anyhow::Error>()
+            })
+            .buffered(IMPORT_PAR)
+            .map(|block| block.and_then(|block| block))
+            .boxed();
+
+        let guard = if pin_roots {
+            let guard = self
+                .client
+                .try_store()?
+                .gc_guard()
+                .await
+                .map_err(|e| map_service_error("store", e))?;
+            Some(guard)
+        } else {
+            None
+        };
+        let client = self.client.clone();
+        let stream = async_stream::try_stream! {
+            let _guard = guard;
+            let added = add_blocks_to_store(Some(client.clone()), blocks).await;
+            tokio::pin!(added);
+            while let Some(res) = added.next().await {
+                let (cid, _) = res?;
+                yield CarImportEvent::Block(cid);
+            }
+
+            if pin_roots {
+                let store = client.try_store()?;
+                for root in roots {
+                    store
+                        .pin(root, true)
+                        .await
+                        .map_err(|e| map_service_error("store", e))?;
+                    yield CarImportEvent::Pinned(root);
+                }
+            }
+        };
+
+        Ok(stream.boxed())
+    }
+
+    /// Exports the DAG below `path` as a CAR file into `writer`.
+    ///
+    /// The resolved root of `path` is the single root of the CAR file. By default blocks
+    /// are written in the breadth-first order in which they are fetched, see
+    /// [`CarExportOptions`] for a reproducible order and for exporting without network
+    /// access. Returns the number of blocks written.
+    pub async fn export_car<W>(
+        &self,
+        path: &IpfsPath,
+        writer: W,
+        options: CarExportOptions,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Send + Unpin,
+    {
+        let blocks = if options.offline {
+            let resolver = Resolver::new(StoreLoader::new(self.client.clone()));
+            export_blocks(&resolver, path.clone(), options.deterministic)
+        } else {
+            export_blocks(&self.resolver, path.clone(), options.deterministic)
+        };
+        tokio::pin!(blocks);
+
+        let root = blocks
+            .next()
+            .await
+            .with_context(|| format!("failed to resolve {path}"))??;
+        let header = CarHeader::new_v1(vec![*root.cid()]);
+        let mut writer = CarWriter::new(header, writer);
+        writer.write(*root.cid(), root.content()).await?;
+        let mut count = 1;
+        while let Some(block) = blocks.next().await {
+            let block = block?;
+            writer.write(*block.cid(), block.content()).await?;
+            count += 1;
+        }
+        writer.finish().await?;
+        Ok(count)
+    }
+
     /// The `add` method encodes the entry into a DAG and adds the resulting
     /// blocks to the store.
     pub async fn add(&self, entry: UnixfsEntry) -> Result<Cid> {
@@ -204,3 +496,207 @@ impl Api {
             .context("No cid found")
     }
 }
+
+/// Streams the blocks of the DAG below `root`, starting with the root itself.
+fn export_blocks<T: ContentLoader>(
+    resolver: &Resolver<T>,
+    root: IpfsPath,
+    deterministic: bool,
+) -> BoxStream<'static, Result<OutRaw>> {
+    if deterministic {
+        resolver.resolve_recursive_raw_dfs(root, None).boxed()
+    } else {
+        resolver.resolve_recursive_raw(root, None).boxed()
+    }
+}
+
+/// Returns true if a block was loaded from the network rather than the store.
+fn is_fetched(source: &Source) -> bool {
+    matches!(source, Source::Bitswap | Source::Http(_))
+}
+
+/// Writes a block fetched from the network to the store.
+async fn put_fetched(store: &StoreClient, cid: Cid, data: Bytes) -> Result<()> {
+    let links = parse_links(&cid, &data).with_context(|| format!("invalid block {cid}"))?;
+    store
+        .put(cid, data, links)
+        .await
+        .map_err(|e| map_service_error("store", e))
+}
+
+/// Checks that the data of a block read from a CAR file matches its [`Cid`], and extracts
+/// its links.
+fn verify_car_block(cid: Cid, data: Bytes) -> Result<Block> {
+    match iroh_util::verify_hash(&cid, &data) {
+        Some(true) => {}
+        Some(false) => bail!("invalid hash for block {}", cid),
+        None => bail!("unsupported multihash for block {}", cid),
+    }
+    let links = parse_links(&cid, &data).with_context(|| format!("invalid block {cid}"))?;
+    Ok(Block::new(cid, data, links))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use cid::multihash::{Code, MultihashDigest};
+    use iroh_rpc_client::Config as RpcClientConfig;
+    use iroh_rpc_types::Addr;
+    use iroh_store::{Config as StoreConfig, Store as StoreService};
+    use iroh_unixfs::{builder::FileBuilder, codecs::Codec};
+    use tokio::task::JoinHandle;
+
+    use super::*;
+
+    async fn test_api() -> (Api, JoinHandle<()>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let server_addr = Addr::new_mem();
+        let client_addr = server_addr.clone();
+        let config = StoreConfig::with_rpc_addr(dir.path().join("db"), client_addr.clone());
+        let store = StoreService::create(config).await.unwrap();
+        let task = tokio::spawn(async move {
+            iroh_store::rpc::new(server_addr, store).await.unwrap();
+        });
+        let client = Client::new(RpcClientConfig {
+            store_addr: Some(client_addr),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let content_loader = FullLoader::new(
+            client.clone(),
+            FullLoaderConfig {
+                http_gateways: Vec::new(),
+                indexer: None,
+            },
+        )
+        .unwrap();
+        let api = Api::from_client_and_resolver(client, Resolver::new(content_loader));
+        (api, task, dir)
+    }
+
+    #[tokio::test]
+    async fn test_gc_during_add() {
+        let (api, task, _dir) = test_api().await;
+        let content: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let file = FileBuilder::new()
+            .name("data")
+            .fixed_chunker(64 * 1024)
+            .content_bytes(content)
+            .build()
+            .await
+            .unwrap();
+        let mut added = api
+            .add_stream_pinned(UnixfsEntry::File(file))
+            .await
+            .unwrap();
+
+        // the first batch of blocks is written, but nothing is pinned yet
+        let mut cids = Vec::new();
+        for _ in 0..32 {
+            let (cid, _) = added.next().await.unwrap().unwrap();
+            cids.push(cid);
+        }
+        let gc = tokio::spawn({
+            let api = api.clone();
+            async move { api.gc().await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!gc.is_finished(), "gc must wait for the add");
+
+        while let Some(res) = added.next().await {
+            let (cid, _) = res.unwrap();
+            cids.push(cid);
+        }
+        let stats = tokio::time::timeout(Duration::from_secs(5), gc)
+            .await
+            .expect("gc runs once the add is done")
+            .unwrap();
+        assert_eq!(stats.blocks, 0);
+
+        let root = *cids.last().unwrap();
+        assert_eq!(
+            api.pins(None).await.unwrap(),
+            vec![(root, PinType::Recursive)]
+        );
+        let store = api.client.try_store().unwrap();
+        for cid in cids {
+            assert!(store.has(cid).await.unwrap(), "{cid} was removed");
+        }
+
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_import_car() {
+        let (api, task, _dir) = test_api().await;
+        let content: Vec<u8> = (0..2 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let file = FileBuilder::new()
+            .name("data")
+            .fixed_chunker(64 * 1024)
+            .content_bytes(content)
+            .build()
+            .await
+            .unwrap();
+        let blocks: Vec<Block> = file.encode().await.unwrap().try_collect().await.unwrap();
+        let root = *blocks.last().unwrap().cid();
+        let mut writer = CarWriter::new(CarHeader::new_v1(vec![root]), Vec::new());
+        for block in &blocks {
+            writer.write(*block.cid(), block.data()).await.unwrap();
+        }
+        let car = writer.finish().await.unwrap();
+
+        let mut events = api
+            .import_car(std::io::Cursor::new(car), true)
+            .await
+            .unwrap();
+        // gc is started in the middle of the import and must not remove anything
+        let first = events.next().await.unwrap().unwrap();
+        assert_eq!(first, CarImportEvent::Block(*blocks[0].cid()));
+        let gc = tokio::spawn({
+            let api = api.clone();
+            async move { api.gc().await.unwrap() }
+        });
+        let events: Vec<_> = events.try_collect().await.unwrap();
+        assert_eq!(events.len(), blocks.len());
+        assert_eq!(events.last(), Some(&CarImportEvent::Pinned(root)));
+        let stats = tokio::time::timeout(Duration::from_secs(5), gc)
+            .await
+            .expect("gc runs once the import is done")
+            .unwrap();
+        assert_eq!(stats.blocks, 0);
+
+        assert_eq!(
+            api.pins(None).await.unwrap(),
+            vec![(root, PinType::Recursive)]
+        );
+        let store = api.client.try_store().unwrap();
+        for block in &blocks {
+            assert!(store.has(*block.cid()).await.unwrap());
+        }
+        let mut out = Vec::new();
+        let options = CarExportOptions {
+            deterministic: true,
+            offline: true,
+        };
+        let count = api
+            .export_car(&IpfsPath::from_cid(root), &mut out, options)
+            .await
+            .unwrap();
+        assert_eq!(count as usize, blocks.len());
+
+        task.abort();
+    }
+
+    #[test]
+    fn test_verify_car_block() {
+        let data = Bytes::from_static(b"hello");
+        let cid = Cid::new_v1(Codec::Raw as _, Code::Sha2_256.digest(&data));
+        let block = verify_car_block(cid, data).unwrap();
+        assert!(block.links().is_empty());
+
+        let err = verify_car_block(cid, Bytes::from_static(b"world")).unwrap_err();
+        assert!(err.to_string().contains("invalid hash"));
+    }
+}
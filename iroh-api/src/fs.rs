@@ -4,12 +4,12 @@ use anyhow::{anyhow, ensure, Result};
 use futures::{Stream, StreamExt};
 use relative_path::RelativePathBuf;
 
-use crate::{IpfsPath, OutType};
+use crate::{IpfsPath, OutMetadata, OutType};
 
 /// Takes a stream of blocks as from `get` and writes it to the filesystem.
 pub async fn write_get_stream(
     ipfs_path: &IpfsPath,
-    blocks: impl Stream<Item = Result<(RelativePathBuf, OutType)>>,
+    blocks: impl Stream<Item = Result<(RelativePathBuf, OutType, OutMetadata)>>,
     output_path: Option<&Path>,
 ) -> Result<PathBuf> {
     let root_path = get_root_path(ipfs_path, output_path)
@@ -26,38 +26,75 @@ pub async fn write_get_stream(
 
 async fn save_get_stream(
     root_path: &Path,
-    blocks: impl Stream<Item = Result<(RelativePathBuf, OutType)>>,
+    blocks: impl Stream<Item = Result<(RelativePathBuf, OutType, OutMetadata)>>,
 ) -> Result<()> {
+    // The metadata of directories is applied once all of their entries are written,
+    // as writing the entries changes the modification time.
+    let mut dirs = Vec::new();
     tokio::pin!(blocks);
     while let Some(block) = blocks.next().await {
-        let (path, out) = block?;
+        let (path, out, metadata) = block?;
         let full_path = path.to_path(root_path);
         match out {
             OutType::Dir => {
-                tokio::fs::create_dir_all(full_path).await?;
+                tokio::fs::create_dir_all(&full_path).await?;
+                dirs.push((full_path, metadata));
             }
             OutType::Reader(mut reader) => {
                 if let Some(parent) = path.parent() {
                     tokio::fs::create_dir_all(parent.to_path(root_path)).await?;
                 }
-                let mut f = tokio::fs::File::create(full_path).await?;
+                let mut f = tokio::fs::File::create(&full_path).await?;
                 tokio::io::copy(&mut reader, &mut f).await?;
+                drop(f);
+                apply_metadata(&full_path, metadata).await?;
             }
             OutType::Symlink(target) => {
                 if let Some(parent) = path.parent() {
                     tokio::fs::create_dir_all(parent.to_path(root_path)).await?;
                 }
                 #[cfg(windows)]
-                tokio::task::spawn_blocking(move || {
-                    make_windows_symlink(target, full_path).map_err(|e| anyhow::anyhow!(e))
-                })
-                .await??;
+                {
+                    let full_path = full_path.clone();
+                    tokio::task::spawn_blocking(move || {
+                        make_windows_symlink(target, full_path).map_err(|e| anyhow::anyhow!(e))
+                    })
+                    .await??;
+                }
 
                 #[cfg(unix)]
-                tokio::fs::symlink(target, full_path).await?;
+                tokio::fs::symlink(target, &full_path).await?;
+
+                // the permissions of symlinks are meaningless, only restore the mtime
+                if let Some(mtime) = metadata.mtime {
+                    let mtime = filetime::FileTime::from_system_time(mtime);
+                    tokio::task::spawn_blocking(move || {
+                        filetime::set_symlink_file_times(full_path, mtime, mtime)
+                    })
+                    .await??;
+                }
             }
         }
     }
+    // children before their parents
+    for (path, metadata) in dirs.into_iter().rev() {
+        apply_metadata(&path, metadata).await?;
+    }
+    Ok(())
+}
+
+/// Sets the permissions and modification time of the file or directory at `path`.
+async fn apply_metadata(path: &Path, metadata: OutMetadata) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = metadata.mode {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+    }
+    if let Some(mtime) = metadata.mtime {
+        let path = path.to_path_buf();
+        let mtime = filetime::FileTime::from_system_time(mtime);
+        tokio::task::spawn_blocking(move || filetime::set_file_mtime(path, mtime)).await??;
+    }
     Ok(())
 }
 
@@ -88,19 +125,26 @@ fn get_root_path(ipfs_path: &IpfsPath, output_path: Option<&Path>) -> Option<Pat
 mod tests {
     use super::*;
     use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
     use tempfile::TempDir;
 
     #[tokio::test]
     async fn test_save_get_stream() {
         let stream = Box::pin(futures::stream::iter(vec![
-            Ok((RelativePathBuf::from_path("a").unwrap(), OutType::Dir)),
+            Ok((
+                RelativePathBuf::from_path("a").unwrap(),
+                OutType::Dir,
+                OutMetadata::default(),
+            )),
             Ok((
                 RelativePathBuf::from_path("a/c").unwrap(),
                 OutType::Symlink(PathBuf::from("../b")),
+                OutMetadata::default(),
             )),
             Ok((
                 RelativePathBuf::from_path("b").unwrap(),
                 OutType::Reader(Box::new(std::io::Cursor::new("hello"))),
+                OutMetadata::default(),
             )),
         ]));
         let tmp_dir = TempDir::new().unwrap().path().join("test_save_get_stream");
@@ -114,6 +158,45 @@ mod tests {
         assert_eq!(std::fs::read_to_string(tmp_dir.join("b")).unwrap(), "hello");
     }
 
+    #[tokio::test]
+    async fn test_save_get_stream_metadata() {
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let metadata = OutMetadata {
+            mode: Some(0o750),
+            mtime: Some(mtime),
+        };
+        let stream = Box::pin(futures::stream::iter(vec![
+            Ok((
+                RelativePathBuf::from_path("a").unwrap(),
+                OutType::Dir,
+                metadata,
+            )),
+            Ok((
+                RelativePathBuf::from_path("a/b").unwrap(),
+                OutType::Reader(Box::new(std::io::Cursor::new("hello"))),
+                OutMetadata {
+                    mode: Some(0o640),
+                    mtime: Some(mtime),
+                },
+            )),
+        ]));
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().join("test_save_get_stream_metadata");
+        save_get_stream(&root, stream).await.unwrap();
+
+        for (path, mode) in [("a", 0o750), ("a/b", 0o640)] {
+            let meta = std::fs::metadata(root.join(path)).unwrap();
+            assert_eq!(meta.modified().unwrap(), mtime);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(meta.permissions().mode() & 0o7777, mode);
+            }
+            #[cfg(not(unix))]
+            let _ = mode;
+        }
+    }
+
     #[test]
     fn test_get_root_path() {
         let ipfs_path =
@@ -1,17 +1,21 @@
 pub use crate::api::Api;
-pub use crate::api::OutType;
+pub use crate::api::{CarExportOptions, CarImportEvent, OutMetadata, OutType};
 pub use crate::config::Config;
 pub use crate::error::ApiError;
 pub use crate::p2p::P2p as P2pApi;
 pub use crate::p2p::PeerIdOrAddr;
 pub use bytes::Bytes;
-pub use cid::Cid;
+pub use cid::multihash::Code as MultihashCode;
+pub use cid::{Cid, Version as CidVersion};
 pub use iroh_resolver::resolver::Path as IpfsPath;
 pub use iroh_rpc_client::{ClientStatus, Lookup, ServiceStatus, ServiceType, StatusType};
+pub use iroh_rpc_types::store::{GcResponse as GcStats, PinType};
+pub use iroh_unixfs::balanced_tree::TreeBuilder;
 pub use iroh_unixfs::builder::{
     Config as UnixfsConfig, DirectoryBuilder, Entry as UnixfsEntry, FileBuilder, SymlinkBuilder,
 };
 pub use iroh_unixfs::chunker::{ChunkerConfig, DEFAULT_CHUNKS_SIZE};
+pub use iroh_unixfs::unixfs::{parse_hash, CidBuilder, DEFAULT_INLINE_LIMIT};
 pub use iroh_unixfs::Block;
 pub use libp2p::gossipsub::MessageId;
 pub use libp2p::{Multiaddr, PeerId};
@@ -1,5 +1,6 @@
 use crate::error::map_service_error;
 use anyhow::Result;
+use cid::Cid;
 use iroh_rpc_client::{Lookup, P2pClient};
 use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
 use std::collections::HashMap;
@@ -52,6 +53,16 @@ impl P2p {
             .await
             .map_err(|e| map_service_error("p2p", e))
     }
+
+    /// Publishes `cid` under an IPNS name, the peer id of the keychain key named `key`.
+    ///
+    /// Without a key the node's own identity is used.
+    pub async fn publish_name(&self, cid: Cid, key: Option<String>) -> Result<PeerId> {
+        self.client
+            .publish_name(cid, key)
+            .await
+            .map_err(|e| map_service_error("p2p", e))
+    }
 }
 
 fn peer_id_from_multiaddr(addr: &Multiaddr) -> Result<PeerId> {
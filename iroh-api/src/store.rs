@@ -17,6 +17,10 @@ pub trait Store: 'static + Send + Sync + Clone {
     async fn has(&self, &cid: Cid) -> Result<bool>;
     async fn put(&self, cid: Cid, blob: Bytes, links: Vec<Cid>) -> Result<()>;
     async fn put_many(&self, blocks: Vec<Block>) -> Result<()>;
+    /// Deletes a block, returning its size if it was stored.
+    async fn delete(&self, cid: Cid) -> Result<Option<u64>>;
+    /// Deletes several blocks, returning the blocks actually deleted and their sizes.
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<Vec<(Cid, u64)>>;
 }
 
 #[async_trait]
@@ -34,6 +38,14 @@ impl Store for Client {
             .put_many(blocks.into_iter().map(|x| x.into_parts()).collect())
             .await
     }
+
+    async fn delete(&self, cid: Cid) -> Result<Option<u64>> {
+        self.try_store()?.delete(cid).await
+    }
+
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<Vec<(Cid, u64)>> {
+        self.try_store()?.delete_many(cids).await
+    }
 }
 
 #[async_trait]
@@ -53,6 +65,20 @@ impl Store for Arc<tokio::sync::Mutex<std::collections::HashMap<Cid, Bytes>>> {
         }
         Ok(())
     }
+
+    async fn delete(&self, cid: Cid) -> Result<Option<u64>> {
+        let removed = self.lock().await.remove(&cid);
+        Ok(removed.map(|blob| blob.len() as u64))
+    }
+
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<Vec<(Cid, u64)>> {
+        let mut this = self.lock().await;
+        let deleted = cids
+            .into_iter()
+            .filter_map(|cid| this.remove(&cid).map(|blob| (cid, blob.len() as u64)))
+            .collect();
+        Ok(deleted)
+    }
 }
 
 fn add_blocks_to_store_chunked<S: Store>(
@@ -2,9 +2,11 @@ use std::{ops::Deref, pin::Pin, sync::Arc, time::Duration};
 
 use ahash::AHashSet;
 use anyhow::{anyhow, ensure, Result};
+use bytes::Bytes;
 use cid::Cid;
 use futures::{future, stream, StreamExt};
 use iroh_metrics::{bitswap::BitswapMetrics, core::MRecorder, inc, record};
+use iroh_util::inline_data;
 use libp2p::PeerId;
 use tokio::{
     sync::oneshot,
@@ -299,13 +301,33 @@ impl Session {
         ensure!(!keys.is_empty(), "missing keys");
         debug!("get blocks: {:?}", keys);
 
+        // blocks inlined into identity hashed cids are never requested from the network
+        let (inline_keys, keys): (Vec<Cid>, Vec<Cid>) =
+            keys.iter().partition(|key| inline_data(key).is_some());
+        let inline_blocks: Vec<Block> = inline_keys
+            .into_iter()
+            .filter_map(|key| {
+                let data = Bytes::copy_from_slice(inline_data(&key)?);
+                Some(Block::new(data, key))
+            })
+            .collect();
+
         let (s, r) = async_channel::bounded(8);
         let mut remaining: AHashSet<Cid> = keys.iter().copied().collect();
         let mut block_channel = self.inner.notify.new_receiver();
         let incoming = self.inner.incoming.clone();
         let (closer_s, mut closer_r) = oneshot::channel();
         let worker = tokio::task::spawn(async move {
-            loop {
+            let mut closed = false;
+            for block in inline_blocks {
+                if s.send(block).await.is_err() {
+                    // receiver dropped, shutdown
+                    closed = true;
+                    break;
+                }
+            }
+
+            while !closed && !remaining.is_empty() {
                 inc!(BitswapMetrics::SessionGetBlockLoopTick);
                 tokio::select! {
                     biased;
@@ -358,7 +380,9 @@ impl Session {
             }
         });
 
-        self.inner.incoming.send(Op::Want(keys.to_vec())).await?;
+        if !keys.is_empty() {
+            self.inner.incoming.send(Op::Want(keys)).await?;
+        }
 
         Ok(BlockReceiver {
             receiver: r,
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
 /// Car utility error
@@ -13,6 +15,8 @@ pub enum Error {
     Cbor(#[from] ipld::error::Error),
     #[error("ld read too large {0}")]
     LdReadTooLarge(usize),
+    #[error("Failed to open CAR file {}: {1}", .0.display())]
+    Open(PathBuf, Box<Error>),
 }
 
 impl From<cid::Error> for Error {
@@ -4,11 +4,21 @@ use ipld_cbor::DagCborCodec;
 
 use crate::error::Error;
 
+/// The fixed first bytes of a CARv2 file, a length prefixed CARv1 header with only
+/// `version: 2`, so that CARv1 readers fail early.
+pub const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Size of the fixed CARv2 header, following the pragma.
+pub const CARV2_HEADER_SIZE: usize = 40;
+
 /// A car header.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum CarHeader {
     V1(CarHeaderV1),
+    V2(CarHeaderV2),
 }
 
 impl CarHeader {
@@ -16,6 +26,7 @@ impl CarHeader {
         Self::V1(roots.into())
     }
 
+    /// Decodes a CARv1 header, or the header of the CARv1 data inside a CARv2 file.
     pub fn decode(buffer: &[u8]) -> Result<Self, Error> {
         let header: CarHeaderV1 = DagCborCodec
             .decode(buffer)
@@ -26,32 +37,36 @@ impl CarHeader {
         }
 
         if header.version != 1 {
-            return Err(Error::InvalidFile(
-                "Only CAR file version 1 is supported".to_string(),
-            ));
+            return Err(Error::InvalidFile(format!(
+                "unsupported CAR file version {}",
+                header.version
+            )));
         }
 
         Ok(CarHeader::V1(header))
     }
 
+    /// Encodes the CARv1 header. For CARv2 this is the header of the inner CARv1 data.
     pub fn encode(&self) -> Result<Vec<u8>, Error> {
-        match self {
-            CarHeader::V1(ref header) => {
-                let res = DagCborCodec.encode(header)?;
-                Ok(res)
-            }
-        }
+        let res = DagCborCodec.encode(self.inner())?;
+        Ok(res)
     }
 
     pub fn roots(&self) -> &[Cid] {
-        match self {
-            CarHeader::V1(header) => &header.roots,
-        }
+        &self.inner().roots
     }
 
     pub fn version(&self) -> u64 {
         match self {
             CarHeader::V1(_) => 1,
+            CarHeader::V2(_) => 2,
+        }
+    }
+
+    fn inner(&self) -> &CarHeaderV1 {
+        match self {
+            CarHeader::V1(header) => header,
+            CarHeader::V2(header) => &header.inner,
         }
     }
 }
@@ -78,6 +93,101 @@ impl From<Vec<Cid>> for CarHeaderV1 {
     }
 }
 
+/// CAR file header version 2.
+///
+/// The CARv1 data of the file is found at `data_offset`, and is followed by an optional
+/// index at `index_offset`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CarHeaderV2 {
+    pub characteristics: Characteristics,
+    /// Offset of the CARv1 data from the start of the file.
+    pub data_offset: u64,
+    /// Size of the CARv1 data in bytes.
+    pub data_size: u64,
+    /// Offset of the index from the start of the file, `0` if there is no index.
+    pub index_offset: u64,
+    /// Header of the inner CARv1 data.
+    pub inner: CarHeaderV1,
+}
+
+impl CarHeaderV2 {
+    /// Encodes the fixed size part of the header, which directly follows the pragma.
+    pub fn encode_fixed(&self) -> [u8; CARV2_HEADER_SIZE] {
+        let mut buffer = [0u8; CARV2_HEADER_SIZE];
+        buffer[..16].copy_from_slice(&self.characteristics.encode());
+        buffer[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        buffer[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        buffer[32..].copy_from_slice(&self.index_offset.to_le_bytes());
+        buffer
+    }
+
+    /// Decodes the fixed size part of the header, the inner header is left empty.
+    pub fn decode_fixed(buffer: &[u8; CARV2_HEADER_SIZE]) -> Result<Self, Error> {
+        let u64_at = |i: usize| u64::from_le_bytes(buffer[i..i + 8].try_into().unwrap());
+        let header = CarHeaderV2 {
+            characteristics: Characteristics::decode(buffer[..16].try_into().unwrap()),
+            data_offset: u64_at(16),
+            data_size: u64_at(24),
+            index_offset: u64_at(32),
+            inner: Default::default(),
+        };
+
+        if header.data_offset < (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64 {
+            return Err(Error::InvalidFile("invalid CARv2 data offset".to_string()));
+        }
+        let data_end = header
+            .data_offset
+            .checked_add(header.data_size)
+            .ok_or_else(|| Error::InvalidFile("invalid CARv2 data size".to_string()))?;
+        if header.has_index() && header.index_offset < data_end {
+            return Err(Error::InvalidFile(
+                "CARv2 index overlaps the data".to_string(),
+            ));
+        }
+
+        Ok(header)
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.index_offset != 0
+    }
+}
+
+/// The characteristics bitfield of a CARv2 header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Characteristics(pub u128);
+
+impl Characteristics {
+    const FULLY_INDEXED: u128 = 1 << 127;
+
+    /// Is every block, including the ones with identity CIDs, in the index?
+    pub fn is_fully_indexed(&self) -> bool {
+        self.0 & Self::FULLY_INDEXED != 0
+    }
+
+    pub fn set_fully_indexed(&mut self, fully_indexed: bool) {
+        if fully_indexed {
+            self.0 |= Self::FULLY_INDEXED;
+        } else {
+            self.0 &= !Self::FULLY_INDEXED;
+        }
+    }
+
+    /// The most significant 64 bits are stored first, each half in little endian.
+    fn encode(&self) -> [u8; 16] {
+        let mut buffer = [0u8; 16];
+        buffer[..8].copy_from_slice(&((self.0 >> 64) as u64).to_le_bytes());
+        buffer[8..].copy_from_slice(&(self.0 as u64).to_le_bytes());
+        buffer
+    }
+
+    fn decode(buffer: &[u8; 16]) -> Self {
+        let hi = u64::from_le_bytes(buffer[..8].try_into().unwrap());
+        let lo = u64::from_le_bytes(buffer[8..].try_into().unwrap());
+        Characteristics((hi as u128) << 64 | lo as u128)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ipld::codec::{Decode, Encode};
@@ -101,4 +211,35 @@ mod tests {
             header
         );
     }
+
+    #[test]
+    fn carv2_pragma() {
+        let pragma: ipld::Ipld = DagCborCodec.decode(&CARV2_PRAGMA[1..]).unwrap();
+        let expected: ipld::Ipld = ipld::ipld!({ "version": 2 });
+        assert_eq!(pragma, expected);
+        assert_eq!(CARV2_PRAGMA[0] as usize, CARV2_PRAGMA.len() - 1);
+    }
+
+    #[test]
+    fn symmetric_header_v2() {
+        let mut characteristics = Characteristics::default();
+        characteristics.set_fully_indexed(true);
+        let header = CarHeaderV2 {
+            characteristics,
+            data_offset: 51,
+            data_size: 448,
+            index_offset: 499,
+            inner: Default::default(),
+        };
+
+        let bytes = header.encode_fixed();
+        assert_eq!(bytes[7], 0x80);
+        assert_eq!(&bytes[16..24], &[51, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(CarHeaderV2::decode_fixed(&bytes).unwrap(), header);
+        assert!(header.characteristics.is_fully_indexed());
+
+        let mut bytes = bytes;
+        bytes[16] = 10;
+        assert!(CarHeaderV2::decode_fixed(&bytes).is_err());
+    }
 }
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use cid::{multihash::Multihash, Cid};
+use integer_encoding::VarInt;
+
+use crate::error::Error;
+
+/// Multicodec code of the `car-multihash-index-sorted` index format.
+pub const MULTIHASH_INDEX_SORTED_CODE: u64 = 0x0401;
+
+/// Size of the offset stored after each digest.
+const OFFSET_SIZE: usize = 8;
+
+/// The `MultihashIndexSorted` index of CARv2 files.
+///
+/// Maps the multihash of every block to the offset of its section, relative to the start
+/// of the CARv1 data. The records are grouped by multihash code and digest length, and are
+/// sorted by digest, so lookups are a binary search.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultihashIndexSorted {
+    /// Multihash code -> record width -> sorted records of digest and little endian offset.
+    buckets: BTreeMap<u64, BTreeMap<u32, Vec<u8>>>,
+}
+
+impl MultihashIndexSorted {
+    /// Builds the index from the [`Cid`] and offset of each block.
+    pub fn from_offsets(offsets: impl IntoIterator<Item = (Cid, u64)>) -> Self {
+        let mut records: BTreeMap<u64, BTreeMap<u32, Vec<(Vec<u8>, u64)>>> = BTreeMap::new();
+        for (cid, offset) in offsets {
+            let hash = cid.hash();
+            let width = (hash.digest().len() + OFFSET_SIZE) as u32;
+            records
+                .entry(hash.code())
+                .or_default()
+                .entry(width)
+                .or_default()
+                .push((hash.digest().to_vec(), offset));
+        }
+
+        let buckets = records
+            .into_iter()
+            .map(|(code, widths)| {
+                let widths = widths
+                    .into_iter()
+                    .map(|(width, mut records)| {
+                        records.sort();
+                        let mut bytes = Vec::with_capacity(records.len() * width as usize);
+                        for (digest, offset) in records {
+                            bytes.extend_from_slice(&digest);
+                            bytes.extend_from_slice(&offset.to_le_bytes());
+                        }
+                        (width, bytes)
+                    })
+                    .collect();
+                (code, widths)
+            })
+            .collect();
+
+        MultihashIndexSorted { buckets }
+    }
+
+    /// Returns the offset of the block with the multihash of `cid`, if it is in the index.
+    pub fn get(&self, cid: &Cid) -> Option<u64> {
+        let hash = cid.hash();
+        let digest = hash.digest();
+        let width = digest.len() + OFFSET_SIZE;
+        let records = self.buckets.get(&hash.code())?.get(&(width as u32))?;
+
+        let (mut low, mut high) = (0, records.len() / width);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let record = &records[mid * width..(mid + 1) * width];
+            match record[..digest.len()].cmp(digest) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    let offset = record[digest.len()..].try_into().unwrap();
+                    return Some(u64::from_le_bytes(offset));
+                }
+            }
+        }
+        None
+    }
+
+    /// Iterates over the multihashes of all records.
+    pub fn multihashes(&self) -> impl Iterator<Item = Multihash> + '_ {
+        self.buckets.iter().flat_map(|(code, widths)| {
+            widths.iter().flat_map(move |(width, records)| {
+                let digest_len = *width as usize - OFFSET_SIZE;
+                records
+                    .chunks_exact(*width as usize)
+                    .filter_map(move |record| Multihash::wrap(*code, &record[..digest_len]).ok())
+            })
+        })
+    }
+
+    /// Number of records in the index.
+    pub fn len(&self) -> usize {
+        self.buckets
+            .values()
+            .flat_map(|widths| widths.iter())
+            .map(|(width, records)| records.len() / *width as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Encodes the index, prefixed with its multicodec code.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = MULTIHASH_INDEX_SORTED_CODE.encode_var_vec();
+        bytes.extend_from_slice(&(self.buckets.len() as i32).to_le_bytes());
+        for (code, widths) in &self.buckets {
+            bytes.extend_from_slice(&code.to_le_bytes());
+            bytes.extend_from_slice(&(widths.len() as i32).to_le_bytes());
+            for (width, records) in widths {
+                bytes.extend_from_slice(&width.to_le_bytes());
+                bytes.extend_from_slice(&(records.len() as u64).to_le_bytes());
+                bytes.extend_from_slice(records);
+            }
+        }
+        bytes
+    }
+
+    /// Decodes an index, prefixed with its multicodec code.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (code, read) = u64::decode_var(bytes)
+            .ok_or_else(|| Error::Parsing("invalid CARv2 index codec".to_string()))?;
+        if code != MULTIHASH_INDEX_SORTED_CODE {
+            return Err(Error::InvalidFile(format!(
+                "unsupported CARv2 index codec {code:#x}"
+            )));
+        }
+
+        let mut bytes = IndexBytes(&bytes[read..]);
+        let mut buckets = BTreeMap::new();
+        for _ in 0..bytes.read_count()? {
+            let code = u64::from_le_bytes(bytes.read()?);
+            let mut widths = BTreeMap::new();
+            for _ in 0..bytes.read_count()? {
+                let width = u32::from_le_bytes(bytes.read()?);
+                let len = u64::from_le_bytes(bytes.read()?);
+                let records = bytes.read_slice(len)?;
+                if (width as usize) <= OFFSET_SIZE || records.len() % width as usize != 0 {
+                    return Err(Error::Parsing(format!("invalid CARv2 index width {width}")));
+                }
+                widths.insert(width, records.to_vec());
+            }
+            buckets.insert(code, widths);
+        }
+
+        Ok(MultihashIndexSorted { buckets })
+    }
+}
+
+/// Bounds checked reads of the little endian index encoding.
+struct IndexBytes<'a>(&'a [u8]);
+
+impl<'a> IndexBytes<'a> {
+    fn read_slice(&mut self, len: u64) -> Result<&'a [u8], Error> {
+        if len > self.0.len() as u64 {
+            return Err(Error::Parsing("unexpected end of CARv2 index".to_string()));
+        }
+        let (slice, rest) = self.0.split_at(len as usize);
+        self.0 = rest;
+        Ok(slice)
+    }
+
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.read_slice(N as u64)?.try_into().unwrap())
+    }
+
+    fn read_count(&mut self) -> Result<i32, Error> {
+        let count = i32::from_le_bytes(self.read()?);
+        if count < 0 {
+            return Err(Error::Parsing("negative count in CARv2 index".to_string()));
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ipld_cbor::DagCborCodec;
+    use multihash::MultihashDigest;
+
+    use super::*;
+
+    fn cid(code: multihash::Code, data: &[u8]) -> Cid {
+        Cid::new_v1(DagCborCodec.into(), code.digest(data))
+    }
+
+    #[test]
+    fn index_get() {
+        let cids: Vec<_> = (0..100u32)
+            .map(|i| {
+                let code = if i % 3 == 0 {
+                    multihash::Code::Blake2b256
+                } else {
+                    multihash::Code::Sha2_256
+                };
+                cid(code, &i.to_le_bytes())
+            })
+            .collect();
+        let index = MultihashIndexSorted::from_offsets(
+            cids.iter().enumerate().map(|(i, c)| (*c, i as u64)),
+        );
+        assert_eq!(index.len(), 100);
+
+        for (i, c) in cids.iter().enumerate() {
+            assert_eq!(index.get(c), Some(i as u64));
+        }
+        assert_eq!(index.get(&cid(multihash::Code::Sha2_256, b"missing")), None);
+        assert_eq!(index.get(&cid(multihash::Code::Sha2_512, b"missing")), None);
+
+        let mut hashes: Vec<_> = index.multihashes().map(|h| h.to_bytes()).collect();
+        hashes.sort();
+        let mut expected: Vec<_> = cids.iter().map(|c| c.hash().to_bytes()).collect();
+        expected.sort();
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn index_encoding() {
+        let foo = cid(multihash::Code::Sha2_256, b"foo");
+        let index = MultihashIndexSorted::from_offsets([(foo, 59)]);
+
+        let bytes = index.encode();
+        let mut expected = vec![
+            0x81, 0x08, 1, 0, 0, 0, 0x12, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0,
+        ];
+        expected.extend_from_slice(&[40, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(foo.hash().digest());
+        expected.extend_from_slice(&[59, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes, expected);
+
+        assert_eq!(MultihashIndexSorted::decode(&bytes).unwrap(), index);
+        assert!(MultihashIndexSorted::decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(MultihashIndexSorted::decode(&[0x80, 0x08, 0, 0, 0, 0]).is_err());
+    }
+}
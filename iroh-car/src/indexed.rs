@@ -0,0 +1,255 @@
+use std::io::SeekFrom;
+
+use cid::Cid;
+use integer_encoding::VarInt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::{
+    error::Error,
+    header::CarHeader,
+    index::{MultihashIndexSorted, MULTIHASH_INDEX_SORTED_CODE},
+    reader::CarReader,
+    util::{read_node, read_section_header},
+};
+
+/// Random access to the blocks of a CAR file.
+///
+/// The index of a CARv2 file is loaded into memory. For CARv1 files, and CARv2 files
+/// without a `MultihashIndexSorted` index, the index is generated by scanning the file once.
+#[derive(Debug)]
+pub struct IndexedCarReader<R> {
+    reader: R,
+    header: CarHeader,
+    /// Offset of the CARv1 data from the start of the file.
+    data_offset: u64,
+    index: MultihashIndexSorted,
+    buffer: Vec<u8>,
+}
+
+impl<R> IndexedCarReader<R>
+where
+    R: AsyncRead + AsyncSeek + Send + Unpin,
+{
+    /// Opens the CAR file that starts at the beginning of `reader`.
+    pub async fn new(mut reader: R) -> Result<Self, Error> {
+        reader.seek(SeekFrom::Start(0)).await?;
+        let car = CarReader::new(&mut reader).await?;
+        let header = car.header().clone();
+        let (data_offset, index) = match header {
+            CarHeader::V1(_) => (0, scan_index(car).await?),
+            CarHeader::V2(ref v2) if !v2.has_index() => (v2.data_offset, scan_index(car).await?),
+            CarHeader::V2(ref v2) => {
+                drop(car);
+                reader.seek(SeekFrom::Start(v2.index_offset)).await?;
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes).await?;
+                let index = match u64::decode_var(&bytes) {
+                    Some((MULTIHASH_INDEX_SORTED_CODE, _)) => MultihashIndexSorted::decode(&bytes)?,
+                    // e.g. the `IndexSorted` index written by older go-car versions, which
+                    // does not record the hash function of the digests
+                    _ => {
+                        reader.seek(SeekFrom::Start(0)).await?;
+                        scan_index(CarReader::new(&mut reader).await?).await?
+                    }
+                };
+                (v2.data_offset, index)
+            }
+        };
+
+        Ok(IndexedCarReader {
+            reader,
+            header,
+            data_offset,
+            index,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Returns the header of this car file.
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// Returns the index of this car file.
+    pub fn index(&self) -> &MultihashIndexSorted {
+        &self.index
+    }
+
+    /// Offset of the CARv1 data from the start of the file, the offsets in the index are
+    /// relative to it.
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// Does the car file contain a block with the multihash of `cid`?
+    pub fn has(&self, cid: &Cid) -> bool {
+        self.index.get(cid).is_some()
+    }
+
+    /// Reads the data of the block with the multihash of `cid`.
+    ///
+    /// Blocks are looked up by multihash, so this also finds the block if it is stored
+    /// under a [`Cid`] with a different version or codec.
+    pub async fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>, Error> {
+        let offset = match self.index.get(cid) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+
+        read_block_at(
+            &mut self.reader,
+            self.data_offset + offset,
+            cid,
+            &mut self.buffer,
+        )
+        .await
+        .map(Some)
+    }
+}
+
+/// Reads the block of `cid` from the section at `position`, checking that the index entry
+/// pointed at the right section.
+pub(crate) async fn read_block_at<R>(
+    reader: &mut R,
+    position: u64,
+    cid: &Cid,
+    buffer: &mut Vec<u8>,
+) -> Result<Vec<u8>, Error>
+where
+    R: AsyncRead + AsyncSeek + Send + Unpin,
+{
+    reader.seek(SeekFrom::Start(position)).await?;
+    match read_node(reader, buffer).await? {
+        Some((found, data)) if found.hash() == cid.hash() => Ok(data),
+        _ => Err(Error::InvalidFile(format!(
+            "index entry of {cid} does not point to its block"
+        ))),
+    }
+}
+
+/// Reads the length of the block of `cid` from the header of the section at `position`,
+/// checking that the index entry pointed at the right section.
+pub(crate) async fn read_block_len_at<R>(
+    reader: &mut R,
+    position: u64,
+    cid: &Cid,
+) -> Result<usize, Error>
+where
+    R: AsyncRead + AsyncSeek + Send + Unpin,
+{
+    reader.seek(SeekFrom::Start(position)).await?;
+    match read_section_header(reader, &mut Vec::new()).await? {
+        Some((found, len)) if found.hash() == cid.hash() => Ok(len),
+        _ => Err(Error::InvalidFile(format!(
+            "index entry of {cid} does not point to its block"
+        ))),
+    }
+}
+
+/// Generates the index of a car file by reading all of its blocks.
+async fn scan_index<R>(mut car: CarReader<R>) -> Result<MultihashIndexSorted, Error>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let mut offsets = Vec::new();
+    while let Some((cid, _, offset)) = car.next_block_with_offset().await? {
+        offsets.push((cid, offset));
+    }
+    Ok(MultihashIndexSorted::from_offsets(offsets))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ipld_cbor::DagCborCodec;
+    use multihash::MultihashDigest;
+
+    use crate::writer::{CarWriter, CarWriterV2};
+
+    use super::*;
+
+    fn blocks() -> Vec<(Cid, Vec<u8>)> {
+        (0..20u32)
+            .map(|i| {
+                let data = format!("block {i}").into_bytes();
+                let cid = Cid::new_v1(DagCborCodec.into(), multihash::Code::Sha2_256.digest(&data));
+                (cid, data)
+            })
+            .collect()
+    }
+
+    async fn check_get<R>(mut car: IndexedCarReader<R>)
+    where
+        R: AsyncRead + AsyncSeek + Send + Unpin,
+    {
+        let blocks = blocks();
+        assert_eq!(car.header().roots(), [blocks[0].0]);
+        assert_eq!(car.index().len(), blocks.len());
+        for (cid, data) in blocks.iter().rev() {
+            assert!(car.has(cid));
+            assert_eq!(car.get(cid).await.unwrap().as_ref(), Some(data));
+        }
+
+        let missing = Cid::new_v1(
+            DagCborCodec.into(),
+            multihash::Code::Sha2_256.digest(b"missing"),
+        );
+        assert!(!car.has(&missing));
+        assert_eq!(car.get(&missing).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn indexed_get_v2() {
+        let blocks = blocks();
+        let mut writer = CarWriterV2::new(vec![blocks[0].0], Cursor::new(Vec::new()));
+        for (cid, data) in &blocks {
+            writer.write(*cid, data).await.unwrap();
+        }
+        let buffer = writer.finish().await.unwrap();
+
+        let car = IndexedCarReader::new(buffer).await.unwrap();
+        assert_eq!(car.header().version(), 2);
+        check_get(car).await;
+    }
+
+    #[tokio::test]
+    async fn indexed_get_v2_index_sorted() {
+        let blocks = blocks();
+        let mut writer = CarWriterV2::new(vec![blocks[0].0], Cursor::new(Vec::new()));
+        for (cid, data) in &blocks {
+            writer.write(*cid, data).await.unwrap();
+        }
+        let mut buffer = writer.finish().await.unwrap().into_inner();
+
+        // replace the index with the `IndexSorted` (0x0400) index of go-car v2.0, which is a
+        // single bucket of `MultihashIndexSorted` without the multihash code
+        let index_offset = match CarReader::new(&buffer[..]).await.unwrap().header() {
+            CarHeader::V2(v2) => v2.index_offset as usize,
+            CarHeader::V1(_) => unreachable!(),
+        };
+        let index = buffer.split_off(index_offset);
+        assert_eq!(&index[..2], &[0x81, 0x08]);
+        // skip the codec, the number of buckets and the multihash code
+        buffer.extend_from_slice(&[0x80, 0x08]);
+        buffer.extend_from_slice(&index[2 + 4 + 8..]);
+
+        let car = IndexedCarReader::new(Cursor::new(buffer)).await.unwrap();
+        assert_eq!(car.header().version(), 2);
+        check_get(car).await;
+    }
+
+    #[tokio::test]
+    async fn indexed_get_v1() {
+        let blocks = blocks();
+        let mut writer = CarWriter::new(CarHeader::new_v1(vec![blocks[0].0]), Vec::new());
+        for (cid, data) in &blocks {
+            writer.write(*cid, data).await.unwrap();
+        }
+        let buffer = writer.finish().await.unwrap();
+
+        let car = IndexedCarReader::new(Cursor::new(buffer)).await.unwrap();
+        assert_eq!(car.header().version(), 1);
+        check_get(car).await;
+    }
+}
@@ -2,10 +2,17 @@
 
 mod error;
 mod header;
+mod index;
+mod indexed;
 mod reader;
+mod store;
 mod util;
 mod writer;
 
-pub use crate::header::CarHeader;
+pub use crate::error::Error;
+pub use crate::header::{CarHeader, CarHeaderV1, CarHeaderV2, Characteristics};
+pub use crate::index::{MultihashIndexSorted, MULTIHASH_INDEX_SORTED_CODE};
+pub use crate::indexed::IndexedCarReader;
 pub use crate::reader::CarReader;
-pub use crate::writer::CarWriter;
+pub use crate::store::CarStore;
+pub use crate::writer::{CarWriter, CarWriterV2};
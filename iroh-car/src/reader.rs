@@ -1,19 +1,26 @@
 use cid::Cid;
 use futures::Stream;
-use tokio::io::AsyncRead;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::{
     error::Error,
-    header::CarHeader,
-    util::{ld_read, read_node},
+    header::{CarHeader, CarHeaderV2, CARV2_HEADER_SIZE, CARV2_PRAGMA},
+    util::{ld_read, read_section, section_len},
 };
 
 /// Reads CAR files that are in a BufReader
+///
+/// Both CARv1 and CARv2 files are supported, for CARv2 files the blocks of the inner CARv1
+/// data are read and the index is ignored.
 #[derive(Debug)]
 pub struct CarReader<R> {
     reader: R,
     header: CarHeader,
     buffer: Vec<u8>,
+    /// Offset of the next block, relative to the start of the CARv1 data.
+    offset: u64,
+    /// The size of the CARv1 data, if it is known.
+    data_size: Option<u64>,
 }
 
 impl<R> CarReader<R>
@@ -24,20 +31,28 @@ where
     pub async fn new(mut reader: R) -> Result<Self, Error> {
         let mut buffer = Vec::new();
 
-        match ld_read(&mut reader, &mut buffer).await? {
-            Some(buf) => {
-                let header = CarHeader::decode(buf)?;
-
-                Ok(CarReader {
-                    reader,
-                    header,
-                    buffer,
-                })
+        let (header, header_len) = match ld_read(&mut reader, &mut buffer).await? {
+            Some(buf) if buf == &CARV2_PRAGMA[1..] => read_v2_header(&mut reader).await?,
+            Some(buf) => (CarHeader::decode(buf)?, buf.len()),
+            None => {
+                return Err(Error::Parsing(
+                    "failed to parse uvarint for header".to_string(),
+                ))
             }
-            None => Err(Error::Parsing(
-                "failed to parse uvarint for header".to_string(),
-            )),
-        }
+        };
+        let data_size = match header {
+            CarHeader::V1(_) => None,
+            CarHeader::V2(ref header) => Some(header.data_size),
+        };
+        let offset = section_len(header_len);
+
+        Ok(CarReader {
+            reader,
+            header,
+            buffer,
+            offset,
+            data_size,
+        })
     }
 
     /// Returns the header of this car file.
@@ -47,17 +62,82 @@ where
 
     /// Returns the next IPLD Block in the buffer
     pub async fn next_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>, Error> {
-        read_node(&mut self.reader, &mut self.buffer).await
+        Ok(self
+            .next_block_with_offset()
+            .await?
+            .map(|(cid, data, _)| (cid, data)))
+    }
+
+    /// Returns the next block, with the offset of its section in the CARv1 data.
+    pub(crate) async fn next_block_with_offset(
+        &mut self,
+    ) -> Result<Option<(Cid, Vec<u8>, u64)>, Error> {
+        if matches!(self.data_size, Some(size) if self.offset >= size) {
+            return Ok(None);
+        }
+        match read_section(&mut self.reader, &mut self.buffer).await? {
+            Some((cid, data, len)) => {
+                let offset = self.offset;
+                self.offset += len;
+                if matches!(self.data_size, Some(size) if self.offset > size) {
+                    return Err(Error::InvalidFile(
+                        "block exceeds the CARv2 data size".to_string(),
+                    ));
+                }
+                Ok(Some((cid, data, offset)))
+            }
+            None if self.data_size.is_some() => Err(Error::InvalidFile(
+                "CARv2 data is shorter than its header claims".to_string(),
+            )),
+            None => Ok(None),
+        }
     }
 
     pub fn stream(self) -> impl Stream<Item = Result<(Cid, Vec<u8>), Error>> {
         futures::stream::try_unfold(self, |mut this| async move {
-            let maybe_block = read_node(&mut this.reader, &mut this.buffer).await?;
+            let maybe_block = this.next_block().await?;
             Ok(maybe_block.map(|b| (b, this)))
         })
     }
 }
 
+/// Reads the rest of the CARv2 header after the pragma, up to the first block.
+///
+/// Returns the header and the length of the inner CARv1 header.
+async fn read_v2_header<R>(mut reader: R) -> Result<(CarHeader, usize), Error>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let mut fixed = [0u8; CARV2_HEADER_SIZE];
+    reader.read_exact(&mut fixed).await?;
+    let mut header = CarHeaderV2::decode_fixed(&fixed)?;
+
+    // skip the padding before the data
+    let padding = header.data_offset - (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64;
+    let skipped = tokio::io::copy(&mut (&mut reader).take(padding), &mut tokio::io::sink()).await?;
+    if skipped != padding {
+        return Err(Error::InvalidFile(
+            "CARv2 data offset is past the end of the file".to_string(),
+        ));
+    }
+
+    let mut buffer = Vec::new();
+    let (inner, inner_len) = match ld_read(&mut reader, &mut buffer).await? {
+        Some(buf) => (CarHeader::decode(buf)?, buf.len()),
+        None => {
+            return Err(Error::Parsing(
+                "failed to parse uvarint for CARv2 inner header".to_string(),
+            ))
+        }
+    };
+    match inner {
+        CarHeader::V1(inner) => header.inner = inner,
+        _ => return Err(Error::InvalidFile("CARv2 data must be a CARv1".to_string())),
+    }
+
+    Ok((CarHeader::V2(header), inner_len))
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use std::collections::HashSet;
+
+use cid::{multihash::Multihash, Cid};
+use tokio::{fs::File, io::BufReader};
+
+use crate::{
+    error::Error,
+    index::MultihashIndexSorted,
+    indexed::{read_block_at, read_block_len_at, IndexedCarReader},
+};
+
+/// A read-only block store, backed by one or more CAR files.
+///
+/// The indexes of the files are kept in memory, the blocks themselves are read from disk on
+/// every access. Every read opens its own handle to the file, so concurrent reads do not wait
+/// on each other. Clones share the indexes.
+#[derive(Debug, Clone, Default)]
+pub struct CarStore {
+    cars: Arc<Vec<IndexedCarFile>>,
+}
+
+/// The index of a CAR file, and where to find the file.
+#[derive(Debug)]
+struct IndexedCarFile {
+    path: PathBuf,
+    data_offset: u64,
+    index: MultihashIndexSorted,
+}
+
+impl CarStore {
+    /// Opens the CAR files at `paths`, generating an index for files without one.
+    pub async fn open<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self, Error> {
+        let mut cars = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let car = async {
+                let file = File::open(path).await?;
+                IndexedCarReader::new(BufReader::new(file)).await
+            }
+            .await
+            .map_err(|err| Error::Open(PathBuf::from(path), Box::new(err)))?;
+            cars.push(IndexedCarFile {
+                path: PathBuf::from(path),
+                data_offset: car.data_offset(),
+                index: car.index().clone(),
+            });
+        }
+
+        Ok(CarStore {
+            cars: Arc::new(cars),
+        })
+    }
+
+    /// Number of CAR files in the store.
+    pub fn len(&self) -> usize {
+        self.cars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cars.is_empty()
+    }
+
+    /// Reads the data of the block with the multihash of `cid` from the first file that
+    /// contains it.
+    pub async fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>, Error> {
+        for car in self.cars.iter() {
+            if let Some(offset) = car.index.get(cid) {
+                let mut reader = BufReader::new(File::open(&car.path).await?);
+                let data =
+                    read_block_at(&mut reader, car.data_offset + offset, cid, &mut Vec::new())
+                        .await?;
+                return Ok(Some(data));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the size of the block with the multihash of `cid`.
+    ///
+    /// The index only records where each block starts, the size is read from the header of
+    /// its section, without reading the block itself.
+    pub async fn get_size(&self, cid: &Cid) -> Result<Option<usize>, Error> {
+        for car in self.cars.iter() {
+            if let Some(offset) = car.index.get(cid) {
+                let mut reader = BufReader::new(File::open(&car.path).await?);
+                let len = read_block_len_at(&mut reader, car.data_offset + offset, cid).await?;
+                return Ok(Some(len));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterates over the multihashes of all blocks, once even if several files contain them.
+    pub fn multihashes(&self) -> impl Iterator<Item = Multihash> + '_ {
+        let mut seen = HashSet::new();
+        self.cars
+            .iter()
+            .flat_map(|car| car.index.multihashes())
+            .filter(move |hash| self.cars.len() == 1 || seen.insert(*hash))
+    }
+
+    /// Does any of the files contain a block with the multihash of `cid`?
+    pub async fn has(&self, cid: &Cid) -> bool {
+        self.cars.iter().any(|car| car.index.get(cid).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ipld_cbor::DagCborCodec;
+    use multihash::MultihashDigest;
+
+    use crate::writer::CarWriterV2;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn car_store_get() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut blocks = Vec::new();
+        let mut paths = Vec::new();
+        for i in 0..2 {
+            let data = format!("block {i}").into_bytes();
+            let cid = Cid::new_v1(DagCborCodec.into(), multihash::Code::Sha2_256.digest(&data));
+            let path = dir.path().join(format!("{i}.car"));
+            let file = File::create(&path).await.unwrap();
+            let mut writer = CarWriterV2::new(vec![cid], file);
+            writer.write(cid, &data).await.unwrap();
+            writer.finish().await.unwrap();
+            blocks.push((cid, data));
+            paths.push(path);
+        }
+
+        let store = CarStore::open(&paths).await.unwrap();
+        assert_eq!(store.len(), 2);
+        for (cid, data) in &blocks {
+            assert!(store.has(cid).await);
+            assert_eq!(store.get(cid).await.unwrap().as_ref(), Some(data));
+            assert_eq!(store.get_size(cid).await.unwrap(), Some(data.len()));
+        }
+
+        let missing = Cid::new_v1(
+            DagCborCodec.into(),
+            multihash::Code::Sha2_256.digest(b"missing"),
+        );
+        assert!(!store.has(&missing).await);
+        assert_eq!(store.get(&missing).await.unwrap(), None);
+        assert_eq!(store.get_size(&missing).await.unwrap(), None);
+
+        let mut hashes: Vec<_> = store.multihashes().map(|h| h.to_bytes()).collect();
+        hashes.sort();
+        let mut expected: Vec<_> = blocks
+            .iter()
+            .map(|(cid, _)| cid.hash().to_bytes())
+            .collect();
+        expected.sort();
+        assert_eq!(hashes, expected);
+
+        // reads of the same file do not wait on each other
+        let reads = (0..8).map(|_| {
+            let store = store.clone();
+            let (cid, data) = blocks[0].clone();
+            tokio::spawn(async move {
+                assert_eq!(store.get(&cid).await.unwrap(), Some(data));
+            })
+        });
+        for read in futures::future::join_all(reads).await {
+            read.unwrap();
+        }
+
+        let err = CarStore::open([dir.path().join("missing.car")])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Open(..)));
+    }
+}
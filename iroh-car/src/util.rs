@@ -1,5 +1,5 @@
 use cid::Cid;
-use integer_encoding::VarIntAsyncReader;
+use integer_encoding::{VarInt, VarIntAsyncReader};
 use tokio::io::{AsyncRead, AsyncReadExt};
 
 use super::error::Error;
@@ -7,6 +7,9 @@ use super::error::Error;
 /// Maximum size that is used for single node.
 pub(crate) const MAX_ALLOC: usize = 4 * 1024 * 1024;
 
+/// Upper bound of the encoded length of a cid, with a 64 byte digest.
+const MAX_CID_LEN: usize = 128;
+
 pub(crate) async fn ld_read<R>(mut reader: R, buf: &mut Vec<u8>) -> Result<Option<&[u8]>, Error>
 where
     R: AsyncRead + Send + Unpin,
@@ -40,6 +43,19 @@ pub(crate) async fn read_node<R>(
     buf_reader: &mut R,
     buf: &mut Vec<u8>,
 ) -> Result<Option<(Cid, Vec<u8>)>, Error>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    Ok(read_section(buf_reader, buf)
+        .await?
+        .map(|(cid, data, _)| (cid, data)))
+}
+
+/// Reads a block, together with the total length of its section including the length prefix.
+pub(crate) async fn read_section<R>(
+    buf_reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> Result<Option<(Cid, Vec<u8>, u64)>, Error>
 where
     R: AsyncRead + Send + Unpin,
 {
@@ -48,11 +64,43 @@ where
         let c = Cid::read_bytes(&mut cursor)?;
         let pos = cursor.position() as usize;
 
-        return Ok(Some((c, buf[pos..].to_vec())));
+        return Ok(Some((c, buf[pos..].to_vec(), section_len(buf.len()))));
     }
     Ok(None)
 }
 
+/// Reads the cid of a section and the length of its block data, without reading the data.
+pub(crate) async fn read_section_header<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> Result<Option<(Cid, usize)>, Error>
+where
+    R: AsyncRead + Send + Unpin,
+{
+    let length: usize = match VarIntAsyncReader::read_varint_async(&mut *reader).await {
+        Ok(len) => len,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(Error::Parsing(e.to_string())),
+    };
+
+    // the cid is at the start of the section, and never longer than this
+    let cid_len = length.min(MAX_CID_LEN);
+    buf.resize(cid_len, 0);
+    reader
+        .read_exact(&mut buf[..])
+        .await
+        .map_err(|e| Error::Parsing(e.to_string()))?;
+    let mut cursor = std::io::Cursor::new(&buf[..]);
+    let cid = Cid::read_bytes(&mut cursor)?;
+
+    Ok(Some((cid, length - cursor.position() as usize)))
+}
+
+/// Length of a section with `len` bytes of content, including its length prefix.
+pub(crate) fn section_len(len: usize) -> u64 {
+    (len.required_space() + len) as u64
+}
+
 #[cfg(test)]
 mod tests {
     use integer_encoding::VarIntAsyncWriter;
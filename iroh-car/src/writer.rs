@@ -1,8 +1,15 @@
+use std::io::SeekFrom;
+
 use cid::Cid;
 use integer_encoding::VarIntAsyncWriter;
-use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 
-use crate::{error::Error, header::CarHeader};
+use crate::{
+    error::Error,
+    header::{CarHeader, CarHeaderV1, CarHeaderV2, CARV2_HEADER_SIZE, CARV2_PRAGMA},
+    index::MultihashIndexSorted,
+    util::section_len,
+};
 
 #[derive(Debug)]
 pub struct CarWriter<W> {
@@ -39,15 +46,7 @@ where
         }
 
         // Write the given block.
-        self.cid_buffer.clear();
-        cid.write_bytes(&mut self.cid_buffer).expect("vec write");
-
-        let data = data.as_ref();
-        let len = self.cid_buffer.len() + data.len();
-
-        self.writer.write_varint_async(len).await?;
-        self.writer.write_all(&self.cid_buffer).await?;
-        self.writer.write_all(data).await?;
+        write_block(&mut self.writer, &mut self.cid_buffer, cid, data.as_ref()).await?;
 
         Ok(())
     }
@@ -69,3 +68,116 @@ where
         self.writer
     }
 }
+
+/// Writes CARv2 files, with a [`MultihashIndexSorted`] index of all blocks.
+///
+/// The size of the data is part of the header, so the header is only written in
+/// [`CarWriterV2::finish`] and the writer needs to be seekable.
+#[derive(Debug)]
+pub struct CarWriterV2<W> {
+    header: CarHeaderV2,
+    writer: W,
+    cid_buffer: Vec<u8>,
+    is_header_written: bool,
+    /// Offset of the next block, relative to the start of the CARv1 data.
+    offset: u64,
+    offsets: Vec<(Cid, u64)>,
+}
+
+impl<W> CarWriterV2<W>
+where
+    W: AsyncWrite + AsyncSeek + Send + Unpin,
+{
+    /// Creates a new writer, the file starts at the current position of `writer`.
+    pub fn new(roots: Vec<Cid>, writer: W) -> Self {
+        CarWriterV2 {
+            header: CarHeaderV2 {
+                data_offset: (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64,
+                inner: CarHeaderV1::from(roots),
+                ..Default::default()
+            },
+            writer,
+            cid_buffer: Vec::new(),
+            is_header_written: false,
+            offset: 0,
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Writes a block, the header is written before the first block.
+    pub async fn write<T>(&mut self, cid: Cid, data: T) -> Result<(), Error>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.write_header().await?;
+
+        let len = write_block(&mut self.writer, &mut self.cid_buffer, cid, data.as_ref()).await?;
+        self.offsets.push((cid, self.offset));
+        self.offset += section_len(len);
+
+        Ok(())
+    }
+
+    /// Writes the index and the final header, flushes and returns the writer.
+    pub async fn finish(mut self) -> Result<W, Error> {
+        self.write_header().await?;
+
+        let start = self.writer.stream_position().await? - self.header.data_offset - self.offset;
+        let index = MultihashIndexSorted::from_offsets(self.offsets.drain(..));
+        self.writer.write_all(&index.encode()).await?;
+        let end = self.writer.stream_position().await?;
+
+        // all blocks are indexed, including the ones with identity CIDs
+        self.header.characteristics.set_fully_indexed(true);
+        self.header.data_size = self.offset;
+        self.header.index_offset = self.header.data_offset + self.offset;
+        self.writer
+            .seek(SeekFrom::Start(start + CARV2_PRAGMA.len() as u64))
+            .await?;
+        self.writer.write_all(&self.header.encode_fixed()).await?;
+        self.writer.seek(SeekFrom::Start(end)).await?;
+
+        self.writer.flush().await?;
+        Ok(self.writer)
+    }
+
+    /// Writes the pragma, a placeholder for the CARv2 header, and the CARv1 header.
+    async fn write_header(&mut self) -> Result<(), Error> {
+        if self.is_header_written {
+            return Ok(());
+        }
+
+        self.writer.write_all(&CARV2_PRAGMA).await?;
+        self.writer.write_all(&[0u8; CARV2_HEADER_SIZE]).await?;
+
+        let header_bytes = CarHeader::V1(self.header.inner.clone()).encode()?;
+        self.writer.write_varint_async(header_bytes.len()).await?;
+        self.writer.write_all(&header_bytes).await?;
+        self.offset = section_len(header_bytes.len());
+        self.is_header_written = true;
+
+        Ok(())
+    }
+}
+
+/// Writes a single block section, returns the length of the section without its prefix.
+async fn write_block<W>(
+    writer: &mut W,
+    cid_buffer: &mut Vec<u8>,
+    cid: Cid,
+    data: &[u8],
+) -> Result<usize, Error>
+where
+    W: AsyncWrite + Send + Unpin,
+{
+    cid_buffer.clear();
+    cid.write_bytes(&mut *cid_buffer).expect("vec write");
+
+    let len = cid_buffer.len() + data.len();
+
+    writer.write_varint_async(len).await?;
+    writer.write_all(cid_buffer).await?;
+    writer.write_all(data).await?;
+
+    Ok(len)
+}
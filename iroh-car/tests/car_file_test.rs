@@ -72,3 +72,46 @@ async fn roundtrip_carv1_basic_fixtures_file() {
     let file = fs::read("tests/carv1_basic.car").await.unwrap();
     assert_eq!(file, buffer);
 }
+
+#[tokio::test]
+async fn roundtrip_carv1_to_carv2_test_file() {
+    let file = File::open("tests/testv1.car").await.unwrap();
+    let car_reader = CarReader::new(BufReader::new(file)).await.unwrap();
+    let roots = car_reader.header().roots().to_vec();
+    let files: Vec<_> = car_reader.stream().try_collect().await.unwrap();
+
+    let mut writer = CarWriterV2::new(roots.clone(), std::io::Cursor::new(Vec::new()));
+    for (cid, data) in &files {
+        writer.write(*cid, data).await.unwrap();
+    }
+    let buffer = writer.finish().await.unwrap().into_inner();
+    assert_eq!(
+        buffer[..11],
+        [0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02]
+    );
+
+    // the data of a CARv2 file is the CARv1 file
+    let v1 = fs::read("tests/testv1.car").await.unwrap();
+    assert_eq!(buffer[51..51 + v1.len()], v1);
+
+    let car_reader = CarReader::new(buffer.as_slice()).await.unwrap();
+    match car_reader.header() {
+        CarHeader::V2(header) => {
+            assert!(header.characteristics.is_fully_indexed());
+            assert_eq!(header.data_offset, 51);
+            assert_eq!(header.data_size, v1.len() as u64);
+            assert_eq!(header.index_offset, 51 + v1.len() as u64);
+        }
+        header => panic!("unexpected header {header:?}"),
+    }
+    assert_eq!(car_reader.header().roots(), roots);
+    let v2_files: Vec<_> = car_reader.stream().try_collect().await.unwrap();
+    assert_eq!(v2_files, files);
+
+    let mut car = IndexedCarReader::new(std::io::Cursor::new(buffer))
+        .await
+        .unwrap();
+    for (cid, data) in &files {
+        assert_eq!(car.get(cid).await.unwrap().as_ref(), Some(data));
+    }
+}
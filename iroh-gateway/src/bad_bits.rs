@@ -1,12 +1,33 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use cid::Cid;
+use iroh_metrics::gateway::record_denylist_update;
+use iroh_resolver::resolver::{CidOrDomain, Path};
+use iroh_unixfs::codecs::Codec;
 use serde::{de, Deserialize, Deserializer, Serialize};
 use sha2::{Digest, Sha256};
-use std::{collections::HashSet, sync::Arc, time::Duration};
-use tokio::{sync::RwLock, task::JoinHandle};
-use tracing::{debug, log::error};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::RwLock, task::JoinHandle, time::Instant};
+use tracing::{debug, info, warn};
+
+use crate::constants::SCHEME_IPNS;
 
 const BAD_BITS_UPDATE_INTERVAL: Duration = Duration::from_secs(3600 * 8);
-const DEFAULT_DENY_LIST_URI: &str = "http://badbits.dwebops.pub/denylist.json";
+/// How long to wait before fetching a remote denylist again after a failure.
+const BAD_BITS_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 5);
+/// How often sources are checked, local files are reloaded when they changed since.
+const BAD_BITS_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Remote denylists which do not download in time are retried later.
+const BAD_BITS_FETCH_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+/// Largest denylist which is accepted, 64MB.
+const MAX_DENY_LIST_SIZE: u64 = 1 << 26;
+pub const DEFAULT_DENY_LIST_URI: &str = "http://badbits.dwebops.pub/denylist.json";
 
 #[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
 pub struct BadBitsAnchor {
@@ -19,14 +40,211 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    let b = hex::decode(s).unwrap();
-    b[..32].try_into().map_err(de::Error::custom)
+    parse_anchor(&s).map_err(de::Error::custom)
+}
+
+fn parse_anchor(s: &str) -> Result<[u8; 32]> {
+    let b = hex::decode(s)?;
+    <[u8; 32]>::try_from(b).map_err(|b| anyhow!("anchor must be 32 bytes, got {}", b.len()))
+}
+
+/// Where a denylist is loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DenylistSource {
+    /// A local file, reloaded whenever it is modified.
+    File(PathBuf),
+    /// An http(s) URL, fetched every few hours.
+    Url(String),
+}
+
+impl FromStr for DenylistSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ensure!(!s.is_empty(), "empty denylist source");
+        if s.starts_with("http://") || s.starts_with("https://") {
+            let url = reqwest::Url::parse(s).with_context(|| format!("invalid url {s}"))?;
+            return Ok(DenylistSource::Url(url.to_string()));
+        }
+        let path = s.strip_prefix("file://").unwrap_or(s);
+        Ok(DenylistSource::File(PathBuf::from(path)))
+    }
+}
+
+impl fmt::Display for DenylistSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DenylistSource::File(path) => write!(f, "{}", path.display()),
+            DenylistSource::Url(url) => write!(f, "{url}"),
+        }
+    }
+}
+
+/// A rule below a blocked CID or IPNS name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathRule {
+    /// Blocks exactly this path.
+    Exact(String),
+    /// Blocks every path starting with this prefix, the empty prefix blocks everything.
+    Prefix(String),
+}
+
+impl PathRule {
+    fn parse(rest: &str) -> Self {
+        let rest = rest.trim_start_matches('/');
+        match rest.strip_suffix('*') {
+            Some(prefix) => PathRule::Prefix(prefix.to_string()),
+            None if rest.trim_end_matches('/').is_empty() => PathRule::Prefix(String::new()),
+            None => PathRule::Exact(rest.trim_end_matches('/').to_string()),
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            PathRule::Exact(exact) => path == exact,
+            PathRule::Prefix(prefix) => {
+                path.starts_with(prefix.as_str()) || path == prefix.trim_end_matches('/')
+            }
+        }
+    }
+}
+
+/// A single denylist, either in the JSON format of <https://badbits.dwebops.pub> or in the
+/// compact line based format used by NOpfs.
+///
+/// Supported lines of the compact format are:
+/// - `//<hex>`: the hashed anchor of a CID and path, as used by the JSON format
+/// - `<cid>` or `/ipfs/<cid>`: the CID, and every path below it
+/// - `/ipfs/<cid>/path`: exactly this path, `/ipfs/<cid>/path/*` everything below it
+/// - `/ipns/<name>`: the name, and every path below it
+/// - `/ipns/<name>/path`: exactly this path, `/ipns/<name>/path/*` everything below it
+///
+/// IPNS keys match whether they are written as a peer id or as a CID.
+///
+/// An optional header is terminated by a `---` line, `#` starts a comment. Allow rules
+/// starting with `!` are not supported and ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Denylist {
+    anchors: HashSet<BadBitsAnchor>,
+    cids: HashMap<Cid, Vec<PathRule>>,
+    names: HashMap<String, Vec<PathRule>>,
+}
+
+impl Denylist {
+    pub fn parse(content: &str) -> Result<Self> {
+        if content.trim_start().starts_with('[') {
+            let anchors = serde_json::from_str::<Vec<BadBitsAnchor>>(content)
+                .context("invalid json denylist")?;
+            return Ok(Denylist {
+                anchors: anchors.into_iter().collect(),
+                ..Default::default()
+            });
+        }
+
+        let lines: Vec<&str> = content.lines().collect();
+        let start = lines
+            .iter()
+            .position(|line| line.trim() == "---")
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let mut list = Denylist::default();
+        for (i, line) in lines.iter().enumerate().skip(start) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('!') {
+                debug!("denylist line {}: allow rules are not supported", i + 1);
+                continue;
+            }
+            // a single broken line should not discard the rest of the list
+            if let Err(e) = list.add_rule(line) {
+                warn!("denylist line {}: {}", i + 1, e);
+            }
+        }
+        Ok(list)
+    }
+
+    fn add_rule(&mut self, line: &str) -> Result<()> {
+        if let Some(hash) = line.strip_prefix("//") {
+            let value = parse_anchor(hash).context("unsupported hash")?;
+            self.anchors.insert(BadBitsAnchor { value });
+        } else if let Some(rest) = line.strip_prefix("/ipfs/") {
+            let (cid, rest) = rest.split_once('/').unwrap_or((rest, ""));
+            let cid = Cid::from_str(cid).with_context(|| format!("invalid cid {cid}"))?;
+            self.cids
+                .entry(normalize_cid(cid))
+                .or_default()
+                .push(PathRule::parse(rest));
+        } else if let Some(rest) = line.strip_prefix("/ipns/") {
+            let (name, rest) = rest.split_once('/').unwrap_or((rest, ""));
+            ensure!(!name.is_empty(), "missing ipns name");
+            self.names
+                .entry(normalize_name(name))
+                .or_default()
+                .push(PathRule::parse(rest));
+        } else if line.starts_with('/') {
+            bail!("unsupported rule {}", line);
+        } else {
+            let cid = Cid::from_str(line).with_context(|| format!("invalid cid {line}"))?;
+            self.cids
+                .entry(normalize_cid(cid))
+                .or_default()
+                .push(PathRule::Prefix(String::new()));
+        }
+        Ok(())
+    }
+
+    /// Number of rules in this list.
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+            + self.cids.values().map(Vec::len).sum::<usize>()
+            + self.names.values().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_bad(&self, cid: &Cid, path: &str) -> bool {
+        if self.anchors.contains(&BadBits::to_anchor(*cid, path)) {
+            return true;
+        }
+        let path = path.trim_matches('/');
+        self.cids
+            .get(&normalize_cid(*cid))
+            .map(|rules| rules.iter().any(|rule| rule.matches(path)))
+            .unwrap_or(false)
+    }
+
+    pub fn is_bad_name(&self, name: &str, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        self.names
+            .get(&normalize_name(name))
+            .map(|rules| rules.iter().any(|rule| rule.matches(path)))
+            .unwrap_or(false)
+    }
+}
+
+fn normalize_cid(cid: Cid) -> Cid {
+    cid.into_v1().unwrap_or(cid)
+}
+
+/// IPNS keys are compared as libp2p-key CIDv1, whichever peer id or CID form they are
+/// written in, DNSLink domains are compared case insensitively.
+fn normalize_name(name: &str) -> String {
+    match Path::from_parts(SCHEME_IPNS, name, "").map(|path| path.root().clone()) {
+        Ok(CidOrDomain::Cid(cid)) => Cid::new_v1(Codec::Libp2pKey.into(), *cid.hash()).to_string(),
+        _ => name.to_ascii_lowercase(),
+    }
 }
 
 #[derive(Debug)]
 pub struct BadBits {
     pub last_updated: time::Instant,
     pub denylist: HashSet<BadBitsAnchor>,
+    /// Denylists loaded from the configured sources, by source.
+    lists: BTreeMap<String, Denylist>,
 }
 
 impl BadBits {
@@ -34,6 +252,7 @@ impl BadBits {
         Self {
             last_updated: time::Instant::now(),
             denylist: HashSet::new(),
+            lists: BTreeMap::new(),
         }
     }
 
@@ -42,9 +261,29 @@ impl BadBits {
         self.denylist = denylist;
     }
 
+    /// Replaces the denylist loaded from `source`.
+    pub fn set_list(&mut self, source: &DenylistSource, list: Denylist) {
+        self.last_updated = time::Instant::now();
+        self.lists.insert(source.to_string(), list);
+    }
+
+    /// Number of entries across all denylists.
+    pub fn len(&self) -> usize {
+        self.denylist.len() + self.lists.values().map(Denylist::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn is_bad(&self, cid: &Cid, path: &str) -> bool {
         let hash = BadBits::to_anchor(*cid, path);
-        self.denylist.contains(&hash)
+        self.denylist.contains(&hash) || self.lists.values().any(|list| list.is_bad(cid, path))
+    }
+
+    /// Checks a path below an IPNS name or DNSLink domain.
+    pub fn is_bad_name(&self, name: &str, path: &str) -> bool {
+        self.lists.values().any(|list| list.is_bad_name(name, path))
     }
 
     pub fn to_anchor(cid: Cid, path: &str) -> BadBitsAnchor {
@@ -66,49 +305,151 @@ impl Default for BadBits {
     }
 }
 
-pub fn spawn_bad_bits_updater(bad_bits: Arc<Option<RwLock<BadBits>>>) -> Option<JoinHandle<()>> {
-    if bad_bits.is_some() {
-        return Some(tokio::spawn(async move {
-            let bad_bits = bad_bits.as_ref();
-            if let Some(bbits) = bad_bits {
-                loop {
-                    let denylist_uri = DEFAULT_DENY_LIST_URI;
-                    let res = reqwest::get(denylist_uri).await.unwrap();
-                    if res.status().is_success() {
-                        let body = res.bytes().await.unwrap();
-                        if body.len() > 1 << 26 {
-                            // 64MB
-                            error!("denylist too large: {}", body.len());
-                        } else {
-                            let body =
-                                serde_json::from_slice::<Vec<BadBitsAnchor>>(&body[..]).unwrap();
-                            let new_denylist = HashSet::from_iter(body);
-                            bbits.write().await.update(new_denylist);
-                            debug!(
-                                "updated denylist: len={}",
-                                bbits.read().await.denylist.len()
-                            );
+/// Parses the configured denylists, falling back to [`DEFAULT_DENY_LIST_URI`] if none are set.
+pub fn denylist_sources(denylists: &[String]) -> Result<Vec<DenylistSource>> {
+    if denylists.is_empty() {
+        return Ok(vec![DenylistSource::Url(DEFAULT_DENY_LIST_URI.to_string())]);
+    }
+    denylists.iter().map(|s| s.parse()).collect()
+}
+
+/// Tracks when a source needs to be loaded again.
+#[derive(Debug)]
+struct SourceState {
+    source: DenylistSource,
+    next_fetch: Instant,
+    modified: Option<SystemTime>,
+}
+
+impl SourceState {
+    /// Loads the source if it is due, returns `None` otherwise.
+    async fn poll(&mut self) -> Option<Result<Denylist>> {
+        match &self.source {
+            DenylistSource::File(path) => {
+                let modified = match tokio::fs::metadata(path).await.and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    Err(e) => {
+                        // only report a missing file once, not on every poll
+                        if self.modified.take().is_some() || self.next_fetch <= Instant::now() {
+                            self.next_fetch = Instant::now() + BAD_BITS_UPDATE_INTERVAL;
+                            return Some(Err(anyhow!(e)));
                         }
-                    } else {
-                        error!("Failed to fetch denylist: {}", res.status());
+                        return None;
                     }
-                    tokio::time::sleep(BAD_BITS_UPDATE_INTERVAL).await;
+                };
+                if self.modified == Some(modified) {
+                    return None;
+                }
+                self.modified = Some(modified);
+                Some(load_file(path).await)
+            }
+            DenylistSource::Url(url) => {
+                if self.next_fetch > Instant::now() {
+                    return None;
                 }
+                let res = fetch_url(url).await;
+                self.next_fetch = Instant::now()
+                    + match res {
+                        Ok(_) => BAD_BITS_UPDATE_INTERVAL,
+                        Err(_) => BAD_BITS_RETRY_INTERVAL,
+                    };
+                Some(res)
+            }
+        }
+    }
+}
+
+async fn load_file(path: &std::path::Path) -> Result<Denylist> {
+    let size = tokio::fs::metadata(path).await?.len();
+    ensure!(size <= MAX_DENY_LIST_SIZE, "denylist too large: {}", size);
+    let content = tokio::fs::read_to_string(path).await?;
+    Denylist::parse(&content)
+}
+
+async fn fetch_url(url: &str) -> Result<Denylist> {
+    let client = reqwest::Client::builder()
+        .timeout(BAD_BITS_FETCH_TIMEOUT)
+        .build()?;
+    let res = client.get(url).send().await?;
+    ensure!(
+        res.status().is_success(),
+        "failed to fetch denylist: {}",
+        res.status()
+    );
+    if let Some(len) = res.content_length() {
+        ensure!(len <= MAX_DENY_LIST_SIZE, "denylist too large: {}", len);
+    }
+    let body = res.bytes().await?;
+    ensure!(
+        body.len() as u64 <= MAX_DENY_LIST_SIZE,
+        "denylist too large: {}",
+        body.len()
+    );
+    Denylist::parse(std::str::from_utf8(&body)?)
+}
+
+/// Keeps the denylists from `sources` up to date.
+///
+/// Remote lists are fetched every few hours, local files are reloaded as soon as they change.
+/// A list which fails to load keeps its previous version. Sources are polled concurrently,
+/// so a slow remote list does not hold up the others.
+pub fn spawn_bad_bits_updater(
+    bad_bits: Arc<Option<RwLock<BadBits>>>,
+    sources: Vec<DenylistSource>,
+) -> Option<JoinHandle<()>> {
+    bad_bits.is_some().then(|| {
+        tokio::spawn(async move {
+            let bbits = match bad_bits.as_ref() {
+                Some(bbits) => bbits,
+                None => return,
+            };
+            futures::future::join_all(
+                sources
+                    .into_iter()
+                    .map(|source| update_source(bbits, source)),
+            )
+            .await;
+        })
+    })
+}
+
+async fn update_source(bbits: &RwLock<BadBits>, source: DenylistSource) {
+    let mut state = SourceState {
+        source,
+        next_fetch: Instant::now(),
+        modified: None,
+    };
+    let name = state.source.to_string();
+    loop {
+        match state.poll().await {
+            Some(Ok(list)) => {
+                info!("updated denylist {}: len={}", name, list.len());
+                record_denylist_update(&name, Some(list.len() as u64));
+                bbits.write().await.set_list(&state.source, list);
+            }
+            Some(Err(e)) => {
+                warn!("failed to update denylist {}: {:?}", name, e);
+                record_denylist_update(&name, None);
             }
-        }));
+            None => {}
+        }
+        tokio::time::sleep(BAD_BITS_POLL_INTERVAL).await;
     }
-    None
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
+    use futures::TryStreamExt;
     use hex_literal::hex;
     use http::StatusCode;
     use iroh_resolver::dns_resolver::Config as DnsResolverConfig;
+    use iroh_resolver::test_utils::RecordLoader;
     use iroh_rpc_client::{Client as RpcClient, Config as RpcClientConfig};
+    use iroh_unixfs::builder::{DirectoryBuilder, FileBuilder};
     use iroh_unixfs::content_loader::{FullLoader, FullLoaderConfig, GatewayUrl};
+    use iroh_unixfs::unixfs::UnixfsNode;
 
     use super::*;
     use crate::config::Config;
@@ -158,6 +499,197 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_denylist_sources() {
+        assert_eq!(
+            denylist_sources(&[]).unwrap(),
+            vec![DenylistSource::Url(DEFAULT_DENY_LIST_URI.to_string())]
+        );
+        assert_eq!(
+            denylist_sources(&[
+                "https://example.com/deny.txt".to_string(),
+                "/etc/iroh/deny.txt".to_string(),
+                "file://local.deny".to_string(),
+            ])
+            .unwrap(),
+            vec![
+                DenylistSource::Url("https://example.com/deny.txt".to_string()),
+                DenylistSource::File(PathBuf::from("/etc/iroh/deny.txt")),
+                DenylistSource::File(PathBuf::from("local.deny")),
+            ]
+        );
+        assert!(denylist_sources(&["https://".to_string()]).is_err());
+    }
+
+    #[test]
+    fn parse_denylist() {
+        let cid =
+            Cid::from_str("bafkreidyeivj7adnnac6ljvzj2e3rd5xdw3revw4da7mx2ckrstapoupoq").unwrap();
+        let cid_v0 = Cid::from_str("QmdZ8zoh1iCsk8TdSAWN49tziH5MMn8XPvJcWmpFD1ygB7").unwrap();
+        let cid_paths =
+            Cid::from_str("bafkreieq5jui4j25lacwomsqgjeswwl3y5zcdrresptwgmfylxo2depppq").unwrap();
+        let anchored =
+            Cid::from_str("bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi").unwrap();
+        let anchor = hex::encode(BadBits::to_anchor(anchored, "bad/foo.jpeg").value);
+
+        let list = Denylist::parse(&format!(
+            "version: 1\n\
+             name: test\n\
+             ---\n\
+             # comment\n\
+             {cid}\n\
+             /ipfs/{cid_v0}\n\
+             /ipfs/{cid_paths}/exact/file.txt\n\
+             /ipfs/{cid_paths}/dir/*\n\
+             /ipns/example.com\n\
+             /ipns/docs.example.com/private/*\n\
+             //{anchor}\n\
+             !/ipfs/{cid}\n\
+             /ipfs/not-a-cid\n\
+             /unsupported/rule\n"
+        ))
+        .unwrap();
+        assert_eq!(list.len(), 7);
+
+        assert!(list.is_bad(&cid, ""));
+        assert!(list.is_bad(&cid, "any/path"));
+        assert!(list.is_bad(&cid_v0, ""));
+        assert!(list.is_bad(&cid_v0.into_v1().unwrap(), "/foo"));
+
+        assert!(!list.is_bad(&cid_paths, ""));
+        assert!(list.is_bad(&cid_paths, "exact/file.txt"));
+        assert!(list.is_bad(&cid_paths, "/exact/file.txt/"));
+        assert!(!list.is_bad(&cid_paths, "exact/file.txt/more"));
+        assert!(!list.is_bad(&cid_paths, "exact"));
+        assert!(list.is_bad(&cid_paths, "dir"));
+        assert!(list.is_bad(&cid_paths, "dir/a/b"));
+        assert!(!list.is_bad(&cid_paths, "directory"));
+
+        assert!(list.is_bad(&anchored, "bad/foo.jpeg"));
+        assert!(!list.is_bad(&anchored, "good/foo.jpeg"));
+
+        assert!(list.is_bad_name("example.com", ""));
+        assert!(list.is_bad_name("example.com", "index.html"));
+        assert!(!list.is_bad_name("docs.example.com", ""));
+        assert!(list.is_bad_name("docs.example.com", "private/secret.txt"));
+        assert!(!list.is_bad_name("other.example.com", ""));
+    }
+
+    #[test]
+    fn denylist_peer_id_names() {
+        let peer_id = libp2p::PeerId::random();
+        let key = Cid::new_v1(
+            Codec::Libp2pKey.into(),
+            cid::multihash::Multihash::from_bytes(&peer_id.to_bytes()).unwrap(),
+        );
+        let base36 = key
+            .to_string_of_base(cid::multibase::Base::Base36Lower)
+            .unwrap();
+        let other = libp2p::PeerId::random();
+
+        let list = Denylist::parse(&format!("/ipns/{peer_id}\n")).unwrap();
+        assert!(list.is_bad_name(&peer_id.to_base58(), ""));
+        assert!(list.is_bad_name(&base36, "index.html"));
+        // the root of a parsed path, as checked by the handlers
+        let path = Path::from_parts(SCHEME_IPNS, &peer_id.to_base58(), "").unwrap();
+        assert!(list.is_bad_name(&path.root().to_string(), ""));
+        assert!(!list.is_bad_name(&other.to_base58(), ""));
+
+        let list = Denylist::parse(&format!("/ipns/{base36}/private/*\n")).unwrap();
+        assert!(list.is_bad_name(&peer_id.to_base58(), "private/key.txt"));
+        assert!(list.is_bad_name(&key.to_string(), "private/key.txt"));
+        assert!(!list.is_bad_name(&peer_id.to_base58(), "public/index.html"));
+    }
+
+    #[test]
+    fn parse_json_denylist() {
+        let cid =
+            Cid::from_str("bafkreidyeivj7adnnac6ljvzj2e3rd5xdw3revw4da7mx2ckrstapoupoq").unwrap();
+        let list = Denylist::parse(
+            r#"[{"anchor": "d572cfd7fca1f89293f2d71270c51d82445b4502207a0df0707586b3e799521b"}]"#,
+        )
+        .unwrap();
+        assert_eq!(list.len(), 1);
+        assert!(list.is_bad(&cid, ""));
+        assert!(!list.is_bad(&cid, "test"));
+
+        assert!(Denylist::parse(r#"[{"anchor": "not hex"}]"#).is_err());
+        assert!(Denylist::parse(r#"[{"anchor": "d572"}]"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn reload_denylist_file() {
+        let cid =
+            Cid::from_str("bafkreidyeivj7adnnac6ljvzj2e3rd5xdw3revw4da7mx2ckrstapoupoq").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deny.txt");
+        let mut state = SourceState {
+            source: DenylistSource::File(path.clone()),
+            next_fetch: Instant::now(),
+            modified: None,
+        };
+
+        // a missing file is reported once
+        assert!(state.poll().await.unwrap().is_err());
+        assert!(state.poll().await.is_none());
+
+        tokio::fs::write(&path, format!("/ipfs/{cid}\n"))
+            .await
+            .unwrap();
+        let list = state.poll().await.unwrap().unwrap();
+        assert!(list.is_bad(&cid, ""));
+        // unchanged files are not loaded again
+        assert!(state.poll().await.is_none());
+
+        let mut bbits = BadBits::new();
+        bbits.set_list(&state.source, list);
+        assert!(bbits.is_bad(&cid, ""));
+        assert_eq!(bbits.len(), 1);
+
+        // force a different modification time, some filesystems only have second precision
+        state.modified = Some(SystemTime::UNIX_EPOCH);
+        tokio::fs::write(&path, "# emptied\n").await.unwrap();
+        let list = state.poll().await.unwrap().unwrap();
+        bbits.set_list(&state.source, list);
+        assert!(!bbits.is_bad(&cid, ""));
+        assert!(bbits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn hanging_denylist_url() {
+        let cid =
+            Cid::from_str("bafkreidyeivj7adnnac6ljvzj2e3rd5xdw3revw4da7mx2ckrstapoupoq").unwrap();
+        // accepts connections, but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/denylist.txt", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut conns = Vec::new();
+            while let Ok((conn, _)) = listener.accept().await {
+                conns.push(conn);
+            }
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deny.txt");
+        tokio::fs::write(&path, format!("/ipfs/{cid}\n"))
+            .await
+            .unwrap();
+
+        let bad_bits = Arc::new(Some(RwLock::new(BadBits::new())));
+        let sources = vec![DenylistSource::Url(url), DenylistSource::File(path)];
+        let updater = spawn_bad_bits_updater(Arc::clone(&bad_bits), sources).unwrap();
+        let bbits = bad_bits.as_ref().as_ref().unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !bbits.read().await.is_bad(&cid, "") {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("the local file is loaded while the url hangs");
+
+        updater.abort();
+        server.abort();
+    }
+
     #[tokio::test]
     async fn gateway_bad_bits() {
         let bad_cid =
@@ -209,7 +741,7 @@ mod tests {
         )
         .await
         .unwrap();
-        let server = handler.server();
+        let server = handler.server().unwrap();
         let addr = server.local_addr();
         let core_task = tokio::spawn(async move {
             server.await.unwrap();
@@ -290,4 +822,85 @@ mod tests {
         core_task.abort();
         core_task.await.unwrap_err();
     }
+
+    #[tokio::test]
+    async fn gateway_bad_bits_resolved() {
+        let file = FileBuilder::new()
+            .name("bad.txt")
+            .content_bytes(b"bad".to_vec())
+            .build()
+            .await
+            .unwrap();
+        let dir = DirectoryBuilder::new()
+            .name("dir")
+            .add_file(file)
+            .build()
+            .await
+            .unwrap();
+        let blocks: Vec<_> = dir.encode().try_collect().await.unwrap();
+        let root = blocks.last().unwrap();
+        let dir_cid = *root.cid();
+        let bad_cid = UnixfsNode::decode(&dir_cid, root.data().clone())
+            .unwrap()
+            .links()
+            .next()
+            .unwrap()
+            .unwrap()
+            .cid;
+        let blocks = blocks
+            .into_iter()
+            .map(|block| (*block.cid(), block.data().clone()))
+            .collect();
+        let (loader, name) = RecordLoader::with_name(blocks, bad_cid, Duration::from_secs(60));
+
+        let mut bbits = BadBits::new();
+        let source = DenylistSource::File("test.deny".into());
+        bbits.set_list(&source, Denylist::parse(&format!("{bad_cid}\n")).unwrap());
+
+        let mut config = Config::new(
+            0,
+            RpcClientConfig {
+                gateway_addr: None,
+                p2p_addr: None,
+                store_addr: None,
+                channels: Some(1),
+            },
+        );
+        config.set_default_headers();
+        let handler = crate::core::Core::new(
+            Arc::new(config),
+            "irpc://0.0.0.0:0".parse().unwrap(),
+            Arc::new(Some(RwLock::new(bbits))),
+            loader,
+            DnsResolverConfig::default(),
+        )
+        .await
+        .unwrap();
+        let server = handler.server().unwrap();
+        let addr = server.local_addr();
+        let core_task = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+
+        let get = |path: String| async move {
+            let uri = hyper::Uri::builder()
+                .scheme("http")
+                .authority(format!("localhost:{}", addr.port()))
+                .path_and_query(path)
+                .build()
+                .unwrap();
+            hyper::Client::new().get(uri).await.unwrap().status()
+        };
+        // the directory itself is fine
+        assert_eq!(StatusCode::OK, get(format!("/ipfs/{dir_cid}")).await);
+        // the denied file is blocked as a path below it, and through a name resolving to it
+        assert_eq!(
+            StatusCode::GONE,
+            get(format!("/ipfs/{dir_cid}/bad.txt")).await
+        );
+        assert_eq!(StatusCode::GONE, get(format!("/ipns/{name}")).await);
+
+        core_task.abort();
+        core_task.await.unwrap_err();
+    }
 }
@@ -1,12 +1,19 @@
+use std::num::NonZeroUsize;
 use std::ops::Range;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::Poll;
+use std::time::SystemTime;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Result};
+use async_recursion::async_recursion;
 use bytes::Bytes;
-use cid::Cid;
-use futures::{StreamExt, TryStream};
-use http::HeaderMap;
+use cid::{multihash::Code, Cid};
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStream, TryStreamExt,
+};
+use http::{HeaderMap, StatusCode};
 use iroh_car::{CarHeader, CarWriter};
 use iroh_metrics::{
     core::{MObserver, MRecorder},
@@ -15,19 +22,41 @@ use iroh_metrics::{
     resolver::OutMetrics,
 };
 use iroh_resolver::dns_resolver::Config;
-use iroh_resolver::resolver::{CidOrDomain, Metadata, Out, OutPrettyReader, OutType, Resolver};
-use iroh_unixfs::{content_loader::ContentLoader, Source};
+use iroh_resolver::resolver::{
+    CidOrDomain, LinkNotFound, Metadata, Out, OutContent, OutPrettyReader, OutType, Resolver,
+};
+use iroh_unixfs::{
+    builder::{DirectoryBuilder, FileBuilder},
+    codecs::Codec,
+    content_loader::ContentLoader,
+    unixfs::CidBuilder,
+    Block, Link, Source,
+};
+use iroh_util::IDENTITY_HASH_CODE;
+use libipld::{codec::Encode, IpldCodec};
+use lru::LruCache;
 use mime::Mime;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWrite};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+};
 use tokio_util::io::ReaderStream;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use crate::response::ResponseFormat;
-use crate::{constants::RECURSION_LIMIT, handler_params::GetParams};
+use crate::{
+    constants::{
+        MAX_CONCURRENT_WRITE_CHECKS, MAX_RAW_BLOCK_SIZE, MAX_TAR_BUFFERED_FILE_SIZE,
+        RECURSION_LIMIT, REDIRECTS_CACHE_SIZE, STORE_BATCH_SIZE,
+    },
+    handler_params::GetParams,
+    redirects::{Redirects, MAX_REDIRECTS_FILE_SIZE, REDIRECTS_FILE},
+};
 
 #[derive(Debug, Clone)]
 pub struct Client<T: ContentLoader> {
     pub(crate) resolver: Resolver<T>,
+    /// Parsed `_redirects` rules by website root, `None` for roots without a valid file.
+    redirects: Arc<Mutex<LruCache<Cid, Option<Arc<Redirects>>>>>,
 }
 
 #[derive(Debug)]
@@ -92,6 +121,9 @@ impl<T: ContentLoader + std::marker::Unpin> Client<T> {
     pub fn new(rpc_client: &T, dns_resolver_config: Config) -> Self {
         Self {
             resolver: Resolver::with_dns_resolver(rpc_client.clone(), dns_resolver_config),
+            redirects: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(REDIRECTS_CACHE_SIZE).unwrap(),
+            ))),
         }
     }
 
@@ -100,18 +132,14 @@ impl<T: ContentLoader + std::marker::Unpin> Client<T> {
         &self,
         path: iroh_resolver::resolver::Path,
         format: Option<ResponseFormat>,
-    ) -> Result<Out, String> {
+    ) -> Result<Out> {
         info!("retrieve path metadata {}", path);
         if let Some(f) = format {
-            if f == ResponseFormat::Raw {
-                return self
-                    .resolver
-                    .resolve_raw(path)
-                    .await
-                    .map_err(|e| e.to_string());
+            if f == ResponseFormat::Raw || f.is_codec() {
+                return self.resolver.resolve_raw(path).await;
             }
         }
-        self.resolver.resolve(path).await.map_err(|e| e.to_string())
+        self.resolver.resolve(path).await
     }
 
     #[tracing::instrument(skip(self))]
@@ -126,7 +154,9 @@ impl<T: ContentLoader + std::marker::Unpin> Client<T> {
         let path_metadata = if let Some(path_metadata) = path_metadata {
             path_metadata
         } else {
-            self.retrieve_path_metadata(path.clone(), None).await?
+            self.retrieve_path_metadata(path.clone(), None)
+                .await
+                .map_err(|e| e.to_string())?
         };
         let metadata = path_metadata.metadata().clone();
         record_ttfb_metrics(start_time, &metadata.source);
@@ -184,6 +214,26 @@ impl<T: ContentLoader + std::marker::Unpin> Client<T> {
         Ok(body)
     }
 
+    /// Streams the directory tree at `path` as a TAR archive.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_tar_recursive(
+        self,
+        path: iroh_resolver::resolver::Path,
+        start_time: std::time::Instant,
+    ) -> Result<axum::body::StreamBody<ReaderStream<tokio::io::DuplexStream>>, String> {
+        info!("get tar {}", path);
+        let (writer, reader) = tokio::io::duplex(1024 * 64);
+        let body = axum::body::StreamBody::new(ReaderStream::new(reader));
+        let client = self.clone();
+        tokio::task::spawn(async move {
+            if let Err(e) = fetch_tar_recursive(&client.resolver, path, writer, start_time).await {
+                warn!("failed to load recursively: {:?}", e);
+            }
+        });
+
+        Ok(body)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn get_file_recursive(
         self,
@@ -234,15 +284,363 @@ impl<T: ContentLoader + std::marker::Unpin> Client<T> {
 }
 
 impl<T: ContentLoader> Client<T> {
+    /// Returns the rules of the `_redirects` file at the website root `root`, unless it has
+    /// none or an invalid one.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_redirects(&self, root: Cid) -> Result<Option<Arc<Redirects>>> {
+        if let Some(redirects) = self.redirects.lock().unwrap().get(&root) {
+            return Ok(redirects.clone());
+        }
+
+        let path =
+            iroh_resolver::resolver::Path::from_parts("ipfs", &root.to_string(), REDIRECTS_FILE)?;
+        let redirects = match self.resolver.resolve(path).await {
+            Ok(out) => {
+                let too_large =
+                    matches!(out.metadata().size, Some(size) if size > MAX_REDIRECTS_FILE_SIZE);
+                // the size is not known for every kind of file, so never read more than allowed
+                let mut content = Vec::new();
+                if !too_large {
+                    out.pretty(self.resolver.clone(), OutMetrics::default(), None)?
+                        .take(MAX_REDIRECTS_FILE_SIZE + 1)
+                        .read_to_end(&mut content)
+                        .await?;
+                }
+                match parse_redirects(too_large, &content) {
+                    Ok(redirects) => Some(Arc::new(redirects)),
+                    // the file of a root never changes, so the website is served without
+                    // rules, and this is only logged until the root drops out of the cache
+                    Err(e) => {
+                        warn!("ignoring invalid {} in {}: {:?}", REDIRECTS_FILE, root, e);
+                        None
+                    }
+                }
+            }
+            // a missing file is cached as well, failures to fetch it are retried
+            Err(e) if e.downcast_ref::<LinkNotFound>().is_some() => None,
+            Err(e) => return Err(e),
+        };
+        self.redirects.lock().unwrap().put(root, redirects.clone());
+        Ok(redirects)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn has_file_locally(&self, cid: &Cid) -> Result<bool> {
         info!("has cid {}", cid);
         self.resolver.has_cid(cid).await
     }
+
+    /// Adds `content` as a UnixFS file, or as a single raw block, and returns its cid.
+    ///
+    /// The new content is pinned, so garbage collection does not remove it.
+    #[tracing::instrument(skip(self, content))]
+    pub async fn add<R>(&self, content: R, raw: bool) -> Result<Cid, WriteError>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let mut written = Vec::new();
+        let blocks: BoxStream<'static, Result<Block>> = if raw {
+            let mut data = Vec::new();
+            content
+                .take(MAX_RAW_BLOCK_SIZE as u64 + 1)
+                .read_to_end(&mut data)
+                .await
+                .map_err(anyhow::Error::from)?;
+            if data.len() > MAX_RAW_BLOCK_SIZE {
+                return Err(WriteError::RawBlockTooLarge);
+            }
+            let cid = CidBuilder::default().build(Codec::Raw, &data);
+            stream::iter(Some(Ok(Block::new(cid, data.into(), Vec::new())))).boxed()
+        } else {
+            let file = FileBuilder::new()
+                .name("")
+                .content_reader(content)
+                .build()
+                .await?;
+            file.encode().await?.boxed()
+        };
+        let (cid, _) = self.store_blocks(blocks, &mut written).await?;
+        self.pin_written(cid, &written, None).await?;
+        info!("added {}", cid);
+        Ok(cid)
+    }
+
+    /// Adds `content` as a UnixFS file at `path` below the directory `root`, and returns
+    /// the cid of the new root.
+    ///
+    /// Missing directories along the path are created, an existing entry at `path` is
+    /// replaced. The pin of `root` is moved to the new root.
+    #[tracing::instrument(skip(self, content))]
+    pub async fn put_file<R>(
+        &self,
+        root: Cid,
+        path: &[String],
+        content: R,
+    ) -> Result<Cid, WriteError>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        let mut written = Vec::new();
+        let file = FileBuilder::new()
+            .name("")
+            .content_reader(content)
+            .build()
+            .await?;
+        let (cid, tsize) = self
+            .store_blocks(file.encode().await?.boxed(), &mut written)
+            .await?;
+        let link = Link {
+            cid,
+            name: None,
+            tsize: Some(tsize),
+        };
+        let new_root = self
+            .update_directory(Some(root), path, Some(link), &mut written)
+            .await?;
+        self.pin_written(new_root.cid, &written, Some(root)).await?;
+        info!("put {} into new root {}", cid, new_root.cid);
+        Ok(new_root.cid)
+    }
+
+    /// Removes the entry at `path` below the directory `root`, and returns the cid of the
+    /// new root. The pin of `root` is moved to the new root.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_path(&self, root: Cid, path: &[String]) -> Result<Cid, WriteError> {
+        let mut written = Vec::new();
+        let new_root = self
+            .update_directory(Some(root), path, None, &mut written)
+            .await?;
+        self.pin_written(new_root.cid, &written, Some(root)).await?;
+        info!("new root {}", new_root.cid);
+        Ok(new_root.cid)
+    }
+
+    /// Pins `root`, the result of a write, and unpins the root it replaces.
+    ///
+    /// The blocks are written without holding off garbage collection, so a slow client can
+    /// not stall it. Instead, all `written` blocks are checked to still be there once it is
+    /// held off.
+    async fn pin_written(
+        &self,
+        root: Cid,
+        written: &[Cid],
+        replaced: Option<Cid>,
+    ) -> Result<(), WriteError> {
+        let loader = self.resolver.loader();
+        let _guard = loader.gc_guard().await?;
+        let mut checks = stream::iter(written)
+            .map(|cid| async move { loader.has_cid(cid).await.map(|has| (cid, has)) })
+            .buffer_unordered(MAX_CONCURRENT_WRITE_CHECKS);
+        while let Some(check) = checks.next().await {
+            let (cid, has) = check?;
+            if !has {
+                return Err(anyhow!(
+                    "{} was garbage collected while it was written, retry the request",
+                    cid
+                )
+                .into());
+            }
+        }
+        loader.pin(&root).await?;
+        if let Some(replaced) = replaced {
+            // fails if the old root was not pinned by itself, which leaves nothing to move
+            if let Err(err) = loader.unpin(&replaced).await {
+                debug!("not unpinning {}: {:?}", replaced, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the entry at `path` below the directory `dir` to `entry`, or removes it, and
+    /// stores the new directories up to `dir`. Returns the unnamed link to the new `dir`.
+    ///
+    /// `None` for `dir` creates a new directory. The mode and mtime of existing directories
+    /// are kept.
+    #[async_recursion]
+    async fn update_directory(
+        &self,
+        dir: Option<Cid>,
+        path: &[String],
+        entry: Option<Link>,
+        written: &mut Vec<Cid>,
+    ) -> Result<Link, WriteError> {
+        let (name, rest) = path
+            .split_first()
+            .ok_or_else(|| anyhow!("missing name of the entry"))?;
+        let (mut links, mode, mtime) = match dir {
+            Some(dir) => self.read_directory(dir).await?,
+            None => (Vec::new(), None, None),
+        };
+        let pos = links
+            .iter()
+            .position(|link| link.name.as_deref() == Some(name.as_str()));
+
+        let new_link = if rest.is_empty() {
+            entry
+        } else {
+            let child = pos.map(|pos| links[pos].cid);
+            if child.is_none() && entry.is_none() {
+                return Err(WriteError::NotFound(name.clone()));
+            }
+            Some(self.update_directory(child, rest, entry, written).await?)
+        };
+        match (pos, new_link) {
+            (Some(pos), Some(link)) => {
+                links[pos] = Link {
+                    name: Some(name.clone()),
+                    ..link
+                }
+            }
+            (None, Some(link)) => links.push(Link {
+                name: Some(name.clone()),
+                ..link
+            }),
+            (Some(pos), None) => {
+                links.remove(pos);
+            }
+            (None, None) => return Err(WriteError::NotFound(name.clone())),
+        }
+
+        let cid_builder = dir.map(|dir| cid_builder_of(&dir)).unwrap_or_default();
+        let mut builder = DirectoryBuilder::new().cid_builder(cid_builder);
+        if let Some(mode) = mode {
+            builder = builder.mode(mode);
+        }
+        if let Some(mtime) = mtime {
+            builder = builder.mtime(mtime);
+        }
+        let links_size: u64 = links.iter().filter_map(|link| link.tsize).sum();
+        let dir = links
+            .into_iter()
+            .fold(builder, DirectoryBuilder::add_link)
+            .build()
+            .await?;
+        let (cid, tsize) = self.store_blocks(dir.encode(), written).await?;
+        Ok(Link {
+            cid,
+            name: None,
+            tsize: Some(tsize + links_size),
+        })
+    }
+
+    /// Lists the links of a UnixFS directory, together with its mode and mtime.
+    async fn read_directory(
+        &self,
+        dir: Cid,
+    ) -> Result<(Vec<Link>, Option<u32>, Option<SystemTime>), WriteError> {
+        let out = self
+            .resolver
+            .resolve(iroh_resolver::resolver::Path::from_cid(dir))
+            .await?;
+        let links = out
+            .unixfs_read_dir(&self.resolver, OutMetrics::default())?
+            .ok_or(WriteError::NotADirectory(dir))?
+            .try_collect()
+            .await?;
+        let metadata = out.metadata();
+        Ok((links, metadata.mode, metadata.mtime))
+    }
+
+    /// Writes `blocks` to the store in batches, returns the cid of the last block, which is
+    /// the root, and the total size of all blocks.
+    ///
+    /// The cids of the blocks are added to `written`.
+    async fn store_blocks(
+        &self,
+        mut blocks: BoxStream<'_, Result<Block>>,
+        written: &mut Vec<Cid>,
+    ) -> Result<(Cid, u64)> {
+        let loader = self.resolver.loader();
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+        let mut total_size = 0;
+        let mut root = None;
+        while let Some(block) = blocks.next().await {
+            let block = block?;
+            let size = block.data().len() as u64;
+            if batch_size + size > STORE_BATCH_SIZE {
+                loader.store_blocks(std::mem::take(&mut batch)).await?;
+                batch_size = 0;
+            }
+            root = Some(*block.cid());
+            written.push(*block.cid());
+            batch_size += size;
+            total_size += size;
+            batch.push(block);
+        }
+        loader.store_blocks(batch).await?;
+        let root = root.ok_or_else(|| anyhow!("no blocks to store"))?;
+        Ok((root, total_size))
+    }
+}
+
+/// Parses the content of a `_redirects` file, which must not exceed the size limit.
+fn parse_redirects(too_large: bool, content: &[u8]) -> Result<Redirects> {
+    ensure!(
+        !too_large && content.len() as u64 <= MAX_REDIRECTS_FILE_SIZE,
+        "{} is too large",
+        REDIRECTS_FILE
+    );
+    Redirects::parse(std::str::from_utf8(content)?)
+}
+
+/// The cid version and hash function of an existing block, to encode its replacement with.
+fn cid_builder_of(cid: &Cid) -> CidBuilder {
+    if cid.hash().code() == IDENTITY_HASH_CODE {
+        return CidBuilder::default();
+    }
+    Code::try_from(cid.hash().code())
+        .ok()
+        .and_then(|hash| CidBuilder::new(cid.version(), hash).ok())
+        .unwrap_or_default()
+}
+
+/// Why a write through the gateway failed.
+#[derive(Debug)]
+pub enum WriteError {
+    /// There is no entry at the path to remove, or to put a file below.
+    NotFound(String),
+    /// An entry along the path is not a directory.
+    NotADirectory(Cid),
+    /// The content is too large for a single raw block.
+    RawBlockTooLarge,
+    /// Loading or storing blocks failed.
+    Other(anyhow::Error),
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::NotFound(name) => write!(f, "failed to find {name}"),
+            WriteError::NotADirectory(cid) => write!(f, "{cid} is not a directory"),
+            WriteError::RawBlockTooLarge => {
+                write!(f, "raw blocks are limited to {MAX_RAW_BLOCK_SIZE} bytes")
+            }
+            WriteError::Other(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::Other(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for WriteError {
+    fn from(err: anyhow::Error) -> Self {
+        WriteError::Other(err)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct IpfsRequest {
+    /// Status of a successful response, changed by `_redirects` rules which serve
+    /// custom error pages.
+    pub status_code: StatusCode,
     pub format: ResponseFormat,
     pub cid: CidOrDomain,
     pub resolved_path: iroh_resolver::resolver::Path,
@@ -293,13 +691,158 @@ where
     Ok(())
 }
 
+/// Writes the tree at `path` as a TAR archive, with all entries below a directory named
+/// after the last segment of `path`.
+///
+/// Only one file is read at a time, so memory use does not depend on the size of the tree.
+async fn fetch_tar_recursive<T, W>(
+    resolver: &Resolver<T>,
+    path: iroh_resolver::resolver::Path,
+    writer: W,
+    start_time: std::time::Instant,
+) -> Result<(), anyhow::Error>
+where
+    T: ContentLoader,
+    W: AsyncWrite + Send + Unpin,
+{
+    let root_name = match path.tail().iter().rev().find(|s| !s.is_empty()) {
+        Some(name) => name.clone(),
+        None => path.root().to_string(),
+    };
+    let root_depth = path.tail().iter().filter(|s| !s.is_empty()).count();
+    let stream = resolver.resolve_recursive_with_paths(path);
+    tokio::pin!(stream);
+
+    let mut builder = tokio_tar::Builder::new(writer);
+    while let Some(res) = stream.next().await {
+        let (entry_path, out) = res?;
+        let metadata = out.metadata().clone();
+        record_ttfb_metrics(start_time, &metadata.source);
+        let name = std::iter::once(root_name.as_str())
+            .chain(
+                entry_path
+                    .tail()
+                    .iter()
+                    .filter(|s| !s.is_empty())
+                    .skip(root_depth)
+                    .map(String::as_str),
+            )
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut header = tokio_tar::Header::new_gnu();
+        let mtime = metadata
+            .mtime
+            .and_then(|mtime| mtime.duration_since(std::time::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        header.set_mtime(mtime.as_secs());
+        let om = OutMetrics { start: start_time };
+        if out.is_dir() {
+            header.set_entry_type(tokio_tar::EntryType::Directory);
+            header.set_mode(metadata.mode.unwrap_or(0o755));
+            header.set_size(0);
+            builder
+                .append_data(&mut header, format!("{name}/"), tokio::io::empty())
+                .await?;
+        } else if out.is_symlink() {
+            let mut target = String::new();
+            out.pretty(resolver.clone(), om, None)?
+                .read_to_string(&mut target)
+                .await?;
+            header.set_entry_type(tokio_tar::EntryType::Symlink);
+            header.set_mode(0o777);
+            header.set_size(0);
+            header.set_link_name(&target)?;
+            builder
+                .append_data(&mut header, name, tokio::io::empty())
+                .await?;
+        } else {
+            header.set_entry_type(tokio_tar::EntryType::Regular);
+            header.set_mode(metadata.mode.unwrap_or(0o644));
+            let size = unixfs_file_size(&out);
+            let reader = out.pretty(resolver.clone(), om, None)?;
+            match size {
+                Some(size) => {
+                    header.set_size(size);
+                    builder.append_data(&mut header, name, reader).await?;
+                }
+                None => {
+                    // the size is needed up front, which only reading the file tells
+                    let mut content = Vec::new();
+                    reader
+                        .take(MAX_TAR_BUFFERED_FILE_SIZE + 1)
+                        .read_to_end(&mut content)
+                        .await?;
+                    ensure!(
+                        content.len() as u64 <= MAX_TAR_BUFFERED_FILE_SIZE,
+                        "{} is too large for a file of unknown size",
+                        name
+                    );
+                    header.set_size(content.len() as u64);
+                    builder.append_data(&mut header, name, &content[..]).await?;
+                }
+            }
+        }
+    }
+    builder.into_inner().await?.shutdown().await?;
+    Ok(())
+}
+
+/// The size of a file, from its metadata or otherwise from the sizes of its blocks.
+fn unixfs_file_size(out: &Out) -> Option<u64> {
+    if let Some(size) = out.metadata().size {
+        return Some(size);
+    }
+    let node = match &out.content {
+        OutContent::Unixfs(node) => node,
+        _ => return None,
+    };
+    if let Some(size) = node.size() {
+        return Some(size as u64);
+    }
+    let blocksizes = node.blocksizes();
+    if blocksizes.is_empty() || blocksizes.len() != node.links().count() {
+        return None;
+    }
+    Some(blocksizes.iter().sum())
+}
+
+/// Encodes a resolved IPLD node in the codec of `format`, returning the codec of the node
+/// and the encoded bytes.
+///
+/// Nodes already in the requested codec are returned as they are, everything else is
+/// converted.
+pub(crate) fn encode_ipld(
+    content: &OutContent,
+    format: &ResponseFormat,
+) -> Result<(IpldCodec, Bytes)> {
+    let (codec, ipld, bytes) = match content {
+        OutContent::DagPb(ipld, bytes) => (IpldCodec::DagPb, ipld, bytes),
+        OutContent::DagCbor(ipld, bytes) => (IpldCodec::DagCbor, ipld, bytes),
+        OutContent::DagJson(ipld, bytes) => (IpldCodec::DagJson, ipld, bytes),
+        OutContent::Raw(ipld, bytes) => (IpldCodec::Raw, ipld, bytes),
+        OutContent::Unixfs(_) => bail!("not an IPLD node"),
+    };
+    let target = match format {
+        ResponseFormat::DagJson | ResponseFormat::Json => IpldCodec::DagJson,
+        ResponseFormat::DagCbor | ResponseFormat::Cbor => IpldCodec::DagCbor,
+        _ => bail!("{:?} is not an IPLD codec", format),
+    };
+    if codec == target {
+        return Ok((codec, bytes.clone()));
+    }
+    let mut converted = Vec::new();
+    ipld.encode(target, &mut converted)?;
+    Ok((codec, converted.into()))
+}
+
 fn record_ttfb_metrics(start_time: std::time::Instant, source: &Source) {
     record!(
         GatewayMetrics::TimeToFetchFirstBlock,
         start_time.elapsed().as_millis() as u64
     );
     match *source {
-        Source::Store(_) => observe!(
+        Source::Store(_) | Source::Inline => observe!(
             GatewayHistograms::TimeToFetchFirstBlockCached,
             start_time.elapsed().as_millis() as f64
         ),
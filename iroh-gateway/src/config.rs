@@ -1,3 +1,8 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
 use crate::constants::*;
 use anyhow::Result;
 use axum::http::{header::*, Method};
@@ -66,8 +71,16 @@ pub struct Config {
     pub public_url_base: String,
     /// default port to listen on
     pub port: u16,
+    /// Addresses to listen on, defaults to `0.0.0.0:{port}` when empty.
+    /// Eg `["127.0.0.1:9050", "[::1]:9050"]` to only accept local connections.
+    #[serde(default)]
+    pub listen_addrs: Vec<SocketAddr>,
     /// flag to toggle whether the gateway should use denylist on requests
     pub use_denylist: bool,
+    /// Denylists to load when `use_denylist` is set, local files or http(s) URLs.
+    /// Defaults to the badbits list of <https://badbits.dwebops.pub> when empty.
+    #[serde(default)]
+    pub denylists: Vec<String>,
     /// URL of gateways to be used by the racing resolver.
     /// Strings can either be urls or subdomain gateway roots
     /// values without https:// prefix are treated as subdomain gateways (eg: dweb.link)
@@ -78,6 +91,9 @@ pub struct Config {
     pub dns_resolver: DnsResolverConfig,
     /// Indexer node to use.
     pub indexer_endpoint: Option<String>,
+    /// CAR files to serve content from, without importing them into the store.
+    #[serde(default)]
+    pub car_files: Vec<PathBuf>,
     /// rpc addresses for the gateway & addresses for the rpc client to dial
     pub rpc_client: RpcClientConfig,
     // NOTE: for toml to serialize properly, the "table" values must be serialized at the end, and
@@ -85,9 +101,16 @@ pub struct Config {
     /// set of user provided headers to attach to all responses
     #[serde(with = "http_serde::header_map")]
     pub headers: HeaderMap,
+    /// Serves HTTPS instead of plain HTTP on all listeners
+    pub tls: Option<TlsConfig>,
+    /// Limits the requests of each client
+    pub rate_limit: Option<RateLimitConfig>,
     /// Redirects to subdomains for path requests
     #[serde(default)]
     pub redirect_to_subdomain: bool,
+    /// Accepts POST, PUT and DELETE requests to add content and create modified roots
+    #[serde(default)]
+    pub writable: bool,
 }
 
 impl Config {
@@ -96,12 +119,18 @@ impl Config {
             public_url_base: String::new(),
             headers: HeaderMap::new(),
             port,
+            listen_addrs: Vec::new(),
+            tls: None,
+            rate_limit: None,
             rpc_client,
             http_resolvers: None,
             dns_resolver: DnsResolverConfig::default(),
             indexer_endpoint: None,
+            car_files: Vec::new(),
             use_denylist: false,
+            denylists: Vec::new(),
             redirect_to_subdomain: false,
+            writable: false,
         }
     }
 
@@ -112,6 +141,107 @@ impl Config {
     pub fn rpc_addr(&self) -> Option<GatewayAddr> {
         self.rpc_client.gateway_addr.clone()
     }
+
+    /// The addresses to listen on, falling back to all interfaces on `port`.
+    pub fn listen_addrs(&self) -> Vec<SocketAddr> {
+        if self.listen_addrs.is_empty() {
+            vec![SocketAddr::from((Ipv4Addr::UNSPECIFIED, self.port))]
+        } else {
+            self.listen_addrs.clone()
+        }
+    }
+}
+
+/// Certificate and private key to terminate TLS with.
+///
+/// Both files are PEM encoded and reloaded when they change, so renewed certificates are
+/// picked up without a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// Certificate chain, starting with the certificate of the gateway.
+    pub cert_path: PathBuf,
+    /// Private key, in PKCS#8, PKCS#1 or SEC1 format.
+    pub key_path: PathBuf,
+}
+
+/// Limits applied to each client, identified by its IP address or an API token.
+///
+/// IPv6 clients are grouped by their /64 network, as they usually control all of it. A limit
+/// of `0` is disabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Sustained number of requests per second.
+    pub requests_per_second: u32,
+    /// Number of requests allowed in a burst, defaults to `requests_per_second`.
+    pub request_burst: u32,
+    /// Number of requests which can be in flight at the same time.
+    pub max_concurrent_requests: u32,
+    /// Sustained number of response body bytes per second.
+    pub bytes_per_second: u64,
+    /// Number of bytes allowed in a burst, defaults to `bytes_per_second`.
+    pub bytes_burst: u64,
+    /// Header carrying an API token, eg `x-api-key`.
+    pub token_header: Option<String>,
+    /// Requests with one of these tokens are limited per token instead of per IP address.
+    pub api_tokens: Vec<String>,
+    /// Take the client address from the `Forwarded` or `X-Forwarded-For` header, as set by
+    /// the last proxy. Only enable this behind a reverse proxy, clients could pick any
+    /// address otherwise.
+    pub trust_forwarded: bool,
+}
+
+impl Source for RateLimitConfig {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let mut map: Map<String, Value> = Map::new();
+        // same u64 deserialization issue as `port`, hence the signed ints
+        insert_into_config_map(
+            &mut map,
+            "requests_per_second",
+            self.requests_per_second as i64,
+        );
+        insert_into_config_map(&mut map, "request_burst", self.request_burst as i64);
+        insert_into_config_map(
+            &mut map,
+            "max_concurrent_requests",
+            self.max_concurrent_requests as i64,
+        );
+        insert_into_config_map(&mut map, "bytes_per_second", self.bytes_per_second as i64);
+        insert_into_config_map(&mut map, "bytes_burst", self.bytes_burst as i64);
+        if let Some(token_header) = &self.token_header {
+            insert_into_config_map(&mut map, "token_header", token_header.clone());
+        }
+        if !self.api_tokens.is_empty() {
+            insert_into_config_map(&mut map, "api_tokens", self.api_tokens.clone());
+        }
+        insert_into_config_map(&mut map, "trust_forwarded", self.trust_forwarded);
+        Ok(map)
+    }
+}
+
+impl Source for TlsConfig {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let mut map: Map<String, Value> = Map::new();
+        insert_into_config_map(
+            &mut map,
+            "cert_path",
+            self.cert_path.to_string_lossy().into_owned(),
+        );
+        insert_into_config_map(
+            &mut map,
+            "key_path",
+            self.key_path.to_string_lossy().into_owned(),
+        );
+        Ok(map)
+    }
 }
 
 impl From<ServerConfig> for Config {
@@ -172,12 +302,18 @@ impl Default for Config {
             public_url_base: String::new(),
             headers: HeaderMap::new(),
             port: DEFAULT_PORT,
+            listen_addrs: Vec::new(),
+            tls: None,
+            rate_limit: None,
             rpc_client,
             http_resolvers: None,
             dns_resolver: DnsResolverConfig::default(),
             indexer_endpoint: None,
+            car_files: Vec::new(),
             use_denylist: false,
+            denylists: Vec::new(),
             redirect_to_subdomain: false,
+            writable: false,
         };
         t.set_default_headers();
         t
@@ -194,6 +330,7 @@ impl Source for Config {
         let mut map: Map<String, Value> = Map::new();
         insert_into_config_map(&mut map, "public_url_base", self.public_url_base.clone());
         insert_into_config_map(&mut map, "use_denylist", self.use_denylist);
+        insert_into_config_map(&mut map, "writable", self.writable);
         // Some issue between deserializing u64 & u16, converting this to
         // an signed int fixes the issue
         insert_into_config_map(&mut map, "port", self.port as i32);
@@ -206,6 +343,27 @@ impl Source for Config {
         if let Some(indexer_endpoint) = &self.indexer_endpoint {
             insert_into_config_map(&mut map, "indexer_endpoint", indexer_endpoint.clone());
         }
+        if !self.listen_addrs.is_empty() {
+            let listen_addrs: Vec<_> = self.listen_addrs.iter().map(|a| a.to_string()).collect();
+            insert_into_config_map(&mut map, "listen_addrs", listen_addrs);
+        }
+        if let Some(tls) = &self.tls {
+            insert_into_config_map(&mut map, "tls", tls.collect()?);
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            insert_into_config_map(&mut map, "rate_limit", rate_limit.collect()?);
+        }
+        if !self.denylists.is_empty() {
+            insert_into_config_map(&mut map, "denylists", self.denylists.clone());
+        }
+        if !self.car_files.is_empty() {
+            let car_files: Vec<_> = self
+                .car_files
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect();
+            insert_into_config_map(&mut map, "car_files", car_files);
+        }
         Ok(map)
     }
 }
@@ -223,6 +381,18 @@ impl crate::handlers::StateConfig for Config {
         self.port
     }
 
+    fn listen_addrs(&self) -> Vec<SocketAddr> {
+        Config::listen_addrs(self)
+    }
+
+    fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+
+    fn rate_limit(&self) -> Option<&RateLimitConfig> {
+        self.rate_limit.as_ref()
+    }
+
     fn user_headers(&self) -> &HeaderMap<HeaderValue> {
         &self.headers
     }
@@ -230,6 +400,10 @@ impl crate::handlers::StateConfig for Config {
     fn redirect_to_subdomain(&self) -> bool {
         self.redirect_to_subdomain
     }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
 }
 
 fn collect_headers(headers: &HeaderMap) -> Result<Map<String, Value>, ConfigError> {
@@ -330,6 +504,54 @@ mod tests {
         assert_eq!(expect, got);
     }
 
+    #[test]
+    fn test_listen_addrs_and_tls() {
+        let mut expect = Config::default();
+        assert_eq!(
+            expect.listen_addrs(),
+            vec!["0.0.0.0:9050".parse::<SocketAddr>().unwrap()]
+        );
+
+        expect.listen_addrs = vec![
+            "127.0.0.1:8080".parse().unwrap(),
+            "[::1]:8443".parse().unwrap(),
+        ];
+        expect.tls = Some(TlsConfig {
+            cert_path: PathBuf::from("/etc/iroh/cert.pem"),
+            key_path: PathBuf::from("/etc/iroh/key.pem"),
+        });
+        let got: Config = ConfigBuilder::builder()
+            .add_source(expect.clone())
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(expect, got);
+        assert_eq!(got.listen_addrs(), expect.listen_addrs);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut expect = Config::default();
+        expect.rate_limit = Some(RateLimitConfig {
+            requests_per_second: 10,
+            request_burst: 50,
+            max_concurrent_requests: 8,
+            bytes_per_second: 10 * 1024 * 1024,
+            bytes_burst: 0,
+            token_header: Some("x-api-key".to_string()),
+            api_tokens: vec!["secret".to_string()],
+            trust_forwarded: true,
+        });
+        let got: Config = ConfigBuilder::builder()
+            .add_source(expect.clone())
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(expect, got);
+    }
+
     #[test]
     fn test_toml_file() {
         let dir = testdir!();
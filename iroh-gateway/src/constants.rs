@@ -15,10 +15,12 @@ pub static HEADER_CACHE_CONTROL: HeaderName = HeaderName::from_static("cache-con
 pub static HEADER_X_CHUNKED_OUTPUT: HeaderName = HeaderName::from_static("x-chunked-output");
 pub static HEADER_X_STREAM_OUTPUT: HeaderName = HeaderName::from_static("x-stream-output");
 pub static HEADER_X_REQUESTED_WITH: HeaderName = HeaderName::from_static("x-requested-with");
+pub static HEADER_IPFS_HASH: HeaderName = HeaderName::from_static("ipfs-hash");
 
 // Common Header Values
 pub static VALUE_XCTO_NOSNIFF: HeaderValue = HeaderValue::from_static("nosniff");
 pub static VALUE_NONE: HeaderValue = HeaderValue::from_static("none");
+pub static VALUE_VARY_ACCEPT: HeaderValue = HeaderValue::from_static("Accept");
 pub static VAL_IMMUTABLE_MAX_AGE: HeaderValue =
     HeaderValue::from_static("public, max-age=31536000, immutable");
 
@@ -31,6 +33,13 @@ pub static CONTENT_TYPE_IPLD_RAW: HeaderValue =
     HeaderValue::from_static("application/vnd.ipld.raw");
 pub static CONTENT_TYPE_IPLD_CAR: HeaderValue =
     HeaderValue::from_static("application/vnd.ipld.car; version=1");
+pub static CONTENT_TYPE_IPLD_DAG_JSON: HeaderValue =
+    HeaderValue::from_static("application/vnd.ipld.dag-json");
+pub static CONTENT_TYPE_IPLD_DAG_CBOR: HeaderValue =
+    HeaderValue::from_static("application/vnd.ipld.dag-cbor");
+pub static CONTENT_TYPE_X_TAR: HeaderValue = HeaderValue::from_static("application/x-tar");
+pub static CONTENT_TYPE_JSON: HeaderValue = HeaderValue::from_static("application/json");
+pub static CONTENT_TYPE_CBOR: HeaderValue = HeaderValue::from_static("application/cbor");
 
 // Schemes
 pub static SCHEME_IPFS: &str = "ipfs";
@@ -39,3 +48,18 @@ pub static SCHEME_IPNS: &str = "ipns";
 // Max number of links to return in a single recursive request.
 // TODO: Make configurable.
 pub static RECURSION_LIMIT: usize = 4096;
+
+// Largest raw block accepted by the writable gateway, the same as kubo.
+pub static MAX_RAW_BLOCK_SIZE: usize = 1024 * 1024;
+
+// Number of bytes written to the store at once by the writable gateway.
+pub static STORE_BATCH_SIZE: u64 = 1024 * 1024;
+
+// Number of written blocks checked at once before the writable gateway pins them.
+pub static MAX_CONCURRENT_WRITE_CHECKS: usize = 16;
+
+// Number of website roots whose `_redirects` rules are cached.
+pub static REDIRECTS_CACHE_SIZE: usize = 1024;
+
+// Largest file of unknown size which is buffered to be added to a TAR archive.
+pub static MAX_TAR_BUFFERED_FILE_SIZE: u64 = 16 * 1024 * 1024;
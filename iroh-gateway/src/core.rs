@@ -1,6 +1,5 @@
 use std::{collections::HashMap, sync::Arc};
 
-use axum::Router;
 use iroh_resolver::dns_resolver::Config as DnsResolverConfig;
 use iroh_rpc_types::gateway::GatewayAddr;
 use iroh_unixfs::content_loader::ContentLoader;
@@ -12,6 +11,7 @@ use crate::{
     handlers::{get_app_routes, StateConfig},
     rpc,
     rpc::Gateway,
+    server::Server,
     templates,
 };
 
@@ -50,6 +50,10 @@ impl<T: ContentLoader + Unpin> Core<T> {
             "not_found".to_string(),
             templates::NOT_FOUND_TEMPLATE.to_string(),
         );
+        templates.insert(
+            "dag_index".to_string(),
+            templates::DAG_INDEX_TEMPLATE.to_string(),
+        );
         let client = Client::<T>::new(&content_loader, dns_resolver_config);
 
         Ok(Self {
@@ -89,6 +93,10 @@ impl<T: ContentLoader + Unpin> Core<T> {
             "not_found".to_string(),
             templates::NOT_FOUND_TEMPLATE.to_string(),
         );
+        templates.insert(
+            "dag_index".to_string(),
+            templates::DAG_INDEX_TEMPLATE.to_string(),
+        );
         let client = Client::new(&content_loader, dns_resolver_config);
         Ok(Arc::new(State {
             config,
@@ -98,19 +106,10 @@ impl<T: ContentLoader + Unpin> Core<T> {
         }))
     }
 
-    pub fn server(
-        self,
-    ) -> axum::Server<hyper::server::conn::AddrIncoming, axum::routing::IntoMakeService<Router>>
-    {
+    pub fn server(self) -> anyhow::Result<Server> {
         let app = get_app_routes(&self.state);
-
-        // todo(arqu): make configurable
-        let addr = format!("0.0.0.0:{}", self.state.config.port());
-
-        axum::Server::bind(&addr.parse().unwrap())
-            .http1_preserve_header_case(true)
-            .http1_title_case_headers(true)
-            .serve(app.into_make_service())
+        let config = &self.state.config;
+        Server::bind(app, &config.listen_addrs(), config.tls())
     }
 }
 
@@ -121,25 +120,33 @@ mod tests {
 
     use super::*;
     use axum::response::Response;
+    use bytes::Bytes;
+    use cid::multihash::{Code, MultihashDigest};
     use cid::Cid;
     use futures::{StreamExt, TryStreamExt};
-    use http::header::CONTENT_RANGE;
+    use http::header::{CACHE_CONTROL, CONTENT_RANGE, CONTENT_TYPE, ETAG};
     use http::HeaderValue;
     use hyper::Body;
+    use iroh_resolver::resolver::Path;
+    use iroh_resolver::test_utils::RecordLoader;
     use iroh_rpc_client::Client as RpcClient;
     use iroh_rpc_client::Config as RpcClientConfig;
     use iroh_rpc_types::store::StoreAddr;
     use iroh_rpc_types::Addr;
-    use iroh_unixfs::builder::{DirectoryBuilder, FileBuilder};
+    use iroh_unixfs::builder::{DirectoryBuilder, FileBuilder, SymlinkBuilder};
     use iroh_unixfs::content_loader::{FullLoader, FullLoaderConfig};
     use iroh_unixfs::unixfs::UnixfsNode;
+    use libipld::{codec::Encode, ipld, IpldCodec};
     use rand::distributions::{Alphanumeric, DistString};
     use rand::rngs::SmallRng;
     use rand::SeedableRng;
     use std::io;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
     use tokio_util::io::StreamReader;
 
     use crate::config::Config;
+    use crate::handler_params::recode_path_to_inlined_dns_link;
 
     struct TestSetup {
         gateway_addr: SocketAddr,
@@ -160,7 +167,6 @@ mod tests {
     async fn spawn_gateway(
         config: Arc<Config>,
     ) -> (SocketAddr, RpcClient, tokio::task::JoinHandle<()>) {
-        let rpc_addr = "irpc://0.0.0.0:0".parse().unwrap();
         let rpc_client = RpcClient::new(config.rpc_client().clone()).await.unwrap();
         let loader_config = FullLoaderConfig {
             http_gateways: config
@@ -173,6 +179,15 @@ mod tests {
         };
         let content_loader =
             FullLoader::new(rpc_client.clone(), loader_config).expect("invalid config");
+        let (addr, core_task) = spawn_gateway_with_loader(config, content_loader).await;
+        (addr, rpc_client, core_task)
+    }
+
+    async fn spawn_gateway_with_loader<T: ContentLoader + Unpin>(
+        config: Arc<Config>,
+        content_loader: T,
+    ) -> (SocketAddr, tokio::task::JoinHandle<()>) {
+        let rpc_addr = "irpc://0.0.0.0:0".parse().unwrap();
         let core = Core::new(
             config,
             rpc_addr,
@@ -182,12 +197,12 @@ mod tests {
         )
         .await
         .unwrap();
-        let server = core.server();
+        let server = core.server().unwrap();
         let addr = server.local_addr();
         let core_task = tokio::spawn(async move {
             server.await.unwrap();
         });
-        (addr, rpc_client, core_task)
+        (addr, core_task)
     }
 
     async fn spawn_store() -> (StoreAddr, tokio::task::JoinHandle<()>) {
@@ -255,6 +270,27 @@ mod tests {
             .unwrap()
     }
 
+    async fn do_write_request(
+        method: &str,
+        authority: &str,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> Response<Body> {
+        let client = hyper::Client::new();
+        let uri = hyper::Uri::builder()
+            .scheme("http")
+            .authority(authority)
+            .path_and_query(path_and_query)
+            .build()
+            .unwrap();
+        let req = hyper::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(hyper::Body::from(body.to_vec()))
+            .unwrap();
+        client.request(req).await.unwrap()
+    }
+
     async fn setup_test(redirect_to_subdomains: bool, files: &[(String, Vec<u8>)]) -> TestSetup {
         let (store_client_addr, store_task) = spawn_store().await;
         let mut config = Config::new(
@@ -486,6 +522,216 @@ mod tests {
         test_setup.shutdown().await
     }
 
+    #[tokio::test]
+    async fn test_fetch_tar() {
+        let files = &[
+            ("hello.txt".to_string(), b"ola".to_vec()),
+            ("world.txt".to_string(), b"mundo".to_vec()),
+        ];
+        let test_setup = setup_test(false, files).await;
+
+        let res = do_request(
+            "GET",
+            &format!("localhost:{}", test_setup.gateway_addr.port()),
+            &format!("/ipfs/{}?format=tar", test_setup.root_cid),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/x-tar"
+        );
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+        let mut archive = tokio_tar::Archive::new(&body[..]);
+        let mut entries = archive.entries().unwrap();
+        let mut contents = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).await.unwrap();
+            contents.push((path, content));
+        }
+        let root = test_setup.root_cid.to_string();
+        assert_eq!(
+            contents,
+            vec![
+                (format!("{root}/"), vec![]),
+                (format!("{root}/hello.txt"), b"ola".to_vec()),
+                (format!("{root}/world.txt"), b"mundo".to_vec()),
+            ]
+        );
+
+        test_setup.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn test_fetch_tar_metadata() {
+        let (store_client_addr, store_task) = spawn_store().await;
+        let mut config = Config::new(
+            0,
+            RpcClientConfig {
+                gateway_addr: None,
+                p2p_addr: None,
+                store_addr: Some(store_client_addr),
+                channels: Some(1),
+            },
+        );
+        config.set_default_headers();
+        let (gateway_addr, rpc_client, core_task) = spawn_gateway(Arc::new(config)).await;
+
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        let file = FileBuilder::new()
+            .name("run.sh")
+            .content_bytes(b"#!/bin/sh\n".to_vec())
+            .mode(0o750)
+            .mtime(mtime)
+            .build()
+            .await
+            .unwrap();
+        let mut symlink = SymlinkBuilder::new("latest");
+        symlink.target("run.sh");
+        let symlink = symlink.build().await.unwrap();
+        let dir = DirectoryBuilder::new()
+            .name("site")
+            .mode(0o700)
+            .mtime(mtime)
+            .add_file(file)
+            .add_symlink(symlink)
+            .build()
+            .await
+            .unwrap();
+        let store = rpc_client.try_store().unwrap();
+        let mut file_cids = Vec::new();
+        let mut parts = dir.encode();
+        while let Some(part) = parts.next().await {
+            let (cid, bytes, links) = part.unwrap().into_parts();
+            file_cids.push(cid);
+            store.put(cid, bytes, links).await.unwrap();
+        }
+        let test_setup = TestSetup {
+            gateway_addr,
+            root_cid: *file_cids.last().unwrap(),
+            file_cids,
+            core_task,
+            store_task,
+        };
+
+        let res = do_request(
+            "GET",
+            &format!("localhost:{}", test_setup.gateway_addr.port()),
+            &format!("/ipfs/{}?format=tar", test_setup.root_cid),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+        let mut archive = tokio_tar::Archive::new(&body[..]);
+        let mut entries = archive.entries().unwrap();
+        let mut headers = HashMap::new();
+        while let Some(entry) = entries.next().await {
+            let entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            headers.insert(path, entry.header().clone());
+        }
+        let root = test_setup.root_cid.to_string();
+
+        let header = &headers[&format!("{root}/")];
+        assert_eq!(header.entry_type(), tokio_tar::EntryType::Directory);
+        assert_eq!(header.mode().unwrap(), 0o700);
+        assert_eq!(header.mtime().unwrap(), 1_600_000_000);
+
+        let header = &headers[&format!("{root}/run.sh")];
+        assert_eq!(header.entry_type(), tokio_tar::EntryType::Regular);
+        assert_eq!(header.mode().unwrap(), 0o750);
+        assert_eq!(header.mtime().unwrap(), 1_600_000_000);
+        assert_eq!(header.size().unwrap(), 10);
+
+        let header = &headers[&format!("{root}/latest")];
+        assert_eq!(header.entry_type(), tokio_tar::EntryType::Symlink);
+        assert_eq!(
+            header.link_name().unwrap().unwrap().to_string_lossy(),
+            "run.sh"
+        );
+
+        test_setup.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn test_redirects_file() {
+        let files = &[
+            ("index.html".to_string(), b"app".to_vec()),
+            ("404.html".to_string(), b"not here".to_vec()),
+            (
+                "_redirects".to_string(),
+                b"/old /index.html 302\n/app/* /index.html 200\n/* /404.html 404\n".to_vec(),
+            ),
+        ];
+        let test_setup = setup_test(false, files).await;
+        let authority = format!("localhost:{}", test_setup.gateway_addr.port());
+        let host = format!("{}.ipfs.localhost", test_setup.root_cid);
+
+        let res = do_request("GET", &authority, "/old", Some(&[("host", &host)])).await;
+        assert_eq!(http::StatusCode::FOUND, res.status());
+        assert_eq!(res.headers().get("location").unwrap(), "/index.html");
+
+        let res = do_request(
+            "GET",
+            &authority,
+            "/app/deep/link",
+            Some(&[("host", &host)]),
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"app");
+
+        let res = do_request("GET", &authority, "/missing", Some(&[("host", &host)])).await;
+        assert_eq!(http::StatusCode::NOT_FOUND, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"not here");
+
+        // existing files are served as they are
+        let res = do_request("GET", &authority, "/404.html", Some(&[("host", &host)])).await;
+        assert_eq!(http::StatusCode::OK, res.status());
+
+        // the rules only apply to websites on their own origin
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{}/old", test_setup.root_cid),
+            None,
+        )
+        .await;
+        assert!(!res.status().is_redirection());
+
+        test_setup.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn test_redirects_file_too_large() {
+        let mut rules = b"/* /404.html 404\n".to_vec();
+        rules.resize(crate::redirects::MAX_REDIRECTS_FILE_SIZE as usize + 1, b'#');
+        let files = &[
+            ("404.html".to_string(), b"not here".to_vec()),
+            ("_redirects".to_string(), rules),
+        ];
+        let test_setup = setup_test(false, files).await;
+        let authority = format!("localhost:{}", test_setup.gateway_addr.port());
+        let host = format!("{}.ipfs.localhost", test_setup.root_cid);
+
+        // the rules are ignored
+        let res = do_request("GET", &authority, "/missing", Some(&[("host", &host)])).await;
+        assert_eq!(http::StatusCode::NOT_FOUND, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_ne!(&body[..], b"not here");
+
+        test_setup.shutdown().await
+    }
+
     #[tokio::test]
     async fn test_head_request_to_file() {
         let files = &[
@@ -833,4 +1079,398 @@ mod tests {
 
         test_setup.shutdown().await
     }
+
+    #[tokio::test]
+    async fn test_writable_gateway() {
+        let (store_client_addr, store_task) = spawn_store().await;
+        let mut config = Config::new(
+            0,
+            RpcClientConfig {
+                gateway_addr: None,
+                p2p_addr: None,
+                store_addr: Some(store_client_addr),
+                channels: Some(1),
+            },
+        );
+        config.set_default_headers();
+        config.writable = true;
+        let (gateway_addr, rpc_client, core_task) = spawn_gateway(Arc::new(config)).await;
+        let (root_cid, file_cids) = put_directory_with_files(
+            &rpc_client,
+            "demo",
+            &[("hello.txt".to_string(), b"ola".to_vec())],
+        )
+        .await;
+        let test_setup = TestSetup {
+            gateway_addr,
+            root_cid,
+            file_cids,
+            core_task,
+            store_task,
+        };
+        let authority = format!("localhost:{}", test_setup.gateway_addr.port());
+
+        // add a file
+        let res = do_write_request("POST", &authority, "/ipfs/", b"hello").await;
+        assert_eq!(http::StatusCode::CREATED, res.status());
+        let location = res.headers().get("location").unwrap().to_str().unwrap();
+        let res = do_request("GET", &authority, location, None).await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"hello");
+
+        // put a file into a new sub directory
+        let res = do_write_request(
+            "PUT",
+            &authority,
+            &format!("/ipfs/{}/sub/world.txt", test_setup.root_cid),
+            b"mundo",
+        )
+        .await;
+        assert_eq!(http::StatusCode::CREATED, res.status());
+        let location = res.headers().get("location").unwrap().to_str().unwrap();
+        assert!(location.ends_with("/sub/world.txt"));
+        let new_root = res.headers().get("ipfs-hash").unwrap().to_str().unwrap();
+        assert_ne!(new_root, test_setup.root_cid.to_string());
+
+        let res = do_request("GET", &authority, location, None).await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"mundo");
+
+        // non-ascii names are percent-encoded in the location
+        let res = do_write_request(
+            "PUT",
+            &authority,
+            &format!("/ipfs/{new_root}/caf%C3%A9.txt"),
+            b"cafe",
+        )
+        .await;
+        assert_eq!(http::StatusCode::CREATED, res.status());
+        let location = res.headers().get("location").unwrap().to_str().unwrap();
+        assert!(location.ends_with("/caf%C3%A9.txt"));
+        let res = do_request("GET", &authority, location, None).await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"cafe");
+
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{new_root}/hello.txt"),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"ola");
+
+        // delete the original file
+        let res = do_write_request(
+            "DELETE",
+            &authority,
+            &format!("/ipfs/{new_root}/hello.txt"),
+            b"",
+        )
+        .await;
+        assert_eq!(http::StatusCode::CREATED, res.status());
+        let newer_root = res.headers().get("ipfs-hash").unwrap().to_str().unwrap();
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{newer_root}/hello.txt"),
+            None,
+        )
+        .await;
+        assert!(!res.status().is_success());
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{newer_root}/sub/world.txt"),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+
+        // deleting a missing entry fails
+        let res = do_write_request(
+            "DELETE",
+            &authority,
+            &format!("/ipfs/{newer_root}/hello.txt"),
+            b"",
+        )
+        .await;
+        assert_eq!(http::StatusCode::NOT_FOUND, res.status());
+
+        // the pin of a replaced root moves to the new root
+        let pinned: Vec<_> = rpc_client
+            .try_store()
+            .unwrap()
+            .list_pins(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(cid, _)| *cid.hash())
+            .collect();
+        let is_pinned = |cid: &str| pinned.contains(Cid::try_from(cid).unwrap().hash());
+        assert!(is_pinned(newer_root));
+        assert!(!is_pinned(new_root));
+
+        // written content is pinned, garbage collection only removes the original root
+        rpc_client.try_store().unwrap().gc().await.unwrap();
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{newer_root}/sub/world.txt"),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"mundo");
+
+        test_setup.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn test_writable_gateway_keeps_metadata() {
+        let (store_client_addr, store_task) = spawn_store().await;
+        let mut config = Config::new(
+            0,
+            RpcClientConfig {
+                gateway_addr: None,
+                p2p_addr: None,
+                store_addr: Some(store_client_addr),
+                channels: Some(1),
+            },
+        );
+        config.set_default_headers();
+        config.writable = true;
+        let (gateway_addr, rpc_client, core_task) = spawn_gateway(Arc::new(config)).await;
+
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        let dir = DirectoryBuilder::new()
+            .name("site")
+            .mode(0o700)
+            .mtime(mtime)
+            .build()
+            .await
+            .unwrap();
+        let store = rpc_client.try_store().unwrap();
+        let mut file_cids = Vec::new();
+        let mut parts = dir.encode();
+        while let Some(part) = parts.next().await {
+            let (cid, bytes, links) = part.unwrap().into_parts();
+            file_cids.push(cid);
+            store.put(cid, bytes, links).await.unwrap();
+        }
+        let test_setup = TestSetup {
+            gateway_addr,
+            root_cid: *file_cids.last().unwrap(),
+            file_cids,
+            core_task,
+            store_task,
+        };
+        let authority = format!("localhost:{}", test_setup.gateway_addr.port());
+
+        let res = do_write_request(
+            "PUT",
+            &authority,
+            &format!("/ipfs/{}/new.txt", test_setup.root_cid),
+            b"new",
+        )
+        .await;
+        assert_eq!(http::StatusCode::CREATED, res.status());
+        let new_root = res.headers().get("ipfs-hash").unwrap().to_str().unwrap();
+
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{new_root}?format=tar"),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let mut archive = tokio_tar::Archive::new(&body[..]);
+        let mut entries = archive.entries().unwrap();
+        let entry = entries.next().await.unwrap().unwrap();
+        assert_eq!(
+            entry.path().unwrap().to_string_lossy(),
+            format!("{new_root}/")
+        );
+        assert_eq!(entry.header().mode().unwrap(), 0o700);
+        assert_eq!(entry.header().mtime().unwrap(), 1_600_000_000);
+
+        test_setup.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn test_read_only_gateway() {
+        let test_setup = setup_test(false, &[("hello.txt".to_string(), b"ola".to_vec())]).await;
+        let authority = format!("localhost:{}", test_setup.gateway_addr.port());
+
+        let res = do_write_request("POST", &authority, "/ipfs/", b"hello").await;
+        assert_eq!(http::StatusCode::METHOD_NOT_ALLOWED, res.status());
+        let res = do_write_request(
+            "DELETE",
+            &authority,
+            &format!("/ipfs/{}/hello.txt", test_setup.root_cid),
+            b"",
+        )
+        .await;
+        assert_eq!(http::StatusCode::METHOD_NOT_ALLOWED, res.status());
+
+        test_setup.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn test_dag_formats() {
+        let (store_client_addr, store_task) = spawn_store().await;
+        let mut config = Config::new(
+            0,
+            RpcClientConfig {
+                gateway_addr: None,
+                p2p_addr: None,
+                store_addr: Some(store_client_addr),
+                channels: Some(1),
+            },
+        );
+        config.set_default_headers();
+        let (addr, rpc_client, core_task) = spawn_gateway(Arc::new(config)).await;
+        let authority = format!("localhost:{}", addr.port());
+
+        let node = ipld!({ "hello": "world", "answer": 42 });
+        let mut bytes = Vec::new();
+        node.encode(IpldCodec::DagCbor, &mut bytes).unwrap();
+        let cid = Cid::new_v1(IpldCodec::DagCbor.into(), Code::Sha2_256.digest(&bytes));
+        let store = rpc_client.try_store().unwrap();
+        store.put(cid, bytes.clone().into(), vec![]).await.unwrap();
+
+        // served in its own codec by default
+        let res = do_request("GET", &authority, &format!("/ipfs/{cid}"), None).await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/vnd.ipld.dag-cbor"
+        );
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], &bytes[..]);
+
+        // converted to dag-json
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{cid}?format=dag-json"),
+            None,
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/vnd.ipld.dag-json"
+        );
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["hello"], "world");
+        assert_eq!(json["answer"], 42);
+
+        // rendered for browsers
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{cid}"),
+            Some(&[("accept", "text/html")]),
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "text/html");
+        let etag = res
+            .headers()
+            .get(ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(etag.starts_with("\"DagIndex-"));
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("dag-cbor"));
+        assert!(body.contains("world"));
+
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{cid}"),
+            Some(&[("accept", "text/html"), ("if-none-match", &etag)]),
+        )
+        .await;
+        assert_eq!(http::StatusCode::NOT_MODIFIED, res.status());
+
+        // the etag of the rendering does not match other formats
+        let res = do_request(
+            "GET",
+            &authority,
+            &format!("/ipfs/{cid}?format=dag-json"),
+            Some(&[("accept", "text/html"), ("if-none-match", &etag)]),
+        )
+        .await;
+        assert_eq!(http::StatusCode::OK, res.status());
+
+        core_task.abort();
+        store_task.abort();
+        store_task.await.ok();
+    }
+
+    #[tokio::test]
+    async fn test_ipns_cache_control() {
+        let data = Bytes::from_static(b"hello world");
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(&data));
+        let (loader, name) = RecordLoader::with_name(
+            HashMap::from([(cid, data.clone())]),
+            cid,
+            Duration::from_secs(30),
+        );
+
+        let mut config = Config::new(
+            0,
+            RpcClientConfig {
+                gateway_addr: None,
+                p2p_addr: None,
+                store_addr: None,
+                channels: Some(1),
+            },
+        );
+        config.set_default_headers();
+        let (addr, core_task) = spawn_gateway_with_loader(Arc::new(config), loader).await;
+        let authority = format!("localhost:{}", addr.port());
+
+        let assert_max_age = |res: &Response<Body>| {
+            assert_eq!(http::StatusCode::OK, res.status());
+            let cache_control = res.headers().get(CACHE_CONTROL).unwrap().to_str().unwrap();
+            let max_age: u64 = cache_control
+                .strip_prefix("public, max-age=")
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((1..=30).contains(&max_age));
+        };
+
+        // path gateway
+        let res = do_request("GET", &authority, &format!("/ipns/{name}"), None).await;
+        assert_max_age(&res);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], &data[..]);
+
+        // subdomain gateway
+        let path = Path::from_parts("ipns", &name.to_string(), "").unwrap();
+        let host = format!("{}.ipns.localhost", recode_path_to_inlined_dns_link(&path));
+        let res = do_request("GET", &authority, "/", Some(&[("host", &host)])).await;
+        assert_max_age(&res);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], &data[..]);
+
+        core_task.abort();
+        core_task.await.unwrap_err();
+    }
 }
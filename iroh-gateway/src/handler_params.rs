@@ -1,5 +1,5 @@
-use cid::multibase;
 use cid::multibase::Base;
+use cid::{multibase, Cid};
 use iroh_resolver::resolver::{CidOrDomain, Path, PathType};
 use serde::{Deserialize, Serialize};
 
@@ -25,6 +25,11 @@ pub struct DefaultHandlerPathParams {
     pub content_path: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AddHandlerPathParams {
+    pub scheme: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SubdomainHandlerPathParams {
     pub content_path: Option<String>,
@@ -41,17 +46,24 @@ impl GetParams {
     }
 }
 
+/// Encodes the root of `path` as a single, case-insensitive DNS label.
 pub fn recode_path_to_inlined_dns_link(path: &Path) -> String {
     match path.root() {
+        // CIDv0 is base58, which does not survive the lowercasing of hostnames
         CidOrDomain::Cid(cid) => match path.typ() {
-            PathType::Ipfs => cid.to_string(),
+            PathType::Ipfs => Cid::new_v1(cid.codec(), *cid.hash()).to_string(),
             PathType::Ipns => multibase::encode(Base::Base36Lower, cid.to_bytes().as_slice()),
         },
         CidOrDomain::Domain(domain) => domain.replace('-', "--").replace('.', "-"),
     }
 }
 
+/// Decodes a DNS label of a subdomain request back into a DNSLink domain, where single
+/// dashes stand for dots and double dashes for dashes.
 pub fn inlined_dns_link_to_dns_link(dns_link: &str) -> String {
+    if dns_link.len() < 3 {
+        return dns_link.to_string();
+    }
     let dns_link = dns_link.chars().collect::<Vec<_>>();
     // first char + mapping that replaces standalone dashes + last char
     dns_link
@@ -124,4 +136,37 @@ mod tests {
             "goog-l.e.com",
         );
     }
+
+    #[test]
+    fn test_cid_to_inlined_dns_link() {
+        // CIDv0 roots are upgraded to base32 CIDv1
+        let path =
+            Path::from_parts("ipfs", "QmUu8pzQ5yjhDrg4GiHYLeko2oT76vcmYX5bw6sjiEJ82k", "").unwrap();
+        assert_eq!(
+            recode_path_to_inlined_dns_link(&path),
+            "bafybeidbpcafqxig5ydqdmb62cjhack2ocb2mr5rljd7ibcgvel53vpv7e",
+        );
+
+        // peer ids are encoded as base36 libp2p-key CIDs
+        let path = Path::from_parts(
+            "ipns",
+            "12D3KooWJHxkQKX8C5KAyqEPhn2ssT2in4TExyG9SXxi519tycL9",
+            "",
+        )
+        .unwrap();
+        let label = recode_path_to_inlined_dns_link(&path);
+        assert!(label.starts_with('k'));
+        assert_eq!(label, label.to_lowercase());
+        assert_eq!(
+            Path::from_parts("ipns", &inlined_dns_link_to_dns_link(&label), "").unwrap(),
+            path
+        );
+    }
+
+    #[test]
+    fn test_short_inlined_dns_link() {
+        assert_eq!(inlined_dns_link_to_dns_link(""), "");
+        assert_eq!(inlined_dns_link_to_dns_link("a"), "a");
+        assert_eq!(inlined_dns_link_to_dns_link("a-b"), "a.b");
+    }
 }
@@ -2,13 +2,13 @@ use async_recursion::async_recursion;
 use axum::extract::Host;
 use axum::routing::any;
 use axum::{
-    body::Body,
+    body::{Body, BoxBody},
     error_handling::HandleErrorLayer,
     extract::{Extension, Path as AxumPath, Query},
     http::{header::*, Request as HttpRequest, StatusCode},
     middleware,
     response::IntoResponse,
-    routing::{get, head},
+    routing::{get, head, post, put},
     BoxError, Router,
 };
 use cid::Cid;
@@ -16,9 +16,10 @@ use futures::TryStreamExt;
 use handlebars::Handlebars;
 use http::Method;
 use iroh_metrics::{core::MRecorder, gateway::GatewayMetrics, inc, resolver::OutMetrics};
-use iroh_resolver::resolver::{CidOrDomain, UnixfsType};
+use iroh_resolver::resolver::{CidOrDomain, LinkNotFound, OutType, UnixfsType};
 use iroh_unixfs::{content_loader::ContentLoader, Link};
 use iroh_util::human::format_bytes;
+use libipld::IpldCodec;
 use serde_json::{
     json,
     value::{Map, Value as Json},
@@ -26,12 +27,14 @@ use serde_json::{
 use std::{
     collections::HashMap,
     fmt::Write,
+    net::SocketAddr,
     ops::Range,
     sync::Arc,
     time::{self, Duration},
 };
 
 use iroh_resolver::Path;
+use tokio_util::io::StreamReader;
 use tower::{ServiceBuilder, ServiceExt};
 use tower_http::{compression::CompressionLayer, trace::TraceLayer};
 use tracing::info_span;
@@ -39,12 +42,15 @@ use url::Url;
 use urlencoding::encode;
 
 use crate::handler_params::{
-    inlined_dns_link_to_dns_link, recode_path_to_inlined_dns_link, DefaultHandlerPathParams,
-    GetParams, SubdomainHandlerPathParams,
+    inlined_dns_link_to_dns_link, recode_path_to_inlined_dns_link, AddHandlerPathParams,
+    DefaultHandlerPathParams, GetParams, SubdomainHandlerPathParams,
 };
+use crate::rate_limit::RateLimiter;
+use crate::redirects::{Redirect, REDIRECTS_FILE};
 use crate::text::IpfsSubdomain;
 use crate::{
-    client::{FileResult, IpfsRequest},
+    client::{encode_ipld, FileResult, IpfsRequest, WriteError},
+    config::{RateLimitConfig, TlsConfig},
     constants::*,
     core::State,
     error::GatewayError,
@@ -64,12 +70,20 @@ pub trait StateConfig: std::fmt::Debug + Sync + Send {
     fn rpc_client(&self) -> &iroh_rpc_client::Config;
     fn public_url_base(&self) -> &str;
     fn port(&self) -> u16;
+    fn listen_addrs(&self) -> Vec<SocketAddr>;
+    fn tls(&self) -> Option<&TlsConfig>;
+    fn rate_limit(&self) -> Option<&RateLimitConfig>;
     fn user_headers(&self) -> &HeaderMap<HeaderValue>;
     fn redirect_to_subdomain(&self) -> bool;
+    fn writable(&self) -> bool;
 }
 
 pub fn get_app_routes<T: ContentLoader + Unpin>(state: &Arc<State<T>>) -> Router {
     let cors = crate::cors::cors_from_headers(state.config.user_headers());
+    let rate_limiter = state
+        .config
+        .rate_limit()
+        .map(|config| Arc::new(RateLimiter::new(config.clone())));
 
     // todo(arqu): ?uri=... https://github.com/ipfs/go-ipfs/pull/7802
     let path_router = Router::new()
@@ -84,6 +98,11 @@ pub fn get_app_routes<T: ContentLoader + Unpin>(state: &Arc<State<T>>) -> Router
             head(path_handler::<T>),
         )
         .route("/:scheme/:cid_or_domain/", head(path_handler::<T>))
+        .route("/:scheme/", post(add_handler::<T>))
+        .route(
+            "/:scheme/:cid_or_domain/*content_path",
+            put(put_handler::<T>).delete(delete_handler::<T>),
+        )
         .route("/health", get(health_check))
         .route("/icons.css", get(stylesheet_icons))
         .route("/style.css", get(stylesheet_main))
@@ -135,6 +154,11 @@ pub fn get_app_routes<T: ContentLoader + Unpin>(state: &Arc<State<T>>) -> Router
                 .timeout(Duration::from_secs(120))
                 .into_inner(),
         )
+        .layer(middleware::from_fn(
+            move |request: http::Request<Body>, next: middleware::Next<Body>| {
+                crate::rate_limit::middleware(rate_limiter.clone(), request, next)
+            },
+        ))
         .layer(
             // Tracing span for each request
             TraceLayer::new_for_http().make_span_with(|request: &http::Request<Body>| {
@@ -179,32 +203,55 @@ async fn request_preprocessing<T: ContentLoader + Unpin>(
             ));
         }
     }
+    if path.typ().as_str() == SCHEME_IPNS
+        && check_bad_name(state, &path.root().to_string(), &content_path).await
+    {
+        return Err(GatewayError::new(
+            StatusCode::GONE,
+            "name is in the denylist",
+        ));
+    }
 
     // parse query params
     let format = get_response_format(request_headers, &query_params.format)
         .map_err(|err| GatewayError::new(StatusCode::BAD_REQUEST, &err))?;
 
+    let mut path = path.clone();
+    let mut status_code = StatusCode::OK;
     let path_metadata = match state
         .client
         .retrieve_path_metadata(path.clone(), Some(format.clone()))
         .await
     {
         Ok(metadata) => metadata,
-        Err(e) => {
-            if e == "offline" {
-                return Err(GatewayError::new(StatusCode::SERVICE_UNAVAILABLE, &e));
-            } else if e.starts_with("failed to find") {
-                return Err(GatewayError::new(StatusCode::NOT_FOUND, &e));
-            } else {
-                return Err(GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e));
+        // only paths which do not exist are redirected, not content which failed to load
+        Err(e) if e.downcast_ref::<LinkNotFound>().is_none() => return Err(metadata_error(e)),
+        Err(e) => match find_redirect(state, &path, subdomain_mode).await {
+            Some(redirect) if redirect.status.is_redirection() => {
+                return Ok(RequestPreprocessingResult::RespondImmediately(
+                    GatewayResponse::redirect_with_status(&redirect.to, redirect.status),
+                ));
             }
-        }
+            // rewrites and custom error pages serve the content of the target instead
+            Some(redirect) if redirect.to.starts_with('/') => {
+                path =
+                    Path::from_parts(path.typ().as_str(), &path.root().to_string(), &redirect.to)
+                        .map_err(|e| GatewayError::new(StatusCode::BAD_REQUEST, &e.to_string()))?;
+                status_code = redirect.status;
+                state
+                    .client
+                    .retrieve_path_metadata(path.clone(), Some(format.clone()))
+                    .await
+                    .map_err(metadata_error)?
+            }
+            _ => return Err(metadata_error(e)),
+        },
     };
 
-    let resolved_cid = path_metadata.metadata().resolved_path.last();
-    let resolved_cid = match resolved_cid {
-        Some(cid) => cid,
-        None => {
+    let resolved_path = &path_metadata.metadata().resolved_path;
+    let (root_cid, resolved_cid) = match (resolved_path.first(), resolved_path.last()) {
+        (Some(root_cid), Some(resolved_cid)) => (*root_cid, resolved_cid),
+        _ => {
             return Err(GatewayError::new(
                 StatusCode::NOT_FOUND,
                 "failed to resolve path",
@@ -212,14 +259,14 @@ async fn request_preprocessing<T: ContentLoader + Unpin>(
         }
     };
 
-    if check_bad_bits(state, resolved_cid, &content_path).await {
+    if check_resolved_bad_bits(state, resolved_path, &content_path).await {
         return Err(GatewayError::new(
             StatusCode::GONE,
             "CID is in the denylist",
         ));
     }
 
-    if handle_only_if_cached(request_headers, state, path.root()).await? {
+    if handle_only_if_cached(request_headers, state, &root_cid).await? {
         return Ok(RequestPreprocessingResult::RespondImmediately(
             GatewayResponse::new(StatusCode::OK, Body::empty(), HeaderMap::new()),
         ));
@@ -244,9 +291,12 @@ async fn request_preprocessing<T: ContentLoader + Unpin>(
     response_headers.insert(&HEADER_X_IPFS_PATH, hv);
 
     // handle request and fetch data
+    // `/ipns` names and DNSLink domains are mutable, so etags and file names are based
+    // on the root they currently resolve to
     let req = IpfsRequest {
+        status_code,
         format,
-        cid: path.root().clone(),
+        cid: CidOrDomain::Cid(root_cid),
         resolved_path: path.clone(),
         query_file_name: query_params
             .filename
@@ -261,6 +311,45 @@ async fn request_preprocessing<T: ContentLoader + Unpin>(
     Ok(RequestPreprocessingResult::ShouldRequestData(Box::new(req)))
 }
 
+fn metadata_error(err: anyhow::Error) -> GatewayError {
+    if err.downcast_ref::<LinkNotFound>().is_some() {
+        return GatewayError::new(StatusCode::NOT_FOUND, &err.to_string());
+    }
+    let e = err.to_string();
+    if e == "offline" {
+        GatewayError::new(StatusCode::SERVICE_UNAVAILABLE, &e)
+    } else if e.starts_with("failed to find") {
+        GatewayError::new(StatusCode::NOT_FOUND, &e)
+    } else {
+        GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e)
+    }
+}
+
+/// Looks up the `_redirects` rule for a path which failed to resolve.
+///
+/// Rules only apply to websites with their own origin, so only in subdomain mode.
+async fn find_redirect<T: ContentLoader + Unpin>(
+    state: &Arc<State<T>>,
+    path: &Path,
+    subdomain_mode: bool,
+) -> Option<Redirect> {
+    if !subdomain_mode {
+        return None;
+    }
+    let root = Path::from_parts(path.typ().as_str(), &path.root().to_string(), "").ok()?;
+    let root_cid = match state.client.retrieve_path_metadata(root, None).await {
+        Ok(out) => *out.metadata().resolved_path.first()?,
+        Err(_) => return None,
+    };
+    match state.client.get_redirects(root_cid).await {
+        Ok(redirects) => redirects?.find(&format!("/{}", path.to_relative_string())),
+        Err(e) => {
+            tracing::warn!("failed to load {} of {}: {:?}", REDIRECTS_FILE, root_cid, e);
+            None
+        }
+    }
+}
+
 pub async fn handler<T: ContentLoader + Unpin>(
     state: Arc<State<T>>,
     method: Method,
@@ -283,33 +372,79 @@ pub async fn handler<T: ContentLoader + Unpin>(
     .await?
     {
         RequestPreprocessingResult::RespondImmediately(gateway_response) => Ok(gateway_response),
-        RequestPreprocessingResult::ShouldRequestData(req) => match method {
-            Method::HEAD => {
-                add_content_length_header(&mut response_headers, req.path_metadata.metadata().size);
-                Ok(GatewayResponse::empty(response_headers))
-            }
-            Method::GET => {
-                if query_params.recursive.unwrap_or_default() {
-                    serve_car_recursive(&req, state, response_headers, start_time).await
-                } else {
-                    match req.format {
-                        ResponseFormat::Raw => {
-                            serve_raw(&req, state, response_headers, &http_req, start_time).await
-                        }
-                        ResponseFormat::Car => {
-                            serve_car(&req, state, response_headers, start_time).await
-                        }
-                        ResponseFormat::Fs(_) => {
-                            serve_fs(&req, state, response_headers, &http_req, start_time).await
+        RequestPreprocessingResult::ShouldRequestData(req) => {
+            let res = match method {
+                Method::HEAD => {
+                    add_content_length_header(
+                        &mut response_headers,
+                        req.path_metadata.metadata().size,
+                    );
+                    Ok(GatewayResponse::empty(response_headers))
+                }
+                Method::GET => {
+                    if query_params.recursive.unwrap_or_default() {
+                        serve_car_recursive(&req, state, response_headers, start_time).await
+                    } else {
+                        match req.format {
+                            ResponseFormat::Raw => {
+                                serve_raw(&req, state, response_headers, &http_req, start_time)
+                                    .await
+                            }
+                            ResponseFormat::Car => {
+                                serve_car(&req, state, response_headers, start_time).await
+                            }
+                            ResponseFormat::Tar => {
+                                serve_tar(&req, state, response_headers, start_time).await
+                            }
+                            ResponseFormat::DagJson
+                            | ResponseFormat::DagCbor
+                            | ResponseFormat::Json
+                            | ResponseFormat::Cbor => {
+                                let format = req.format.clone();
+                                serve_codec(&req, response_headers, format)
+                            }
+                            ResponseFormat::Fs(_) => {
+                                // IPLD nodes are shown to browsers, or served in their own codec
+                                match req.path_metadata.metadata().typ {
+                                    OutType::DagJson | OutType::DagCbor
+                                        if accepts_html(request_headers) =>
+                                    {
+                                        serve_dag_index(&req, state, response_headers)
+                                    }
+                                    OutType::DagJson => {
+                                        serve_codec(&req, response_headers, ResponseFormat::DagJson)
+                                    }
+                                    OutType::DagCbor => {
+                                        serve_codec(&req, response_headers, ResponseFormat::DagCbor)
+                                    }
+                                    _ => {
+                                        serve_fs(
+                                            &req,
+                                            state,
+                                            response_headers,
+                                            &http_req,
+                                            start_time,
+                                        )
+                                        .await
+                                    }
+                                }
+                            }
                         }
                     }
                 }
-            }
-            _ => Err(GatewayError::new(
-                StatusCode::METHOD_NOT_ALLOWED,
-                "method not allowed",
-            )),
-        },
+                _ => Err(GatewayError::new(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "method not allowed",
+                )),
+            };
+            // custom error pages of `_redirects` rules keep their status
+            res.map(|mut res| {
+                if res.status_code == StatusCode::OK {
+                    res.status_code = req.status_code;
+                }
+                res
+            })
+        }
     }
 }
 
@@ -376,7 +511,7 @@ pub async fn path_handler<T: ContentLoader + Unpin>(
             &query_params,
             &request_headers,
             http_req,
-            true,
+            false,
         )
         .await
         .map_err(|e| maybe_html_error(e, m, request_headers))?;
@@ -384,6 +519,60 @@ pub async fn path_handler<T: ContentLoader + Unpin>(
     }
 }
 
+/// Adds the request body as a UnixFS file, or as a raw block if the content type is
+/// `application/vnd.ipld.raw`.
+#[tracing::instrument(skip(state, body))]
+pub async fn add_handler<T: ContentLoader + Unpin>(
+    Extension(state): Extension<Arc<State<T>>>,
+    AxumPath(path_params): AxumPath<AddHandlerPathParams>,
+    request_headers: HeaderMap,
+    body: Body,
+) -> Result<GatewayResponse, GatewayError> {
+    inc!(GatewayMetrics::Requests);
+    writable_check(&state, &path_params.scheme)?;
+    let raw = request_headers.get(CONTENT_TYPE) == Some(&CONTENT_TYPE_IPLD_RAW);
+    let cid = state
+        .client
+        .add(body_reader(body), raw)
+        .await
+        .map_err(write_error)?;
+    created_response(cid, &[])
+}
+
+/// Adds the request body as a UnixFS file at the content path, creating a new root.
+#[tracing::instrument(skip(state, body))]
+pub async fn put_handler<T: ContentLoader + Unpin>(
+    Extension(state): Extension<Arc<State<T>>>,
+    AxumPath(path_params): AxumPath<DefaultHandlerPathParams>,
+    body: Body,
+) -> Result<GatewayResponse, GatewayError> {
+    inc!(GatewayMetrics::Requests);
+    let (root, tail) = write_request_path(&state, &path_params).await?;
+    let new_root = state
+        .client
+        .put_file(root, &tail, body_reader(body))
+        .await
+        .map_err(write_error)?;
+    created_response(new_root, &tail)
+}
+
+/// Removes the content path, creating a new root.
+#[tracing::instrument(skip(state))]
+pub async fn delete_handler<T: ContentLoader + Unpin>(
+    Extension(state): Extension<Arc<State<T>>>,
+    AxumPath(path_params): AxumPath<DefaultHandlerPathParams>,
+) -> Result<GatewayResponse, GatewayError> {
+    inc!(GatewayMetrics::Requests);
+    let (root, tail) = write_request_path(&state, &path_params).await?;
+    let new_root = state
+        .client
+        .delete_path(root, &tail)
+        .await
+        .map_err(write_error)?;
+    // point at the parent directory of the removed entry
+    created_response(new_root, &tail[..tail.len() - 1])
+}
+
 #[tracing::instrument()]
 pub async fn health_check() -> String {
     "OK".to_string()
@@ -455,6 +644,98 @@ fn protocol_handler_redirect(uri_param: String) -> Result<GatewayResponse, Gatew
     Ok(GatewayResponse::redirect_permanently(&redirect_uri))
 }
 
+fn writable_check<T: ContentLoader>(state: &State<T>, scheme: &str) -> Result<(), GatewayError> {
+    if !state.config.writable() {
+        return Err(GatewayError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed, the gateway is not writable",
+        ));
+    }
+    if scheme != SCHEME_IPFS {
+        return Err(GatewayError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid scheme, must be ipfs",
+        ));
+    }
+    Ok(())
+}
+
+/// Splits the path of a PUT or DELETE request into its root and the path below it.
+async fn write_request_path<T: ContentLoader>(
+    state: &State<T>,
+    path_params: &DefaultHandlerPathParams,
+) -> Result<(Cid, Vec<String>), GatewayError> {
+    writable_check(state, &path_params.scheme)?;
+    let path = Path::from_parts(
+        &path_params.scheme,
+        &path_params.cid_or_domain,
+        path_params.content_path.as_deref().unwrap_or(""),
+    )
+    .map_err(|e| GatewayError::new(StatusCode::BAD_REQUEST, &e.to_string()))?;
+    let root = *path.cid().expect("ipfs paths start with a cid");
+    let tail: Vec<String> = path
+        .tail()
+        .iter()
+        .filter(|segment| !segment.is_empty())
+        .cloned()
+        .collect();
+    if tail.is_empty() {
+        return Err(GatewayError::new(
+            StatusCode::BAD_REQUEST,
+            "missing path below the root cid",
+        ));
+    }
+    if check_bad_bits(state, &root, &path.to_relative_string()).await {
+        return Err(GatewayError::new(
+            StatusCode::GONE,
+            "CID is in the denylist",
+        ));
+    }
+    Ok((root, tail))
+}
+
+fn body_reader(body: Body) -> impl tokio::io::AsyncRead + Send + Unpin + 'static {
+    StreamReader::new(body.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)))
+}
+
+fn write_error(err: WriteError) -> GatewayError {
+    match err {
+        WriteError::NotFound(_) => GatewayError::new(StatusCode::NOT_FOUND, &err.to_string()),
+        WriteError::NotADirectory(_) | WriteError::RawBlockTooLarge => {
+            GatewayError::new(StatusCode::BAD_REQUEST, &err.to_string())
+        }
+        // failures to load the existing directories
+        WriteError::Other(err) => metadata_error(err),
+    }
+}
+
+/// Points at the new content, the segments are percent-encoded as header values must be ASCII.
+fn created_response(root: Cid, tail: &[String]) -> Result<GatewayResponse, GatewayError> {
+    let mut location = format!("/{SCHEME_IPFS}/{root}");
+    for segment in tail {
+        location.push('/');
+        location.push_str(&encode(segment));
+    }
+    let header_value = |value: &str| {
+        HeaderValue::from_str(value).map_err(|err| {
+            GatewayError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("invalid header value: {err}"),
+            )
+        })
+    };
+    let location = header_value(&location)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(LOCATION, location.clone());
+    headers.insert(&HEADER_X_IPFS_PATH, location);
+    headers.insert(&HEADER_IPFS_HASH, header_value(&root.to_string())?);
+    Ok(GatewayResponse::new(
+        StatusCode::CREATED,
+        BoxBody::default(),
+        headers,
+    ))
+}
+
 #[tracing::instrument()]
 fn service_worker_check(
     request_headers: &HeaderMap,
@@ -504,31 +785,27 @@ fn redirect_path_handlers(host: &str, path: &Path, request_headers: &HeaderMap)
     ))
 }
 
+/// Checks for `Cache-Control: only-if-cached` whether the root of the request, after
+/// resolving any `/ipns` name or DNSLink domain, is stored locally.
 #[tracing::instrument()]
 async fn handle_only_if_cached<T: ContentLoader>(
     request_headers: &HeaderMap,
     state: &State<T>,
-    cid: &CidOrDomain,
+    cid: &Cid,
 ) -> Result<bool, GatewayError> {
     if request_headers.contains_key(&HEADER_CACHE_CONTROL) {
         let hv = request_headers.get(&HEADER_CACHE_CONTROL).unwrap();
         if hv.to_str().unwrap() == "only-if-cached" {
-            return match cid {
-                // ToDo: Race is possible if file would have been deleted immediately after the check
-                CidOrDomain::Cid(cid) => match state.client.has_file_locally(cid).await {
-                    Ok(true) => Ok(true),
-                    Ok(false) => Err(GatewayError::new(
-                        StatusCode::PRECONDITION_FAILED,
-                        "File not found in cache",
-                    )),
-                    Err(e) => Err(GatewayError::new(
-                        StatusCode::PRECONDITION_FAILED,
-                        &format!("Error checking cache: {e}"),
-                    )),
-                },
-                CidOrDomain::Domain(_) => Err(GatewayError::new(
+            // ToDo: Race is possible if file would have been deleted immediately after the check
+            return match state.client.has_file_locally(cid).await {
+                Ok(true) => Ok(true),
+                Ok(false) => Err(GatewayError::new(
+                    StatusCode::PRECONDITION_FAILED,
+                    "File not found in cache",
+                )),
+                Err(e) => Err(GatewayError::new(
                     StatusCode::PRECONDITION_FAILED,
-                    "Cannot resolve in cache: invalid CID.",
+                    &format!("Error checking cache: {e}"),
                 )),
             };
         }
@@ -553,6 +830,41 @@ pub async fn check_bad_bits<T: ContentLoader>(
     false
 }
 
+/// Checks the CIDs a path resolved through, so denied content is also blocked when it is
+/// reached through an IPNS name or DNSLink domain, or below another CID.
+///
+/// The root is checked together with `content_path`, the CIDs below it by themselves.
+async fn check_resolved_bad_bits<T: ContentLoader>(
+    state: &State<T>,
+    resolved_path: &[Cid],
+    content_path: &str,
+) -> bool {
+    let (root, rest) = match resolved_path.split_first() {
+        Some(parts) => parts,
+        None => return false,
+    };
+    if check_bad_bits(state, root, content_path).await {
+        return true;
+    }
+    for cid in rest {
+        if check_bad_bits(state, cid, "").await {
+            return true;
+        }
+    }
+    false
+}
+
+pub async fn check_bad_name<T: ContentLoader>(
+    state: &State<T>,
+    name: &str,
+    content_path: &str,
+) -> bool {
+    match state.bad_bits.as_ref() {
+        Some(bbits) => bbits.read().await.is_bad_name(name, content_path),
+        None => false,
+    }
+}
+
 #[tracing::instrument()]
 fn etag_check(
     request_headers: &HeaderMap,
@@ -566,10 +878,15 @@ fn etag_check(
             .to_str()
             .unwrap();
         if !inm.is_empty() {
-            let cid_etag = get_etag(resolved_cid, Some(format.clone()));
-            let dir_etag = get_dir_etag(resolved_cid);
-
-            if etag_matches(inm, &cid_etag) || etag_matches(inm, &dir_etag) {
+            let mut etags = vec![
+                get_etag(resolved_cid, Some(format.clone())),
+                get_dir_etag(resolved_cid),
+            ];
+            // the HTML rendering of IPLD nodes is only served to browsers in the default format
+            if matches!(format, ResponseFormat::Fs(_)) && accepts_html(request_headers) {
+                etags.push(get_dag_index_etag(resolved_cid));
+            }
+            if etags.iter().any(|etag| etag_matches(inm, etag)) {
                 return Some(GatewayResponse::not_modified());
             }
         }
@@ -717,6 +1034,148 @@ async fn serve_car_recursive<T: ContentLoader + Unpin>(
     Ok(GatewayResponse::new(StatusCode::OK, body, headers))
 }
 
+/// Serves the tree below the requested path as a TAR archive.
+#[tracing::instrument()]
+async fn serve_tar<T: ContentLoader + Unpin>(
+    req: &IpfsRequest,
+    state: Arc<State<T>>,
+    mut headers: HeaderMap,
+    start_time: time::Instant,
+) -> Result<GatewayResponse, GatewayError> {
+    let metadata = req.path_metadata.metadata();
+    let resolved_cid = resolved_cid(req)?;
+    let body = state
+        .client
+        .clone()
+        .get_tar_recursive(req.resolved_path.clone(), start_time)
+        .await
+        .map_err(|e| GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e))?;
+
+    let file_name = match req.query_file_name.is_empty() {
+        true => format!("{resolved_cid}.tar"),
+        false => req.query_file_name.clone(),
+    };
+    set_content_disposition_headers(&mut headers, &file_name, DISPOSITION_ATTACHMENT);
+    set_etag_headers(
+        &mut headers,
+        get_etag(&CidOrDomain::Cid(resolved_cid), Some(req.format.clone())),
+    );
+    add_cache_control_headers(&mut headers, metadata);
+    add_ipfs_roots_headers(&mut headers, metadata);
+    Ok(GatewayResponse::new(StatusCode::OK, body, headers))
+}
+
+/// Serves a single IPLD node in the codec of `format`, converting it if needed.
+#[tracing::instrument()]
+fn serve_codec(
+    req: &IpfsRequest,
+    mut headers: HeaderMap,
+    format: ResponseFormat,
+) -> Result<GatewayResponse, GatewayError> {
+    let metadata = req.path_metadata.metadata();
+    let resolved_cid = resolved_cid(req)?;
+    let (_, body) = encode_ipld(&req.path_metadata.content, &format)
+        .map_err(|e| GatewayError::new(StatusCode::NOT_ACCEPTABLE, &e.to_string()))?;
+
+    format.write_headers(&mut headers);
+    let (extension, disposition) = match format {
+        ResponseFormat::DagJson | ResponseFormat::Json => ("json", DISPOSITION_INLINE),
+        _ => ("cbor", DISPOSITION_ATTACHMENT),
+    };
+    let file_name = match req.query_file_name.is_empty() {
+        true => format!("{resolved_cid}.{extension}"),
+        false => req.query_file_name.clone(),
+    };
+    set_content_disposition_headers(&mut headers, &file_name, disposition);
+    // the etag of the requested format, which is checked before the request is served
+    set_etag_headers(
+        &mut headers,
+        get_etag(&CidOrDomain::Cid(resolved_cid), Some(req.format.clone())),
+    );
+    add_cache_control_headers(&mut headers, metadata);
+    add_ipfs_roots_headers(&mut headers, metadata);
+    add_content_length_header(&mut headers, Some(body.len() as u64));
+    Ok(GatewayResponse::new(
+        StatusCode::OK,
+        Body::from(body),
+        headers,
+    ))
+}
+
+/// Renders an IPLD node as an HTML page for browsers.
+#[tracing::instrument()]
+fn serve_dag_index<T: ContentLoader + Unpin>(
+    req: &IpfsRequest,
+    state: Arc<State<T>>,
+    mut headers: HeaderMap,
+) -> Result<GatewayResponse, GatewayError> {
+    let metadata = req.path_metadata.metadata();
+    let resolved_cid = resolved_cid(req)?;
+    let (codec, json) = encode_ipld(&req.path_metadata.content, &ResponseFormat::DagJson)
+        .map_err(|e| GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let json = serde_json::from_slice::<Json>(&json)
+        .and_then(|json| serde_json::to_string_pretty(&json))
+        .map_err(|e| GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let links = req
+        .path_metadata
+        .links()
+        .map_err(|e| GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?
+        .into_iter()
+        .map(|cid| json!({ "cid": cid.to_string(), "path": format!("/{SCHEME_IPFS}/{cid}") }))
+        .collect::<Vec<_>>();
+    let codec = match codec {
+        IpldCodec::DagCbor => "dag-cbor",
+        IpldCodec::DagJson => "dag-json",
+        IpldCodec::DagPb => "dag-pb",
+        IpldCodec::Raw => "raw",
+    };
+
+    let template_data = json!({
+        "root_path": req.resolved_path.to_string(),
+        "cid": resolved_cid.to_string(),
+        "codec": codec,
+        "size": format_bytes(metadata.size.unwrap_or_default()),
+        "links": links,
+        "json": json,
+    });
+    let reg = Handlebars::new();
+    let dag_template = state.handlebars.get("dag_index").unwrap();
+    let res = reg
+        .render_template(dag_template, &template_data)
+        .map_err(|e| GatewayError::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+    headers.insert(VARY, VALUE_VARY_ACCEPT.clone());
+    set_etag_headers(
+        &mut headers,
+        get_dag_index_etag(&CidOrDomain::Cid(resolved_cid)),
+    );
+    add_cache_control_headers(&mut headers, metadata);
+    add_ipfs_roots_headers(&mut headers, metadata);
+    Ok(GatewayResponse::new(
+        StatusCode::OK,
+        Body::from(res),
+        headers,
+    ))
+}
+
+fn resolved_cid(req: &IpfsRequest) -> Result<Cid, GatewayError> {
+    req.path_metadata
+        .metadata()
+        .resolved_path
+        .last()
+        .copied()
+        .ok_or_else(|| GatewayError::new(StatusCode::NOT_FOUND, "failed to resolve path"))
+}
+
+fn accepts_html(request_headers: &HeaderMap) -> bool {
+    request_headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains("text/html"))
+        .unwrap_or_default()
+}
+
 #[tracing::instrument()]
 #[async_recursion]
 async fn serve_fs<T: ContentLoader + Unpin>(
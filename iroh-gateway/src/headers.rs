@@ -113,11 +113,17 @@ pub fn parse_range_header(range: &HeaderValue) -> Option<Range<u64>> {
 pub fn add_cache_control_headers(headers: &mut HeaderMap, metadata: &Metadata) {
     if metadata.path.typ() == PathType::Ipns {
         let lmdt: OffsetDateTime = time::SystemTime::now().into();
-        // TODO: better last modified headers based on actual dns ttls
         headers.insert(
             LAST_MODIFIED,
             HeaderValue::from_str(&lmdt.to_string()).unwrap(),
         );
+        // mutable content is only cached as long as its IPNS record or DNSLink entry
+        if let Some(ttl) = metadata.ttl {
+            headers.insert(
+                CACHE_CONTROL,
+                HeaderValue::from_str(&format!("public, max-age={}", ttl.as_secs())).unwrap(),
+            );
+        }
     } else {
         headers.insert(CACHE_CONTROL, VAL_IMMUTABLE_MAX_AGE.clone());
     }
@@ -191,6 +197,20 @@ pub fn get_dir_etag(cid: &CidOrDomain) -> String {
     }
 }
 
+/// The etag of the HTML rendering of an IPLD node.
+#[tracing::instrument()]
+pub fn get_dag_index_etag(cid: &CidOrDomain) -> String {
+    match cid {
+        CidOrDomain::Cid(cid) => {
+            format!("\"DagIndex-{}-CID-{}\"", *VERSION_TEMPLATE_HASH, cid)
+        }
+        CidOrDomain::Domain(_) => {
+            // TODO:
+            String::new()
+        }
+    }
+}
+
 #[tracing::instrument()]
 pub fn etag_matches(inm: &str, cid_etag: &str) -> bool {
     let mut buf = inm.trim();
@@ -257,11 +277,12 @@ pub fn get_filename(content_path: &str) -> String {
 
 pub fn version_and_template_hash() -> String {
     let v = format!(
-        "{}-{}-{}-{}",
+        "{}-{}-{}-{}-{}",
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION"),
         crate::templates::DIR_LIST_TEMPLATE,
         crate::templates::NOT_FOUND_TEMPLATE,
+        crate::templates::DAG_INDEX_TEMPLATE,
     );
     let mut hasher = sha2::Sha256::new();
     hasher.update(v.as_bytes());
@@ -448,6 +469,34 @@ mod tests {
         assert_eq!(get_filename(""), "");
     }
 
+    #[test]
+    fn add_cache_control_headers_test() {
+        let cid =
+            Cid::try_from("bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy").unwrap();
+        let mut metadata = Metadata {
+            path: iroh_resolver::resolver::Path::from_cid(cid),
+            size: None,
+            typ: iroh_resolver::resolver::OutType::Raw,
+            unixfs_type: None,
+            resolved_path: vec![cid],
+            source: iroh_unixfs::Source::Bitswap,
+            mode: None,
+            mtime: None,
+            ttl: None,
+        };
+        let mut headers = HeaderMap::new();
+        add_cache_control_headers(&mut headers, &metadata);
+        assert_eq!(headers.get(CACHE_CONTROL).unwrap(), &VAL_IMMUTABLE_MAX_AGE);
+        assert!(headers.get(LAST_MODIFIED).is_none());
+
+        metadata.path = iroh_resolver::resolver::Path::from_parts("ipns", "ipfs.io", "").unwrap();
+        metadata.ttl = Some(time::Duration::from_secs(300));
+        let mut headers = HeaderMap::new();
+        add_cache_control_headers(&mut headers, &metadata);
+        assert_eq!(headers.get(CACHE_CONTROL).unwrap(), "public, max-age=300");
+        assert!(headers.get(LAST_MODIFIED).is_some());
+    }
+
     #[test]
     fn etag_test() {
         let any_etag = "*";
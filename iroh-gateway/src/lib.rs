@@ -10,8 +10,11 @@ pub mod handler_params;
 pub mod handlers;
 pub mod headers;
 pub mod metrics;
+mod rate_limit;
+pub mod redirects;
 pub mod response;
 mod rpc;
+pub mod server;
 pub mod templates;
 mod text;
 
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
+use iroh_car::CarStore;
 use iroh_gateway::{
     bad_bits::{self, BadBits},
     cli::Args,
@@ -40,6 +41,7 @@ async fn main() -> Result<()> {
 
     let metrics_config = config.metrics.clone();
     let dns_resolver_config = config.gateway.dns_resolver.clone();
+    let denylist_sources = bad_bits::denylist_sources(&config.gateway.denylists)?;
     let bad_bits = match config.gateway.use_denylist {
         true => Arc::new(Some(RwLock::new(BadBits::new()))),
         false => Arc::new(None),
@@ -67,6 +69,15 @@ async fn main() -> Result<()> {
                 .context("invalid indexer endpoint")?,
         },
     )?;
+    let content_loader = if config.car_files.is_empty() {
+        content_loader
+    } else {
+        let car_store = CarStore::open(&config.car_files)
+            .await
+            .context("failed to open car files")?;
+        println!("serving {} CAR files", car_store.len());
+        content_loader.with_car_store(car_store)
+    };
     let handler = Core::new(
         Arc::new(config),
         rpc_addr,
@@ -76,7 +87,7 @@ async fn main() -> Result<()> {
     )
     .await?;
 
-    let bad_bits_handle = bad_bits::spawn_bad_bits_updater(Arc::clone(&bad_bits));
+    let bad_bits_handle = bad_bits::spawn_bad_bits_updater(Arc::clone(&bad_bits), denylist_sources);
 
     let metrics_handle = iroh_metrics::MetricsHandle::new(metrics_config)
         .await
@@ -90,8 +101,10 @@ async fn main() -> Result<()> {
         }
     }
 
-    let server = handler.server();
-    println!("listening on {}", server.local_addr());
+    let server = handler.server()?;
+    for addr in server.local_addrs() {
+        println!("listening on {addr}");
+    }
     let core_task = tokio::spawn(async move {
         server.await.unwrap();
     });
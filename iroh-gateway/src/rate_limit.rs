@@ -0,0 +1,647 @@
+//! Per-client limits on request rate, concurrent requests and bytes served.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{boxed, BoxBody, Bytes},
+    extract::ConnectInfo,
+    http::{header::RETRY_AFTER, HeaderMap, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body::{Body as HttpBody, SizeHint};
+use iroh_metrics::{core::MRecorder, gateway::GatewayMetrics, inc};
+use tokio::time::Sleep;
+use tracing::debug;
+
+use crate::{config::RateLimitConfig, error::GatewayError, server::ClientAddr};
+
+/// How often state of clients which are back at their full allowance is dropped.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Identifies a client.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientKey {
+    Ip(IpAddr),
+    Token(String),
+}
+
+/// Rejection of a request, with the time after which the client may try again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RateLimited {
+    reason: &'static str,
+    retry_after: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(capacity: f64, now: Instant) -> Self {
+        TokenBucket {
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, capacity: f64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds `tokens` again.
+    fn wait_for(&self, tokens: f64, rate: f64) -> Duration {
+        Duration::from_secs_f64(((tokens - self.tokens) / rate).max(0.0))
+    }
+}
+
+#[derive(Debug)]
+struct ClientState {
+    requests: TokenBucket,
+    bytes: TokenBucket,
+    concurrent: u32,
+}
+
+/// A limit as a rate per second and the size of the bucket.
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    rate: f64,
+    capacity: f64,
+}
+
+impl Limit {
+    fn new(rate: u64, burst: u64) -> Option<Self> {
+        (rate > 0).then(|| Limit {
+            rate: rate as f64,
+            capacity: (if burst > 0 { burst } else { rate }) as f64,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Clients {
+    states: HashMap<ClientKey, ClientState>,
+    last_pruned: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    requests: Option<Limit>,
+    bytes: Option<Limit>,
+    clients: Mutex<Clients>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            requests: Limit::new(
+                config.requests_per_second as u64,
+                config.request_burst as u64,
+            ),
+            bytes: Limit::new(config.bytes_per_second, config.bytes_burst),
+            config,
+            clients: Mutex::new(Clients {
+                states: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Identifies the client by a known API token, or else by its IP address, which is taken
+    /// from the forwarding headers if they are trusted.
+    fn client_key<B>(&self, request: &Request<B>) -> Option<ClientKey> {
+        let token = self
+            .config
+            .token_header
+            .as_ref()
+            .and_then(|name| request.headers().get(name.as_str()))
+            .and_then(|token| token.to_str().ok())
+            .filter(|token| self.config.api_tokens.iter().any(|t| t == token));
+        if let Some(token) = token {
+            return Some(ClientKey::Token(token.to_string()));
+        }
+        let forwarded = if self.config.trust_forwarded {
+            forwarded_ip(request.headers())
+        } else {
+            None
+        };
+        let ip = match forwarded {
+            Some(ip) => ip,
+            None => {
+                let ConnectInfo(ClientAddr(addr)) =
+                    request.extensions().get::<ConnectInfo<ClientAddr>>()?;
+                addr.ip()
+            }
+        };
+        Some(ClientKey::Ip(client_ip(ip)))
+    }
+
+    fn acquire(self: &Arc<Self>, key: ClientKey, now: Instant) -> Result<Permit, RateLimited> {
+        let mut clients = self.clients.lock().unwrap();
+        if now.saturating_duration_since(clients.last_pruned) >= PRUNE_INTERVAL {
+            clients.states.retain(|_, state| !self.is_idle(state, now));
+            clients.last_pruned = now;
+        }
+        let state = clients
+            .states
+            .entry(key.clone())
+            .or_insert_with(|| ClientState {
+                requests: TokenBucket::full(self.requests.map_or(0.0, |l| l.capacity), now),
+                bytes: TokenBucket::full(self.bytes.map_or(0.0, |l| l.capacity), now),
+                concurrent: 0,
+            });
+
+        let max_concurrent = self.config.max_concurrent_requests;
+        if max_concurrent > 0 && state.concurrent >= max_concurrent {
+            return Err(RateLimited {
+                reason: "too many concurrent requests",
+                retry_after: Duration::from_secs(1),
+            });
+        }
+        // bytes are charged while they are sent, so this only rejects clients in debt,
+        // responses in flight are throttled instead
+        if let Some(limit) = self.bytes {
+            state.bytes.refill(limit.rate, limit.capacity, now);
+            if state.bytes.tokens < 0.0 {
+                return Err(RateLimited {
+                    reason: "too many bytes requested",
+                    retry_after: state.bytes.wait_for(0.0, limit.rate),
+                });
+            }
+        }
+        if let Some(limit) = self.requests {
+            state.requests.refill(limit.rate, limit.capacity, now);
+            if state.requests.tokens < 1.0 {
+                return Err(RateLimited {
+                    reason: "too many requests",
+                    retry_after: state.requests.wait_for(1.0, limit.rate),
+                });
+            }
+            state.requests.tokens -= 1.0;
+        }
+        state.concurrent += 1;
+        Ok(Permit {
+            limiter: Arc::clone(self),
+            key,
+        })
+    }
+
+    /// Whether the client has no requests in flight and is back at its full allowance.
+    fn is_idle(&self, state: &ClientState, now: Instant) -> bool {
+        let is_full = |bucket: &TokenBucket, limit: Option<Limit>| match limit {
+            Some(limit) => {
+                let mut bucket = *bucket;
+                bucket.refill(limit.rate, limit.capacity, now);
+                bucket.tokens >= limit.capacity
+            }
+            None => true,
+        };
+        state.concurrent == 0
+            && is_full(&state.requests, self.requests)
+            && is_full(&state.bytes, self.bytes)
+    }
+
+    /// Charges `bytes` to the client and returns how long it has to wait until it is out of
+    /// debt again.
+    fn charge(&self, key: &ClientKey, bytes: usize, now: Instant) -> Duration {
+        let limit = match self.bytes {
+            Some(limit) => limit,
+            None => return Duration::ZERO,
+        };
+        match self.clients.lock().unwrap().states.get_mut(key) {
+            Some(state) => {
+                state.bytes.refill(limit.rate, limit.capacity, now);
+                state.bytes.tokens -= bytes as f64;
+                state.bytes.wait_for(0.0, limit.rate)
+            }
+            None => Duration::ZERO,
+        }
+    }
+
+    fn release(&self, key: &ClientKey) {
+        if let Some(state) = self.clients.lock().unwrap().states.get_mut(key) {
+            state.concurrent = state.concurrent.saturating_sub(1);
+        }
+    }
+}
+
+/// The client address added by the last proxy, from the `Forwarded` header, or else the
+/// `X-Forwarded-For` header. Earlier entries are under the control of the client.
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    let last_element = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .last()
+    };
+    if let Some(element) = last_element("forwarded") {
+        return element.split(';').find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            if name.eq_ignore_ascii_case("for") {
+                parse_node(value)
+            } else {
+                None
+            }
+        });
+    }
+    last_element("x-forwarded-for").and_then(parse_node)
+}
+
+/// Parses an IP address, which may be quoted, in brackets or followed by a port, as in the
+/// forwarding headers.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(node) = node.strip_prefix('[') {
+        return node.split_once(']')?.0.parse().ok();
+    }
+    node.parse()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// IPv6 clients usually control a whole /64 network, so they share their limits.
+fn client_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & !(u64::MAX as u128))),
+        },
+        ip => ip,
+    }
+}
+
+/// A request in flight, released once its response body is dropped.
+#[derive(Debug)]
+struct Permit {
+    limiter: Arc<RateLimiter>,
+    key: ClientKey,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(&self.key);
+    }
+}
+
+/// Charges the bytes of a response body to the client, and holds back further data while
+/// the client is over its limit.
+struct MeteredBody {
+    inner: BoxBody,
+    permit: Permit,
+    throttle: Option<Pin<Box<Sleep>>>,
+}
+
+impl MeteredBody {
+    fn new(inner: BoxBody, permit: Permit) -> Self {
+        MeteredBody {
+            inner,
+            permit,
+            throttle: None,
+        }
+    }
+}
+
+impl HttpBody for MeteredBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(throttle) = &mut this.throttle {
+            // no need to wait for the end of the body, further requests are rejected while
+            // the client is in debt
+            if !this.inner.is_end_stream() {
+                ready!(throttle.as_mut().poll(cx));
+            }
+            this.throttle = None;
+        }
+        let res = Pin::new(&mut this.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(data))) = &res {
+            let wait = this
+                .permit
+                .limiter
+                .charge(&this.permit.key, data.len(), Instant::now());
+            if !wait.is_zero() {
+                this.throttle = Some(Box::pin(tokio::time::sleep(wait)));
+            }
+        }
+        res
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Rejects requests of clients over their limits with `429 Too Many Requests`.
+pub(crate) async fn middleware<B>(
+    limiter: Option<Arc<RateLimiter>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let limiter = match limiter {
+        Some(limiter) => limiter,
+        None => return next.run(request).await,
+    };
+    // requests over unix domain sockets have no address to limit them by
+    let key = match limiter.client_key(&request) {
+        Some(key) => key,
+        None => return next.run(request).await,
+    };
+    match limiter.acquire(key.clone(), Instant::now()) {
+        Ok(permit) => next
+            .run(request)
+            .await
+            .map(|inner| boxed(MeteredBody::new(inner, permit))),
+        Err(limited) => {
+            inc!(GatewayMetrics::RateLimited);
+            debug!("rate limited {:?}: {}", key, limited.reason);
+            let mut res = GatewayError::new(StatusCode::TOO_MANY_REQUESTS, limited.reason)
+                .with_method(request.method().clone())
+                .into_response();
+            // round up, retrying early would only be rejected again
+            let retry_after =
+                limited.retry_after.as_secs() + u64::from(limited.retry_after.subsec_nanos() > 0);
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after.max(1)));
+            res
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use axum::{
+        body::{Body, StreamBody},
+        middleware,
+        routing::get,
+        Router,
+    };
+
+    use super::*;
+    use crate::server::Server;
+
+    fn limiter(config: RateLimitConfig) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(config))
+    }
+
+    fn ip(ip: &str) -> ClientKey {
+        ClientKey::Ip(client_ip(ip.parse().unwrap()))
+    }
+
+    #[test]
+    fn request_rate() {
+        let limiter = limiter(RateLimitConfig {
+            requests_per_second: 2,
+            request_burst: 3,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire(ip("10.0.0.1"), now).unwrap();
+        }
+        let limited = limiter.acquire(ip("10.0.0.1"), now).unwrap_err();
+        assert_eq!(limited.reason, "too many requests");
+        assert_eq!(limited.retry_after, Duration::from_millis(500));
+        // other clients have their own allowance
+        limiter.acquire(ip("10.0.0.2"), now).unwrap();
+
+        let later = now + Duration::from_millis(500);
+        limiter.acquire(ip("10.0.0.1"), later).unwrap();
+        assert!(limiter.acquire(ip("10.0.0.1"), later).is_err());
+    }
+
+    #[test]
+    fn concurrent_requests() {
+        let limiter = limiter(RateLimitConfig {
+            max_concurrent_requests: 2,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let first = limiter.acquire(ip("10.0.0.1"), now).unwrap();
+        let _second = limiter.acquire(ip("10.0.0.1"), now).unwrap();
+        let limited = limiter.acquire(ip("10.0.0.1"), now).unwrap_err();
+        assert_eq!(limited.reason, "too many concurrent requests");
+
+        drop(first);
+        limiter.acquire(ip("10.0.0.1"), now).unwrap();
+    }
+
+    #[test]
+    fn bytes_served() {
+        let limiter = limiter(RateLimitConfig {
+            bytes_per_second: 1000,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let permit = limiter.acquire(ip("10.0.0.1"), now).unwrap();
+        assert_eq!(
+            limiter.charge(&permit.key, 3000, now),
+            Duration::from_secs(2)
+        );
+        drop(permit);
+        let limited = limiter.acquire(ip("10.0.0.1"), now).unwrap_err();
+        assert_eq!(limited.reason, "too many bytes requested");
+        assert_eq!(limited.retry_after, Duration::from_secs(2));
+
+        limiter
+            .acquire(ip("10.0.0.1"), now + Duration::from_secs(2))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn throttle_response() {
+        let limiter = limiter(RateLimitConfig {
+            bytes_per_second: 1000,
+            ..Default::default()
+        });
+        let permit = limiter.acquire(ip("10.0.0.1"), Instant::now()).unwrap();
+        let chunks = vec![
+            Ok::<_, std::io::Error>(Bytes::from(vec![0; 3000])),
+            Ok(Bytes::from(vec![0; 10])),
+        ];
+        let mut body = MeteredBody::new(
+            boxed(StreamBody::new(futures::stream::iter(chunks))),
+            permit,
+        );
+        assert_eq!(body.data().await.unwrap().unwrap().len(), 3000);
+        // 2000 bytes over the limit, the rest of the response is held back for 2 seconds
+        let next = tokio::time::timeout(Duration::from_millis(100), body.data()).await;
+        assert!(next.is_err());
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn forwarded_headers() {
+        let addr = |addr: &str| Some(addr.parse::<IpAddr>().unwrap());
+        assert_eq!(forwarded_ip(&headers(&[])), None);
+        // only the entry of the last proxy can be trusted
+        assert_eq!(
+            forwarded_ip(&headers(&[("x-forwarded-for", "203.0.113.7, 10.0.0.1")])),
+            addr("10.0.0.1")
+        );
+        assert_eq!(
+            forwarded_ip(&headers(&[
+                ("x-forwarded-for", "203.0.113.7"),
+                ("x-forwarded-for", "10.0.0.2")
+            ])),
+            addr("10.0.0.2")
+        );
+        assert_eq!(
+            forwarded_ip(&headers(&[(
+                "forwarded",
+                r#"for=192.0.2.60;proto=http, For="[2001:db8:cafe::17]:4711""#
+            )])),
+            addr("2001:db8:cafe::17")
+        );
+        assert_eq!(
+            forwarded_ip(&headers(&[
+                ("forwarded", r#"proto=https;for="192.0.2.43:47011""#),
+                ("x-forwarded-for", "10.0.0.1"),
+            ])),
+            addr("192.0.2.43")
+        );
+        assert_eq!(
+            forwarded_ip(&headers(&[("forwarded", "for=unknown")])),
+            None
+        );
+    }
+
+    #[test]
+    fn client_keys() {
+        assert_eq!(ip("2001:db8::1"), ip("2001:db8::ffff:1"));
+        assert_ne!(ip("2001:db8::1"), ip("2001:db8:0:1::1"));
+        assert_eq!(ip("::ffff:10.0.0.1"), ip("10.0.0.1"));
+
+        let limiter = limiter(RateLimitConfig {
+            token_header: Some("x-api-key".to_string()),
+            api_tokens: vec!["secret".to_string()],
+            ..Default::default()
+        });
+        let addr = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 1234));
+        let request = |token: Option<&str>| {
+            let mut request = Request::builder();
+            if let Some(token) = token {
+                request = request.header("x-api-key", token);
+            }
+            let mut request = request.body(()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(ClientAddr(addr)));
+            request
+        };
+        assert_eq!(
+            limiter.client_key(&request(Some("secret"))),
+            Some(ClientKey::Token("secret".to_string()))
+        );
+        // unknown tokens must not get a fresh allowance
+        assert_eq!(
+            limiter.client_key(&request(Some("guess"))),
+            Some(ip("10.0.0.1"))
+        );
+        assert_eq!(limiter.client_key(&request(None)), Some(ip("10.0.0.1")));
+        assert_eq!(limiter.client_key(&Request::new(())), None);
+
+        let forwarded = || {
+            let mut request = request(None);
+            request
+                .headers_mut()
+                .insert("x-forwarded-for", HeaderValue::from_static("203.0.113.7"));
+            request
+        };
+        // forwarding headers are ignored unless trusted
+        assert_eq!(limiter.client_key(&forwarded()), Some(ip("10.0.0.1")));
+        let limiter = self::limiter(RateLimitConfig {
+            trust_forwarded: true,
+            ..Default::default()
+        });
+        assert_eq!(limiter.client_key(&forwarded()), Some(ip("203.0.113.7")));
+        assert_eq!(limiter.client_key(&request(None)), Some(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn prune_idle_clients() {
+        let limiter = limiter(RateLimitConfig {
+            requests_per_second: 1,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        limiter.acquire(ip("10.0.0.1"), now).unwrap();
+        let _permit = limiter.acquire(ip("10.0.0.2"), now).unwrap();
+        limiter
+            .acquire(ip("10.0.0.3"), now + PRUNE_INTERVAL)
+            .unwrap();
+        let clients = limiter.clients.lock().unwrap();
+        assert!(!clients.states.contains_key(&ip("10.0.0.1")));
+        assert!(clients.states.contains_key(&ip("10.0.0.2")));
+        assert!(clients.states.contains_key(&ip("10.0.0.3")));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_middleware() {
+        let rate_limiter = Some(limiter(RateLimitConfig {
+            requests_per_second: 1,
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/", get(|| async { "hello" }))
+            .layer(middleware::from_fn(
+                move |request: Request<Body>, next: Next<Body>| {
+                    super::middleware(rate_limiter.clone(), request, next)
+                },
+            ));
+        let server = Server::bind(app, &["127.0.0.1:0".parse().unwrap()], None).unwrap();
+        let uri: hyper::Uri = format!("http://{}/", server.local_addr()).parse().unwrap();
+        let server_task = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+
+        let client = hyper::Client::new();
+        let res = client.get(uri.clone()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = client.get(uri).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(RETRY_AFTER).unwrap(), "1");
+
+        server_task.abort();
+        server_task.await.unwrap_err();
+    }
+}
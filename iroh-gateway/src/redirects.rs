@@ -0,0 +1,217 @@
+//! Rules of the `_redirects` file of websites hosted on subdomains.
+//!
+//! See <https://specs.ipfs.tech/http-gateways/web-redirects-file/> for the specification.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use axum::http::StatusCode;
+
+/// Name of the file at the root of a website which contains the rules.
+pub const REDIRECTS_FILE: &str = "_redirects";
+
+/// Largest `_redirects` file which is accepted.
+pub const MAX_REDIRECTS_FILE_SIZE: u64 = 64 * 1024;
+
+/// The parsed rules of a `_redirects` file, in the order they are matched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Redirects {
+    rules: Vec<Rule>,
+}
+
+/// A single `from to [status]` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub from: String,
+    pub to: String,
+    pub status: StatusCode,
+}
+
+/// The outcome of a matching rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// The target, with all placeholders and the splat filled in.
+    pub to: String,
+    /// A redirect status, or the status to serve the content of `to` with.
+    pub status: StatusCode,
+}
+
+impl Redirects {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rule = Rule::parse(line).map_err(|e| anyhow!("line {}: {}", i + 1, e))?;
+            rules.push(rule);
+        }
+        Ok(Redirects { rules })
+    }
+
+    /// Returns the outcome of the first rule matching `path`.
+    pub fn find(&self, path: &str) -> Option<Redirect> {
+        self.rules.iter().find_map(|rule| rule.apply(path))
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Self> {
+        let mut fields = line.split_whitespace();
+        let from = fields.next().unwrap_or_default();
+        let to = fields
+            .next()
+            .ok_or_else(|| anyhow!("missing redirect target"))?;
+        let status = match fields.next() {
+            Some(status) => parse_status(status)?,
+            None => StatusCode::MOVED_PERMANENTLY,
+        };
+        ensure!(fields.next().is_none(), "too many fields");
+        ensure!(
+            from.starts_with('/'),
+            "source {} is not an absolute path",
+            from
+        );
+        ensure!(
+            to.starts_with('/') || to.starts_with("http://") || to.starts_with("https://"),
+            "target {} is neither an absolute path nor a URL",
+            to
+        );
+        Ok(Rule {
+            from: from.to_string(),
+            to: to.to_string(),
+            status,
+        })
+    }
+
+    /// Matches `path` segment by segment, `:name` placeholders match any single segment
+    /// and a trailing `*` matches the rest of the path.
+    fn apply(&self, path: &str) -> Option<Redirect> {
+        let mut placeholders = Vec::new();
+        let mut parts = path.trim_end_matches('/').split('/');
+        for segment in self.from.trim_end_matches('/').split('/') {
+            if segment == "*" {
+                placeholders.push(("splat", parts.collect::<Vec<_>>().join("/")));
+                return Some(self.redirect(placeholders));
+            }
+            let part = parts.next()?;
+            match segment.strip_prefix(':').filter(|name| !name.is_empty()) {
+                Some(name) if !part.is_empty() => placeholders.push((name, part.to_string())),
+                Some(_) => return None,
+                None if segment != part => return None,
+                None => {}
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(self.redirect(placeholders))
+    }
+
+    fn redirect(&self, mut placeholders: Vec<(&str, String)>) -> Redirect {
+        // longer names first, so `:splat` is not replaced as `:s` followed by `plat`
+        placeholders.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+        let to = placeholders
+            .iter()
+            .fold(self.to.clone(), |to, (name, value)| {
+                to.replace(&format!(":{name}"), value)
+            });
+        Redirect {
+            to,
+            status: self.status,
+        }
+    }
+}
+
+fn parse_status(status: &str) -> Result<StatusCode> {
+    let code: u16 = status
+        .parse()
+        .map_err(|_| anyhow!("invalid status code {}", status))?;
+    match code {
+        200 | 301 | 302 | 303 | 307 | 308 | 404 | 410 | 451 => Ok(StatusCode::from_u16(code)?),
+        _ => bail!("unsupported status code {}", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_redirects() {
+        let redirects = Redirects::parse(
+            "# comment\n\n/old /new\n/app/* /index.html 200\n/gone /404.html 410\n",
+        )
+        .unwrap();
+        assert_eq!(
+            redirects.rules,
+            vec![
+                Rule {
+                    from: "/old".to_string(),
+                    to: "/new".to_string(),
+                    status: StatusCode::MOVED_PERMANENTLY,
+                },
+                Rule {
+                    from: "/app/*".to_string(),
+                    to: "/index.html".to_string(),
+                    status: StatusCode::OK,
+                },
+                Rule {
+                    from: "/gone".to_string(),
+                    to: "/404.html".to_string(),
+                    status: StatusCode::GONE,
+                },
+            ]
+        );
+
+        assert!(Redirects::parse("/missing-target").is_err());
+        assert!(Redirects::parse("relative /to").is_err());
+        assert!(Redirects::parse("/from to").is_err());
+        assert!(Redirects::parse("/from /to 500").is_err());
+        assert!(Redirects::parse("/from /to 301 extra").is_err());
+    }
+
+    #[test]
+    fn find_redirects() {
+        let redirects = Redirects::parse(
+            "/blog/:year/:month/:slug /posts/:year-:month-:slug 302\n\
+             /docs/* https://docs.example.com/:splat 301\n\
+             /app/* /app/index.html 200\n\
+             /* /404.html 404\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            redirects.find("/blog/2022/11/hello"),
+            Some(Redirect {
+                to: "/posts/2022-11-hello".to_string(),
+                status: StatusCode::FOUND,
+            })
+        );
+        assert_eq!(
+            redirects.find("/docs/api/intro.html"),
+            Some(Redirect {
+                to: "https://docs.example.com/api/intro.html".to_string(),
+                status: StatusCode::MOVED_PERMANENTLY,
+            })
+        );
+        assert_eq!(
+            redirects.find("/app/settings/"),
+            Some(Redirect {
+                to: "/app/index.html".to_string(),
+                status: StatusCode::OK,
+            })
+        );
+        // too short for the placeholders, so it falls through to the catch-all
+        assert_eq!(
+            redirects.find("/blog/2022"),
+            Some(Redirect {
+                to: "/404.html".to_string(),
+                status: StatusCode::NOT_FOUND,
+            })
+        );
+
+        let redirects = Redirects::parse("/exact /target").unwrap();
+        assert!(redirects.find("/exact/more").is_none());
+        assert!(redirects.find("/exact/").is_some());
+    }
+}
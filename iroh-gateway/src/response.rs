@@ -18,6 +18,11 @@ pub const ERR_UNSUPPORTED_FORMAT: &str = "unsuported format";
 pub enum ResponseFormat {
     Raw,
     Car,
+    DagJson,
+    DagCbor,
+    Json,
+    Cbor,
+    Tar,
     Fs(String),
 }
 
@@ -28,6 +33,11 @@ impl std::convert::TryFrom<&str> for ResponseFormat {
         match s.to_lowercase().as_str() {
             "application/vnd.ipld.raw" | "raw" => Ok(ResponseFormat::Raw),
             "application/vnd.ipld.car" | "car" => Ok(ResponseFormat::Car),
+            "application/vnd.ipld.dag-json" | "dag-json" => Ok(ResponseFormat::DagJson),
+            "application/vnd.ipld.dag-cbor" | "dag-cbor" => Ok(ResponseFormat::DagCbor),
+            "application/json" | "json" => Ok(ResponseFormat::Json),
+            "application/cbor" | "cbor" => Ok(ResponseFormat::Cbor),
+            "application/x-tar" | "tar" => Ok(ResponseFormat::Tar),
             "fs" | "" => Ok(ResponseFormat::Fs(String::new())),
             rf => {
                 if rf.starts_with("application/vnd.ipld.") {
@@ -52,6 +62,19 @@ impl ResponseFormat {
                 headers.insert(&HEADER_X_CONTENT_TYPE_OPTIONS, VALUE_XCTO_NOSNIFF.clone());
                 headers.insert(ACCEPT_RANGES, VALUE_NONE.clone());
             }
+            ResponseFormat::DagJson
+            | ResponseFormat::DagCbor
+            | ResponseFormat::Json
+            | ResponseFormat::Cbor => {
+                headers.insert(CONTENT_TYPE, self.content_type().unwrap());
+                headers.insert(&HEADER_X_CONTENT_TYPE_OPTIONS, VALUE_XCTO_NOSNIFF.clone());
+                headers.insert(VARY, VALUE_VARY_ACCEPT.clone());
+            }
+            ResponseFormat::Tar => {
+                headers.insert(CONTENT_TYPE, CONTENT_TYPE_X_TAR.clone());
+                headers.insert(&HEADER_X_CONTENT_TYPE_OPTIONS, VALUE_XCTO_NOSNIFF.clone());
+                headers.insert(ACCEPT_RANGES, VALUE_NONE.clone());
+            }
             ResponseFormat::Fs(_) => {
                 // Don't send application/octet-stream in that case, let the
                 // client decide instead.
@@ -63,6 +86,11 @@ impl ResponseFormat {
         match self {
             ResponseFormat::Raw => "bin".to_string(),
             ResponseFormat::Car => "car".to_string(),
+            ResponseFormat::DagJson => "dag-json".to_string(),
+            ResponseFormat::DagCbor => "dag-cbor".to_string(),
+            ResponseFormat::Json => "json".to_string(),
+            ResponseFormat::Cbor => "cbor".to_string(),
+            ResponseFormat::Tar => "tar".to_string(),
             ResponseFormat::Fs(s) => {
                 if s.is_empty() {
                     String::new()
@@ -73,13 +101,41 @@ impl ResponseFormat {
         }
     }
 
+    /// The content type of the formats which serve a single IPLD node.
+    pub fn content_type(&self) -> Option<HeaderValue> {
+        match self {
+            ResponseFormat::Raw => Some(CONTENT_TYPE_IPLD_RAW.clone()),
+            ResponseFormat::DagJson => Some(CONTENT_TYPE_IPLD_DAG_JSON.clone()),
+            ResponseFormat::DagCbor => Some(CONTENT_TYPE_IPLD_DAG_CBOR.clone()),
+            ResponseFormat::Json => Some(CONTENT_TYPE_JSON.clone()),
+            ResponseFormat::Cbor => Some(CONTENT_TYPE_CBOR.clone()),
+            ResponseFormat::Car | ResponseFormat::Tar | ResponseFormat::Fs(_) => None,
+        }
+    }
+
+    /// Is this one of the formats the IPLD codecs are converted to?
+    pub fn is_codec(&self) -> bool {
+        matches!(
+            self,
+            ResponseFormat::DagJson
+                | ResponseFormat::DagCbor
+                | ResponseFormat::Json
+                | ResponseFormat::Cbor
+        )
+    }
+
     pub fn try_from_headers(headers: &HeaderMap) -> Result<Self, String> {
         if headers.contains_key("Accept") {
             if let Some(h_values) = headers.get("Accept") {
                 let h_values = h_values.to_str().unwrap().split(',');
                 for h_value in h_values {
-                    let h_value = h_value.trim();
-                    if h_value.starts_with("application/vnd.ipld.") {
+                    // ignore parameters like the quality or the car version
+                    let h_value = h_value.split(';').next().unwrap_or_default().trim();
+                    if h_value.starts_with("application/vnd.ipld.")
+                        || h_value == "application/json"
+                        || h_value == "application/cbor"
+                        || h_value == "application/x-tar"
+                    {
                         return ResponseFormat::try_from(h_value);
                     }
                 }
@@ -169,6 +225,10 @@ impl GatewayResponse {
         Self::_redirect(to, StatusCode::MOVED_PERMANENTLY)
     }
 
+    pub fn redirect_with_status(to: &str, status_code: StatusCode) -> Self {
+        Self::_redirect(to, status_code)
+    }
+
     pub fn not_modified() -> Self {
         Self::new(
             StatusCode::NOT_MODIFIED,
@@ -193,6 +253,17 @@ mod tests {
         let rf = ResponseFormat::try_from("");
         assert_eq!(rf, Ok(ResponseFormat::Fs(String::new())));
 
+        let rf = ResponseFormat::try_from("dag-json");
+        assert_eq!(rf, Ok(ResponseFormat::DagJson));
+        let rf = ResponseFormat::try_from("application/vnd.ipld.dag-cbor");
+        assert_eq!(rf, Ok(ResponseFormat::DagCbor));
+        let rf = ResponseFormat::try_from("application/json");
+        assert_eq!(rf, Ok(ResponseFormat::Json));
+        let rf = ResponseFormat::try_from("cbor");
+        assert_eq!(rf, Ok(ResponseFormat::Cbor));
+        let rf = ResponseFormat::try_from("application/x-tar");
+        assert_eq!(rf, Ok(ResponseFormat::Tar));
+
         let rf = ResponseFormat::try_from("RaW");
         assert_eq!(rf, Ok(ResponseFormat::Raw));
 
@@ -222,9 +293,44 @@ mod tests {
             &VALUE_XCTO_NOSNIFF
         );
 
+        let rf = ResponseFormat::try_from("dag-json").unwrap();
+        let mut headers = HeaderMap::new();
+        rf.write_headers(&mut headers);
+        assert_eq!(headers.len(), 3);
+        assert_eq!(
+            headers.get(&CONTENT_TYPE).unwrap(),
+            &CONTENT_TYPE_IPLD_DAG_JSON
+        );
+        assert_eq!(headers.get(&VARY).unwrap(), &VALUE_VARY_ACCEPT);
+
         let rf = ResponseFormat::try_from("fs").unwrap();
         let mut headers = HeaderMap::new();
         rf.write_headers(&mut headers);
         assert_eq!(headers.len(), 0);
     }
+
+    #[test]
+    fn response_format_try_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("text/html, application/vnd.ipld.dag-json;q=0.9"),
+        );
+        assert_eq!(
+            ResponseFormat::try_from_headers(&headers),
+            Ok(ResponseFormat::DagJson)
+        );
+
+        headers.insert(ACCEPT, HeaderValue::from_static("application/cbor"));
+        assert_eq!(
+            ResponseFormat::try_from_headers(&headers),
+            Ok(ResponseFormat::Cbor)
+        );
+
+        headers.insert(ACCEPT, HeaderValue::from_static("text/html"));
+        assert_eq!(
+            ResponseFormat::try_from_headers(&headers),
+            Ok(ResponseFormat::Fs(String::new()))
+        );
+    }
 }
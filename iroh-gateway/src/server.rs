@@ -0,0 +1,429 @@
+//! The HTTP(S) listeners of the gateway.
+
+use std::{
+    fs::File,
+    future::{Future, IntoFuture},
+    io::{self, BufReader},
+    net::{Ipv4Addr, SocketAddr},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    task::{Context as TaskContext, Poll},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use axum::{extract::connect_info::Connected, Router};
+use futures::{future::BoxFuture, FutureExt};
+use hyper::server::{accept::Accept, conn::AddrStream};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Semaphore},
+};
+use tokio_rustls::{
+    rustls::{
+        server::{ClientHello, ResolvesServerCert},
+        sign::{self, CertifiedKey},
+        Certificate, PrivateKey, ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tracing::{debug, info, warn};
+
+use crate::config::TlsConfig;
+
+/// How often the certificate and key files are checked for changes.
+#[cfg(not(test))]
+const TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+#[cfg(test)]
+const TLS_RELOAD_INTERVAL: Duration = Duration::from_millis(100);
+/// Connections which do not complete the TLS handshake in time are dropped.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of TLS handshakes in progress per listener, further connections wait in
+/// the accept backlog.
+const MAX_CONCURRENT_TLS_HANDSHAKES: usize = 512;
+
+/// All listeners of the gateway, resolves once any of them fails.
+pub struct Server {
+    local_addrs: Vec<SocketAddr>,
+    tasks: Vec<BoxFuture<'static, Result<()>>>,
+}
+
+impl Server {
+    /// Binds `app` to every address in `addrs`, terminating TLS on all of them if `tls` is set.
+    pub fn bind(app: Router, addrs: &[SocketAddr], tls: Option<&TlsConfig>) -> Result<Self> {
+        ensure!(!addrs.is_empty(), "no addresses to listen on");
+        let mut server = Server {
+            local_addrs: Vec::with_capacity(addrs.len()),
+            tasks: Vec::new(),
+        };
+        let acceptor = match tls {
+            Some(tls) => {
+                let resolver = Arc::new(ReloadingCertResolver::new(tls.clone())?);
+                server.tasks.push(Arc::clone(&resolver).reload().boxed());
+                let mut config = ServerConfig::builder()
+                    .with_safe_defaults()
+                    .with_no_client_auth()
+                    .with_cert_resolver(resolver);
+                config.alpn_protocols = vec![b"http/1.1".to_vec()];
+                Some(TlsAcceptor::from(Arc::new(config)))
+            }
+            None => None,
+        };
+
+        for addr in addrs {
+            let listener = std::net::TcpListener::bind(addr)
+                .with_context(|| format!("failed to listen on {addr}"))?;
+            server.local_addrs.push(listener.local_addr()?);
+            let app = app
+                .clone()
+                .into_make_service_with_connect_info::<ClientAddr>();
+            let serve = match &acceptor {
+                Some(acceptor) => {
+                    listener.set_nonblocking(true)?;
+                    let (incoming, accept) =
+                        TlsIncoming::new(TcpListener::from_std(listener)?, acceptor.clone());
+                    server.tasks.push(accept.boxed());
+                    axum::Server::builder(incoming)
+                        .http1_preserve_header_case(true)
+                        .http1_title_case_headers(true)
+                        .serve(app)
+                        .boxed()
+                }
+                None => axum::Server::from_tcp(listener)?
+                    .http1_preserve_header_case(true)
+                    .http1_title_case_headers(true)
+                    .serve(app)
+                    .boxed(),
+            };
+            server
+                .tasks
+                .push(serve.map(|res| res.map_err(anyhow::Error::from)).boxed());
+        }
+        Ok(server)
+    }
+
+    /// The address of the first listener.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs[0]
+    }
+
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+}
+
+impl IntoFuture for Server {
+    type Output = Result<()>;
+    type IntoFuture = BoxFuture<'static, Result<()>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        futures::future::try_join_all(self.tasks)
+            .map(|res| res.map(|_| ()))
+            .boxed()
+    }
+}
+
+/// The address of the peer of a connection, available to handlers as
+/// `ConnectInfo<ClientAddr>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub SocketAddr);
+
+impl Connected<&AddrStream> for ClientAddr {
+    fn connect_info(target: &AddrStream) -> Self {
+        ClientAddr(target.remote_addr())
+    }
+}
+
+impl Connected<&TlsStream<TcpStream>> for ClientAddr {
+    fn connect_info(target: &TlsStream<TcpStream>) -> Self {
+        let (stream, _) = target.get_ref();
+        ClientAddr(
+            stream
+                .peer_addr()
+                .unwrap_or_else(|_| SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))),
+        )
+    }
+}
+
+/// Accepts TCP connections and completes their TLS handshakes in the background, so a slow
+/// client does not hold up the others. The number of concurrent handshakes is limited.
+struct TlsIncoming {
+    conns: mpsc::Receiver<TlsStream<TcpStream>>,
+}
+
+impl TlsIncoming {
+    /// Returns the incoming connections, and the accept loop feeding them.
+    fn new(
+        listener: TcpListener,
+        acceptor: TlsAcceptor,
+    ) -> (Self, impl Future<Output = Result<()>> + Send + 'static) {
+        let (sender, conns) = mpsc::channel(64);
+        let handshakes = Arc::new(Semaphore::new(MAX_CONCURRENT_TLS_HANDSHAKES));
+        let accept = async move {
+            loop {
+                if sender.is_closed() {
+                    return Ok(());
+                }
+                let permit = Arc::clone(&handshakes).acquire_owned().await?;
+                let (stream, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        // eg. running out of file descriptors, back off instead of spinning
+                        warn!("failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let handshake = acceptor.accept(stream);
+                    let res = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, handshake).await;
+                    drop(permit);
+                    match res {
+                        Ok(Ok(stream)) => {
+                            sender.send(stream).await.ok();
+                        }
+                        Ok(Err(e)) => debug!("tls handshake with {} failed: {}", peer, e),
+                        Err(_) => debug!("tls handshake with {} timed out", peer),
+                    }
+                });
+            }
+        };
+        (TlsIncoming { conns }, accept)
+    }
+}
+
+impl Accept for TlsIncoming {
+    type Conn = TlsStream<TcpStream>;
+    type Error = io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.conns.poll_recv(cx).map(|conn| conn.map(Ok))
+    }
+}
+
+/// Serves the certificate currently on disk.
+struct ReloadingCertResolver {
+    config: TlsConfig,
+    key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadingCertResolver {
+    fn new(config: TlsConfig) -> Result<Self> {
+        let key = load_certified_key(&config)?;
+        Ok(ReloadingCertResolver {
+            config,
+            key: RwLock::new(Arc::new(key)),
+        })
+    }
+
+    /// Reloads the certificate whenever one of the files changes, a broken certificate keeps
+    /// the previous one in use.
+    async fn reload(self: Arc<Self>) -> Result<()> {
+        let mut last_modified = modified_times(&self.config)?;
+        loop {
+            tokio::time::sleep(TLS_RELOAD_INTERVAL).await;
+            let modified = match modified_times(&self.config) {
+                Ok(modified) => modified,
+                Err(e) => {
+                    warn!("failed to check tls certificate: {:?}", e);
+                    continue;
+                }
+            };
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            match load_certified_key(&self.config) {
+                Ok(key) => {
+                    info!(
+                        "reloaded tls certificate {}",
+                        self.config.cert_path.display()
+                    );
+                    *self.key.write().unwrap() = Arc::new(key);
+                }
+                Err(e) => warn!("failed to reload tls certificate: {:?}", e),
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.key.read().unwrap()))
+    }
+}
+
+fn modified_times(config: &TlsConfig) -> Result<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .with_context(|| format!("failed to read {}", path.display()))
+    };
+    Ok((modified(&config.cert_path)?, modified(&config.key_path)?))
+}
+
+fn load_certified_key(config: &TlsConfig) -> Result<CertifiedKey> {
+    let certs = load_certs(&config.cert_path)?;
+    let key = load_private_key(&config.key_path)?;
+    let key = sign::any_supported_type(&key)
+        .map_err(|_| anyhow!("unsupported private key in {}", config.key_path.display()))?;
+    Ok(CertifiedKey::new(certs, key))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?,
+    );
+    let certs = rustls_pemfile::certs(&mut reader)
+        .with_context(|| format!("invalid certificates in {}", path.display()))?;
+    ensure!(!certs.is_empty(), "no certificates in {}", path.display());
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?,
+    );
+    let items = rustls_pemfile::read_all(&mut reader)
+        .with_context(|| format!("invalid private key in {}", path.display()))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("no private key in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::get;
+    use http::StatusCode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::{
+        rustls::{ClientConfig, RootCertStore, ServerName},
+        TlsConnector,
+    };
+
+    use super::*;
+
+    /// Writes a new self-signed certificate for `localhost`, returns it in DER.
+    fn write_certificate(tls: &TlsConfig) -> Vec<u8> {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(&tls.cert_path, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&tls.key_path, cert.serialize_private_key_pem()).unwrap();
+        cert.serialize_der().unwrap()
+    }
+
+    /// Fetches `/` over TLS, trusting only `cert`.
+    async fn https_get(addr: SocketAddr, cert: &[u8]) -> std::io::Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(cert.to_vec())).unwrap();
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut res = Vec::new();
+        match stream.read_to_end(&mut res).await {
+            // the connection may be closed without a TLS close_notify
+            Err(err) if err.kind() != std::io::ErrorKind::UnexpectedEof => return Err(err),
+            _ => {}
+        }
+        Ok(String::from_utf8_lossy(&res).into_owned())
+    }
+
+    #[tokio::test]
+    async fn bind_multiple_addrs() {
+        let app = Router::new().route("/", get(|| async { "hello" }));
+        let addrs = [
+            "127.0.0.1:0".parse().unwrap(),
+            "127.0.0.1:0".parse().unwrap(),
+        ];
+        let server = Server::bind(app, &addrs, None).unwrap();
+        let local_addrs = server.local_addrs().to_vec();
+        assert_eq!(local_addrs.len(), 2);
+        assert_eq!(server.local_addr(), local_addrs[0]);
+        assert_ne!(local_addrs[0].port(), local_addrs[1].port());
+        let server_task = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+
+        let client = hyper::Client::new();
+        for addr in local_addrs {
+            let uri = format!("http://{addr}/").parse().unwrap();
+            let res = client.get(uri).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        server_task.abort();
+        server_task.await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn bind_missing_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = TlsConfig {
+            cert_path: dir.path().join("cert.pem"),
+            key_path: dir.path().join("key.pem"),
+        };
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        assert!(Server::bind(Router::new(), &addrs, Some(&tls)).is_err());
+
+        std::fs::write(&tls.cert_path, "not a certificate").unwrap();
+        std::fs::write(&tls.key_path, "not a key").unwrap();
+        assert!(Server::bind(Router::new(), &addrs, Some(&tls)).is_err());
+    }
+
+    #[tokio::test]
+    async fn serve_and_reload_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = TlsConfig {
+            cert_path: dir.path().join("cert.pem"),
+            key_path: dir.path().join("key.pem"),
+        };
+        let cert = write_certificate(&tls);
+        let app = Router::new().route("/", get(|| async { "hello" }));
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        let server = Server::bind(app, &addrs, Some(&tls)).unwrap();
+        let addr = server.local_addr();
+        let server_task = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+
+        let res = https_get(addr, &cert).await.unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK"), "{res}");
+        assert!(res.ends_with("hello"), "{res}");
+
+        // the new certificate is picked up without a restart
+        let new_cert = write_certificate(&tls);
+        let mut reloaded = false;
+        for _ in 0..50 {
+            tokio::time::sleep(TLS_RELOAD_INTERVAL).await;
+            if https_get(addr, &new_cert).await.is_ok() {
+                reloaded = true;
+                break;
+            }
+        }
+        assert!(reloaded, "the new certificate was not served");
+        assert!(https_get(addr, &cert).await.is_err());
+
+        server_task.abort();
+        server_task.await.unwrap_err();
+    }
+}
@@ -3,6 +3,7 @@ use std::{ffi::OsStr, path::Path};
 
 pub const DIR_LIST_TEMPLATE: &str = include_str!("../assets/dir_list.html");
 pub const NOT_FOUND_TEMPLATE: &str = include_str!("../assets/404.html");
+pub const DAG_INDEX_TEMPLATE: &str = include_str!("../assets/dag_index.html");
 pub const STYLESHEET: &str = include_str!("../assets/style.css");
 pub const ICONS_STYLESHEET: &str = include_str!("../assets/icons.css");
 
@@ -3,6 +3,7 @@ use std::fmt;
 use prometheus_client::{
     metrics::{
         counter::Counter,
+        family::Family,
         gauge::Gauge,
         histogram::{linear_buckets, Histogram},
     },
@@ -12,10 +13,13 @@ use tracing::error;
 
 use crate::{
     core::{HistogramType, MetricType},
-    core::{MObserver, MRecorder, MetricsRecorder},
+    core::{MObserver, MRecorder, MetricsRecorder, CORE},
     Collector,
 };
 
+/// Labels of the denylist metrics, the source of the list.
+type DenylistLabels = Vec<(String, String)>;
+
 #[derive(Clone)]
 pub(crate) struct Metrics {
     requests_total: Counter,
@@ -25,6 +29,10 @@ pub(crate) struct Metrics {
     bytes_streamed: Counter,
     error_count: Counter,
     fail_count: Counter,
+    denylist_updates: Family<DenylistLabels, Counter>,
+    denylist_update_failures: Family<DenylistLabels, Counter>,
+    denylist_entries: Family<DenylistLabels, Gauge>,
+    rate_limited: Counter,
     hist_ttfb: Histogram,
     hist_ttfb_cached: Histogram,
     hist_ttsf: Histogram,
@@ -88,6 +96,34 @@ impl Metrics {
             Box::new(fail_count.clone()),
         );
 
+        let denylist_updates = Family::default();
+        sub_registry.register(
+            METRICS_DENYLIST_UPDATES,
+            "Number of times a denylist was successfully (re)loaded, by source",
+            Box::new(denylist_updates.clone()),
+        );
+
+        let denylist_update_failures = Family::default();
+        sub_registry.register(
+            METRICS_DENYLIST_UPDATE_FAILURES,
+            "Number of times a denylist failed to load, by source",
+            Box::new(denylist_update_failures.clone()),
+        );
+
+        let denylist_entries = Family::default();
+        sub_registry.register(
+            METRICS_DENYLIST_ENTRIES,
+            "Number of entries in the loaded denylist, by source",
+            Box::new(denylist_entries.clone()),
+        );
+
+        let rate_limited = Counter::default();
+        sub_registry.register(
+            METRICS_RATE_LIMITED,
+            "Number of requests rejected by the per-client rate limiter",
+            Box::new(rate_limited.clone()),
+        );
+
         let hist_ttfb = Histogram::new(linear_buckets(0.0, 500.0, 240));
         sub_registry.register(
             METRICS_HIST_TTFB,
@@ -116,6 +152,10 @@ impl Metrics {
             bytes_streamed,
             error_count,
             fail_count,
+            denylist_updates,
+            denylist_update_failures,
+            denylist_entries,
+            rate_limited,
             hist_ttfb,
             hist_ttfb_cached,
             hist_ttsf,
@@ -143,6 +183,8 @@ impl MetricsRecorder for Metrics {
             self.error_count.inc_by(value);
         } else if m.name() == GatewayMetrics::FailCount.name() {
             self.fail_count.inc_by(value);
+        } else if m.name() == GatewayMetrics::RateLimited.name() {
+            self.rate_limited.inc_by(value);
         } else if m.name() == GatewayMetrics::TimeToFetchFirstBlock.name() {
             self.ttf_block.set(value);
         } else if m.name() == GatewayMetrics::TimeToServeFirstBlock.name() {
@@ -176,6 +218,7 @@ pub enum GatewayMetrics {
     BytesStreamed,
     ErrorCount,
     FailCount,
+    RateLimited,
     TimeToFetchFirstBlock,
     TimeToServeFirstBlock,
     TimeToServeFullFile,
@@ -188,6 +231,7 @@ impl MetricType for GatewayMetrics {
             GatewayMetrics::BytesStreamed => METRICS_BYTES_STREAMED,
             GatewayMetrics::ErrorCount => METRICS_ERROR,
             GatewayMetrics::FailCount => METRICS_FAIL,
+            GatewayMetrics::RateLimited => METRICS_RATE_LIMITED,
             GatewayMetrics::TimeToFetchFirstBlock => METRICS_TIME_TO_FETCH_FIRST_BLOCK,
             GatewayMetrics::TimeToServeFirstBlock => METRICS_TIME_TO_SERVE_FIRST_BLOCK,
             GatewayMetrics::TimeToServeFullFile => METRICS_TIME_TO_SERVE_FULL_FILE,
@@ -195,6 +239,28 @@ impl MetricType for GatewayMetrics {
     }
 }
 
+/// Records a (re)load of the denylist from `source`, with its number of entries, or `None`
+/// if it failed to load.
+pub fn record_denylist_update(source: &str, entries: Option<u64>) {
+    if !CORE.enabled() {
+        return;
+    }
+    let metrics = CORE.gateway_metrics();
+    let labels = vec![("source".to_string(), source.to_string())];
+    match entries {
+        Some(entries) => {
+            metrics.denylist_updates.get_or_create(&labels).inc();
+            metrics.denylist_entries.get_or_create(&labels).set(entries);
+        }
+        None => {
+            metrics
+                .denylist_update_failures
+                .get_or_create(&labels)
+                .inc();
+        }
+    }
+}
+
 impl MRecorder for GatewayMetrics {
     fn record(&self, value: u64) {
         crate::record(Collector::Gateway, self.clone(), value);
@@ -246,3 +312,7 @@ const METRICS_HIST_TTFB_CACHED: &str = "hist_time_to_fetch_first_block_cached";
 const METRICS_HIST_TTSERVE: &str = "hist_time_to_serve_full_file";
 const METRICS_ERROR: &str = "error_count";
 const METRICS_FAIL: &str = "fail_count";
+const METRICS_DENYLIST_UPDATES: &str = "denylist_updates";
+const METRICS_DENYLIST_UPDATE_FAILURES: &str = "denylist_update_failures";
+const METRICS_DENYLIST_ENTRIES: &str = "denylist_entries";
+const METRICS_RATE_LIMITED: &str = "rate_limited";
@@ -1,22 +1,31 @@
 use std::fmt;
 
-use prometheus_client::{metrics::counter::Counter, registry::Registry};
+use prometheus_client::{
+    metrics::{
+        counter::Counter,
+        histogram::{linear_buckets, Histogram},
+    },
+    registry::Registry,
+};
 use tracing::error;
 
 use crate::{
-    core::{HistogramType, MRecorder, MetricType, MetricsRecorder},
+    core::{HistogramType, MObserver, MRecorder, MetricType, MetricsRecorder},
     Collector,
 };
 
 pub(crate) type Libp2pMetrics = libp2p::metrics::Metrics;
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub(crate) struct Metrics {
     bad_peers: Counter,
     bad_peers_removed: Counter,
     skipped_peer_bitswap: Counter,
     skipped_peer_kad: Counter,
     loops: Counter,
+    reprovided_keys: Counter,
+    reprovide_failures: Counter,
+    reprovide_time: Histogram,
 }
 
 impl fmt::Debug for Metrics {
@@ -25,6 +34,21 @@ impl fmt::Debug for Metrics {
     }
 }
 
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            bad_peers: Counter::default(),
+            bad_peers_removed: Counter::default(),
+            skipped_peer_bitswap: Counter::default(),
+            skipped_peer_kad: Counter::default(),
+            loops: Counter::default(),
+            reprovided_keys: Counter::default(),
+            reprovide_failures: Counter::default(),
+            reprovide_time: Histogram::new(linear_buckets(0.0, 1.0, 1)),
+        }
+    }
+}
+
 impl Metrics {
     pub(crate) fn new(registry: &mut Registry) -> Self {
         let sub_registry = registry.sub_registry_with_prefix("p2p");
@@ -54,12 +78,34 @@ impl Metrics {
         let loops = Counter::default();
         sub_registry.register(P2PMetrics::LoopCounter.name(), "", Box::new(loops.clone()));
 
+        let reprovided_keys = Counter::default();
+        sub_registry.register(
+            P2PMetrics::ReprovidedKeys.name(),
+            "Number of keys announced to the DHT by the reprovider",
+            Box::new(reprovided_keys.clone()),
+        );
+        let reprovide_failures = Counter::default();
+        sub_registry.register(
+            P2PMetrics::ReprovideFailures.name(),
+            "Number of keys the reprovider failed to announce",
+            Box::new(reprovide_failures.clone()),
+        );
+        let reprovide_time = Histogram::new(linear_buckets(0.0, 60.0, 240));
+        sub_registry.register(
+            P2PHistograms::Reprovide.name(),
+            "Histogram of the duration of reprovide runs in seconds",
+            Box::new(reprovide_time.clone()),
+        );
+
         Self {
             bad_peers,
             bad_peers_removed,
             skipped_peer_bitswap,
             skipped_peer_kad,
             loops,
+            reprovided_keys,
+            reprovide_failures,
+            reprovide_time,
         }
     }
 }
@@ -79,16 +125,24 @@ impl MetricsRecorder for Metrics {
             self.skipped_peer_kad.inc_by(value);
         } else if m.name() == P2PMetrics::LoopCounter.name() {
             self.loops.inc_by(value);
+        } else if m.name() == P2PMetrics::ReprovidedKeys.name() {
+            self.reprovided_keys.inc_by(value);
+        } else if m.name() == P2PMetrics::ReprovideFailures.name() {
+            self.reprovide_failures.inc_by(value);
         } else {
             error!("record (bitswap): unknown metric {}", m.name());
         }
     }
 
-    fn observe<M>(&self, m: M, _value: f64)
+    fn observe<M>(&self, m: M, value: f64)
     where
         M: HistogramType + std::fmt::Display,
     {
-        error!("observe (bitswap): unknown metric {}", m.name());
+        if m.name() == P2PHistograms::Reprovide.name() {
+            self.reprovide_time.observe(value);
+        } else {
+            error!("observe (p2p): unknown metric {}", m.name());
+        }
     }
 }
 
@@ -99,6 +153,8 @@ pub enum P2PMetrics {
     SkippedPeerBitswap,
     SkippedPeerKad,
     LoopCounter,
+    ReprovidedKeys,
+    ReprovideFailures,
 }
 
 impl MetricType for P2PMetrics {
@@ -109,6 +165,8 @@ impl MetricType for P2PMetrics {
             P2PMetrics::SkippedPeerBitswap => "skipped_peer_bitswap",
             P2PMetrics::SkippedPeerKad => "skipped_peer_kad",
             P2PMetrics::LoopCounter => "loop_counter",
+            P2PMetrics::ReprovidedKeys => "reprovided_keys",
+            P2PMetrics::ReprovideFailures => "reprovide_failures",
         }
     }
 }
@@ -124,3 +182,28 @@ impl std::fmt::Display for P2PMetrics {
         write!(f, "{}", self.name())
     }
 }
+
+#[derive(Clone, Debug)]
+pub enum P2PHistograms {
+    Reprovide,
+}
+
+impl HistogramType for P2PHistograms {
+    fn name(&self) -> &'static str {
+        match self {
+            P2PHistograms::Reprovide => "reprovide_time",
+        }
+    }
+}
+
+impl MObserver for P2PHistograms {
+    fn observe(&self, value: f64) {
+        crate::observe(Collector::P2P, self.clone(), value);
+    }
+}
+
+impl std::fmt::Display for P2PHistograms {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -28,6 +28,10 @@ pub(crate) struct Metrics {
     get_links_hit: Counter,
     get_links_miss: Counter,
     get_links_request_time: Histogram,
+    gc_runs_total: Counter,
+    gc_removed_blocks: Counter,
+    gc_removed_bytes: Counter,
+    gc_time: Histogram,
 }
 
 impl fmt::Debug for Metrics {
@@ -51,6 +55,10 @@ impl Default for Metrics {
             get_links_hit: Counter::default(),
             get_links_miss: Counter::default(),
             get_links_request_time: Histogram::new(linear_buckets(0.0, 1.0, 1)),
+            gc_runs_total: Counter::default(),
+            gc_removed_blocks: Counter::default(),
+            gc_removed_bytes: Counter::default(),
+            gc_time: Histogram::new(linear_buckets(0.0, 1.0, 1)),
         }
     }
 }
@@ -133,6 +141,31 @@ impl Metrics {
             Box::new(get_links_request_time.clone()),
         );
 
+        let gc_runs_total = Counter::default();
+        sub_registry.register(
+            METRICS_CNT_GC_RUNS_TOTAL,
+            "Total number of garbage collection runs",
+            Box::new(gc_runs_total.clone()),
+        );
+        let gc_removed_blocks = Counter::default();
+        sub_registry.register(
+            METRICS_CNT_GC_REMOVED_BLOCKS,
+            "Blocks removed by garbage collection",
+            Box::new(gc_removed_blocks.clone()),
+        );
+        let gc_removed_bytes = Counter::default();
+        sub_registry.register(
+            METRICS_CNT_GC_REMOVED_BYTES,
+            "Bytes removed by garbage collection",
+            Box::new(gc_removed_bytes.clone()),
+        );
+        let gc_time = Histogram::new(linear_buckets(0.0, 500.0, 240));
+        sub_registry.register(
+            METRICS_HIST_GC_TIME,
+            "Histogram of garbage collection times",
+            Box::new(gc_time.clone()),
+        );
+
         Self {
             get_requests_total,
             get_store_hit,
@@ -146,6 +179,10 @@ impl Metrics {
             get_links_hit,
             get_links_miss,
             get_links_request_time,
+            gc_runs_total,
+            gc_removed_blocks,
+            gc_removed_bytes,
+            gc_time,
         }
     }
 }
@@ -173,6 +210,12 @@ impl MetricsRecorder for Metrics {
             self.get_links_hit.inc_by(value);
         } else if m.name() == StoreMetrics::GetLinksHit.name() {
             self.get_links_miss.inc_by(value);
+        } else if m.name() == StoreMetrics::GcRuns.name() {
+            self.gc_runs_total.inc_by(value);
+        } else if m.name() == StoreMetrics::GcRemovedBlocks.name() {
+            self.gc_removed_blocks.inc_by(value);
+        } else if m.name() == StoreMetrics::GcRemovedBytes.name() {
+            self.gc_removed_bytes.inc_by(value);
         } else {
             error!("record (store): unknown metric {}", m.name());
         }
@@ -188,6 +231,8 @@ impl MetricsRecorder for Metrics {
             self.put_request_time.observe(value);
         } else if m.name() == StoreHistograms::GetLinksRequests.name() {
             self.get_links_request_time.observe(value);
+        } else if m.name() == StoreHistograms::Gc.name() {
+            self.gc_time.observe(value);
         } else {
             error!("observe (store): unknown metric {}", m.name());
         }
@@ -205,6 +250,9 @@ pub enum StoreMetrics {
     GetLinksRequests,
     GetLinksHit,
     GetLinksMiss,
+    GcRuns,
+    GcRemovedBlocks,
+    GcRemovedBytes,
 }
 
 impl MetricType for StoreMetrics {
@@ -219,6 +267,9 @@ impl MetricType for StoreMetrics {
             StoreMetrics::GetLinksRequests => METRICS_CNT_GET_LINKS_REQUESTS_TOTAL,
             StoreMetrics::GetLinksHit => METRICS_CNT_GET_LINKS_HIT,
             StoreMetrics::GetLinksMiss => METRICS_CNT_GET_LINKS_MISS,
+            StoreMetrics::GcRuns => METRICS_CNT_GC_RUNS_TOTAL,
+            StoreMetrics::GcRemovedBlocks => METRICS_CNT_GC_REMOVED_BLOCKS,
+            StoreMetrics::GcRemovedBytes => METRICS_CNT_GC_REMOVED_BYTES,
         }
     }
 }
@@ -240,6 +291,7 @@ pub enum StoreHistograms {
     GetRequests,
     PutRequests,
     GetLinksRequests,
+    Gc,
 }
 
 impl HistogramType for StoreHistograms {
@@ -248,6 +300,7 @@ impl HistogramType for StoreHistograms {
             StoreHistograms::GetRequests => METRICS_HIST_GET_REQUEST_TIME,
             StoreHistograms::PutRequests => METRICS_HIST_PUT_REQUEST_TIME,
             StoreHistograms::GetLinksRequests => METRICS_HIST_GET_LINKS_REQUEST_TIME,
+            StoreHistograms::Gc => METRICS_HIST_GC_TIME,
         }
     }
 }
@@ -276,3 +329,7 @@ const METRICS_CNT_GET_LINKS_REQUESTS_TOTAL: &str = "get_links_requests";
 const METRICS_CNT_GET_LINKS_HIT: &str = "get_links_hit";
 const METRICS_CNT_GET_LINKS_MISS: &str = "get_links_miss";
 const METRICS_HIST_GET_LINKS_REQUEST_TIME: &str = "get_links_request_time";
+const METRICS_CNT_GC_RUNS_TOTAL: &str = "gc_runs";
+const METRICS_CNT_GC_REMOVED_BLOCKS: &str = "gc_removed_blocks";
+const METRICS_CNT_GC_REMOVED_BYTES: &str = "gc_removed_bytes";
+const METRICS_HIST_GC_TIME: &str = "gc_time";
@@ -2,13 +2,14 @@ use anyhow::Result;
 use axum::http::header::*;
 use config::{ConfigError, Map, Source, Value};
 
+use iroh_gateway::config::{RateLimitConfig, TlsConfig};
 use iroh_metrics::config::Config as MetricsConfig;
 use iroh_p2p::Libp2pConfig;
 use iroh_rpc_client::Config as RpcClientConfig;
 use iroh_store::config::config_data_path;
 use iroh_util::insert_into_config_map;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 /// CONFIG_FILE_NAME is the name of the optional config file located in the iroh home directory
 pub const CONFIG_FILE_NAME: &str = "one.config.toml";
@@ -112,12 +113,16 @@ fn default_store_config(
     Ok(iroh_store::config::Config {
         path,
         rpc_client: ipfsd,
+        gc: Default::default(),
     })
 }
 
 fn default_p2p_config(ipfsd: RpcClientConfig, key_store_path: PathBuf) -> iroh_p2p::config::Config {
     iroh_p2p::config::Config {
         key_store_path,
+        kad_store_path: Some(iroh_util::iroh_data_path("kad").unwrap()),
+        car_files: Vec::new(),
+        reprovider: Default::default(),
         libp2p: Libp2pConfig::default(),
         rpc_client: ipfsd,
     }
@@ -161,6 +166,18 @@ impl iroh_gateway::handlers::StateConfig for Config {
         self.gateway.port
     }
 
+    fn listen_addrs(&self) -> Vec<SocketAddr> {
+        self.gateway.listen_addrs()
+    }
+
+    fn tls(&self) -> Option<&TlsConfig> {
+        self.gateway.tls.as_ref()
+    }
+
+    fn rate_limit(&self) -> Option<&RateLimitConfig> {
+        self.gateway.rate_limit.as_ref()
+    }
+
     fn user_headers(&self) -> &HeaderMap<HeaderValue> {
         &self.gateway.headers
     }
@@ -168,4 +185,8 @@ impl iroh_gateway::handlers::StateConfig for Config {
     fn redirect_to_subdomain(&self) -> bool {
         self.gateway.redirect_to_subdomain
     }
+
+    fn writable(&self) -> bool {
+        self.gateway.writable
+    }
 }
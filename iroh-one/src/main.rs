@@ -1,9 +1,14 @@
 use std::sync::Arc;
 
 #[allow(unused_imports)]
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
-use iroh_gateway::{bad_bits::BadBits, core::Core, metrics};
+use iroh_car::CarStore;
+use iroh_gateway::{
+    bad_bits::{self, BadBits},
+    core::Core,
+    metrics,
+};
 #[cfg(all(feature = "http-uds-gateway", unix))]
 use iroh_one::uds;
 use iroh_one::{
@@ -71,6 +76,7 @@ async fn main() -> Result<()> {
         .rpc_addr()
         .ok_or_else(|| anyhow!("missing gateway rpc addr"))?;
 
+    let denylist_sources = bad_bits::denylist_sources(&config.gateway.denylists)?;
     let bad_bits = match config.gateway.use_denylist {
         true => Arc::new(Some(RwLock::new(BadBits::new()))),
         false => Arc::new(None),
@@ -89,6 +95,14 @@ async fn main() -> Result<()> {
             indexer: None, // TODO
         },
     )?;
+    let content_loader = if config.gateway.car_files.is_empty() {
+        content_loader
+    } else {
+        let car_store = CarStore::open(&config.gateway.car_files)
+            .await
+            .context("failed to open car files")?;
+        content_loader.with_car_store(car_store)
+    };
     let shared_state = Core::make_state(
         Arc::new(config.clone()),
         Arc::clone(&bad_bits),
@@ -98,12 +112,15 @@ async fn main() -> Result<()> {
     .await?;
 
     let handler = Core::new_with_state(gateway_rpc_addr, Arc::clone(&shared_state)).await?;
+    let bad_bits_handle = bad_bits::spawn_bad_bits_updater(Arc::clone(&bad_bits), denylist_sources);
 
     let metrics_handle = iroh_metrics::MetricsHandle::new(metrics_config)
         .await
         .expect("failed to initialize metrics");
-    let server = handler.server();
-    println!("HTTP endpoint listening on {}", server.local_addr());
+    let server = handler.server()?;
+    for addr in server.local_addrs() {
+        println!("HTTP endpoint listening on {addr}");
+    }
     let core_task = tokio::spawn(async move {
         server.await.unwrap();
     });
@@ -141,6 +158,9 @@ async fn main() -> Result<()> {
     #[cfg(all(feature = "http-uds-gateway", unix))]
     uds_server_task.abort();
     core_task.abort();
+    if let Some(handle) = bad_bits_handle {
+        handle.abort();
+    }
 
     metrics_handle.shutdown();
     Ok(())
@@ -1,9 +1,11 @@
+use std::path::Path;
 use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use cid::Cid;
 use iroh_bitswap::{Bitswap, Block, Config as BitswapConfig, Store};
+use iroh_car::CarStore;
 use iroh_rpc_client::Client;
 use libp2p::core::identity::Keypair;
 use libp2p::core::PeerId;
@@ -23,6 +25,7 @@ use tracing::{info, warn};
 pub(crate) use self::event::Event;
 use self::peer_manager::PeerManager;
 use crate::config::Libp2pConfig;
+use crate::record_store::{KadStore, PersistentStore, PersistentStoreConfig};
 
 mod event;
 mod peer_manager;
@@ -37,7 +40,7 @@ pub(crate) struct NodeBehaviour {
     ping: Ping,
     identify: identify::Behaviour,
     pub(crate) bitswap: Toggle<Bitswap<BitswapStore>>,
-    pub(crate) kad: Toggle<Kademlia<MemoryStore>>,
+    pub(crate) kad: Toggle<Kademlia<KadStore>>,
     mdns: Toggle<Mdns>,
     pub(crate) autonat: Toggle<autonat::Behaviour>,
     relay: Toggle<relay::v2::relay::Relay>,
@@ -47,13 +50,24 @@ pub(crate) struct NodeBehaviour {
     pub(crate) peer_manager: PeerManager,
 }
 
+/// Serves blocks from the CAR files, and from the store.
 #[derive(Debug, Clone)]
-pub(crate) struct BitswapStore(Client);
+pub(crate) struct BitswapStore {
+    client: Client,
+    car_store: CarStore,
+}
 
 #[async_trait]
 impl Store for BitswapStore {
     async fn get(&self, cid: &Cid) -> Result<Block> {
-        let store = self.0.try_store()?;
+        if let Some(data) = self.car_store.get(cid).await? {
+            // CAR files are not trusted to contain the right data
+            if iroh_util::verify_hash(cid, &data) == Some(true) {
+                return Ok(Block::new(data.into(), *cid));
+            }
+            warn!("invalid hash for {} in CAR file", cid);
+        }
+        let store = self.client.try_store()?;
         let cid = *cid;
         let data = store
             .get(cid)
@@ -63,7 +77,10 @@ impl Store for BitswapStore {
     }
 
     async fn get_size(&self, cid: &Cid) -> Result<usize> {
-        let store = self.0.try_store()?;
+        if let Some(size) = self.car_store.get_size(cid).await? {
+            return Ok(size);
+        }
+        let store = self.client.try_store()?;
         let cid = *cid;
         let size = store
             .get_size(cid)
@@ -73,7 +90,10 @@ impl Store for BitswapStore {
     }
 
     async fn has(&self, cid: &Cid) -> Result<bool> {
-        let store = self.0.try_store()?;
+        if self.car_store.has(cid).await {
+            return Ok(true);
+        }
+        let store = self.client.try_store()?;
         let cid = *cid;
         let res = store.has(cid).await?;
         Ok(res)
@@ -84,8 +104,10 @@ impl NodeBehaviour {
     pub async fn new(
         local_key: &Keypair,
         config: &Libp2pConfig,
+        kad_store_path: Option<&Path>,
         relay_client: Option<relay::v2::client::Client>,
         rpc_client: Client,
+        car_store: CarStore,
     ) -> Result<Self> {
         let peer_manager = PeerManager::default();
         let pub_key = local_key.public();
@@ -99,7 +121,11 @@ impl NodeBehaviour {
             } else {
                 BitswapConfig::default_client_mode()
             };
-            Some(Bitswap::new(peer_id, BitswapStore(rpc_client), bs_config).await)
+            let store = BitswapStore {
+                client: rpc_client,
+                car_store,
+            };
+            Some(Bitswap::new(peer_id, store, bs_config).await)
         } else {
             None
         }
@@ -115,14 +141,22 @@ impl NodeBehaviour {
 
         let kad = if config.kademlia {
             info!("init kademlia");
-            // TODO: persist to store
-            let mem_store_config = MemoryStoreConfig {
-                // enough for >10gb of unixfs files at the default chunk size
-                max_records: 1024 * 64,
-                max_provided_keys: 1024 * 64,
-                ..Default::default()
+            let store = match kad_store_path {
+                Some(path) => KadStore::Persistent(PersistentStore::open(
+                    peer_id,
+                    path,
+                    PersistentStoreConfig::default(),
+                )?),
+                None => {
+                    let store_config = MemoryStoreConfig {
+                        // enough for >10gb of unixfs files at the default chunk size
+                        max_records: 1024 * 64,
+                        max_provided_keys: 1024 * 64,
+                        ..Default::default()
+                    };
+                    KadStore::Memory(MemoryStore::with_config(peer_id, store_config))
+                }
             };
-            let store = MemoryStore::with_config(peer_id, mem_store_config);
 
             // TODO: make user configurable
             let mut kad_config = KademliaConfig::default();
@@ -1,11 +1,13 @@
+use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::Result;
 use config::{ConfigError, Map, Source, Value};
 use iroh_metrics::config::Config as MetricsConfig;
 use iroh_rpc_client::Config as RpcClientConfig;
 use iroh_rpc_types::p2p::P2pAddr;
-use iroh_util::{insert_into_config_map, iroh_data_root};
+use iroh_util::{insert_into_config_map, iroh_data_path, iroh_data_root};
 use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 
@@ -117,6 +119,86 @@ pub struct Config {
     /// format compatible with how ssh stores keys.  This points to a directory where these
     /// keypairs are stored.
     pub key_store_path: PathBuf,
+    /// Directory where the kademlia records and provider records are persisted.
+    ///
+    /// When not set, the records are only kept in memory and are lost on restart.
+    #[serde(default)]
+    pub kad_store_path: Option<PathBuf>,
+    /// CAR files whose blocks are served over bitswap, in addition to the store.
+    #[serde(default)]
+    pub car_files: Vec<PathBuf>,
+    /// Configuration of the reprovider.
+    #[serde(default)]
+    pub reprovider: ReproviderConfig,
+}
+
+/// Which content the reprovider announces to the DHT.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReprovideStrategy {
+    /// Every block in the store.
+    All,
+    /// Every pinned block, including the descendants of recursive pins.
+    Pinned,
+    /// Only the roots of the pins.
+    Roots,
+}
+
+impl fmt::Display for ReprovideStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReprovideStrategy::All => write!(f, "all"),
+            ReprovideStrategy::Pinned => write!(f, "pinned"),
+            ReprovideStrategy::Roots => write!(f, "roots"),
+        }
+    }
+}
+
+/// The configuration for the reprovider.
+///
+/// Provider records expire from the DHT after a while, the reprovider periodically
+/// announces the content of the store again so it stays discoverable.
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize, Clone)]
+pub struct ReproviderConfig {
+    /// Which content of the store is announced. The blocks of the CAR files in `car_files`
+    /// are always announced.
+    pub strategy: ReprovideStrategy,
+    /// Interval in seconds between two reprovide runs.
+    ///
+    /// Reproviding is disabled when this is `0`.
+    pub interval_secs: u64,
+}
+
+impl Default for ReproviderConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ReprovideStrategy::All,
+            // provider records are valid for 24 hours
+            interval_secs: 12 * 60 * 60,
+        }
+    }
+}
+
+impl ReproviderConfig {
+    /// The interval between reprovide runs, `None` if reproviding is disabled.
+    pub fn interval(&self) -> Option<Duration> {
+        (self.interval_secs > 0).then(|| Duration::from_secs(self.interval_secs))
+    }
+}
+
+impl Source for ReproviderConfig {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let mut map: Map<String, Value> = Map::new();
+        insert_into_config_map(&mut map, "strategy", self.strategy.to_string());
+        // `config` package converts all unsigned integers into U64, which then has problems
+        // downcasting, so store them as signed ints
+        insert_into_config_map(&mut map, "interval_secs", self.interval_secs as i64);
+        Ok(map)
+    }
 }
 
 impl From<ServerConfig> for Config {
@@ -198,6 +280,18 @@ impl Source for Config {
         insert_into_config_map(&mut map, "libp2p", self.libp2p.collect()?);
         insert_into_config_map(&mut map, "rpc_client", self.rpc_client.collect()?);
         insert_into_config_map(&mut map, "key_store_path", self.key_store_path.to_str());
+        if let Some(path) = &self.kad_store_path {
+            insert_into_config_map(&mut map, "kad_store_path", path.to_str());
+        }
+        if !self.car_files.is_empty() {
+            let car_files: Vec<_> = self
+                .car_files
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect();
+            insert_into_config_map(&mut map, "car_files", car_files);
+        }
+        insert_into_config_map(&mut map, "reprovider", self.reprovider.collect()?);
         Ok(map)
     }
 }
@@ -244,6 +338,9 @@ impl Config {
                 ..Default::default()
             },
             key_store_path: iroh_data_root().unwrap(),
+            kad_store_path: None,
+            car_files: Vec::new(),
+            reprovider: Default::default(),
         }
     }
 
@@ -254,6 +351,9 @@ impl Config {
             libp2p: Libp2pConfig::default(),
             rpc_client,
             key_store_path: iroh_data_root().unwrap(),
+            kad_store_path: Some(iroh_data_path("kad").unwrap()),
+            car_files: Vec::new(),
+            reprovider: Default::default(),
         }
     }
 
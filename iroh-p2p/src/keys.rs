@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::{Stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
 use ssh_key::LineEnding;
 use tokio::fs;
 use tracing::warn;
@@ -60,6 +61,26 @@ impl From<Keypair> for libp2p::identity::Keypair {
     }
 }
 
+/// File in the keychain directory listing the IPNS names published with its keys.
+const PUBLISHED_NAMES_FILE: &str = "ipns_names.toml";
+
+/// An IPNS name published with a key of the keychain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishedName {
+    /// Name of the key, or `None` for the identity of the node.
+    pub key: Option<String>,
+    /// The published path.
+    pub value: String,
+    /// Sequence number of the last published record.
+    pub sequence: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PublishedNames {
+    #[serde(default)]
+    names: Vec<PublishedName>,
+}
+
 /// A keychain to manage your keys.
 #[derive(Debug)]
 pub struct Keychain<S: Storage> {
@@ -87,6 +108,11 @@ impl<S: Storage> Keychain<S> {
         self.storage.keys()
     }
 
+    /// Returns the key with the given name, e.g. `id_ed25519_0`.
+    pub async fn get(&self, name: &str) -> Result<Option<Keypair>> {
+        self.storage.get(name).await
+    }
+
     /// Returns how many keys are stored in this keychain.
     pub async fn len(&self) -> Result<usize> {
         self.storage.len().await
@@ -96,6 +122,16 @@ impl<S: Storage> Keychain<S> {
     pub async fn is_empty(&self) -> Result<bool> {
         Ok(self.storage.len().await? == 0)
     }
+
+    /// Returns the IPNS names published with the keys of this keychain.
+    pub async fn published_names(&self) -> Result<Vec<PublishedName>> {
+        self.storage.published_names().await
+    }
+
+    /// Replaces the list of IPNS names published with the keys of this keychain.
+    pub async fn put_published_names(&mut self, names: Vec<PublishedName>) -> Result<()> {
+        self.storage.put_published_names(names).await
+    }
 }
 
 impl Default for Keychain<MemoryStorage> {
@@ -130,6 +166,7 @@ impl Keychain<DiskStorage> {
 #[derive(Debug, Default)]
 pub struct MemoryStorage {
     keys: Vec<Keypair>,
+    published_names: Vec<PublishedName>,
 }
 
 /// On disk storage backend for [`Keychain`].
@@ -197,7 +234,10 @@ impl DiskStorage {
 #[async_trait]
 pub trait Storage: std::fmt::Debug {
     async fn put(&mut self, keypair: Keypair) -> Result<()>;
+    async fn get(&self, name: &str) -> Result<Option<Keypair>>;
     async fn len(&self) -> Result<usize>;
+    async fn published_names(&self) -> Result<Vec<PublishedName>>;
+    async fn put_published_names(&mut self, names: Vec<PublishedName>) -> Result<()>;
 
     fn keys(&self) -> Box<dyn Stream<Item = Result<Keypair>> + Unpin + Send + '_>;
 }
@@ -209,10 +249,34 @@ impl Storage for MemoryStorage {
         Ok(())
     }
 
+    async fn get(&self, name: &str) -> Result<Option<Keypair>> {
+        // keys are named like on disk, counting up per algorithm
+        for (i, key) in self.keys.iter().enumerate() {
+            let alg = key.algorithm();
+            let count = self.keys[..i]
+                .iter()
+                .filter(|k| k.algorithm() == alg)
+                .count();
+            if format!("id_{}_{}", print_algorithm(alg), count) == name {
+                return Ok(Some(key.clone()));
+            }
+        }
+        Ok(None)
+    }
+
     async fn len(&self) -> Result<usize> {
         Ok(self.keys.len())
     }
 
+    async fn published_names(&self) -> Result<Vec<PublishedName>> {
+        Ok(self.published_names.clone())
+    }
+
+    async fn put_published_names(&mut self, names: Vec<PublishedName>) -> Result<()> {
+        self.published_names = names;
+        Ok(())
+    }
+
     fn keys(&self) -> Box<dyn Stream<Item = Result<Keypair>> + Unpin + Send + '_> {
         let s = async_stream::stream! {
             for key in &self.keys {
@@ -235,11 +299,40 @@ impl Storage for DiskStorage {
         Ok(())
     }
 
+    async fn get(&self, name: &str) -> Result<Option<Keypair>> {
+        let path = self.path.join(name);
+        if !path_is_private_key(&path) || !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path).await?;
+        let keypair = ssh_key::private::PrivateKey::from_openssh(&content)?;
+        Ok(Some(Keypair::try_from(&keypair)?))
+    }
+
     async fn len(&self) -> Result<usize> {
         let files: Vec<_> = self.key_files().try_collect().await?;
         Ok(files.len())
     }
 
+    async fn published_names(&self) -> Result<Vec<PublishedName>> {
+        let path = self.path.join(PUBLISHED_NAMES_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path).await?;
+        let published: PublishedNames = toml::from_str(&content)?;
+        Ok(published.names)
+    }
+
+    async fn put_published_names(&mut self, names: Vec<PublishedName>) -> Result<()> {
+        let content = toml::to_string(&PublishedNames { names })?;
+        // write to a temporary file first, so a crash can not leave a truncated list behind
+        let tmp_path = self.path.join(format!("{}.tmp", PUBLISHED_NAMES_FILE));
+        fs::write(&tmp_path, content.as_bytes()).await?;
+        fs::rename(&tmp_path, self.path.join(PUBLISHED_NAMES_FILE)).await?;
+        Ok(())
+    }
+
     fn keys(&self) -> Box<dyn Stream<Item = Result<Keypair>> + Unpin + Send + '_> {
         let s = async_stream::try_stream! {
             let mut reader = fs::read_dir(&self.path).await?;
@@ -320,6 +413,13 @@ mod tests {
 
         let keys: Vec<_> = kc.keys().try_collect().await.unwrap();
         assert_eq!(keys.len(), 2);
+
+        let key = kc.get("id_ed25519_1").await.unwrap().unwrap();
+        assert_eq!(
+            libp2p::identity::Keypair::from(key).public(),
+            libp2p::identity::Keypair::from(keys[1].clone()).public()
+        );
+        assert!(kc.get("id_ed25519_2").await.unwrap().is_none());
     }
 
     #[tokio::test]
@@ -359,5 +459,41 @@ mod tests {
 
         let keys: Vec<_> = kc.keys().try_collect().await.unwrap();
         assert_eq!(keys.len(), 2);
+
+        assert!(kc.get("id_ed25519_0").await.unwrap().is_some());
+        assert!(kc.get("id_ed25519_2").await.unwrap().is_none());
+        assert!(kc.get("id_foo").await.unwrap().is_none());
+        assert!(kc.get("id_ed25119_4").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn published_names_disk_keychain() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut kc = Keychain::<DiskStorage>::with_root(dir.path().into())
+            .await
+            .unwrap();
+        assert!(kc.published_names().await.unwrap().is_empty());
+
+        let names = vec![
+            PublishedName {
+                key: None,
+                value: "/ipfs/bafkqaaa".to_string(),
+                sequence: 3,
+            },
+            PublishedName {
+                key: Some("id_ed25519_1".to_string()),
+                value: "/ipfs/bafkqaaa/foo".to_string(),
+                sequence: 0,
+            },
+        ];
+        kc.put_published_names(names.clone()).await.unwrap();
+        // the list is not mistaken for a key
+        assert_eq!(kc.len().await.unwrap(), 0);
+
+        let kc = Keychain::<DiskStorage>::with_root(dir.path().into())
+            .await
+            .unwrap();
+        assert_eq!(kc.published_names().await.unwrap(), names);
     }
 }
@@ -5,6 +5,8 @@ mod keys;
 pub mod metrics;
 mod node;
 mod providers;
+mod record_store;
+mod reprovider;
 pub mod rpc;
 mod swarm;
 
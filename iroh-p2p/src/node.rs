@@ -4,9 +4,12 @@ use std::time::Duration;
 
 use ahash::AHashMap;
 use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
 use cid::Cid;
 use futures_util::stream::StreamExt;
 use iroh_metrics::{core::MRecorder, inc, libp2p_metrics, p2p::P2PMetrics};
+use iroh_resolver::ipns::{self, IpnsRecord};
+use iroh_resolver::Path;
 use iroh_rpc_client::Client as RpcClient;
 use iroh_rpc_types::p2p::P2pAddr;
 use libp2p::core::Multiaddr;
@@ -15,9 +18,10 @@ pub use libp2p::gossipsub::{IdentTopic, Topic};
 use libp2p::identify::{Event as IdentifyEvent, Info as IdentifyInfo};
 use libp2p::identity::Keypair;
 use libp2p::kad::kbucket::{Distance, NodeStatus};
+use libp2p::kad::store::RecordStore;
 use libp2p::kad::{
-    self, BootstrapOk, GetClosestPeersError, GetClosestPeersOk, GetProvidersOk, KademliaEvent,
-    QueryId, QueryResult,
+    self, BootstrapOk, GetClosestPeersError, GetClosestPeersOk, GetProvidersOk, GetRecordOk,
+    KademliaEvent, PeerRecord, QueryId, QueryResult, Quorum, Record,
 };
 use libp2p::mdns;
 use libp2p::metrics::Recorder;
@@ -26,16 +30,20 @@ use libp2p::ping::Result as PingResult;
 use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
 use libp2p::swarm::{ConnectionHandler, IntoConnectionHandler, NetworkBehaviour, SwarmEvent};
 use libp2p::{PeerId, Swarm};
+use time::OffsetDateTime;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::oneshot::{self, Sender as OneShotSender};
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, trace, warn};
 
 use iroh_bitswap::{BitswapEvent, Block};
+use iroh_car::CarStore;
 use iroh_rpc_client::Lookup;
 
-use crate::keys::{Keychain, Storage};
+use crate::keys::{Keychain, PublishedName as StoredName, Storage};
 use crate::providers::Providers;
+use crate::record_store::KadStore;
+use crate::reprovider::Reprovider;
 use crate::rpc::{P2p, ProviderRequestKey};
 use crate::swarm::build_swarm;
 use crate::{
@@ -77,16 +85,23 @@ pub struct Node<KeyStorage: Storage> {
     lookup_queries: AHashMap<PeerId, Vec<oneshot::Sender<Result<IdentifyInfo>>>>,
     // TODO(ramfox): use new providers queue instead
     find_on_dht_queries: AHashMap<Vec<u8>, DHTQuery>,
+    record_queries: AHashMap<QueryId, RecordQuery>,
+    /// Publish requests waiting for their record to be put into the DHT.
+    publish_queries: AHashMap<QueryId, PublishQuery>,
     network_events: Vec<Sender<NetworkEvent>>,
-    #[allow(dead_code)]
     rpc_client: RpcClient,
-    _keychain: Keychain<KeyStorage>,
+    keychain: Keychain<KeyStorage>,
+    ipns_names: AHashMap<PeerId, PublishedName>,
+    /// Set when `ipns_names` changed and has not been written to the keychain yet.
+    ipns_names_changed: bool,
     #[allow(dead_code)]
     kad_last_range: Option<(Distance, Distance)>,
     rpc_task: JoinHandle<()>,
     use_dht: bool,
     bitswap_sessions: BitswapSessions,
     providers: Providers,
+    reprovider: Reprovider,
+    reprovide_interval: Option<Duration>,
     listen_addrs: Vec<Multiaddr>,
 }
 
@@ -98,14 +113,20 @@ impl<T: Storage> fmt::Debug for Node<T> {
             .field("dial_queries", &self.dial_queries)
             .field("lookup_queries", &self.lookup_queries)
             .field("find_on_dht_queries", &self.find_on_dht_queries)
+            .field("record_queries", &self.record_queries)
+            .field("publish_queries", &self.publish_queries)
             .field("network_events", &self.network_events)
             .field("rpc_client", &self.rpc_client)
-            .field("_keychain", &self._keychain)
+            .field("keychain", &self.keychain)
+            .field("ipns_names", &self.ipns_names)
+            .field("ipns_names_changed", &self.ipns_names_changed)
             .field("kad_last_range", &self.kad_last_range)
             .field("rpc_task", &self.rpc_task)
             .field("use_dht", &self.use_dht)
             .field("bitswap_sessions", &self.bitswap_sessions)
             .field("providers", &self.providers)
+            .field("reprovider", &self.reprovider)
+            .field("reprovide_interval", &self.reprovide_interval)
             .finish()
     }
 }
@@ -113,12 +134,53 @@ impl<T: Storage> fmt::Debug for Node<T> {
 // TODO(ramfox): use new providers queue instead
 type DHTQuery = (PeerId, Vec<oneshot::Sender<Result<()>>>);
 
+/// An IPNS name published by this node, which is republished periodically.
+#[derive(Debug)]
+struct PublishedName {
+    /// Name of the keychain key, `None` for the identity of the node.
+    key: Option<String>,
+    keypair: Keypair,
+    value: Path,
+    sequence: u64,
+}
+
+/// The records found so far for a DHT record lookup, and who is waiting for them.
+type RecordQuery = (Vec<Bytes>, RecordQueryTarget);
+
+#[derive(Debug)]
+enum RecordQueryTarget {
+    /// A record requested over rpc.
+    Rpc(oneshot::Sender<Result<Vec<Bytes>>>),
+    /// The current record of a name about to be published, needed to pick the sequence
+    /// number of the new record. The sequence of the `PublishedName` is set once the
+    /// lookup is done.
+    Publish(PublishedName, oneshot::Sender<Result<PeerId>>),
+}
+
+/// The name being published, and where to send it once its record is in the DHT.
+type PublishQuery = (PublishedName, oneshot::Sender<Result<PeerId>>);
+
 type BitswapSessions = AHashMap<u64, Vec<(oneshot::Sender<()>, JoinHandle<()>)>>;
 
 pub(crate) const DEFAULT_PROVIDER_LIMIT: usize = 10;
 const NICE_INTERVAL: Duration = Duration::from_secs(6);
 const BOOTSTRAP_INTERVAL: Duration = Duration::from_secs(5 * 60);
 const EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+/// How long published IPNS records are valid.
+const IPNS_RECORD_LIFETIME: Duration = Duration::from_secs(48 * 60 * 60);
+/// How long other nodes may cache published IPNS records.
+const IPNS_RECORD_TTL: Duration = Duration::from_secs(60 * 60);
+const IPNS_REPUBLISH_INTERVAL: Duration = Duration::from_secs(4 * 60 * 60);
+/// Delay before the first republish of the names loaded at startup, giving the node time
+/// to join the DHT.
+const IPNS_REPUBLISH_INITIAL_DELAY: Duration = Duration::from_secs(60);
+/// Stop looking for more copies of a record on the DHT once this many have been found.
+const MAX_RECORDS_PER_QUERY: usize = 16;
+/// Delay before the first reprovide run, giving the store time to come up.
+const REPROVIDE_INITIAL_DELAY: Duration = Duration::from_secs(60);
+/// Number of kademlia records, and of keys with providers, checked for expiry per tick of
+/// the expiry interval.
+const KAD_EXPIRY_BATCH_SIZE: usize = 256;
 
 impl<KeyStorage: Storage> Drop for Node<KeyStorage> {
     fn drop(&mut self) {
@@ -137,6 +199,9 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
         let Config {
             libp2p: libp2p_config,
             rpc_client,
+            kad_store_path,
+            car_files,
+            reprovider,
             ..
         } = config;
 
@@ -151,8 +216,20 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
             .await
             .context("failed to create rpc client")?;
 
+        let car_store = CarStore::open(&car_files)
+            .await
+            .context("failed to open car files")?;
+
         let keypair = load_identity(&mut keychain).await?;
-        let mut swarm = build_swarm(&libp2p_config, &keypair, rpc_client.clone()).await?;
+        let ipns_names = load_ipns_names(&keychain).await?;
+        let mut swarm = build_swarm(
+            &libp2p_config,
+            kad_store_path.as_deref(),
+            &keypair,
+            rpc_client.clone(),
+            car_store.clone(),
+        )
+        .await?;
 
         let mut listen_addrs = vec![];
         for addr in &libp2p_config.listening_multiaddrs {
@@ -167,14 +244,20 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
             lookup_queries: Default::default(),
             // TODO(ramfox): use new providers queue instead
             find_on_dht_queries: Default::default(),
+            record_queries: Default::default(),
+            publish_queries: Default::default(),
             network_events: Vec::new(),
             rpc_client,
-            _keychain: keychain,
+            keychain,
+            ipns_names,
+            ipns_names_changed: false,
             kad_last_range: None,
             rpc_task,
             use_dht: libp2p_config.kademlia,
             bitswap_sessions: Default::default(),
             providers: Providers::new(4),
+            reprovider: Reprovider::new(reprovider.strategy, car_store),
+            reprovide_interval: reprovider.interval(),
             listen_addrs,
         })
     }
@@ -195,6 +278,14 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
         let mut nice_interval = self.use_dht.then(|| tokio::time::interval(NICE_INTERVAL));
         let mut bootstrap_interval = tokio::time::interval(BOOTSTRAP_INTERVAL);
         let mut expiry_interval = tokio::time::interval(EXPIRY_INTERVAL);
+        let mut republish_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + IPNS_REPUBLISH_INITIAL_DELAY,
+            IPNS_REPUBLISH_INTERVAL,
+        );
+        let mut reprovide_interval = self.reprovide_interval.map(|interval| {
+            let start = tokio::time::Instant::now() + REPROVIDE_INITIAL_DELAY;
+            tokio::time::interval_at(start, interval)
+        });
 
         loop {
             inc!(P2PMetrics::LoopCounter);
@@ -209,9 +300,29 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
                     if let Some(kad) = self.swarm.behaviour_mut().kad.as_mut() {
                         self.providers.poll(kad);
                     }
+
+                    if self.ipns_names_changed {
+                        self.ipns_names_changed = false;
+                        if let Err(err) = self.save_ipns_names().await {
+                            warn!("failed to save the published names: {:?}", err);
+                        }
+                    }
                 }
                 rpc_message = self.net_receiver_in.recv() => {
                     match rpc_message {
+                        Some(RpcMessage::PublishName(response_channel, key, cid)) => {
+                            // needs the keychain, which can only be accessed asynchronously
+                            match self.lookup_ipns_name(key, cid).await {
+                                Ok((query_id, published)) => {
+                                    let target =
+                                        RecordQueryTarget::Publish(published, response_channel);
+                                    self.record_queries.insert(query_id, (Vec::new(), target));
+                                }
+                                Err(err) => {
+                                    response_channel.send(Err(err)).ok();
+                                }
+                            }
+                        }
                         Some(rpc_message) => {
                             match self.handle_rpc_message(rpc_message) {
                                 Ok(true) => {
@@ -252,6 +363,21 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
                         warn!("expiry error {:?}", err);
                     }
                 }
+                _ = republish_interval.tick() => {
+                    self.republish_names();
+                }
+                _ = async {
+                    if let Some(ref mut reprovide_interval) = reprovide_interval {
+                        reprovide_interval.tick().await
+                    } else {
+                        unreachable!()
+                    }
+                }, if reprovide_interval.is_some() => {
+                    self.reprovider.start(&self.rpc_client);
+                }
+                key = self.reprovider.next_key(), if self.reprovider.wants_key() => {
+                    self.reprovider.provide(key, self.swarm.behaviour_mut().kad.as_mut());
+                }
             }
         }
     }
@@ -279,9 +405,144 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
             self.destroy_session(session_id, s);
         }
 
+        // The memory store is bounded and kademlia ignores expired entries, the persistent
+        // store has to be cleaned up so it does not grow forever.
+        if let Some(kad) = self.swarm.behaviour_mut().kad.as_mut() {
+            if let KadStore::Persistent(store) = kad.store_mut() {
+                store.poll_expiry(KAD_EXPIRY_BATCH_SIZE)?;
+            }
+        }
+
         Ok(())
     }
 
+    /// Looks up the current record of the name of the keychain key named `key`, or of the
+    /// node's identity, before publishing `cid` under it.
+    ///
+    /// Returns the lookup query, and the name to publish once it is done.
+    async fn lookup_ipns_name(
+        &mut self,
+        key: Option<String>,
+        cid: Cid,
+    ) -> Result<(QueryId, PublishedName)> {
+        let keypair = load_ipns_key(&self.keychain, key.as_deref()).await?;
+        let name = keypair.public().to_peer_id();
+        let kad = self
+            .swarm
+            .behaviour_mut()
+            .kad
+            .as_mut()
+            .ok_or_else(|| anyhow!("kademlia is not available"))?;
+        let query_id = kad.get_record(kad::record::Key::new(&ipns::record_key(&name)));
+        let published = PublishedName {
+            key,
+            keypair,
+            value: Path::from_cid(cid),
+            sequence: 0,
+        };
+
+        Ok((query_id, published))
+    }
+
+    /// Signs a record for `published`, superseding the `records` found on the DHT, and
+    /// starts putting it into the DHT.
+    fn publish_name(
+        &mut self,
+        mut published: PublishedName,
+        records: &[Bytes],
+        chan: oneshot::Sender<Result<PeerId>>,
+    ) {
+        let name = published.keypair.public().to_peer_id();
+        let mut previous: Vec<_> = records
+            .iter()
+            .filter_map(|record| IpnsRecord::decode_and_verify(&name, record).ok())
+            .chain(self.stored_ipns_record(&name))
+            .map(|record| (record.value().clone(), record.sequence()))
+            .collect();
+        if let Some(known) = self.ipns_names.get(&name) {
+            previous.push((known.value.clone(), known.sequence));
+        }
+        published.sequence = next_sequence(&published.value, previous);
+
+        match self.put_ipns_record(&name, &published) {
+            Ok(query_id) => {
+                info!("publishing {} to /ipns/{}", published.value, name);
+                self.publish_queries.insert(query_id, (published, chan));
+            }
+            Err(err) => {
+                chan.send(Err(err)).ok();
+            }
+        }
+    }
+
+    /// Sends the `records` found by a DHT lookup to whoever is waiting for them.
+    fn finish_record_query(&mut self, target: RecordQueryTarget, records: Result<Vec<Bytes>>) {
+        match target {
+            RecordQueryTarget::Rpc(chan) => {
+                chan.send(records).ok();
+            }
+            RecordQueryTarget::Publish(published, chan) => {
+                // the lookup fails when no record exists anywhere, which is fine for a new name
+                let records = records.unwrap_or_default();
+                self.publish_name(published, &records, chan);
+            }
+        }
+    }
+
+    /// Writes the names published by this node to the keychain, so they are republished
+    /// after a restart.
+    async fn save_ipns_names(&mut self) -> Result<()> {
+        let names = self
+            .ipns_names
+            .values()
+            .map(|published| StoredName {
+                key: published.key.clone(),
+                value: published.value.to_string(),
+                sequence: published.sequence,
+            })
+            .collect();
+        self.keychain.put_published_names(names).await
+    }
+
+    /// Returns the valid record for the IPNS `name` held by the local DHT store, if any.
+    fn stored_ipns_record(&mut self, name: &PeerId) -> Option<IpnsRecord> {
+        let kad = self.swarm.behaviour_mut().kad.as_mut()?;
+        let key = kad::record::Key::new(&ipns::record_key(name));
+        let record = kad.store_mut().get(&key)?;
+        IpnsRecord::decode_and_verify(name, &record.value).ok()
+    }
+
+    /// Creates a fresh record for a published name and starts putting it into the DHT.
+    fn put_ipns_record(&mut self, name: &PeerId, published: &PublishedName) -> Result<QueryId> {
+        let kad = self
+            .swarm
+            .behaviour_mut()
+            .kad
+            .as_mut()
+            .ok_or_else(|| anyhow!("kademlia is not available"))?;
+        let value = ipns::create_record(
+            &published.keypair,
+            &published.value,
+            published.sequence,
+            OffsetDateTime::now_utc() + IPNS_RECORD_LIFETIME,
+            IPNS_RECORD_TTL,
+        )?;
+        let query_id = kad.put_record(Record::new(ipns::record_key(name), value), Quorum::One)?;
+        Ok(query_id)
+    }
+
+    /// Republishes all names published by this node, before their records expire.
+    fn republish_names(&mut self) {
+        let names = std::mem::take(&mut self.ipns_names);
+        for (name, published) in &names {
+            debug!("republishing /ipns/{}", name);
+            if let Err(err) = self.put_ipns_record(name, published) {
+                warn!("failed to republish /ipns/{}: {:?}", name, err);
+            }
+        }
+        self.ipns_names = names;
+    }
+
     /// Check the next node in the DHT.
     #[tracing::instrument(skip(self))]
     async fn dht_nice_tick(&mut self) {
@@ -485,7 +746,9 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
                 match e {
                     BitswapEvent::Provide { key } => {
                         info!("bitswap provide {}", key);
-                        if let Some(kad) = self.swarm.behaviour_mut().kad.as_mut() {
+                        if iroh_util::inline_data(&key).is_some() {
+                            // the content is in the cid, there is nothing to find
+                        } else if let Some(kad) = self.swarm.behaviour_mut().kad.as_mut() {
                             match kad.start_providing(key.hash().to_bytes().into()) {
                                 Ok(_query_id) => {
                                     // TODO: track query?
@@ -627,6 +890,71 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
                                 });
                             }
                         }
+                        QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(PeerRecord {
+                            record,
+                            ..
+                        }))) => {
+                            debug!("GetRecord found record for {:?}", record.key);
+                            if let Some((records, _)) = self.record_queries.get_mut(&id) {
+                                records.push(record.value.into());
+                                if records.len() >= MAX_RECORDS_PER_QUERY || step.last {
+                                    if let Some(mut query) = self
+                                        .swarm
+                                        .behaviour_mut()
+                                        .kad
+                                        .as_mut()
+                                        .and_then(|kad| kad.query_mut(&id))
+                                    {
+                                        query.finish();
+                                    }
+                                    if let Some((records, target)) = self.record_queries.remove(&id)
+                                    {
+                                        self.finish_record_query(target, Ok(records));
+                                    }
+                                }
+                            }
+                        }
+                        QueryResult::GetRecord(Ok(
+                            GetRecordOk::FinishedWithNoAdditionalRecord { .. },
+                        )) => {
+                            if let Some((records, target)) = self.record_queries.remove(&id) {
+                                self.finish_record_query(target, Ok(records));
+                            }
+                        }
+                        QueryResult::StartProviding(res) => {
+                            self.reprovider.handle_query_result(id, res.is_ok());
+                        }
+                        QueryResult::PutRecord(res) => {
+                            if let Some((published, chan)) = self.publish_queries.remove(&id) {
+                                let name = published.keypair.public().to_peer_id();
+                                let res = match res {
+                                    Ok(_) => {
+                                        info!("published /ipns/{}", name);
+                                        self.ipns_names.insert(name, published);
+                                        self.ipns_names_changed = true;
+                                        Ok(name)
+                                    }
+                                    Err(error) => Err(anyhow!(
+                                        "failed to put the record of /ipns/{} into the DHT: {:?}",
+                                        name,
+                                        error
+                                    )),
+                                };
+                                chan.send(res).ok();
+                            }
+                        }
+                        QueryResult::GetRecord(Err(error)) => {
+                            debug!("GetRecord error: {:?}", error);
+                            if let Some((records, target)) = self.record_queries.remove(&id) {
+                                // records found before the error are still worth returning
+                                let res = if records.is_empty() {
+                                    Err(anyhow!("failed to get record: {:?}", error))
+                                } else {
+                                    Ok(records)
+                                };
+                                self.finish_record_query(target, res);
+                            }
+                        }
                         other => {
                             debug!("Libp2p => Unhandled Kademlia query result: {:?}", other)
                         }
@@ -845,6 +1173,19 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
                         .ok();
                 }
             }
+            RpcMessage::GetRecord(response_channel, key) => {
+                if let Some(kad) = self.swarm.behaviour_mut().kad.as_mut() {
+                    let query_id = kad.get_record(key);
+                    self.record_queries.insert(
+                        query_id,
+                        (Vec::new(), RecordQueryTarget::Rpc(response_channel)),
+                    );
+                } else {
+                    response_channel
+                        .send(Err(anyhow!("kademlia is not available")))
+                        .ok();
+                }
+            }
             RpcMessage::NetListeningAddrs(response_channel) => {
                 let mut listeners: Vec<_> = Swarm::listeners(&self.swarm).cloned().collect();
                 let peer_id = *Swarm::local_peer_id(&self.swarm);
@@ -1058,6 +1399,12 @@ impl<KeyStorage: Storage> Node<KeyStorage> {
                     });
                 }
             }
+            RpcMessage::PublishName(response_channel, ..) => {
+                // needs the keychain, so `Node::run` handles it before getting here
+                response_channel
+                    .send(Err(anyhow!("publishing names is not supported here")))
+                    .ok();
+            }
             RpcMessage::Shutdown => {
                 return Ok(true);
             }
@@ -1084,6 +1431,66 @@ async fn load_identity<S: Storage>(kc: &mut Keychain<S>) -> Result<Keypair> {
     Err(anyhow!("inconsistent keystate"))
 }
 
+/// Returns the keychain key named `key`, or the identity of the node.
+async fn load_ipns_key<S: Storage>(kc: &Keychain<S>, key: Option<&str>) -> Result<Keypair> {
+    let keypair = match key {
+        Some(key) => kc
+            .get(key)
+            .await?
+            .ok_or_else(|| anyhow!("no key named {}", key))?,
+        None => kc
+            .keys()
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("no identity key"))??,
+    };
+    Ok(keypair.into())
+}
+
+/// Loads the names published before the last shutdown, to keep republishing them.
+async fn load_ipns_names<S: Storage>(kc: &Keychain<S>) -> Result<AHashMap<PeerId, PublishedName>> {
+    let mut names = AHashMap::default();
+    for stored in kc.published_names().await? {
+        let keypair = match load_ipns_key(kc, stored.key.as_deref()).await {
+            Ok(keypair) => keypair,
+            Err(err) => {
+                warn!("not republishing {}: {:?}", stored.value, err);
+                continue;
+            }
+        };
+        let value = match stored.value.parse() {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("not republishing {}: {:?}", stored.value, err);
+                continue;
+            }
+        };
+        let name = keypair.public().to_peer_id();
+        names.insert(
+            name,
+            PublishedName {
+                key: stored.key,
+                keypair,
+                value,
+                sequence: stored.sequence,
+            },
+        );
+    }
+    Ok(names)
+}
+
+/// Picks the sequence number for a record publishing `value`, given the values and sequence
+/// numbers of the known records of the name.
+///
+/// Like kubo, the sequence number is only bumped when the value changes.
+fn next_sequence(value: &Path, previous: impl IntoIterator<Item = (Path, u64)>) -> u64 {
+    match previous.into_iter().max_by_key(|(_, sequence)| *sequence) {
+        Some((previous, sequence)) if previous == *value => sequence,
+        Some((_, sequence)) => sequence + 1,
+        None => 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::keys::{Keypair, MemoryStorage};
@@ -1151,6 +1558,8 @@ mod tests {
         seed: Option<ChaCha8Rng>,
         /// Optional `Keys` the node should provide to the DHT on start up.
         keys: Option<Vec<Key>>,
+        /// An optional second key for the keychain, named `id_ed25519_1`.
+        extra_key: Option<Keypair>,
     }
 
     impl TestRunnerBuilder {
@@ -1161,6 +1570,7 @@ mod tests {
                 bootstrap: true,
                 seed: None,
                 keys: None,
+                extra_key: None,
             }
         }
 
@@ -1184,6 +1594,11 @@ mod tests {
             self
         }
 
+        fn with_extra_key(mut self, key: Keypair) -> Self {
+            self.extra_key = Some(key);
+            self
+        }
+
         async fn build(self) -> Result<TestRunner> {
             let (rpc_server_addr, rpc_client_addr) = match self.rpc_addrs {
                 Some((rpc_server_addr, rpc_client_addr)) => (rpc_server_addr, rpc_client_addr),
@@ -1221,6 +1636,9 @@ mod tests {
             let peer_id = PeerId::from(libp2p_keypair.public());
             let mut storage = MemoryStorage::default();
             storage.put(keypair).await?;
+            if let Some(extra_key) = self.extra_key {
+                storage.put(extra_key).await?;
+            }
             let kc = Keychain::from_storage(storage);
 
             let mut p2p = Node::new(network_config, rpc_server_addr, kc).await?;
@@ -1677,6 +2095,149 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_publish_name() -> Result<()> {
+        let cid_a: Cid = "bafkreieq5jui4j25lacwomsqgjeswwl3y5zcdrresptwgmfylxo2depppq"
+            .parse()
+            .unwrap();
+        let cid_b: Cid = "bafkqaaa".parse().unwrap();
+        // a key shared by a and c, to publish the same name from both
+        let shared_key = Keypair::Ed25519(Ed25519Keypair::random(ChaCha8Rng::from_seed([2; 32])));
+        let shared_name = PeerId::from(Libp2pKeypair::from(shared_key.clone()).public());
+
+        // set up three nodes, a and c connect to b
+        let test_runner_a = TestRunnerBuilder::new()
+            .no_bootstrap()
+            .with_extra_key(shared_key.clone())
+            .build()
+            .await?;
+        // peer_id 12D3KooWLo6JTNKXfjkZtKf8ooLQoXVXUEeuu4YDY3CYqK6rxHXt
+        let mut test_runner_b = TestRunnerBuilder::new()
+            .no_bootstrap()
+            .with_seed(ChaCha8Rng::from_seed([0; 32]))
+            .build()
+            .await?;
+        let test_runner_c = TestRunnerBuilder::new()
+            .no_bootstrap()
+            .with_seed(ChaCha8Rng::from_seed([1; 32]))
+            .with_extra_key(shared_key)
+            .build()
+            .await?;
+
+        for test_runner in [&test_runner_a, &test_runner_c] {
+            test_runner
+                .client
+                .connect(test_runner_b.peer_id, vec![test_runner_b.addr.clone()])
+                .await?;
+            match test_runner_b.network_events.recv().await {
+                Some(NetworkEvent::PeerConnected(peer_id)) => {
+                    assert_eq!(test_runner.peer_id, peer_id);
+                }
+                n => {
+                    anyhow::bail!("expected NetworkEvent::PeerConnected, got {:?}", n);
+                }
+            };
+        }
+
+        // the record of a name held by b
+        let published = |name: PeerId| {
+            let client = test_runner_b.client.clone();
+            async move {
+                let records = client.get_record(ipns::record_key(&name).into()).await?;
+                let record = IpnsRecord::decode_and_verify(&name, &records[0])?;
+                anyhow::Ok((record.value().clone(), record.sequence()))
+            }
+        };
+
+        let name = test_runner_a.client.publish_name(cid_a, None).await?;
+        assert_eq!(name, test_runner_a.peer_id);
+        assert_eq!(published(name).await?, (Path::from_cid(cid_a), 0));
+
+        // publishing the same value again keeps the sequence number
+        test_runner_a.client.publish_name(cid_a, None).await?;
+        assert_eq!(published(name).await?, (Path::from_cid(cid_a), 0));
+
+        // a new value bumps it
+        test_runner_a.client.publish_name(cid_b, None).await?;
+        assert_eq!(published(name).await?, (Path::from_cid(cid_b), 1));
+
+        // c has never published the shared name, its record has to supersede the one a put
+        // into the DHT
+        let key = Some("id_ed25519_1".to_string());
+        let name = test_runner_a
+            .client
+            .publish_name(cid_a, key.clone())
+            .await?;
+        assert_eq!(name, shared_name);
+        test_runner_a
+            .client
+            .publish_name(cid_b, key.clone())
+            .await?;
+        assert_eq!(published(name).await?, (Path::from_cid(cid_b), 1));
+        test_runner_c.client.publish_name(cid_a, key).await?;
+        assert_eq!(published(name).await?, (Path::from_cid(cid_a), 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_sequence() {
+        let a = Path::from_cid("bafkqaaa".parse().unwrap());
+        let b = Path::from_cid(
+            "bafkreieq5jui4j25lacwomsqgjeswwl3y5zcdrresptwgmfylxo2depppq"
+                .parse()
+                .unwrap(),
+        );
+
+        assert_eq!(next_sequence(&a, []), 0);
+        assert_eq!(next_sequence(&a, [(a.clone(), 3)]), 3);
+        assert_eq!(next_sequence(&a, [(b.clone(), 3)]), 4);
+        // the newest record wins, even when an older one already has the value
+        assert_eq!(next_sequence(&a, [(a.clone(), 2), (b.clone(), 5)]), 6);
+        assert_eq!(next_sequence(&a, [(b, 1), (a, 5)]), 5);
+    }
+
+    #[tokio::test]
+    async fn test_load_ipns_names() -> Result<()> {
+        let mut kc = Keychain::<MemoryStorage>::new();
+        kc.create_ed25519_key().await?;
+        kc.create_ed25519_key().await?;
+        let identity: Libp2pKeypair = kc.get("id_ed25519_0").await?.unwrap().into();
+        let other: Libp2pKeypair = kc.get("id_ed25519_1").await?.unwrap().into();
+
+        kc.put_published_names(vec![
+            StoredName {
+                key: None,
+                value: "/ipfs/bafkqaaa".to_string(),
+                sequence: 2,
+            },
+            StoredName {
+                key: Some("id_ed25519_1".to_string()),
+                value: "/ipfs/bafkqaaa/foo".to_string(),
+                sequence: 0,
+            },
+            // keys removed from the keychain are skipped
+            StoredName {
+                key: Some("id_ed25519_2".to_string()),
+                value: "/ipfs/bafkqaaa".to_string(),
+                sequence: 0,
+            },
+        ])
+        .await?;
+
+        let names = load_ipns_names(&kc).await?;
+        assert_eq!(names.len(), 2);
+        let published = &names[&identity.public().to_peer_id()];
+        assert_eq!(published.key, None);
+        assert_eq!(published.value.to_string(), "/ipfs/bafkqaaa");
+        assert_eq!(published.sequence, 2);
+        let published = &names[&other.public().to_peer_id()];
+        assert_eq!(published.key.as_deref(), Some("id_ed25519_1"));
+        assert_eq!(published.value.to_string(), "/ipfs/bafkqaaa/foo");
+
+        Ok(())
+    }
+
     async fn poll_for_providers(client: P2pClient, cid: &Cid) -> Result<Vec<HashSet<PeerId>>> {
         loop {
             let stream = client.fetch_providers_dht(cid).await?;
@@ -0,0 +1,818 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use futures::FutureExt;
+use libp2p::kad::record::{Key, ProviderRecord, Record};
+use libp2p::kad::store::{self, MemoryStore, RecordStore};
+use libp2p::kad::K_VALUE;
+use libp2p::{Multiaddr, PeerId};
+use rocksdb::{ColumnFamily, Direction, IteratorMode, Options, WriteBatch, DB};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Column family storing the records, keyed by record key.
+const CF_RECORDS_V0: &str = "records-v0";
+/// Column family storing the known providers, keyed by record key.
+const CF_PROVIDERS_V0: &str = "providers-v0";
+/// Column family storing the provider records of this node, keyed by record key.
+const CF_PROVIDED_V0: &str = "provided-v0";
+
+/// The record store used by the kademlia behaviour.
+pub enum KadStore {
+    Memory(MemoryStore),
+    Persistent(PersistentStore),
+}
+
+impl<'a> RecordStore<'a> for KadStore {
+    type RecordsIter = Box<dyn Iterator<Item = Cow<'a, Record>> + 'a>;
+    type ProvidedIter = Box<dyn Iterator<Item = Cow<'a, ProviderRecord>> + 'a>;
+
+    fn get(&'a self, k: &Key) -> Option<Cow<'_, Record>> {
+        match self {
+            KadStore::Memory(s) => s.get(k),
+            KadStore::Persistent(s) => s.get(k),
+        }
+    }
+
+    fn put(&'a mut self, r: Record) -> store::Result<()> {
+        match self {
+            KadStore::Memory(s) => s.put(r),
+            KadStore::Persistent(s) => s.put(r),
+        }
+    }
+
+    fn remove(&'a mut self, k: &Key) {
+        match self {
+            KadStore::Memory(s) => s.remove(k),
+            KadStore::Persistent(s) => s.remove(k),
+        }
+    }
+
+    fn records(&'a self) -> Self::RecordsIter {
+        match self {
+            KadStore::Memory(s) => Box::new(s.records()),
+            KadStore::Persistent(s) => s.records(),
+        }
+    }
+
+    fn add_provider(&'a mut self, record: ProviderRecord) -> store::Result<()> {
+        match self {
+            KadStore::Memory(s) => s.add_provider(record),
+            KadStore::Persistent(s) => s.add_provider(record),
+        }
+    }
+
+    fn providers(&'a self, key: &Key) -> Vec<ProviderRecord> {
+        match self {
+            KadStore::Memory(s) => s.providers(key),
+            KadStore::Persistent(s) => s.providers(key),
+        }
+    }
+
+    fn provided(&'a self) -> Self::ProvidedIter {
+        match self {
+            KadStore::Memory(s) => Box::new(s.provided()),
+            KadStore::Persistent(s) => s.provided(),
+        }
+    }
+
+    fn remove_provider(&'a mut self, k: &Key, p: &PeerId) {
+        match self {
+            KadStore::Memory(s) => s.remove_provider(k, p),
+            KadStore::Persistent(s) => s.remove_provider(k, p),
+        }
+    }
+}
+
+/// Configuration of a [`PersistentStore`].
+#[derive(Debug, Clone)]
+pub struct PersistentStoreConfig {
+    /// The maximum number of records, unbounded if `None`.
+    pub max_records: Option<usize>,
+    /// The maximum size of record values, in bytes.
+    pub max_value_bytes: usize,
+    /// The maximum number of providers stored for a key.
+    pub max_providers_per_key: usize,
+    /// The maximum number of keys provided by this node, unbounded if `None`.
+    pub max_provided_keys: Option<usize>,
+}
+
+impl Default for PersistentStoreConfig {
+    fn default() -> Self {
+        PersistentStoreConfig {
+            max_records: None,
+            max_value_bytes: 65 * 1024,
+            max_providers_per_key: K_VALUE.get(),
+            max_provided_keys: None,
+        }
+    }
+}
+
+/// A kademlia record store persisted in RocksDB.
+///
+/// Records, providers and the keys provided by this node survive restarts, so a node does
+/// not have to wait for peers to republish records, or re-announce everything it provides,
+/// before it can serve them again.
+///
+/// Expired records and providers are only removed by [`PersistentStore::poll_expiry`].
+pub struct PersistentStore {
+    local_key: PeerId,
+    config: PersistentStoreConfig,
+    db: Arc<DB>,
+    /// Number of stored records, only counted if they are limited.
+    num_records: Option<usize>,
+    /// Number of keys provided by this node, only counted if they are limited.
+    num_provided: Option<usize>,
+    /// The scan for expired entries, unless it is running in `expiry_task`.
+    expiry_scan: Option<ExpiryScan>,
+    expiry_task: Option<JoinHandle<(ExpiryScan, Result<ExpiredKeys>)>>,
+}
+
+/// Scans the store for keys with expired records or providers, in chunks.
+struct ExpiryScan {
+    db: Arc<DB>,
+    /// Where the next scan continues.
+    records_cursor: Option<Vec<u8>>,
+    providers_cursor: Option<Vec<u8>>,
+}
+
+/// Keys found by an [`ExpiryScan`].
+#[derive(Debug, Default)]
+struct ExpiredKeys {
+    /// Keys of expired records.
+    records: Vec<Key>,
+    /// Keys with at least one expired provider.
+    providers: Vec<Key>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordV0 {
+    value: Vec<u8>,
+    publisher: Option<PeerId>,
+    /// Expiration as milliseconds since the unix epoch.
+    expires: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProviderV0 {
+    provider: PeerId,
+    /// Expiration as milliseconds since the unix epoch.
+    expires: Option<u64>,
+    addresses: Vec<Multiaddr>,
+}
+
+impl ProviderV0 {
+    fn from_record(record: &ProviderRecord) -> Self {
+        ProviderV0 {
+            provider: record.provider,
+            expires: record.expires.map(to_timestamp),
+            addresses: record.addresses.clone(),
+        }
+    }
+
+    fn into_record(self, key: Key) -> ProviderRecord {
+        ProviderRecord {
+            key,
+            provider: self.provider,
+            expires: self.expires.map(from_timestamp),
+            addresses: self.addresses,
+        }
+    }
+}
+
+impl PersistentStore {
+    /// Opens the store at the given path, creating it if it does not exist.
+    pub fn open(local_key: PeerId, path: &Path, config: PersistentStoreConfig) -> Result<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let db = DB::open_cf(
+            &options,
+            path,
+            [CF_RECORDS_V0, CF_PROVIDERS_V0, CF_PROVIDED_V0],
+        )
+        .with_context(|| format!("failed to open kademlia store at {}", path.display()))?;
+
+        let db = Arc::new(db);
+
+        // counting reads all keys, which is only worth it to enforce a limit
+        let num_records = match config.max_records {
+            Some(_) => Some(count(&db, CF_RECORDS_V0)?),
+            None => None,
+        };
+        let num_provided = match config.max_provided_keys {
+            Some(_) => Some(count(&db, CF_PROVIDED_V0)?),
+            None => None,
+        };
+        Ok(PersistentStore {
+            local_key,
+            config,
+            expiry_scan: Some(ExpiryScan::new(db.clone())),
+            expiry_task: None,
+            db,
+            num_records,
+            num_provided,
+        })
+    }
+
+    /// Removes expired records and providers, without blocking on the scan for them.
+    ///
+    /// The scan for expired entries runs on a blocking thread and checks up to `limit` keys
+    /// of each kind, continuing after the keys checked by the previous scan, so a large
+    /// store is not scanned all at once. Each call removes the entries found by a finished
+    /// scan, and starts the next one.
+    pub fn poll_expiry(&mut self, limit: usize) -> Result<()> {
+        if let Some(task) = self.expiry_task.take() {
+            if !task.is_finished() {
+                self.expiry_task = Some(task);
+                return Ok(());
+            }
+            match task.now_or_never() {
+                Some(Ok((scan, expired))) => {
+                    self.expiry_scan = Some(scan);
+                    self.remove_expired_keys(expired?)?;
+                }
+                Some(Err(err)) => {
+                    // start over with a new scan
+                    self.expiry_scan = Some(ExpiryScan::new(self.db.clone()));
+                    return Err(anyhow!("kademlia expiry scan failed: {:?}", err));
+                }
+                None => unreachable!("the task is finished"),
+            }
+        }
+        if let Some(mut scan) = self.expiry_scan.take() {
+            self.expiry_task = Some(tokio::task::spawn_blocking(move || {
+                let expired = scan.scan(limit);
+                (scan, expired)
+            }));
+        }
+        Ok(())
+    }
+
+    /// Removes the expired entries of the keys found by an [`ExpiryScan`].
+    ///
+    /// The entries are checked again, as they may have been replaced since the scan.
+    fn remove_expired_keys(&mut self, expired: ExpiredKeys) -> Result<()> {
+        let now = Instant::now();
+        let is_expired = |expires: Option<Instant>| expires.map_or(false, |expires| expires <= now);
+
+        for key in expired.records {
+            if let Some(record) = self.get_record(&key)? {
+                if is_expired(record.expires) {
+                    self.remove(&key);
+                }
+            }
+        }
+        for key in expired.providers {
+            let providers = self.get_providers(&key)?;
+            if providers.iter().any(|p| is_expired(p.expires)) {
+                let providers: Vec<_> = providers
+                    .into_iter()
+                    .filter(|p| !is_expired(p.expires))
+                    .collect();
+                self.put_providers(&key, &providers)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cf(&self, name: &str) -> &ColumnFamily {
+        cf(&self.db, name)
+    }
+
+    fn get_record(&self, key: &Key) -> Result<Option<Record>> {
+        let raw = match self
+            .db
+            .get_pinned_cf(self.cf(CF_RECORDS_V0), key.to_vec())?
+        {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let record: RecordV0 = bincode::deserialize(&raw)?;
+        Ok(Some(Record {
+            key: key.clone(),
+            value: record.value,
+            publisher: record.publisher,
+            expires: record.expires.map(from_timestamp),
+        }))
+    }
+
+    fn has_record(&self, key: &Key) -> Result<bool> {
+        Ok(self
+            .db
+            .get_pinned_cf(self.cf(CF_RECORDS_V0), key.to_vec())?
+            .is_some())
+    }
+
+    fn is_provided(&self, key: &Key) -> Result<bool> {
+        Ok(self
+            .db
+            .get_pinned_cf(self.cf(CF_PROVIDED_V0), key.to_vec())?
+            .is_some())
+    }
+
+    fn put_record(&self, record: &Record) -> Result<()> {
+        let raw = bincode::serialize(&RecordV0 {
+            value: record.value.clone(),
+            publisher: record.publisher,
+            expires: record.expires.map(to_timestamp),
+        })?;
+        self.db
+            .put_cf(self.cf(CF_RECORDS_V0), record.key.to_vec(), raw)?;
+        Ok(())
+    }
+
+    fn get_providers(&self, key: &Key) -> Result<Vec<ProviderRecord>> {
+        let raw = match self
+            .db
+            .get_pinned_cf(self.cf(CF_PROVIDERS_V0), key.to_vec())?
+        {
+            Some(raw) => raw,
+            None => return Ok(Vec::new()),
+        };
+        let providers: Vec<ProviderV0> = bincode::deserialize(&raw)?;
+        Ok(providers
+            .into_iter()
+            .map(|p| p.into_record(key.clone()))
+            .collect())
+    }
+
+    /// Writes the providers of `key`, updating the provided keys of this node accordingly.
+    fn put_providers(&mut self, key: &Key, providers: &[ProviderRecord]) -> Result<()> {
+        let was_provided = self.is_provided(key)?;
+        let mut batch = WriteBatch::default();
+        if providers.is_empty() {
+            batch.delete_cf(self.cf(CF_PROVIDERS_V0), key.to_vec());
+        } else {
+            let stored: Vec<_> = providers.iter().map(ProviderV0::from_record).collect();
+            batch.put_cf(
+                self.cf(CF_PROVIDERS_V0),
+                key.to_vec(),
+                bincode::serialize(&stored)?,
+            );
+        }
+        let local = providers.iter().find(|p| p.provider == self.local_key);
+        match local {
+            Some(local) => batch.put_cf(
+                self.cf(CF_PROVIDED_V0),
+                key.to_vec(),
+                bincode::serialize(&ProviderV0::from_record(local))?,
+            ),
+            None => batch.delete_cf(self.cf(CF_PROVIDED_V0), key.to_vec()),
+        }
+        self.db.write(batch)?;
+        if let Some(ref mut num_provided) = self.num_provided {
+            match (was_provided, local.is_some()) {
+                (false, true) => *num_provided += 1,
+                (true, false) => *num_provided = num_provided.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn update_providers(&mut self, record: ProviderRecord) -> Result<()> {
+        let key = record.key.clone();
+        let mut providers = self.get_providers(&key)?;
+        if let Some(existing) = providers.iter_mut().find(|p| p.provider == record.provider) {
+            *existing = record;
+        } else if providers.len() < self.config.max_providers_per_key
+            || record.provider == self.local_key
+        {
+            // Like the `MemoryStore`, only keep up to `max_providers_per_key` providers per
+            // key, but always keep ourselves.
+            providers.push(record);
+        } else {
+            return Ok(());
+        }
+        self.put_providers(&key, &providers)
+    }
+
+    fn delete_provider(&mut self, key: &Key, provider: &PeerId) -> Result<()> {
+        let mut providers = self.get_providers(key)?;
+        let len = providers.len();
+        providers.retain(|p| &p.provider != provider);
+        if providers.len() != len {
+            self.put_providers(key, &providers)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> RecordStore<'a> for PersistentStore {
+    type RecordsIter = Box<dyn Iterator<Item = Cow<'a, Record>> + 'a>;
+    type ProvidedIter = Box<dyn Iterator<Item = Cow<'a, ProviderRecord>> + 'a>;
+
+    fn get(&'a self, k: &Key) -> Option<Cow<'_, Record>> {
+        match self.get_record(k) {
+            Ok(record) => record.map(Cow::Owned),
+            Err(err) => {
+                warn!("failed to load kademlia record: {:?}", err);
+                None
+            }
+        }
+    }
+
+    fn put(&'a mut self, r: Record) -> store::Result<()> {
+        if r.value.len() >= self.config.max_value_bytes {
+            return Err(store::Error::ValueTooLarge);
+        }
+        let storage_error = |err: anyhow::Error| {
+            warn!("failed to store kademlia record: {:?}", err);
+            // there is no error variant for storage failures, the store is as good as full
+            store::Error::MaxRecords
+        };
+        if let (Some(num_records), Some(max_records)) = (self.num_records, self.config.max_records)
+        {
+            let exists = self.has_record(&r.key).map_err(storage_error)?;
+            if !exists && num_records >= max_records {
+                return Err(store::Error::MaxRecords);
+            }
+            self.put_record(&r).map_err(storage_error)?;
+            if !exists {
+                self.num_records = Some(num_records + 1);
+            }
+            return Ok(());
+        }
+        self.put_record(&r).map_err(storage_error)
+    }
+
+    fn remove(&'a mut self, k: &Key) {
+        let res = self.has_record(k).and_then(|exists| {
+            if exists {
+                self.db.delete_cf(self.cf(CF_RECORDS_V0), k.to_vec())?;
+                if let Some(ref mut num_records) = self.num_records {
+                    *num_records = num_records.saturating_sub(1);
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = res {
+            warn!("failed to remove kademlia record: {:?}", err);
+        }
+    }
+
+    fn records(&'a self) -> Self::RecordsIter {
+        let iter = self
+            .db
+            .iterator_cf(self.cf(CF_RECORDS_V0), IteratorMode::Start)
+            .filter_map(|item| {
+                let (key, raw) = item
+                    .map_err(|err| warn!("failed to iterate kademlia records: {:?}", err))
+                    .ok()?;
+                let record: RecordV0 = bincode::deserialize(&raw)
+                    .map_err(|err| warn!("invalid kademlia record: {:?}", err))
+                    .ok()?;
+                Some(Cow::Owned(Record {
+                    key: Key::from(key.to_vec()),
+                    value: record.value,
+                    publisher: record.publisher,
+                    expires: record.expires.map(from_timestamp),
+                }))
+            });
+        Box::new(iter)
+    }
+
+    fn add_provider(&'a mut self, record: ProviderRecord) -> store::Result<()> {
+        let storage_error = |err: anyhow::Error| {
+            warn!("failed to store kademlia provider: {:?}", err);
+            store::Error::MaxProvidedKeys
+        };
+        if let (Some(num_provided), Some(max_provided_keys)) =
+            (self.num_provided, self.config.max_provided_keys)
+        {
+            if record.provider == self.local_key
+                && num_provided >= max_provided_keys
+                && !self.is_provided(&record.key).map_err(storage_error)?
+            {
+                return Err(store::Error::MaxProvidedKeys);
+            }
+        }
+        self.update_providers(record).map_err(storage_error)
+    }
+
+    fn providers(&'a self, key: &Key) -> Vec<ProviderRecord> {
+        self.get_providers(key).unwrap_or_else(|err| {
+            warn!("failed to load kademlia providers: {:?}", err);
+            Vec::new()
+        })
+    }
+
+    fn provided(&'a self) -> Self::ProvidedIter {
+        let iter = self
+            .db
+            .iterator_cf(self.cf(CF_PROVIDED_V0), IteratorMode::Start)
+            .filter_map(|item| {
+                let (key, raw) = item
+                    .map_err(|err| warn!("failed to iterate provided keys: {:?}", err))
+                    .ok()?;
+                let provider: ProviderV0 = bincode::deserialize(&raw)
+                    .map_err(|err| warn!("invalid provider record: {:?}", err))
+                    .ok()?;
+                Some(Cow::Owned(provider.into_record(Key::from(key.to_vec()))))
+            });
+        Box::new(iter)
+    }
+
+    fn remove_provider(&'a mut self, k: &Key, p: &PeerId) {
+        if let Err(err) = self.delete_provider(k, p) {
+            warn!("failed to remove kademlia provider: {:?}", err);
+        }
+    }
+}
+
+impl ExpiryScan {
+    fn new(db: Arc<DB>) -> Self {
+        ExpiryScan {
+            db,
+            records_cursor: None,
+            providers_cursor: None,
+        }
+    }
+
+    /// Checks up to `limit` records, and the providers of up to `limit` keys, for expired
+    /// entries, continuing after the keys checked by the previous scan.
+    fn scan(&mut self, limit: usize) -> Result<ExpiredKeys> {
+        let now = to_timestamp(Instant::now());
+        let is_expired = |expires: Option<u64>| expires.map_or(false, |expires| expires <= now);
+        let mut expired = ExpiredKeys::default();
+
+        let cursor = self.records_cursor.take();
+        let (entries, cursor) = self.entries(CF_RECORDS_V0, cursor, limit)?;
+        self.records_cursor = cursor;
+        for (key, raw) in entries {
+            match bincode::deserialize::<RecordV0>(&raw) {
+                Ok(record) if is_expired(record.expires) => {
+                    expired.records.push(Key::from(key.to_vec()));
+                }
+                Ok(_) => {}
+                Err(err) => warn!("invalid kademlia record: {:?}", err),
+            }
+        }
+
+        let cursor = self.providers_cursor.take();
+        let (entries, cursor) = self.entries(CF_PROVIDERS_V0, cursor, limit)?;
+        self.providers_cursor = cursor;
+        for (key, raw) in entries {
+            match bincode::deserialize::<Vec<ProviderV0>>(&raw) {
+                Ok(providers) if providers.iter().any(|p| is_expired(p.expires)) => {
+                    expired.providers.push(Key::from(key.to_vec()));
+                }
+                Ok(_) => {}
+                Err(err) => warn!("invalid provider record: {:?}", err),
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Reads up to `limit` entries of the column family `name`, starting after `cursor`.
+    /// Returns the cursor to continue from, `None` once the end was reached.
+    #[allow(clippy::type_complexity)]
+    fn entries(
+        &self,
+        name: &str,
+        cursor: Option<Vec<u8>>,
+        limit: usize,
+    ) -> Result<(Vec<(Box<[u8]>, Box<[u8]>)>, Option<Vec<u8>>)> {
+        let mode = match cursor {
+            Some(ref cursor) => IteratorMode::From(cursor, Direction::Forward),
+            None => IteratorMode::Start,
+        };
+        let mut entries = Vec::new();
+        for item in self.db.iterator_cf(cf(&self.db, name), mode) {
+            let (key, value) = item?;
+            if cursor.as_deref() == Some(&key[..]) {
+                continue;
+            }
+            entries.push((key, value));
+            if entries.len() >= limit {
+                let next = entries.last().map(|(key, _)| key.to_vec());
+                return Ok((entries, next));
+            }
+        }
+        Ok((entries, None))
+    }
+}
+
+fn cf<'a>(db: &'a DB, name: &str) -> &'a ColumnFamily {
+    db.cf_handle(name)
+        .expect("column families are created on open")
+}
+
+fn count(db: &DB, name: &str) -> Result<usize> {
+    let mut count = 0;
+    for item in db.iterator_cf(cf(db, name), IteratorMode::Start) {
+        item?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Converts an [`Instant`] into milliseconds since the unix epoch.
+fn to_timestamp(instant: Instant) -> u64 {
+    let now = Instant::now();
+    let time = if instant >= now {
+        SystemTime::now() + (instant - now)
+    } else {
+        SystemTime::now() - (now - instant)
+    };
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Converts milliseconds since the unix epoch into an [`Instant`].
+fn from_timestamp(timestamp: u64) -> Instant {
+    let time = UNIX_EPOCH + Duration::from_millis(timestamp);
+    let now = Instant::now();
+    match time.duration_since(SystemTime::now()) {
+        Ok(remaining) => now + remaining,
+        Err(err) => now.checked_sub(err.duration()).unwrap_or(now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(key: &Key, provider: PeerId) -> ProviderRecord {
+        ProviderRecord {
+            key: key.clone(),
+            provider,
+            expires: Some(Instant::now() + Duration::from_secs(3600)),
+            addresses: vec!["/ip4/127.0.0.1/tcp/4444".parse().unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store =
+            PersistentStore::open(PeerId::random(), dir.path(), Default::default()).unwrap();
+
+        let key = Key::new(&"hello");
+        let mut record = Record::new(key.clone(), b"world".to_vec());
+        record.publisher = Some(PeerId::random());
+        record.expires = Some(Instant::now() + Duration::from_secs(60));
+        store.put(record.clone()).unwrap();
+
+        let got = store.get(&key).unwrap().into_owned();
+        assert_eq!(got.value, record.value);
+        assert_eq!(got.publisher, record.publisher);
+        let (got_expires, expires) = (got.expires.unwrap(), record.expires.unwrap());
+        assert!(got_expires.saturating_duration_since(expires) < Duration::from_secs(1));
+        assert!(expires.saturating_duration_since(got_expires) < Duration::from_secs(1));
+        assert_eq!(store.records().count(), 1);
+
+        let too_large = Record::new(Key::new(&"large"), vec![0u8; 65 * 1024]);
+        assert!(matches!(
+            store.put(too_large),
+            Err(store::Error::ValueTooLarge)
+        ));
+
+        store.remove(&key);
+        assert!(store.get(&key).is_none());
+        assert_eq!(store.records().count(), 0);
+    }
+
+    #[test]
+    fn test_providers() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+
+        let key = Key::new(&"content");
+        for _ in 0..K_VALUE.get() + 5 {
+            store
+                .add_provider(provider(&key, PeerId::random()))
+                .unwrap();
+        }
+        assert_eq!(store.providers(&key).len(), K_VALUE.get());
+        assert_eq!(store.provided().count(), 0);
+
+        // the local node is always kept, even when the key has enough providers
+        store.add_provider(provider(&key, local)).unwrap();
+        assert_eq!(store.providers(&key).len(), K_VALUE.get() + 1);
+        let provided: Vec<_> = store.provided().collect();
+        assert_eq!(provided.len(), 1);
+        assert_eq!(provided[0].key, key);
+        assert_eq!(provided[0].provider, local);
+
+        store.remove_provider(&key, &local);
+        assert_eq!(store.providers(&key).len(), K_VALUE.get());
+        assert_eq!(store.provided().count(), 0);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let key = Key::new(&"persisted");
+
+        {
+            let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+            store
+                .put(Record::new(key.clone(), b"value".to_vec()))
+                .unwrap();
+            store.add_provider(provider(&key, local)).unwrap();
+        }
+
+        let store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+        assert_eq!(store.get(&key).unwrap().value, b"value".to_vec());
+        assert_eq!(store.providers(&key).len(), 1);
+        assert_eq!(store.provided().count(), 1);
+    }
+
+    #[test]
+    fn test_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let config = PersistentStoreConfig {
+            max_records: Some(2),
+            max_provided_keys: Some(2),
+            ..Default::default()
+        };
+        let mut store = PersistentStore::open(local, dir.path(), config.clone()).unwrap();
+
+        for i in 0..2 {
+            store
+                .put(Record::new(Key::new(&i.to_string()), b"value".to_vec()))
+                .unwrap();
+            store
+                .add_provider(provider(&Key::new(&i.to_string()), local))
+                .unwrap();
+        }
+        assert!(matches!(
+            store.put(Record::new(Key::new(&"full"), b"value".to_vec())),
+            Err(store::Error::MaxRecords)
+        ));
+        assert!(matches!(
+            store.add_provider(provider(&Key::new(&"full"), local)),
+            Err(store::Error::MaxProvidedKeys)
+        ));
+        // updating existing entries, and providers of other nodes, are still fine
+        store
+            .put(Record::new(Key::new(&"0"), b"new value".to_vec()))
+            .unwrap();
+        store
+            .add_provider(provider(&Key::new(&"0"), local))
+            .unwrap();
+        store
+            .add_provider(provider(&Key::new(&"full"), PeerId::random()))
+            .unwrap();
+
+        // the counts survive a restart
+        drop(store);
+        let mut store = PersistentStore::open(local, dir.path(), config).unwrap();
+        assert!(matches!(
+            store.put(Record::new(Key::new(&"full"), b"value".to_vec())),
+            Err(store::Error::MaxRecords)
+        ));
+        store.remove(&Key::new(&"0"));
+        store
+            .put(Record::new(Key::new(&"full"), b"value".to_vec()))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = PeerId::random();
+        let mut store = PersistentStore::open(local, dir.path(), Default::default()).unwrap();
+
+        let past = Instant::now() - Duration::from_secs(1);
+        for i in 0..5 {
+            let key = Key::new(&i.to_string());
+            let mut record = Record::new(key.clone(), b"value".to_vec());
+            let mut expired = provider(&key, PeerId::random());
+            if i % 2 == 0 {
+                record.expires = Some(past);
+                expired.expires = Some(past);
+            }
+            store.put(record).unwrap();
+            store.add_provider(expired).unwrap();
+            store.add_provider(provider(&key, local)).unwrap();
+        }
+
+        // checks two keys at a time, so it takes three scans to get through all five, the
+        // entries found by the last one are removed by the next call
+        for _ in 0..4 {
+            store.poll_expiry(2).unwrap();
+            while !store.expiry_task.as_ref().unwrap().is_finished() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+        assert_eq!(store.records().count(), 3);
+        for i in 0..5 {
+            let key = Key::new(&i.to_string());
+            assert_eq!(store.get(&key).is_some(), i % 2 == 1);
+            assert_eq!(store.providers(&key).len(), if i % 2 == 0 { 1 } else { 2 });
+        }
+        assert_eq!(store.provided().count(), 5);
+    }
+}
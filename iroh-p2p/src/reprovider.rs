@@ -0,0 +1,380 @@
+use std::time::Instant;
+
+use ahash::AHashSet;
+use anyhow::Result;
+use cid::Cid;
+use futures::{pin_mut, Stream, StreamExt};
+use iroh_car::CarStore;
+use iroh_metrics::{
+    core::{MObserver, MRecorder},
+    inc, observe,
+    p2p::{P2PHistograms, P2PMetrics},
+};
+use iroh_rpc_client::Client as RpcClient;
+use iroh_rpc_types::store::PinType;
+use libp2p::kad::{record::Key, store, Kademlia, QueryId};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::config::ReprovideStrategy;
+use crate::record_store::KadStore;
+
+/// Maximum number of provide queries running at the same time during a reprovide run.
+const MAX_CONCURRENT_QUERIES: usize = 32;
+
+/// Periodically announces the content of the store to the DHT.
+///
+/// The keys to announce are enumerated from the store in a background task, the provide
+/// queries are started by the node as they arrive, with a limited number of them in flight.
+/// The blocks of the CAR files served by the node are announced with every strategy.
+#[derive(Debug)]
+pub struct Reprovider {
+    strategy: ReprovideStrategy,
+    car_store: CarStore,
+    run: Option<Run>,
+}
+
+/// A single reprovide run.
+#[derive(Debug)]
+struct Run {
+    started: Instant,
+    keys: mpsc::Receiver<Key>,
+    /// All keys have been received.
+    enumerated: bool,
+    queries: AHashSet<QueryId>,
+    provided: u64,
+    failed: u64,
+}
+
+impl Run {
+    fn new(keys: mpsc::Receiver<Key>) -> Self {
+        Run {
+            started: Instant::now(),
+            keys,
+            enumerated: false,
+            queries: Default::default(),
+            provided: 0,
+            failed: 0,
+        }
+    }
+}
+
+impl Reprovider {
+    pub fn new(strategy: ReprovideStrategy, car_store: CarStore) -> Self {
+        Reprovider {
+            strategy,
+            car_store,
+            run: None,
+        }
+    }
+
+    /// Starts a new run, unless the previous one is still in progress.
+    pub fn start(&mut self, rpc_client: &RpcClient) {
+        if self.run.is_some() {
+            warn!("previous reprovide run still in progress, skipping");
+            return;
+        }
+
+        info!("starting reprovide run ({})", self.strategy);
+        let (sender, keys) = mpsc::channel(MAX_CONCURRENT_QUERIES);
+        let rpc_client = rpc_client.clone();
+        let strategy = self.strategy;
+        let car_store = self.car_store.clone();
+        tokio::task::spawn(async move {
+            if let Err(err) = enumerate_keys(rpc_client, strategy, car_store, sender).await {
+                // not a failure of any key, so it is only logged
+                warn!("failed to enumerate keys to reprovide: {:?}", err);
+            }
+        });
+
+        self.run = Some(Run::new(keys));
+    }
+
+    /// Whether the next key should be requested with [`Reprovider::next_key`].
+    pub fn wants_key(&self) -> bool {
+        matches!(&self.run, Some(run) if !run.enumerated && run.queries.len() < MAX_CONCURRENT_QUERIES)
+    }
+
+    /// Waits for the next key to announce, returns `None` once all keys have been
+    /// enumerated.
+    pub async fn next_key(&mut self) -> Option<Key> {
+        match self.run {
+            Some(ref mut run) => run.keys.recv().await,
+            None => None,
+        }
+    }
+
+    /// Starts announcing `key`, or finishes the enumeration if `key` is `None`.
+    pub fn provide(&mut self, key: Option<Key>, kad: Option<&mut Kademlia<KadStore>>) {
+        let run = match self.run {
+            Some(ref mut run) => run,
+            None => return,
+        };
+        match (key, kad) {
+            (Some(key), Some(kad)) => match kad.start_providing(key) {
+                Ok(id) => {
+                    run.queries.insert(id);
+                }
+                Err(store::Error::MaxProvidedKeys) => {
+                    // every further key would fail the same way
+                    warn!("the kademlia store is full, stopping the reprovide run");
+                    run.failed += 1;
+                    inc!(P2PMetrics::ReprovideFailures);
+                    run.enumerated = true;
+                }
+                Err(err) => {
+                    debug!("failed to reprovide: {:?}", err);
+                    run.failed += 1;
+                    inc!(P2PMetrics::ReprovideFailures);
+                }
+            },
+            (Some(_), None) => {
+                // kademlia is disabled, there is nowhere to announce to
+                run.enumerated = true;
+            }
+            (None, _) => {
+                run.enumerated = true;
+            }
+        }
+        self.maybe_finish();
+    }
+
+    /// Handles the result of a provide query, ignoring queries not started by the reprovider.
+    pub fn handle_query_result(&mut self, id: QueryId, success: bool) {
+        let run = match self.run {
+            Some(ref mut run) => run,
+            None => return,
+        };
+        if !run.queries.remove(&id) {
+            return;
+        }
+        if success {
+            run.provided += 1;
+            inc!(P2PMetrics::ReprovidedKeys);
+        } else {
+            run.failed += 1;
+            inc!(P2PMetrics::ReprovideFailures);
+        }
+        self.maybe_finish();
+    }
+
+    fn maybe_finish(&mut self) {
+        if matches!(&self.run, Some(run) if run.enumerated && run.queries.is_empty()) {
+            let run = self.run.take().expect("checked above");
+            let elapsed = run.started.elapsed();
+            observe!(P2PHistograms::Reprovide, elapsed.as_secs_f64());
+            info!(
+                "reprovided {} keys in {:?}, {} failed",
+                run.provided, elapsed, run.failed
+            );
+        }
+    }
+}
+
+/// Sends the DHT keys of the blocks of `car_store` and of the content of the store selected
+/// by `strategy` to `sender`.
+async fn enumerate_keys(
+    rpc_client: RpcClient,
+    strategy: ReprovideStrategy,
+    car_store: CarStore,
+    sender: mpsc::Sender<Key>,
+) -> Result<()> {
+    // the index of the CAR files is in memory, so they are announced first
+    for hash in car_store.multihashes() {
+        // identity hashes, like inlined cids
+        if hash.code() == 0 {
+            continue;
+        }
+        if sender.send(hash.to_bytes().into()).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    let store = rpc_client.try_store()?;
+    match strategy {
+        ReprovideStrategy::All => {
+            let blocks = store.list_blocks(None, None, None, None).await?;
+            let keys = block_keys(blocks);
+            pin_mut!(keys);
+            while let Some(key) = keys.next().await {
+                if sender.send(key?).await.is_err() {
+                    break;
+                }
+            }
+        }
+        ReprovideStrategy::Pinned | ReprovideStrategy::Roots => {
+            for key in pin_keys(store.list_pins(None).await?, strategy) {
+                if sender.send(key).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the DHT keys of a stream of blocks ordered by multihash.
+///
+/// Providers are announced per multihash, so a multihash stored under several codecs is
+/// only announced once. Identity hashed cids are skipped, their content is in the cid and
+/// never needs to be found.
+fn block_keys(blocks: impl Stream<Item = Result<(Cid, u64)>>) -> impl Stream<Item = Result<Key>> {
+    let mut last = None;
+    blocks.filter_map(move |block| {
+        let key = match block {
+            Ok((cid, _size)) if iroh_util::inline_data(&cid).is_some() => None,
+            Ok((cid, _size)) => {
+                let hash = cid.hash().to_bytes();
+                if last.as_ref() == Some(&hash) {
+                    None
+                } else {
+                    last = Some(hash.clone());
+                    Some(Ok(hash.into()))
+                }
+            }
+            Err(err) => Some(Err(err)),
+        };
+        futures::future::ready(key)
+    })
+}
+
+/// Returns the DHT keys of the pins selected by `strategy`, once per multihash.
+fn pin_keys(pins: Vec<(Cid, PinType)>, strategy: ReprovideStrategy) -> Vec<Key> {
+    let mut seen = AHashSet::default();
+    pins.into_iter()
+        .filter(|(_, pin_type)| {
+            strategy != ReprovideStrategy::Roots || *pin_type != PinType::Indirect
+        })
+        .filter(|(cid, _)| iroh_util::inline_data(cid).is_none())
+        .map(|(cid, _)| cid.hash().to_bytes())
+        .filter(|hash| seen.insert(hash.clone()))
+        .map(Key::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::{Code, MultihashDigest};
+    use libp2p::identity::Keypair;
+    use libp2p::kad::store::{MemoryStore, MemoryStoreConfig};
+
+    use super::*;
+
+    const RAW: u64 = 0x55;
+    const DAG_PB: u64 = 0x70;
+
+    fn cid(codec: u64, data: &[u8]) -> Cid {
+        Cid::new_v1(codec, Code::Sha2_256.digest(data))
+    }
+
+    fn key(cid: &Cid) -> Key {
+        cid.hash().to_bytes().into()
+    }
+
+    fn kademlia(max_provided_keys: usize) -> Kademlia<KadStore> {
+        let peer_id = Keypair::generate_ed25519().public().to_peer_id();
+        let config = MemoryStoreConfig {
+            max_provided_keys,
+            ..Default::default()
+        };
+        let store = KadStore::Memory(MemoryStore::with_config(peer_id, config));
+        Kademlia::new(peer_id, store)
+    }
+
+    #[tokio::test]
+    async fn test_block_keys() {
+        let a = cid(RAW, b"a");
+        let b = cid(RAW, b"b");
+        let inline = Cid::new_v1(RAW, cid::multihash::Multihash::wrap(0, b"i").unwrap());
+        // the same multihash under two codecs is listed next to each other
+        let a_pb = Cid::new_v1(DAG_PB, *a.hash());
+        let blocks =
+            futures::stream::iter(vec![Ok((a, 1)), Ok((a_pb, 1)), Ok((inline, 1)), Ok((b, 1))]);
+
+        let keys: Vec<_> = block_keys(blocks).map(|key| key.unwrap()).collect().await;
+        assert_eq!(keys, vec![key(&a), key(&b)]);
+    }
+
+    #[test]
+    fn test_pin_keys() {
+        let root = cid(DAG_PB, b"root");
+        let child = cid(RAW, b"child");
+        let direct = cid(RAW, b"direct");
+        let pins = vec![
+            (root, PinType::Recursive),
+            (child, PinType::Indirect),
+            (direct, PinType::Direct),
+            // pinned under another codec as well
+            (Cid::new_v1(DAG_PB, *direct.hash()), PinType::Direct),
+        ];
+
+        assert_eq!(
+            pin_keys(pins.clone(), ReprovideStrategy::Pinned),
+            vec![key(&root), key(&child), key(&direct)]
+        );
+        assert_eq!(
+            pin_keys(pins, ReprovideStrategy::Roots),
+            vec![key(&root), key(&direct)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batching_and_failures() {
+        let (sender, keys) = mpsc::channel(1);
+        drop(sender);
+        let mut reprovider = Reprovider::new(ReprovideStrategy::All, CarStore::default());
+        reprovider.run = Some(Run::new(keys));
+        let mut kad = kademlia(MAX_CONCURRENT_QUERIES * 2);
+
+        // only a limited number of queries is started at once
+        let mut queries = Vec::new();
+        while reprovider.wants_key() {
+            let cid = cid(RAW, &queries.len().to_be_bytes());
+            reprovider.provide(Some(key(&cid)), Some(&mut kad));
+            queries = reprovider
+                .run
+                .as_ref()
+                .unwrap()
+                .queries
+                .iter()
+                .copied()
+                .collect();
+        }
+        assert_eq!(queries.len(), MAX_CONCURRENT_QUERIES);
+
+        // a finished query makes room for the next key
+        reprovider.handle_query_result(queries[0], true);
+        reprovider.handle_query_result(queries[1], false);
+        assert!(reprovider.wants_key());
+        // results of queries not started by the reprovider are ignored
+        reprovider.handle_query_result(queries[0], false);
+        let run = reprovider.run.as_ref().unwrap();
+        assert_eq!((run.provided, run.failed), (1, 1));
+
+        // the run ends once all keys are enumerated and all queries are done
+        reprovider.provide(None, Some(&mut kad));
+        assert!(reprovider.run.is_some());
+        for id in &queries[2..] {
+            reprovider.handle_query_result(*id, true);
+        }
+        assert!(reprovider.run.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_full_store_stops_run() {
+        let (_sender, keys) = mpsc::channel(1);
+        let mut reprovider = Reprovider::new(ReprovideStrategy::All, CarStore::default());
+        reprovider.run = Some(Run::new(keys));
+        let mut kad = kademlia(1);
+
+        reprovider.provide(Some(key(&cid(RAW, b"a"))), Some(&mut kad));
+        reprovider.provide(Some(key(&cid(RAW, b"b"))), Some(&mut kad));
+        let run = reprovider.run.as_ref().unwrap();
+        assert_eq!(run.failed, 1);
+        assert!(!reprovider.wants_key());
+
+        let id = *run.queries.iter().next().unwrap();
+        reprovider.handle_query_result(id, true);
+        assert!(reprovider.run.is_none());
+    }
+}
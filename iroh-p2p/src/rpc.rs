@@ -247,6 +247,32 @@ impl P2p {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self, req))]
+    async fn get_record(self, req: GetRecordRequest) -> Result<GetRecordResponse> {
+        trace!("received GetRecord request: {:?}", req.key);
+        let key_bytes: &[u8] = req.key.0.as_ref();
+        let key = libp2p::kad::record::Key::new(&key_bytes);
+        let (s, r) = oneshot::channel();
+        let msg = RpcMessage::GetRecord(s, key);
+
+        self.sender.send(msg).await?;
+
+        let records = r.await??;
+        Ok(GetRecordResponse { records })
+    }
+
+    #[tracing::instrument(skip(self, req))]
+    async fn publish_name(self, req: PublishNameRequest) -> Result<PublishNameResponse> {
+        trace!("received PublishName request: {:?}", req);
+        let (s, r) = oneshot::channel();
+        let msg = RpcMessage::PublishName(s, req.key, req.cid);
+
+        self.sender.send(msg).await?;
+
+        let name = r.await??;
+        Ok(PublishNameResponse { name })
+    }
+
     #[tracing::instrument(skip(self, req))]
     async fn stop_providing(self, req: StopProvidingRequest) -> Result<()> {
         trace!("received StopProviding request: {:?}", req.key);
@@ -554,6 +580,8 @@ async fn dispatch(s: P2pServer, req: P2pRequest, chan: ServerSocket<P2pService>,
         StopSessionBitswap(req) => s.rpc_map_err(req, chan, target, P2p::stop_session_bitswap).await,
         StartProviding(req) => s.rpc_map_err(req, chan, target, P2p::start_providing).await,
         StopProviding(req) => s.rpc_map_err(req, chan, target, P2p::stop_providing).await,
+        GetRecord(req) => s.rpc_map_err(req, chan, target, P2p::get_record).await,
+        PublishName(req) => s.rpc_map_err(req, chan, target, P2p::publish_name).await,
         LocalPeerId(req) => s.rpc_map_err(req, chan, target, P2p::local_peer_id).await,
         NotifyNewBlocksBitswap(req) => s.rpc_map_err(req, chan, target, P2p::notify_new_blocks_bitswap).await,
         GetListeningAddrs(req) => s.rpc_map_err(req, chan, target, P2p::get_listening_addrs).await,
@@ -641,6 +669,8 @@ pub enum RpcMessage {
     },
     StartProviding(oneshot::Sender<Result<libp2p::kad::QueryId>>, Key),
     StopProviding(oneshot::Sender<Result<()>>, Key),
+    GetRecord(oneshot::Sender<Result<Vec<Bytes>>>, Key),
+    PublishName(oneshot::Sender<Result<PeerId>>, Option<String>, Cid),
     NetListeningAddrs(oneshot::Sender<(PeerId, Vec<Multiaddr>)>),
     NetPeers(oneshot::Sender<HashMap<PeerId, Vec<Multiaddr>>>),
     NetConnectByPeerId(oneshot::Sender<Result<()>>, PeerId),
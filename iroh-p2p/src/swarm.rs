@@ -1,6 +1,8 @@
+use std::path::Path;
 use std::time::Duration;
 
 use anyhow::Result;
+use iroh_car::CarStore;
 use iroh_rpc_client::Client;
 use libp2p::{
     core::{
@@ -112,13 +114,23 @@ async fn build_transport(
 
 pub(crate) async fn build_swarm(
     config: &Libp2pConfig,
+    kad_store_path: Option<&Path>,
     keypair: &Keypair,
     rpc_client: Client,
+    car_store: CarStore,
 ) -> Result<Swarm<NodeBehaviour>> {
     let peer_id = keypair.public().to_peer_id();
 
     let (transport, relay_client) = build_transport(keypair, config).await;
-    let behaviour = NodeBehaviour::new(keypair, config, relay_client, rpc_client).await?;
+    let behaviour = NodeBehaviour::new(
+        keypair,
+        config,
+        kad_store_path,
+        relay_client,
+        rpc_client,
+        car_store,
+    )
+    .await?;
 
     let limits = ConnectionLimits::default()
         .with_max_pending_incoming(Some(config.max_conns_pending_in))
@@ -0,0 +1,5 @@
+fn main() {
+    prost_build::Config::new()
+        .compile_protos(&["src/ipns.proto"], &["src"])
+        .unwrap();
+}
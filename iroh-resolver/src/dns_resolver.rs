@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -80,8 +81,15 @@ impl DnsResolver {
 
     #[tracing::instrument]
     pub async fn resolve_dnslink(&self, url: &str) -> Result<Vec<Path>> {
+        let (records, _) = self.resolve_dnslink_with_ttl(url).await?;
+        Ok(records)
+    }
+
+    /// Resolves the DNSLink entries of `url`, together with how long they may be cached.
+    #[tracing::instrument]
+    pub async fn resolve_dnslink_with_ttl(&self, url: &str) -> Result<(Vec<Path>, Duration)> {
         let url = format!("_dnslink.{url}.");
-        let records = self.resolve_txt_record(&url).await?;
+        let (records, ttl) = self.resolve_txt_record_with_ttl(&url).await?;
         let records = records
             .into_iter()
             .filter(|r| r.starts_with("dnslink="))
@@ -90,10 +98,16 @@ impl DnsResolver {
                 p.parse()
             })
             .collect::<Result<_>>()?;
-        Ok(records)
+        Ok((records, ttl))
     }
 
     pub async fn resolve_txt_record(&self, url: &str) -> Result<Vec<String>> {
+        let (records, _) = self.resolve_txt_record_with_ttl(url).await?;
+        Ok(records)
+    }
+
+    /// Resolves the TXT records of `url`, together with the remaining TTL of the lookup.
+    pub async fn resolve_txt_record_with_ttl(&self, url: &str) -> Result<(Vec<String>, Duration)> {
         let tld = url.split('.').filter(|s| !s.is_empty()).last();
         let resolver = tld
             .and_then(|tld| {
@@ -103,8 +117,11 @@ impl DnsResolver {
             })
             .unwrap_or(&self.default_resolver);
         let txt_response = resolver.txt_lookup(url).await?;
+        let ttl = txt_response
+            .valid_until()
+            .saturating_duration_since(Instant::now());
         let out = txt_response.into_iter().map(|r| r.to_string()).collect();
-        Ok(out)
+        Ok((out, ttl))
     }
 }
 
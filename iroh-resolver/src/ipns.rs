@@ -0,0 +1,443 @@
+//! Decoding & validation of IPNS records.
+//!
+//! See <https://github.com/ipfs/specs/blob/main/ipns/IPNS.md> for the specification.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use cid::Cid;
+use libipld::prelude::Codec as _;
+use libipld::{Ipld, IpldCodec};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::multihash::Code;
+use libp2p::PeerId;
+use prost::Message;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::resolver::Path;
+
+mod ipns_pb {
+    #![allow(clippy::all)]
+    include!(concat!(env!("OUT_DIR"), "/ipns_pb.rs"));
+}
+
+use ipns_pb::{ipns_entry::ValidityType, IpnsEntry};
+
+/// Prefix of the DHT keys under which IPNS records are stored.
+const IPNS_KEY_PREFIX: &[u8] = b"/ipns/";
+
+/// V2 signatures sign the record data prefixed with this.
+const SIGNATURE_V2_PREFIX: &[u8] = b"ipns-signature:";
+
+/// How long to cache records which do not specify a TTL.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// Returns the IPNS name, the peer id of the signing key, from an `/ipns/<cid>` root.
+pub fn name_from_cid(cid: &Cid) -> Result<PeerId> {
+    PeerId::from_bytes(&cid.hash().to_bytes())
+        .map_err(|_| anyhow!("{} is not a valid IPNS name", cid))
+}
+
+/// Returns the DHT key under which the records for the IPNS `name` are stored.
+pub fn record_key(name: &PeerId) -> Vec<u8> {
+    [IPNS_KEY_PREFIX, &name.to_bytes()[..]].concat()
+}
+
+/// Creates a record publishing `value` under the name of `keypair`.
+///
+/// The record carries both a V1 and a V2 signature, so it is accepted by older
+/// implementations as well.
+pub fn create_record(
+    keypair: &Keypair,
+    value: &Path,
+    sequence: u64,
+    validity: OffsetDateTime,
+    ttl: Duration,
+) -> Result<Vec<u8>> {
+    let value = value.to_string().into_bytes();
+    let validity = validity
+        .format(&Rfc3339)
+        .context("invalid IPNS record validity")?
+        .into_bytes();
+    let ttl = u64::try_from(ttl.as_nanos()).context("IPNS record TTL too large")?;
+
+    let data = Ipld::Map(BTreeMap::from([
+        ("Value".to_string(), Ipld::Bytes(value.clone())),
+        ("Validity".to_string(), Ipld::Bytes(validity.clone())),
+        (
+            "ValidityType".to_string(),
+            Ipld::Integer((ValidityType::Eol as i32).into()),
+        ),
+        ("Sequence".to_string(), Ipld::Integer(sequence.into())),
+        ("TTL".to_string(), Ipld::Integer(ttl.into())),
+    ]));
+    let data = IpldCodec::DagCbor.encode(&data)?;
+
+    let signature_v1 = keypair.sign(&[&value[..], &validity[..], &b"EOL"[..]].concat())?;
+    let signature_v2 = keypair.sign(&[SIGNATURE_V2_PREFIX, &data[..]].concat())?;
+
+    // keys which are not embedded in the name need to be carried by the record
+    let public_key = keypair.public();
+    let pub_key = (public_key.to_peer_id().as_ref().code() != u64::from(Code::Identity))
+        .then(|| public_key.to_protobuf_encoding());
+
+    let entry = IpnsEntry {
+        value: Some(value),
+        signature_v1: Some(signature_v1),
+        validity_type: Some(ValidityType::Eol as i32),
+        validity: Some(validity),
+        sequence: Some(sequence),
+        ttl: Some(ttl),
+        pub_key,
+        signature_v2: Some(signature_v2),
+        data: Some(data),
+    };
+    Ok(entry.encode_to_vec())
+}
+
+/// A decoded IPNS record with a valid signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpnsRecord {
+    value: Path,
+    sequence: u64,
+    validity: OffsetDateTime,
+    ttl: Duration,
+}
+
+impl IpnsRecord {
+    /// Decodes a record published under `name`.
+    ///
+    /// Fails if the record is not correctly signed by the key of `name` or is expired.
+    pub fn decode_and_verify(name: &PeerId, bytes: &[u8]) -> Result<Self> {
+        let entry = IpnsEntry::decode(bytes).context("invalid IPNS record")?;
+        let public_key = public_key(name, &entry)?;
+
+        let fields = if let Some(signature) = &entry.signature_v2 {
+            let data = entry
+                .data
+                .as_deref()
+                .ok_or_else(|| anyhow!("IPNS record is missing its signed data"))?;
+            let signed = [SIGNATURE_V2_PREFIX, data].concat();
+            ensure!(
+                public_key.verify(&signed, signature),
+                "invalid IPNS record signature"
+            );
+            let fields = Fields::from_cbor(data)?;
+            ensure!(
+                fields.matches(&entry),
+                "IPNS record fields do not match its signed data"
+            );
+            fields
+        } else if let Some(signature) = &entry.signature_v1 {
+            let signed = [entry.value(), entry.validity(), &b"EOL"[..]].concat();
+            ensure!(
+                public_key.verify(&signed, signature),
+                "invalid IPNS record signature"
+            );
+            Fields::from_entry(&entry)
+        } else {
+            bail!("IPNS record is not signed");
+        };
+
+        let record = fields.into_record()?;
+        ensure!(!record.is_expired(), "IPNS record is expired");
+        Ok(record)
+    }
+
+    /// The path this record points to.
+    pub fn value(&self) -> &Path {
+        &self.value
+    }
+
+    /// The sequence number, records with a higher sequence number supersede older ones.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The point in time until which the record is valid.
+    pub fn validity(&self) -> OffsetDateTime {
+        self.validity
+    }
+
+    /// How long the record may be cached.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.validity <= OffsetDateTime::now_utc()
+    }
+
+    /// The point in time until which this record may be cached, which is never past its
+    /// validity.
+    pub fn cache_deadline(&self) -> Instant {
+        let remaining = (self.validity - OffsetDateTime::now_utc())
+            .try_into()
+            .unwrap_or_default();
+        Instant::now() + self.ttl.min(remaining)
+    }
+
+    /// Picks the record which should be used out of several records for the same name.
+    pub fn select_best(records: impl IntoIterator<Item = Self>) -> Option<Self> {
+        records
+            .into_iter()
+            .max_by_key(|record| (record.sequence, record.validity))
+    }
+}
+
+/// Returns the public key of `name`, either embedded in the name or carried by the record.
+fn public_key(name: &PeerId, entry: &IpnsEntry) -> Result<PublicKey> {
+    let public_key = match &entry.pub_key {
+        Some(key) => {
+            PublicKey::from_protobuf_encoding(key).context("invalid IPNS record public key")?
+        }
+        None => {
+            let multihash = name.as_ref();
+            ensure!(
+                multihash.code() == u64::from(Code::Identity),
+                "IPNS record for {} does not contain a public key",
+                name
+            );
+            PublicKey::from_protobuf_encoding(multihash.digest())
+                .context("invalid public key in IPNS name")?
+        }
+    };
+    ensure!(
+        public_key.to_peer_id() == *name,
+        "IPNS record public key does not belong to {}",
+        name
+    );
+    Ok(public_key)
+}
+
+/// The signed fields of an IPNS record.
+#[derive(Debug)]
+struct Fields {
+    value: Vec<u8>,
+    validity: Vec<u8>,
+    validity_type: i32,
+    sequence: u64,
+    ttl: u64,
+}
+
+impl Fields {
+    fn from_entry(entry: &IpnsEntry) -> Self {
+        Fields {
+            value: entry.value().to_vec(),
+            validity: entry.validity().to_vec(),
+            validity_type: entry.validity_type.unwrap_or(ValidityType::Eol as i32),
+            sequence: entry.sequence(),
+            ttl: entry.ttl(),
+        }
+    }
+
+    /// Decodes the DAG-CBOR data signed by a V2 signature.
+    fn from_cbor(data: &[u8]) -> Result<Self> {
+        let map = match IpldCodec::DagCbor
+            .decode::<Ipld>(data)
+            .context("invalid IPNS record data")?
+        {
+            Ipld::Map(map) => map,
+            _ => bail!("invalid IPNS record data: expected a map"),
+        };
+        Ok(Fields {
+            value: get_bytes(&map, "Value")?,
+            validity: get_bytes(&map, "Validity")?,
+            validity_type: get_int(&map, "ValidityType")?,
+            sequence: get_int(&map, "Sequence")?,
+            ttl: get_int(&map, "TTL")?,
+        })
+    }
+
+    /// Checks that the unsigned protobuf fields which are present agree with these.
+    fn matches(&self, entry: &IpnsEntry) -> bool {
+        entry.value.as_ref().map_or(true, |v| *v == self.value)
+            && entry
+                .validity
+                .as_ref()
+                .map_or(true, |v| *v == self.validity)
+            && entry
+                .validity_type
+                .map_or(true, |v| v == self.validity_type)
+            && entry.sequence.map_or(true, |v| v == self.sequence)
+            && entry.ttl.map_or(true, |v| v == self.ttl)
+    }
+
+    fn into_record(self) -> Result<IpnsRecord> {
+        ensure!(
+            self.validity_type == ValidityType::Eol as i32,
+            "unsupported IPNS validity type {}",
+            self.validity_type
+        );
+        let validity = std::str::from_utf8(&self.validity)
+            .ok()
+            .and_then(|v| OffsetDateTime::parse(v, &Rfc3339).ok())
+            .ok_or_else(|| anyhow!("invalid IPNS record validity"))?;
+        let value = std::str::from_utf8(&self.value)
+            .context("invalid IPNS record value")?
+            .parse()
+            .context("invalid IPNS record value")?;
+        let ttl = match self.ttl {
+            0 => DEFAULT_TTL,
+            nanos => Duration::from_nanos(nanos),
+        };
+
+        Ok(IpnsRecord {
+            value,
+            sequence: self.sequence,
+            validity,
+            ttl,
+        })
+    }
+}
+
+fn get_bytes(map: &BTreeMap<String, Ipld>, key: &str) -> Result<Vec<u8>> {
+    match map.get(key) {
+        Some(Ipld::Bytes(bytes)) => Ok(bytes.clone()),
+        _ => bail!("invalid IPNS record data: missing {}", key),
+    }
+}
+
+fn get_int<T: TryFrom<i128>>(map: &BTreeMap<String, Ipld>, key: &str) -> Result<T> {
+    match map.get(key) {
+        Some(Ipld::Integer(i)) => {
+            T::try_from(*i).map_err(|_| anyhow!("invalid IPNS record data: {} out of range", key))
+        }
+        _ => bail!("invalid IPNS record data: missing {}", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validity_in(offset: time::Duration) -> OffsetDateTime {
+        OffsetDateTime::now_utc() + offset
+    }
+
+    fn create_entry(
+        keypair: &Keypair,
+        value: &str,
+        sequence: u64,
+        validity: OffsetDateTime,
+    ) -> IpnsEntry {
+        let value = value.parse().unwrap();
+        let ttl = Duration::from_secs(30);
+        let record = create_record(keypair, &value, sequence, validity, ttl).unwrap();
+        IpnsEntry::decode(&record[..]).unwrap()
+    }
+
+    const VALUE: &str = "/ipfs/bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy";
+
+    #[test]
+    fn test_decode_v2() {
+        let keypair = Keypair::generate_ed25519();
+        let name = keypair.public().to_peer_id();
+        let entry = create_entry(&keypair, VALUE, 3, validity_in(time::Duration::hours(1)));
+
+        let record = IpnsRecord::decode_and_verify(&name, &entry.encode_to_vec()).unwrap();
+        assert_eq!(record.value(), &VALUE.parse::<Path>().unwrap());
+        assert_eq!(record.sequence(), 3);
+        assert_eq!(record.ttl(), Duration::from_secs(30));
+        assert!(record.cache_deadline() <= Instant::now() + Duration::from_secs(30));
+
+        // V2 records do not need the V1 fields
+        let entry = IpnsEntry {
+            value: None,
+            signature_v1: None,
+            validity_type: None,
+            validity: None,
+            sequence: None,
+            ttl: None,
+            ..entry
+        };
+        let stripped = IpnsRecord::decode_and_verify(&name, &entry.encode_to_vec()).unwrap();
+        assert_eq!(stripped, record);
+    }
+
+    #[test]
+    fn test_decode_v1() {
+        let keypair = Keypair::generate_ed25519();
+        let name = keypair.public().to_peer_id();
+        let entry = IpnsEntry {
+            signature_v2: None,
+            data: None,
+            ..create_entry(&keypair, VALUE, 1, validity_in(time::Duration::hours(1)))
+        };
+
+        let record = IpnsRecord::decode_and_verify(&name, &entry.encode_to_vec()).unwrap();
+        assert_eq!(record.value(), &VALUE.parse::<Path>().unwrap());
+        assert_eq!(record.sequence(), 1);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let keypair = Keypair::generate_ed25519();
+        let name = keypair.public().to_peer_id();
+        let entry = create_entry(&keypair, VALUE, 1, validity_in(time::Duration::hours(1)));
+
+        // signed by a different key
+        let other = Keypair::generate_ed25519().public().to_peer_id();
+        assert!(IpnsRecord::decode_and_verify(&other, &entry.encode_to_vec()).is_err());
+
+        // unsigned fields disagree with the signed data
+        let tampered = IpnsEntry {
+            sequence: Some(2),
+            ..entry.clone()
+        };
+        assert!(IpnsRecord::decode_and_verify(&name, &tampered.encode_to_vec()).is_err());
+
+        // tampered V1 record
+        let tampered = IpnsEntry {
+            value: Some(b"/ipfs/bafkqaaa".to_vec()),
+            signature_v2: None,
+            data: None,
+            ..entry.clone()
+        };
+        assert!(IpnsRecord::decode_and_verify(&name, &tampered.encode_to_vec()).is_err());
+
+        // unsigned
+        let unsigned = IpnsEntry {
+            signature_v1: None,
+            signature_v2: None,
+            ..entry
+        };
+        assert!(IpnsRecord::decode_and_verify(&name, &unsigned.encode_to_vec()).is_err());
+
+        // expired
+        let expired = create_entry(&keypair, VALUE, 1, validity_in(-time::Duration::hours(1)));
+        assert!(IpnsRecord::decode_and_verify(&name, &expired.encode_to_vec()).is_err());
+
+        assert!(IpnsRecord::decode_and_verify(&name, b"not a record").is_err());
+    }
+
+    #[test]
+    fn test_select_best() {
+        let keypair = Keypair::generate_ed25519();
+        let name = keypair.public().to_peer_id();
+        let records: Vec<_> = [(1, 2), (3, 1), (3, 2), (2, 3)]
+            .into_iter()
+            .map(|(sequence, hours)| {
+                let validity = validity_in(time::Duration::hours(hours));
+                let entry = create_entry(&keypair, VALUE, sequence, validity);
+                IpnsRecord::decode_and_verify(&name, &entry.encode_to_vec()).unwrap()
+            })
+            .collect();
+
+        let best = IpnsRecord::select_best(records.clone()).unwrap();
+        assert_eq!(best, records[2]);
+        assert!(IpnsRecord::select_best(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_record_key() {
+        let name = Keypair::generate_ed25519().public().to_peer_id();
+        let cid = Cid::new_v1(
+            0x72,
+            cid::multihash::Multihash::from_bytes(&name.to_bytes()).unwrap(),
+        );
+        assert_eq!(name_from_cid(&cid).unwrap(), name);
+        assert!(record_key(&name).starts_with(b"/ipns/"));
+    }
+}
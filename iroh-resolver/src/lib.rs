@@ -1,4 +1,7 @@
 pub mod dns_resolver;
+pub mod ipns;
 pub mod resolver;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 pub use resolver::{Path, PathType};
@@ -1,10 +1,12 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::fmt::{self, Debug, Display, Formatter};
+use std::num::NonZeroUsize;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{anyhow, bail, Context as _, Result};
 use async_trait::async_trait;
@@ -22,6 +24,8 @@ use iroh_unixfs::{
 use libipld::codec::Encode;
 use libipld::prelude::Codec as _;
 use libipld::{Ipld, IpldCodec};
+use libp2p::PeerId;
+use lru::LruCache;
 use tokio::io::{AsyncRead, AsyncSeek};
 use tokio::task::JoinHandle;
 use tracing::{debug, trace, warn};
@@ -32,9 +36,13 @@ use iroh_metrics::{
 };
 
 use crate::dns_resolver::{Config, DnsResolver};
+use crate::ipns::{self, IpnsRecord};
 
 pub const IROH_STORE: &str = "iroh-store";
 
+/// Maximum number of IPNS records kept in the resolver's cache.
+const IPNS_CACHE_SIZE: usize = 1024;
+
 // ToDo: Remove this function
 // Related issue: https://github.com/n0-computer/iroh/issues/593
 fn from_peer_id(id: &str) -> Option<libipld::Multihash> {
@@ -176,6 +184,33 @@ impl Display for CidOrDomain {
     }
 }
 
+/// A segment of a [`Path`] does not exist in the node it is looked up in.
+///
+/// Unlike failures to load blocks this means the path does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkNotFound {
+    /// The kind of node the segment was looked up in.
+    pub node: &'static str,
+    pub name: String,
+}
+
+impl LinkNotFound {
+    fn new(node: &'static str, name: impl Into<String>) -> Self {
+        LinkNotFound {
+            node,
+            name: name.into(),
+        }
+    }
+}
+
+impl Display for LinkNotFound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} link '{}' not found", self.node, self.name)
+    }
+}
+
+impl std::error::Error for LinkNotFound {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PathType {
     /// `/ipfs`
@@ -205,6 +240,8 @@ impl FromStr for Path {
             let root = parts.next().ok_or_else(|| anyhow!("path too short"))?;
             let root = if let Ok(c) = Cid::from_str(root) {
                 CidOrDomain::Cid(c)
+            } else if let Some(multihash) = from_peer_id(root) {
+                CidOrDomain::Cid(Cid::new_v1(Codec::Libp2pKey.into(), multihash))
             } else {
                 // TODO: url validation?
                 CidOrDomain::Domain(root.to_string())
@@ -441,6 +478,14 @@ pub struct Metadata {
     /// to a block.
     pub resolved_path: Vec<Cid>,
     pub source: Source,
+    /// Unix permission bits, if stored in the UnixFS node.
+    pub mode: Option<u32>,
+    /// Modification time, if stored in the UnixFS node.
+    pub mtime: Option<SystemTime>,
+    /// How long the IPNS and DNSLink lookups of an `/ipns` path may be cached.
+    ///
+    /// The smallest TTL of all lookups made, `None` for `/ipfs` paths.
+    pub ttl: Option<Duration>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -589,6 +634,7 @@ impl<T: ContentLoader + Unpin + 'static> AsyncSeek for OutPrettyReader<T> {
 pub struct Resolver<T: ContentLoader> {
     loader: T,
     dns_resolver: Arc<DnsResolver>,
+    ipns_cache: Arc<Mutex<LruCache<PeerId, (IpnsRecord, Instant)>>>,
     next_id: Arc<AtomicU64>,
     _worker: Arc<JoinHandle<()>>,
     session_closer: async_channel::Sender<ContextId>,
@@ -620,6 +666,9 @@ impl<T: ContentLoader> Resolver<T> {
         Resolver {
             loader,
             dns_resolver: Arc::new(DnsResolver::from_config(dns_resolver_config)),
+            ipns_cache: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(IPNS_CACHE_SIZE).unwrap(),
+            ))),
             next_id: Arc::new(AtomicU64::new(0)),
             _worker: Arc::new(worker),
             session_closer: session_closer_s,
@@ -717,6 +766,43 @@ impl<T: ContentLoader> Resolver<T> {
         })
     }
 
+    /// Resolve a path recursively and yield the raw bytes plus metadata, in depth-first order.
+    ///
+    /// Unlike [`Resolver::resolve_recursive_raw`] blocks are loaded one at a time and every
+    /// block is yielded only once, so the order only depends on the DAG itself.
+    #[tracing::instrument(skip(self))]
+    pub fn resolve_recursive_raw_dfs(
+        &self,
+        root: Path,
+        recursion_limit: Option<usize>,
+    ) -> impl Stream<Item = Result<OutRaw>> {
+        let mut ctx = LoaderContext::from_path(self.next_id(), self.session_closer.clone());
+        let this = self.clone();
+        let mut counter = 0;
+        async_stream::try_stream! {
+            let root_cid = this.resolve_path_to_cid(&root, &mut ctx).await?;
+            let mut stack = vec![root_cid];
+            let mut seen = HashSet::new();
+            while let Some(cid) = stack.pop() {
+                if !seen.insert(cid) {
+                    continue;
+                }
+                let loaded = this.load_cid(&cid, &mut ctx).await?;
+                let current = OutRaw::from_loaded(cid, loaded);
+                let links = current.links()?;
+                counter += links.len();
+                if let Some(limit) = recursion_limit {
+                    if counter > limit {
+                        Err(anyhow::anyhow!("Number of links exceeds the recursion limit."))?;
+                    }
+                }
+                // push in reverse, so the first link is visited first
+                stack.extend(links.into_iter().rev());
+                yield current;
+            }
+        }
+    }
+
     /// Resolve a path recursively and supply a closure to resolve cids to outputs.
     #[tracing::instrument(skip(self, resolve))]
     pub fn resolve_recursive_mapped<O, M, F>(
@@ -800,9 +886,9 @@ impl<T: ContentLoader> Resolver<T> {
         force_raw: bool,
     ) -> Result<Out> {
         // Resolve the root block.
-        let (root_cid, loaded_cid) = self.resolve_root(&path, &mut ctx).await?;
+        let (root_cid, loaded_cid, ttl) = self.resolve_root(&path, &mut ctx).await?;
         match loaded_cid.source {
-            Source::Store(_) => inc!(ResolverMetrics::CacheHit),
+            Source::Store(_) | Source::Inline => inc!(ResolverMetrics::CacheHit),
             _ => inc!(ResolverMetrics::CacheMiss),
         }
 
@@ -811,16 +897,18 @@ impl<T: ContentLoader> Resolver<T> {
             false => Codec::try_from(root_cid.codec()).context("unknown codec")?,
         };
 
-        match codec {
+        let mut out = match codec {
             Codec::DagPb => {
                 self.resolve_dag_pb_or_unixfs(path, root_cid, loaded_cid, ctx)
-                    .await
+                    .await?
             }
             Codec::DagCbor | Codec::DagJson | Codec::Raw => {
-                self.resolve_ipld(path, root_cid, loaded_cid, ctx).await
+                self.resolve_ipld(path, root_cid, loaded_cid, ctx).await?
             }
             _ => bail!("unsupported codec {:?}", codec),
-        }
+        };
+        out.metadata.ttl = ttl;
+        Ok(out)
     }
 
     // TODO(ramfox): when get the cid of the next link, we should
@@ -838,7 +926,7 @@ impl<T: ContentLoader> Resolver<T> {
                 let next_link = current
                     .get_link_by_name(&part)
                     .await?
-                    .ok_or_else(|| anyhow!("UnixfsNode::Directory link '{}' not found", part))?;
+                    .ok_or_else(|| LinkNotFound::new("UnixfsNode::Directory", part))?;
                 let loaded_cid = self.load_cid(&next_link.cid, ctx).await?;
                 let next_node = UnixfsNode::decode(&next_link.cid, loaded_cid.data)?;
                 resolved_path.push(next_link.cid);
@@ -849,7 +937,7 @@ impl<T: ContentLoader> Resolver<T> {
                 let (next_link, next_node) = hamt
                     .get(ctx.clone(), self.loader().clone(), part.as_bytes())
                     .await?
-                    .ok_or_else(|| anyhow!("UnixfsNode::HamtShard link '{}' not found", part))?;
+                    .ok_or_else(|| LinkNotFound::new("UnixfsNode::HamtShard", part))?;
                 // TODO: is this the right way to to resolved path here?
                 resolved_path.push(next_link.cid);
 
@@ -901,6 +989,9 @@ impl<T: ContentLoader> Resolver<T> {
                 unixfs_type,
                 resolved_path,
                 source: loaded_cid.source,
+                mode: current.mode(),
+                mtime: current.mtime(),
+                ttl: None,
             };
             Ok(Out {
                 metadata,
@@ -955,6 +1046,9 @@ impl<T: ContentLoader> Resolver<T> {
             unixfs_type: None,
             resolved_path: vec![cid],
             source: loaded_cid.source,
+            mode: None,
+            mtime: None,
+            ttl: None,
         };
         Ok(Out {
             metadata,
@@ -987,13 +1081,9 @@ impl<T: ContentLoader> Resolver<T> {
                 } else {
                     part.clone().into()
                 };
-                current = current.take(index).map_err(|_| {
-                    anyhow!(
-                        "IPLD resolve error: Couldn't find part {} in path '{}'",
-                        part,
-                        path.join("/")
-                    )
-                })?;
+                current = current
+                    .take(index)
+                    .map_err(|_| LinkNotFound::new("Ipld", part.as_str()))?;
             }
         }
         if let libipld::Ipld::Link(c) = current {
@@ -1046,12 +1136,25 @@ impl<T: ContentLoader> Resolver<T> {
                 _ => return Err(anyhow!("expected DagPb link to have a string Name field")),
             }
         }
-        anyhow::bail!("could not find DagPb link '{}'", name);
+        Err(LinkNotFound::new("DagPb", name).into())
     }
 
     #[tracing::instrument(skip(self))]
     async fn resolve_path_to_cid(&self, root: &Path, ctx: &mut LoaderContext) -> Result<Cid> {
+        let (cid, _) = self.resolve_path_to_cid_with_ttl(root, ctx).await?;
+        Ok(cid)
+    }
+
+    /// Resolves the root of `root` to a [`Cid`], following IPNS records and DNSLink
+    /// entries, together with the smallest TTL of these lookups.
+    #[tracing::instrument(skip(self))]
+    async fn resolve_path_to_cid_with_ttl(
+        &self,
+        root: &Path,
+        ctx: &mut LoaderContext,
+    ) -> Result<(Cid, Option<Duration>)> {
         let mut current = root.clone();
+        let mut ttl: Option<Duration> = None;
 
         // maximum cursion of ipns lookups
         const MAX_LOOKUPS: usize = 16;
@@ -1060,20 +1163,23 @@ impl<T: ContentLoader> Resolver<T> {
             match current.typ() {
                 PathType::Ipfs => match current.root() {
                     CidOrDomain::Cid(ref c) => {
-                        return Ok(*c);
+                        return Ok((*c, ttl));
                     }
                     CidOrDomain::Domain(_) => bail!("invalid domain encountered"),
                 },
                 PathType::Ipns => match current.root() {
                     CidOrDomain::Cid(ref c) => {
-                        let c = self.load_ipns_record(c).await?;
-                        current = Path::from_cid(c);
+                        let (path, record_ttl) = self.load_ipns_record(c).await?;
+                        ttl = Some(ttl.map_or(record_ttl, |ttl| ttl.min(record_ttl)));
+                        current = path;
                     }
                     CidOrDomain::Domain(ref domain) => {
-                        let mut records = self.dns_resolver.resolve_dnslink(domain).await?;
+                        let (mut records, dns_ttl) =
+                            self.dns_resolver.resolve_dnslink_with_ttl(domain).await?;
                         if records.is_empty() {
                             bail!("no valid dnslink records found for {}", domain);
                         }
+                        ttl = Some(ttl.map_or(dns_ttl, |ttl| ttl.min(dns_ttl)));
                         current = records.remove(0);
                     }
                 },
@@ -1084,10 +1190,14 @@ impl<T: ContentLoader> Resolver<T> {
     }
 
     #[tracing::instrument(skip(self))]
-    async fn resolve_root(&self, root: &Path, ctx: &mut LoaderContext) -> Result<(Cid, LoadedCid)> {
-        let cid = self.resolve_path_to_cid(root, ctx).await?;
+    async fn resolve_root(
+        &self,
+        root: &Path,
+        ctx: &mut LoaderContext,
+    ) -> Result<(Cid, LoadedCid, Option<Duration>)> {
+        let (cid, ttl) = self.resolve_path_to_cid_with_ttl(root, ctx).await?;
         let loaded_cid = self.load_cid(&cid, ctx).await?;
-        Ok((cid, loaded_cid))
+        Ok((cid, loaded_cid, ttl))
     }
 
     #[tracing::instrument(skip(self))]
@@ -1100,9 +1210,56 @@ impl<T: ContentLoader> Resolver<T> {
         self.loader.has_cid(cid).await
     }
 
+    /// Resolves the IPNS name `cid` to the path of its current record, and how long
+    /// that path may be cached.
+    ///
+    /// Records are looked up in the DHT and cached for their TTL.
     #[tracing::instrument(skip(self))]
-    async fn load_ipns_record(&self, cid: &Cid) -> Result<Cid> {
-        todo!()
+    async fn load_ipns_record(&self, cid: &Cid) -> Result<(Path, Duration)> {
+        let name = ipns::name_from_cid(cid)?;
+        if let Some((record, deadline)) = self.cached_ipns_record(&name) {
+            debug!("using cached IPNS record for {}", name);
+            let ttl = deadline.saturating_duration_since(Instant::now());
+            return Ok((record.value().clone(), ttl));
+        }
+
+        let records = self.loader.load_record(&ipns::record_key(&name)).await?;
+        let records =
+            records
+                .iter()
+                .filter_map(|bytes| match IpnsRecord::decode_and_verify(&name, bytes) {
+                    Ok(record) => Some(record),
+                    Err(err) => {
+                        debug!("ignoring invalid IPNS record for {}: {:?}", name, err);
+                        None
+                    }
+                });
+        let record = IpnsRecord::select_best(records)
+            .ok_or_else(|| anyhow!("no valid IPNS record found for {}", name))?;
+        trace!("resolved IPNS name {} to {}", name, record.value());
+
+        let path = record.value().clone();
+        let deadline = record.cache_deadline();
+        let ttl = deadline.saturating_duration_since(Instant::now());
+        self.ipns_cache
+            .lock()
+            .unwrap()
+            .put(name, (record, deadline));
+        Ok((path, ttl))
+    }
+
+    fn cached_ipns_record(&self, name: &PeerId) -> Option<(IpnsRecord, Instant)> {
+        let mut cache = self.ipns_cache.lock().unwrap();
+        match cache.get(name) {
+            Some((record, deadline)) if *deadline > Instant::now() => {
+                Some((record.clone(), *deadline))
+            }
+            Some(_) => {
+                cache.pop(name);
+                None
+            }
+            None => None,
+        }
     }
 }
 
@@ -1146,8 +1303,11 @@ mod tests {
     };
 
     use super::*;
+    use crate::test_utils::RecordLoader;
     use cid::multihash::{Code, MultihashDigest};
     use futures::{StreamExt, TryStreamExt};
+    use iroh_unixfs::builder::{DirectoryBuilder, FileBuilder};
+    use iroh_unixfs::unixfs::CidBuilder;
     use libipld::{codec::Encode, Ipld, IpldCodec};
     use tokio::io::AsyncSeekExt;
 
@@ -1200,10 +1360,16 @@ mod tests {
             assert_eq!(p.to_string(), test);
         }
 
-        let valid_tests = [(
-            "bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy",
-            "/ipfs/bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy",
-        )];
+        let valid_tests = [
+            (
+                "bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy",
+                "/ipfs/bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy",
+            ),
+            (
+                "/ipns/12D3KooWJHxkQKX8C5KAyqEPhn2ssT2in4TExyG9SXxi519tycL9",
+                "/ipns/bafzaajaiaejca7ppi4acansaev3n3b2ob64uprd3qlgprdwav34mzinniepfnwjs",
+            ),
+        ];
         for (test_in, test_out) in valid_tests {
             println!("{test_in}");
             let p: Path = test_in.parse().unwrap();
@@ -1408,6 +1574,14 @@ mod tests {
         let loader = Arc::new(loader);
         let resolver = Resolver::new(loader.clone());
 
+        // a missing entry is reported as such, not as a failure to load
+        {
+            let path = format!("/ipfs/{root_cid_str}/bar/missing.txt");
+            let err = resolver.resolve(path.parse().unwrap()).await.unwrap_err();
+            let err = err.downcast_ref::<LinkNotFound>().unwrap();
+            assert_eq!(err.name, "missing.txt");
+        }
+
         {
             let path = format!("/ipfs/{root_cid_str}");
             let ipld_foo = resolver.resolve(path.parse().unwrap()).await.unwrap();
@@ -1698,6 +1872,257 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_add_chunked_matches_kubo() {
+        // QmUr9cs4mhWxabKqm9PYPSQQ6AQGbHJBtyrNmxtKgxqUx9 README.md
+        //
+        // imported with `go-ipfs add --chunker size-100`
+        let pieces_cid_str = [
+            "QmccJ8pV5hG7DEbq66ih1ZtowxgvqVS6imt98Ku62J2WRw",
+            "QmUajVwSkEp9JvdW914Qh1BCMRSUf2ztiQa6jqy1aWhwJv",
+            "QmNyLad1dWGS6mv2zno4iEviBSYSUR2SrQ8JoZNDz1UHYy",
+            "QmcXoBdCgmFMoNbASaQCNVswRuuuqbw4VvA7e5GtHbhRNp",
+            "QmP9yKRwuji5i7RTgrevwJwXp7uqQu1prv88nxq9uj99rW",
+        ];
+        let root_cid_str = "QmUr9cs4mhWxabKqm9PYPSQQ6AQGbHJBtyrNmxtKgxqUx9";
+
+        let mut content = Vec::new();
+        for c in &pieces_cid_str {
+            let cid: Cid = c.parse().unwrap();
+            match UnixfsNode::decode(&cid, load_fixture(c).await).unwrap() {
+                UnixfsNode::File(node) => content.extend(node.data().unwrap()),
+                node => panic!("unexpected leaf: {node:?}"),
+            }
+        }
+        assert_eq!(content.len(), 426);
+
+        let file = FileBuilder::new()
+            .name("README.md")
+            .content_bytes(content)
+            .fixed_chunker(100)
+            .cid_builder(CidBuilder::v0())
+            .raw_leaves(false)
+            .build()
+            .await
+            .unwrap();
+        let blocks: Vec<_> = file.encode().await.unwrap().try_collect().await.unwrap();
+        let cids: Vec<String> = blocks.iter().map(|b| b.cid().to_string()).collect();
+        let mut expected: Vec<_> = pieces_cid_str.iter().map(|c| c.to_string()).collect();
+        expected.push(root_cid_str.to_string());
+        assert_eq!(cids, expected);
+
+        for block in &blocks {
+            let fixture = load_fixture(&block.cid().to_string()).await;
+            assert_eq!(block.data(), &fixture);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_directory_matches_kubo() {
+        // QmaRGe7bVmVaLmxbrMiVNXqW4pRNNp3xq7hFtyRKA3mtJL foo/bar/bar.txt
+        // QmZULkCELmmk5XNfCgTnCyFgAVxBRBXyDHGGMVoLFLiXEN foo/hello.txt
+        // QmcHTZfwWWYG2Gbv9wR6bWZBvAgpFV5BcDoLrC2XMCkggn foo/bar
+        // QmdkGfDx42RNdAZFALHn5hjHqUq7L9o6Ef4zLnFEu3Y4Go foo
+        let cid_builder = CidBuilder::v0();
+        let file = |name: &'static str, content: &'static str| {
+            FileBuilder::new()
+                .name(name)
+                .content_bytes(content)
+                .cid_builder(cid_builder)
+                .raw_leaves(false)
+                .build()
+        };
+        let bar = DirectoryBuilder::new()
+            .name("bar")
+            .cid_builder(cid_builder)
+            .add_file(file("bar.txt", "world\n").await.unwrap())
+            .build()
+            .await
+            .unwrap();
+        let foo = DirectoryBuilder::new()
+            .name("foo")
+            .cid_builder(cid_builder)
+            .add_dir(bar)
+            .unwrap()
+            .add_file(file("hello.txt", "hello\n").await.unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        let blocks: Vec<_> = foo.encode().try_collect().await.unwrap();
+        let cids: Vec<String> = blocks.iter().map(|b| b.cid().to_string()).collect();
+        assert_eq!(
+            cids,
+            [
+                "QmaRGe7bVmVaLmxbrMiVNXqW4pRNNp3xq7hFtyRKA3mtJL",
+                "QmcHTZfwWWYG2Gbv9wR6bWZBvAgpFV5BcDoLrC2XMCkggn",
+                "QmZULkCELmmk5XNfCgTnCyFgAVxBRBXyDHGGMVoLFLiXEN",
+                "QmdkGfDx42RNdAZFALHn5hjHqUq7L9o6Ef4zLnFEu3Y4Go",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_hamt_directory_matches_kubo() {
+        // the hamt directory of `test_unixfs_hamt_dir`, generated by kubo:
+        // for n in $(seq 10000); do echo $n > foo/$n.txt; done
+        // ipfs add --recursive foo
+        let root_cid_str = "QmUu8pzQ5yjhDrg4GiHYLeko2oT76vcmYX5bw6sjiEJ82k";
+        let reader = tokio::io::BufReader::new(
+            tokio::fs::File::open("./fixtures/big-foo.car")
+                .await
+                .unwrap(),
+        );
+        let fixture: HashMap<Cid, Vec<u8>> = iroh_car::CarReader::new(reader)
+            .await
+            .unwrap()
+            .stream()
+            .try_collect()
+            .await
+            .unwrap();
+
+        let cid_builder = CidBuilder::v0();
+        let file = |name: String, content: String| {
+            FileBuilder::new()
+                .name(name)
+                .content_bytes(content)
+                .cid_builder(cid_builder)
+                .raw_leaves(false)
+                .build()
+        };
+        let bar = DirectoryBuilder::new()
+            .name("bar")
+            .cid_builder(cid_builder)
+            .add_file(
+                file("bar.txt".to_string(), "world\n".to_string())
+                    .await
+                    .unwrap(),
+            )
+            .build()
+            .await
+            .unwrap();
+        let mut foo = DirectoryBuilder::new()
+            .name("foo")
+            .hamt()
+            .cid_builder(cid_builder)
+            .add_dir(bar)
+            .unwrap()
+            .add_file(
+                file("hello.txt".to_string(), "hello\n".to_string())
+                    .await
+                    .unwrap(),
+            );
+        for n in 1..=10000 {
+            foo = foo.add_file(file(format!("{n}.txt"), format!("{n}\n")).await.unwrap());
+        }
+        let foo = foo.build().await.unwrap();
+
+        let blocks: Vec<_> = foo.encode().try_collect().await.unwrap();
+        for block in &blocks {
+            assert_eq!(
+                fixture.get(block.cid()).map(|data| &data[..]),
+                Some(&block.data()[..]),
+                "block {} differs from kubo",
+                block.cid()
+            );
+        }
+        assert_eq!(blocks.last().unwrap().cid().to_string(), root_cid_str);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_recursive_raw_dfs() {
+        let file = |name: &'static str, content: &'static str| {
+            FileBuilder::new().name(name).content_bytes(content).build()
+        };
+        let bar = DirectoryBuilder::new()
+            .name("bar")
+            .add_file(file("bar.txt", "world").await.unwrap())
+            .build()
+            .await
+            .unwrap();
+        let foo = DirectoryBuilder::new()
+            .name("foo")
+            .add_dir(bar)
+            .unwrap()
+            .add_file(file("hello.txt", "hello").await.unwrap())
+            .add_file(file("hello2.txt", "hello").await.unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        let blocks: Vec<_> = foo.encode().try_collect().await.unwrap();
+        let cids: Vec<Cid> = blocks.iter().map(|b| *b.cid()).collect();
+        // bar.txt, bar, hello.txt, hello2.txt, foo
+        assert_eq!(cids.len(), 5);
+        assert_eq!(cids[2], cids[3]);
+        let loader: HashMap<Cid, Bytes> = blocks
+            .into_iter()
+            .map(|b| (*b.cid(), b.data().clone()))
+            .collect();
+        let resolver = Resolver::new(Arc::new(loader));
+        let path = Path::from_cid(cids[4]);
+
+        // breadth-first, with the duplicate leaf
+        let bfs: Vec<Cid> = resolver
+            .resolve_recursive_raw(path.clone(), None)
+            .map_ok(|out| *out.cid())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(bfs, [cids[4], cids[1], cids[2], cids[3], cids[0]]);
+
+        // depth-first, every block once
+        let dfs: Vec<Cid> = resolver
+            .resolve_recursive_raw_dfs(path.clone(), None)
+            .map_ok(|out| *out.cid())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(dfs, [cids[4], cids[1], cids[0], cids[2]]);
+
+        let res: Result<Vec<_>> = resolver
+            .resolve_recursive_raw_dfs(path, Some(3))
+            .try_collect()
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_inline_cid() {
+        let cid_builder = CidBuilder::default().inline(32).unwrap();
+        let file = FileBuilder::new()
+            .name("hello.txt")
+            .content_bytes("hello")
+            .cid_builder(cid_builder)
+            .build()
+            .await
+            .unwrap();
+        let dir = DirectoryBuilder::new()
+            .cid_builder(cid_builder)
+            .add_file(file)
+            .build()
+            .await
+            .unwrap();
+        let blocks: Vec<_> = dir.encode().try_collect().await.unwrap();
+        assert_eq!(blocks.len(), 2);
+
+        // only the directory is known to the loader
+        let root = &blocks[1];
+        let loader: Arc<HashMap<_, _>> =
+            Arc::new([(*root.cid(), root.data().clone())].into_iter().collect());
+        let resolver = Resolver::new(loader);
+
+        let path = format!("/ipfs/{}/hello.txt", root.cid());
+        let out = resolver.resolve(path.parse().unwrap()).await.unwrap();
+        assert_eq!(out.metadata().size, Some(5));
+        let content = read_to_string(
+            out.pretty(resolver.clone(), OutMetrics::default(), None)
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(content, "hello");
+    }
+
     #[tokio::test]
     async fn test_resolve_recursive_unixfs_basics_cid_v0() {
         // Test content
@@ -2481,6 +2906,29 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_car_store_loader() {
+        // the same hamt directory as in `test_unixfs_hamt_dir`, served from the CAR file
+        let root_cid_str = "QmUu8pzQ5yjhDrg4GiHYLeko2oT76vcmYX5bw6sjiEJ82k";
+        let loader = iroh_car::CarStore::open(["./fixtures/big-foo.car"])
+            .await
+            .unwrap();
+        let resolver = Resolver::new(loader.clone());
+
+        let path = format!("/ipfs/{root_cid_str}/bar/bar.txt");
+        let out = resolver.resolve(path.parse().unwrap()).await.unwrap();
+        let reader = out
+            .pretty(resolver.clone(), OutMetrics::default(), None)
+            .unwrap();
+        assert_eq!(read_to_string(reader).await, "world\n");
+
+        let missing = Path::from_cid(Cid::new_v1(
+            IpldCodec::Raw.into(),
+            Code::Sha2_256.digest(b"missing"),
+        ));
+        assert!(resolver.resolve(missing).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_resolve_recursive_with_path() {
         // Test content
@@ -2549,4 +2997,28 @@ mod tests {
             format!("/ipfs/{root_cid_str}/bar/bar.txt")
         );
     }
+
+    #[tokio::test]
+    async fn test_resolve_ipns_ttl() {
+        let data = Bytes::from_static(b"hello world");
+        let cid = Cid::new_v1(IpldCodec::Raw.into(), Code::Sha2_256.digest(&data));
+        let (loader, name) = RecordLoader::with_name(
+            HashMap::from([(cid, data.clone())]),
+            cid,
+            Duration::from_secs(30),
+        );
+        let resolver = Resolver::new(loader);
+
+        let out = resolver.resolve(Path::from_cid(cid)).await.unwrap();
+        assert_eq!(out.metadata().ttl, None);
+
+        let path = Path::from_parts("ipns", &name.to_string(), "").unwrap();
+        // the second lookup is answered from the cache
+        for _ in 0..2 {
+            let out = resolver.resolve(path.clone()).await.unwrap();
+            assert_eq!(out.metadata().resolved_path, vec![cid]);
+            let ttl = out.metadata().ttl.unwrap();
+            assert!(ttl > Duration::ZERO && ttl <= Duration::from_secs(30));
+        }
+    }
 }
@@ -0,0 +1,68 @@
+//! Helpers for tests resolving content, enabled by the `test-utils` feature.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use cid::Cid;
+use iroh_unixfs::content_loader::{ContentLoader, ContextId, LoaderContext};
+use iroh_unixfs::types::LoadedCid;
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+
+use crate::ipns;
+use crate::resolver::Path;
+
+/// Serves IPNS records next to the blocks of a map.
+#[derive(Debug, Clone, Default)]
+pub struct RecordLoader {
+    pub blocks: Arc<HashMap<Cid, Bytes>>,
+    pub records: Arc<HashMap<Vec<u8>, Bytes>>,
+}
+
+impl RecordLoader {
+    /// Serves `blocks`, and a record publishing `value` with the given `ttl` under a new
+    /// name, which is returned as well. The record is valid for an hour.
+    pub fn with_name(blocks: HashMap<Cid, Bytes>, value: Cid, ttl: Duration) -> (Self, PeerId) {
+        let keypair = Keypair::generate_ed25519();
+        let name = keypair.public().to_peer_id();
+        let record = ipns::create_record(
+            &keypair,
+            &Path::from_cid(value),
+            1,
+            time::OffsetDateTime::now_utc() + time::Duration::hours(1),
+            ttl,
+        )
+        .expect("valid record");
+        let loader = RecordLoader {
+            blocks: Arc::new(blocks),
+            records: Arc::new(HashMap::from([(
+                ipns::record_key(&name),
+                Bytes::from(record),
+            )])),
+        };
+        (loader, name)
+    }
+}
+
+#[async_trait]
+impl ContentLoader for RecordLoader {
+    async fn load_cid(&self, cid: &Cid, ctx: &LoaderContext) -> Result<LoadedCid> {
+        self.blocks.load_cid(cid, ctx).await
+    }
+
+    async fn stop_session(&self, _ctx: ContextId) -> Result<()> {
+        Ok(())
+    }
+
+    async fn has_cid(&self, cid: &Cid) -> Result<bool> {
+        self.blocks.has_cid(cid).await
+    }
+
+    async fn load_record(&self, key: &[u8]) -> Result<Vec<Bytes>> {
+        Ok(self.records.get(key).cloned().into_iter().collect())
+    }
+}
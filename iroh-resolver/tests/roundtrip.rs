@@ -4,7 +4,7 @@ use bytes::Bytes;
 use futures::{Stream, TryStreamExt};
 use iroh_metrics::resolver::OutMetrics;
 use iroh_unixfs::{
-    balanced_tree::DEFAULT_DEGREE,
+    balanced_tree::{TreeBuilder, DEFAULT_DEGREE},
     builder::{Directory, DirectoryBuilder, FileBuilder, SymlinkBuilder},
     chunker::DEFAULT_CHUNKS_SIZE,
     content_loader::ContentLoader,
@@ -13,7 +13,7 @@ use proptest::prelude::*;
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 use std::collections::BTreeMap;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 use iroh_resolver::resolver::{read_to_vec, stream_to_resolver, Out, Resolver};
 
@@ -131,12 +131,12 @@ fn dir_roundtrip_test_sync(dir: TestDir, hamt: bool) -> bool {
 async fn file_roundtrip_test(
     data: Bytes,
     chunk_size: usize,
-    degree: usize,
+    tree_builder: TreeBuilder,
 ) -> Result<(Vec<u8>, Vec<u8>)> {
     let file = FileBuilder::new()
         .name("file.bin")
         .fixed_chunker(chunk_size)
-        .degree(degree)
+        .tree_builder(tree_builder)
         .content_bytes(data.clone())
         .build()
         .await?;
@@ -173,8 +173,12 @@ async fn symlink_roundtrip_test() -> Result<()> {
 }
 
 /// sync version of file_roundtrip_test for use in proptest
-fn file_roundtrip_test_sync(data: Bytes, chunk_size: usize, degree: usize) -> (Vec<u8>, Vec<u8>) {
-    let f = file_roundtrip_test(data, chunk_size, degree);
+fn file_roundtrip_test_sync(
+    data: Bytes,
+    chunk_size: usize,
+    tree_builder: TreeBuilder,
+) -> (Vec<u8>, Vec<u8>) {
+    let f = file_roundtrip_test(data, chunk_size, tree_builder);
     tokio::runtime::Builder::new_current_thread()
         .build()
         .unwrap()
@@ -194,9 +198,14 @@ fn arb_test_dir() -> impl Strategy<Value = TestDir> {
     prop::collection::btree_map(".+", arb_dir_entry(), 0..10)
 }
 
-fn arb_degree() -> impl Strategy<Value = usize> {
-    // use either the smallest possible degree for complex tree structures, or the default value for realism
-    prop_oneof![Just(2), Just(DEFAULT_DEGREE)]
+fn arb_tree_builder() -> impl Strategy<Value = TreeBuilder> {
+    // use either the smallest possible parameters for complex tree structures, or the default values for realism
+    prop_oneof![
+        Just(TreeBuilder::balanced_tree_with_degree(2)),
+        Just(TreeBuilder::balanced_tree_with_degree(DEFAULT_DEGREE)),
+        Just(TreeBuilder::trickle_with_params(1, 1)),
+        Just(TreeBuilder::trickle()),
+    ]
 }
 
 fn arb_chunk_size() -> impl Strategy<Value = usize> {
@@ -206,8 +215,8 @@ fn arb_chunk_size() -> impl Strategy<Value = usize> {
 
 proptest! {
     #[test]
-    fn test_file_roundtrip(data in proptest::collection::vec(any::<u8>(), 0usize..1024), chunk_size in arb_chunk_size(), degree in arb_degree()) {
-        let (left, right) = file_roundtrip_test_sync(data.into(), chunk_size, degree);
+    fn test_file_roundtrip(data in proptest::collection::vec(any::<u8>(), 0usize..1024), chunk_size in arb_chunk_size(), tree_builder in arb_tree_builder()) {
+        let (left, right) = file_roundtrip_test_sync(data.into(), chunk_size, tree_builder);
         assert_eq!(left, right);
     }
 
@@ -259,7 +268,8 @@ async fn test_builder_roundtrip_complex_tree_1() -> Result<()> {
     let mut rng = ChaCha8Rng::from_seed([0; 32]);
     let mut data = vec![0u8; 1024 * 128];
     rng.fill(data.as_mut_slice());
-    let (left, right) = file_roundtrip_test(data.into(), 1024, 4).await?;
+    let (left, right) =
+        file_roundtrip_test(data.into(), 1024, TreeBuilder::balanced_tree_with_degree(4)).await?;
     assert_eq!(left, right);
     Ok(())
 }
@@ -270,8 +280,57 @@ async fn test_builder_roundtrip_128m() -> Result<()> {
     let mut rng = ChaCha8Rng::from_seed([0; 32]);
     let mut data = vec![0u8; 128 * 1024 * 1024];
     rng.fill(data.as_mut_slice());
+    let (left, right) = file_roundtrip_test(
+        data.into(),
+        DEFAULT_CHUNKS_SIZE,
+        TreeBuilder::balanced_tree(),
+    )
+    .await?;
+    assert_eq!(left, right);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_builder_roundtrip_trickle_complex_tree() -> Result<()> {
+    // fill with random data so we get distinct cids for all blocks
+    let mut rng = ChaCha8Rng::from_seed([0; 32]);
+    let mut data = vec![0u8; 1024 * 128];
+    rng.fill(data.as_mut_slice());
     let (left, right) =
-        file_roundtrip_test(data.into(), DEFAULT_CHUNKS_SIZE, DEFAULT_DEGREE).await?;
+        file_roundtrip_test(data.into(), 1024, TreeBuilder::trickle_with_params(3, 2)).await?;
     assert_eq!(left, right);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_trickle_seek() -> Result<()> {
+    // fill with random data so we get distinct cids for all blocks
+    let mut rng = ChaCha8Rng::from_seed([0; 32]);
+    let mut data = vec![0u8; 1024 * 64];
+    rng.fill(data.as_mut_slice());
+    let file = FileBuilder::new()
+        .name("file.bin")
+        .fixed_chunker(100)
+        .tree_builder(TreeBuilder::trickle_with_params(3, 2))
+        .content_bytes(data.clone())
+        .build()
+        .await?;
+    let (root, resolver) = stream_to_resolver(file.encode().await?).await?;
+    let out = resolver
+        .resolve(iroh_resolver::resolver::Path::from_cid(root))
+        .await?;
+    let mut reader = out.pretty(resolver, OutMetrics::default(), None)?;
+
+    // seek back and forth across leaves and subtrees of different depths
+    for start in [60_000, 0, 299, 300, 12_345, 65_436, 4_000, 65_535] {
+        let pos = reader
+            .seek(tokio::io::SeekFrom::Start(start as u64))
+            .await?;
+        assert_eq!(pos, start as u64);
+        let end = (start + 1000).min(data.len());
+        let mut buf = vec![0u8; end - start];
+        reader.read_exact(&mut buf).await?;
+        assert_eq!(buf, data[start..end]);
+    }
+    Ok(())
+}
@@ -101,13 +101,40 @@ impl P2pClient {
         Ok(providers_stream)
     }
 
+    /// Announces this node as provider of `key` in the DHT.
+    ///
+    /// Identity hashed cids are not announced, as their content is part of the cid.
     #[tracing::instrument(skip(self))]
     pub async fn start_providing(&self, key: &Cid) -> Result<()> {
+        if iroh_util::inline_data(key).is_some() {
+            return Ok(());
+        }
         let key = Key(key.hash().to_bytes().into());
         self.client.rpc(StartProvidingRequest { key }).await??;
         Ok(())
     }
 
+    /// Looks up all records stored in the DHT under the given key.
+    ///
+    /// The records are not validated, this is up to the caller.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_record(&self, key: Bytes) -> Result<Vec<Bytes>> {
+        let res = self
+            .client
+            .rpc(GetRecordRequest { key: Key(key) })
+            .await??;
+        Ok(res.records)
+    }
+
+    /// Publishes an IPNS record pointing at `cid`, signed with the keychain key named `key`.
+    ///
+    /// Returns the name the record was published under.
+    #[tracing::instrument(skip(self))]
+    pub async fn publish_name(&self, cid: Cid, key: Option<String>) -> Result<PeerId> {
+        let res = self.client.rpc(PublishNameRequest { cid, key }).await??;
+        Ok(res.name)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn stop_providing(&self, key: &Cid) -> Result<()> {
         let key = Key(key.hash().to_bytes().into());
@@ -1,8 +1,8 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_stream::stream;
 use bytes::Bytes;
 use cid::Cid;
-use futures::{Stream, StreamExt};
+use futures::{stream::BoxStream, Stream, StreamExt, TryStreamExt};
 use iroh_rpc_types::{store::*, VersionRequest, WatchRequest};
 
 use crate::open_client;
@@ -65,6 +65,83 @@ impl StoreClient {
         Ok(res.size)
     }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn delete(&self, cid: Cid) -> Result<Option<u64>> {
+        let res = self.client.rpc(DeleteRequest { cid }).await??;
+        Ok(res.size)
+    }
+
+    #[tracing::instrument(skip(self, cids))]
+    pub async fn delete_many(&self, cids: Vec<Cid>) -> Result<Vec<(Cid, u64)>> {
+        let res = self.client.rpc(DeleteManyRequest { cids }).await??;
+        Ok(res.deleted)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn pin(&self, cid: Cid, recursive: bool) -> Result<()> {
+        self.client.rpc(PinRequest { cid, recursive }).await??;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn unpin(&self, cid: Cid) -> Result<()> {
+        self.client.rpc(UnpinRequest { cid }).await??;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn list_pins(&self, pin_type: Option<PinType>) -> Result<Vec<(Cid, PinType)>> {
+        let res = self.client.rpc(ListPinsRequest { pin_type }).await??;
+        Ok(res.pins)
+    }
+
+    /// Lists the blocks in the store, ordered by multihash, together with their sizes.
+    #[tracing::instrument(skip(self))]
+    pub async fn list_blocks(
+        &self,
+        codec: Option<u64>,
+        hash_prefix: Option<Bytes>,
+        after: Option<Cid>,
+        limit: Option<u64>,
+    ) -> Result<impl Stream<Item = Result<(Cid, u64)>>> {
+        let res = self
+            .client
+            .server_streaming(ListBlocksRequest {
+                codec,
+                hash_prefix,
+                after,
+                limit,
+            })
+            .await?;
+        let blocks = res
+            .map(|p| {
+                let blocks = p??.blocks.into_iter().map(Ok);
+                Ok::<_, anyhow::Error>(futures::stream::iter(blocks))
+            })
+            .try_flatten();
+        Ok(blocks)
+    }
+
+    /// Runs garbage collection, removing all blocks that are not pinned.
+    #[tracing::instrument(skip(self))]
+    pub async fn gc(&self) -> Result<GcResponse> {
+        let res = self.client.rpc(GcRequest).await??;
+        Ok(res)
+    }
+
+    /// Holds off garbage collection in the store until the returned guard is dropped.
+    #[tracing::instrument(skip(self))]
+    pub async fn gc_guard(&self) -> Result<GcGuard> {
+        let mut res = self.client.server_streaming(GcGuardRequest).await?;
+        // the first response confirms the guard is held
+        res.next()
+            .await
+            .ok_or_else(|| anyhow!("store closed the gc guard"))??;
+        Ok(GcGuard {
+            _responses: res.map(|_| ()).boxed(),
+        })
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn check(&self) -> (StatusType, String) {
         match self.version().await {
@@ -90,3 +167,16 @@ impl StoreClient {
         }
     }
 }
+
+/// Holds off garbage collection in the store while it is alive.
+///
+/// The store releases the guard once the stream of responses is dropped.
+pub struct GcGuard {
+    _responses: BoxStream<'static, ()>,
+}
+
+impl std::fmt::Debug for GcGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GcGuard").finish()
+    }
+}
@@ -89,6 +89,31 @@ pub struct StopProvidingRequest {
     pub key: Key,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetRecordRequest {
+    pub key: Key,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetRecordResponse {
+    /// The values of all records found for the key, unvalidated.
+    pub records: Vec<Bytes>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublishNameRequest {
+    /// The content to point the name at.
+    pub cid: Cid,
+    /// Name of the keychain key to publish under, defaults to the node's identity.
+    pub key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PublishNameResponse {
+    /// The IPNS name the record was published under.
+    pub name: PeerId,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetListeningAddrsRequest;
 
@@ -241,6 +266,8 @@ pub enum P2pRequest {
     GossipsubUnsubscribe(GossipsubUnsubscribeRequest),
     StartProviding(StartProvidingRequest),
     StopProviding(StopProvidingRequest),
+    GetRecord(GetRecordRequest),
+    PublishName(PublishNameRequest),
     LocalPeerId(LocalPeerIdRequest),
     ExternalAddrs(ExternalAddrsRequest),
     Listeners(ListenersRequest),
@@ -264,6 +291,8 @@ pub enum P2pResponse {
     LocalPeerId(RpcResult<LocalPeerIdResponse>),
     ExternalAddrs(RpcResult<ExternalAddrsResponse>),
     Listeners(RpcResult<ListenersResponse>),
+    GetRecord(RpcResult<GetRecordResponse>),
+    PublishName(RpcResult<PublishNameResponse>),
     UnitResult(RpcResult<()>),
 }
 
@@ -383,6 +412,14 @@ impl RpcMsg<P2pService> for StopProvidingRequest {
     type Response = RpcResult<()>;
 }
 
+impl RpcMsg<P2pService> for GetRecordRequest {
+    type Response = RpcResult<GetRecordResponse>;
+}
+
+impl RpcMsg<P2pService> for PublishNameRequest {
+    type Response = RpcResult<PublishNameResponse>;
+}
+
 impl RpcMsg<P2pService> for LocalPeerIdRequest {
     type Response = RpcResult<LocalPeerIdResponse>;
 }
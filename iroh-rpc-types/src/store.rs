@@ -75,6 +75,108 @@ pub struct GetSizeResponse {
     pub size: Option<u64>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteRequest {
+    pub cid: Cid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteResponse {
+    /// Size of the deleted block, `None` if it was not stored.
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteManyRequest {
+    pub cids: Vec<Cid>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeleteManyResponse {
+    /// The blocks that were actually deleted, with their sizes.
+    pub deleted: Vec<(Cid, u64)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListBlocksRequest {
+    /// Only list blocks with this codec.
+    pub codec: Option<u64>,
+    /// Only list blocks whose multihash starts with these bytes.
+    pub hash_prefix: Option<Bytes>,
+    /// Resume listing after this block.
+    pub after: Option<Cid>,
+    /// Maximum number of blocks to list.
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListBlocksResponse {
+    /// A page of blocks, ordered by multihash, with their sizes.
+    pub blocks: Vec<(Cid, u64)>,
+}
+
+/// The way in which a block is protected by the pin set.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PinType {
+    /// Only the block itself is pinned.
+    Direct,
+    /// The block and all of its descendants are pinned.
+    Recursive,
+    /// The block is pinned because it is a descendant of a recursive pin.
+    Indirect,
+}
+
+impl fmt::Display for PinType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinType::Direct => write!(f, "direct"),
+            PinType::Recursive => write!(f, "recursive"),
+            PinType::Indirect => write!(f, "indirect"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PinRequest {
+    pub cid: Cid,
+    pub recursive: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnpinRequest {
+    pub cid: Cid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListPinsRequest {
+    /// Only list pins of this type, lists all pins when `None`.
+    pub pin_type: Option<PinType>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListPinsResponse {
+    pub pins: Vec<(Cid, PinType)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GcRequest;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GcResponse {
+    /// Number of blocks removed.
+    pub blocks: u64,
+    /// Number of bytes of block data removed.
+    pub bytes: u64,
+}
+
+/// Holds off garbage collection for as long as the response stream is open.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GcGuardRequest;
+
+/// Sent once the guard is held, and repeatedly afterwards to detect a closed stream.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GcGuardResponse;
+
 #[derive(Serialize, Deserialize, Debug, From, TryInto)]
 pub enum StoreRequest {
     Watch(WatchRequest),
@@ -85,6 +187,14 @@ pub enum StoreRequest {
     Has(HasRequest),
     GetLinks(GetLinksRequest),
     GetSize(GetSizeRequest),
+    Delete(DeleteRequest),
+    DeleteMany(DeleteManyRequest),
+    ListBlocks(ListBlocksRequest),
+    Pin(PinRequest),
+    Unpin(UnpinRequest),
+    ListPins(ListPinsRequest),
+    Gc(GcRequest),
+    GcGuard(GcGuardRequest),
 }
 
 #[derive(Serialize, Deserialize, Debug, From, TryInto)]
@@ -95,6 +205,12 @@ pub enum StoreResponse {
     Has(RpcResult<HasResponse>),
     GetLinks(RpcResult<GetLinksResponse>),
     GetSize(RpcResult<GetSizeResponse>),
+    Delete(RpcResult<DeleteResponse>),
+    DeleteMany(RpcResult<DeleteManyResponse>),
+    ListBlocks(RpcResult<ListBlocksResponse>),
+    ListPins(RpcResult<ListPinsResponse>),
+    Gc(RpcResult<GcResponse>),
+    GcGuard(GcGuardResponse),
     Unit(()),
     UnitResult(RpcResult<()>),
 }
@@ -143,3 +259,43 @@ impl RpcMsg<StoreService> for GetLinksRequest {
 impl RpcMsg<StoreService> for GetSizeRequest {
     type Response = RpcResult<GetSizeResponse>;
 }
+
+impl RpcMsg<StoreService> for PinRequest {
+    type Response = RpcResult<()>;
+}
+
+impl RpcMsg<StoreService> for UnpinRequest {
+    type Response = RpcResult<()>;
+}
+
+impl RpcMsg<StoreService> for ListPinsRequest {
+    type Response = RpcResult<ListPinsResponse>;
+}
+
+impl RpcMsg<StoreService> for GcRequest {
+    type Response = RpcResult<GcResponse>;
+}
+
+impl RpcMsg<StoreService> for DeleteRequest {
+    type Response = RpcResult<DeleteResponse>;
+}
+
+impl RpcMsg<StoreService> for DeleteManyRequest {
+    type Response = RpcResult<DeleteManyResponse>;
+}
+
+impl Msg<StoreService> for ListBlocksRequest {
+    type Response = RpcResult<ListBlocksResponse>;
+
+    type Update = Self;
+
+    type Pattern = ServerStreaming;
+}
+
+impl Msg<StoreService> for GcGuardRequest {
+    type Response = GcGuardResponse;
+
+    type Update = Self;
+
+    type Pattern = ServerStreaming;
+}
@@ -163,6 +163,9 @@ impl P2pNode {
             libp2p: libp2p_config,
             rpc_client: rpc_p2p_client_config.clone(),
             key_store_path: db_path.parent().unwrap().to_path_buf(),
+            kad_store_path: None,
+            car_files: Vec::new(),
+            reprovider: Default::default(),
         };
 
         let rpc = Client::new(rpc_p2p_client_config).await?;
@@ -172,6 +175,7 @@ impl P2pNode {
         let store_config = iroh_store::Config {
             path: db_path.to_path_buf(),
             rpc_client: rpc_store_client_config,
+            gc: Default::default(),
         };
 
         let store = if store_config.path.exists() {
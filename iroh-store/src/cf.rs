@@ -14,6 +14,12 @@ pub const CF_GRAPH_V0: &str = "graph-v0";
 ///
 /// By storing multihash first we can search for ids either by cid = (multihash, code) or by multihash.
 pub const CF_ID_V0: &str = "id-v0";
+/// Column family that stores the pin set.
+/// - Maps id (u64) to the pin of the blob
+///
+/// Only direct and recursive pins are stored, indirect pins are derived by walking
+/// [`CF_GRAPH_V0`] from the recursive pins.
+pub const CF_PINS_V0: &str = "pins-v0";
 
 // This wrapper type serializes the contained value out-of-line so that newer
 // versions can be viewed as the older version.
@@ -37,3 +43,13 @@ pub struct MetadataV0 {
 pub struct GraphV0 {
     pub children: Vec<u64>,
 }
+
+#[derive(Debug, Archive, Deserialize, Serialize)]
+#[repr(C)]
+#[archive_attr(repr(C), derive(CheckBytes))]
+pub struct PinV0 {
+    /// Whether the pin also protects all descendants of the blob.
+    pub recursive: bool,
+    /// Whether the blob was pinned by its CIDv0, ids only record the codec and multihash.
+    pub v0: bool,
+}
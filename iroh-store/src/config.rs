@@ -6,6 +6,7 @@ use iroh_rpc_types::store::StoreAddr;
 use iroh_util::{insert_into_config_map, iroh_data_path};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// CONFIG_FILE_NAME is the name of the optional config file located in the iroh home directory
 pub const CONFIG_FILE_NAME: &str = "store.config.toml";
@@ -73,6 +74,56 @@ pub struct Config {
     /// Only used to extract the listening address from the `store_addr` field.
     // TODO: split off listening address from RpcClientConfig.
     pub rpc_client: RpcClientConfig,
+    /// Configuration of the garbage collection.
+    #[serde(default)]
+    pub gc: GcConfig,
+}
+
+/// The configuration for the garbage collection of the store.
+///
+/// Garbage collection removes all blocks which are not pinned.  It can always be triggered
+/// manually, this configures the optional background mode which runs it automatically.
+#[derive(PartialEq, Eq, Debug, Deserialize, Serialize, Clone)]
+pub struct GcConfig {
+    /// Size of the stored data in bytes above which garbage collection runs automatically.
+    ///
+    /// Automatic garbage collection is disabled when this is not set.
+    pub watermark: Option<u64>,
+    /// Interval in seconds at which the size of the stored data is checked against the
+    /// watermark.
+    pub interval_secs: u64,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            watermark: None,
+            interval_secs: 60,
+        }
+    }
+}
+
+impl GcConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+impl Source for GcConfig {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let mut map: Map<String, Value> = Map::new();
+        // `config` package converts all unsigned integers into U64, which then has problems
+        // downcasting, so store them as signed ints
+        if let Some(watermark) = self.watermark {
+            insert_into_config_map(&mut map, "watermark", watermark as i64);
+        }
+        insert_into_config_map(&mut map, "interval_secs", self.interval_secs as i64);
+        Ok(map)
+    }
 }
 
 impl From<ServerConfig> for Config {
@@ -91,6 +142,7 @@ impl Config {
         Self {
             path,
             rpc_client: Default::default(),
+            gc: Default::default(),
         }
     }
 
@@ -102,6 +154,7 @@ impl Config {
                 store_addr: Some(addr),
                 ..Default::default()
             },
+            gc: Default::default(),
         }
     }
 
@@ -123,6 +176,7 @@ impl Source for Config {
             .ok_or_else(|| ConfigError::Foreign("No `path` set. Path is required.".into()))?;
         insert_into_config_map(&mut map, "path", path);
         insert_into_config_map(&mut map, "rpc_client", self.rpc_client.collect()?);
+        insert_into_config_map(&mut map, "gc", self.gc.collect()?);
         Ok(map)
     }
 }
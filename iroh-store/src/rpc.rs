@@ -6,16 +6,21 @@ use futures::stream::Stream;
 use iroh_rpc_client::{create_server, ServerError, ServerSocket, StoreServer, HEALTH_POLL_WAIT};
 use iroh_rpc_types::{
     store::{
-        GetLinksRequest, GetLinksResponse, GetRequest, GetResponse, GetSizeRequest,
-        GetSizeResponse, HasRequest, HasResponse, PutManyRequest, PutRequest, StoreAddr,
-        StoreRequest, StoreService,
+        DeleteManyRequest, DeleteManyResponse, DeleteRequest, DeleteResponse, GcGuardRequest,
+        GcGuardResponse, GcRequest, GcResponse, GetLinksRequest, GetLinksResponse, GetRequest,
+        GetResponse, GetSizeRequest, GetSizeResponse, HasRequest, HasResponse, ListBlocksRequest,
+        ListBlocksResponse, ListPinsRequest, ListPinsResponse, PinRequest, PutManyRequest,
+        PutRequest, StoreAddr, StoreRequest, StoreService, UnpinRequest,
     },
-    VersionRequest, VersionResponse, WatchRequest, WatchResponse,
+    RpcResult, VersionRequest, VersionResponse, WatchRequest, WatchResponse,
 };
 use tracing::info;
 
 use crate::{store::Store, VERSION};
 
+/// Number of blocks sent per response when streaming the list of blocks.
+const LIST_BLOCKS_PAGE_SIZE: usize = 1024;
+
 impl iroh_rpc_types::NamedService for Store {
     const NAME: &'static str = "store";
 }
@@ -115,6 +120,120 @@ impl RpcStore {
             })
             .await
     }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete(self, req: DeleteRequest) -> Result<DeleteResponse> {
+        let cid = req.cid;
+        let size = self.0.spawn_blocking(move |x| x.delete(&cid)).await?;
+
+        info!("store rpc call: delete cid {}", cid);
+        Ok(DeleteResponse { size })
+    }
+
+    #[tracing::instrument(skip(self, req))]
+    async fn delete_many(self, req: DeleteManyRequest) -> Result<DeleteManyResponse> {
+        let deleted = self
+            .0
+            .spawn_blocking(move |x| x.delete_many(req.cids))
+            .await?;
+        Ok(DeleteManyResponse { deleted })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn pin(self, req: PinRequest) -> Result<()> {
+        let cid = req.cid;
+        self.0
+            .spawn_blocking(move |x| x.pin(&cid, req.recursive))
+            .await?;
+
+        info!("store rpc call: pin cid {}", cid);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn unpin(self, req: UnpinRequest) -> Result<()> {
+        let cid = req.cid;
+        self.0.spawn_blocking(move |x| x.unpin(&cid)).await?;
+
+        info!("store rpc call: unpin cid {}", cid);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_pins(self, req: ListPinsRequest) -> Result<ListPinsResponse> {
+        self.0
+            .spawn_blocking(move |x| {
+                let pins = x.list_pins(req.pin_type)?;
+                Ok(ListPinsResponse { pins })
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn list_blocks(
+        self,
+        req: ListBlocksRequest,
+    ) -> impl Stream<Item = RpcResult<ListBlocksResponse>> {
+        async_stream::stream! {
+            let mut remaining = req.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+            let mut after = req.after;
+            while remaining > 0 {
+                let limit = remaining.min(LIST_BLOCKS_PAGE_SIZE);
+                let codec = req.codec;
+                let hash_prefix = req.hash_prefix.clone();
+                let page = self
+                    .0
+                    .spawn_blocking(move |x| {
+                        x.list_blocks(codec, hash_prefix.as_deref(), after.as_ref(), limit)
+                    })
+                    .await;
+                match page {
+                    Ok(blocks) => {
+                        let done = blocks.len() < limit;
+                        remaining -= blocks.len();
+                        after = blocks.last().map(|(cid, _)| *cid);
+                        if !blocks.is_empty() {
+                            yield Ok(ListBlocksResponse { blocks });
+                        }
+                        if done {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(e.into());
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn gc(self, _: GcRequest) -> Result<GcResponse> {
+        let stats = self.0.gc().await?;
+
+        info!(
+            "store rpc call: gc removed {} blocks, {} bytes",
+            stats.blocks, stats.bytes
+        );
+        Ok(GcResponse {
+            blocks: stats.blocks,
+            bytes: stats.bytes,
+        })
+    }
+
+    /// Holds a [`GcGuard`](crate::GcGuard) until the client closes the stream, which
+    /// is noticed when sending the next response fails.
+    #[tracing::instrument(skip(self))]
+    fn gc_guard(self, _: GcGuardRequest) -> impl Stream<Item = GcGuardResponse> {
+        async_stream::stream! {
+            let _guard = self.0.gc_guard().await;
+            loop {
+                yield GcGuardResponse;
+                tokio::time::sleep(HEALTH_POLL_WAIT).await;
+            }
+        }
+    }
 }
 
 /// dispatch a single request from the server 
@@ -130,6 +249,14 @@ async fn dispatch(s: StoreServer, req: StoreRequest, chan: ServerSocket<StoreSer
         Has(req) => s.rpc_map_err(req, chan, target, RpcStore::has).await,
         GetLinks(req) => s.rpc_map_err(req, chan, target, RpcStore::get_links).await,
         GetSize(req) => s.rpc_map_err(req, chan, target, RpcStore::get_size).await,
+        Delete(req) => s.rpc_map_err(req, chan, target, RpcStore::delete).await,
+        DeleteMany(req) => s.rpc_map_err(req, chan, target, RpcStore::delete_many).await,
+        ListBlocks(req) => s.server_streaming(req, chan, target, RpcStore::list_blocks).await,
+        Pin(req) => s.rpc_map_err(req, chan, target, RpcStore::pin).await,
+        Unpin(req) => s.rpc_map_err(req, chan, target, RpcStore::unpin).await,
+        ListPins(req) => s.rpc_map_err(req, chan, target, RpcStore::list_pins).await,
+        Gc(req) => s.rpc_map_err(req, chan, target, RpcStore::gc).await,
+        GcGuard(req) => s.server_streaming(req, chan, target, RpcStore::gc_guard).await,
     }
 }
 
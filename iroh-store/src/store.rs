@@ -1,6 +1,12 @@
-use std::{fmt, sync::Arc, thread::available_parallelism};
+use std::{
+    fmt,
+    ops::Deref,
+    sync::{Arc, Weak},
+    thread::available_parallelism,
+    time::Duration,
+};
 
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
 use anyhow::{anyhow, bail, Context, Result};
 use bytes::Bytes;
 use cid::Cid;
@@ -9,6 +15,8 @@ use iroh_metrics::{
     inc, observe, record,
     store::{StoreHistograms, StoreMetrics},
 };
+use iroh_rpc_types::store::PinType;
+use iroh_util::inline_data;
 use multihash::Multihash;
 use rocksdb::{
     BlockBasedOptions, Cache, ColumnFamily, DBPinnableSlice, Direction, IteratorMode, Options,
@@ -16,11 +24,43 @@ use rocksdb::{
 };
 use smallvec::SmallVec;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
-use tokio::task;
+use tokio::{
+    sync::{OwnedRwLockReadGuard, RwLock as AsyncRwLock},
+    task,
+};
+use tracing::{info, warn};
 
-use crate::cf::{GraphV0, MetadataV0, CF_BLOBS_V0, CF_GRAPH_V0, CF_ID_V0, CF_METADATA_V0};
+use crate::cf::{
+    GraphV0, MetadataV0, PinV0, CF_BLOBS_V0, CF_GRAPH_V0, CF_ID_V0, CF_METADATA_V0, CF_PINS_V0,
+};
 use crate::Config;
 
+/// Number of deletes to accumulate in a single write batch during garbage collection.
+const GC_BATCH_SIZE: usize = 4096;
+
+/// The data of a block, either read from the database or inlined into its cid.
+pub enum BlockData<'a> {
+    Stored(DBPinnableSlice<'a>),
+    Inline(Bytes),
+}
+
+impl Deref for BlockData<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            BlockData::Stored(slice) => slice,
+            BlockData::Inline(bytes) => bytes,
+        }
+    }
+}
+
+impl AsRef<[u8]> for BlockData<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Store {
     inner: Arc<InnerStore>,
@@ -29,9 +69,18 @@ pub struct Store {
 struct InnerStore {
     content: RocksDb,
     next_id: RwLock<u64>,
+    /// Held shared by [`GcGuard`]s and exclusively by garbage collection.
+    gc_lock: Arc<AsyncRwLock<()>>,
     _cache: Cache,
 }
 
+/// Holds off garbage collection while it is alive.
+///
+/// Blocks which are added and only afterwards pinned must be written and pinned while
+/// holding a guard, otherwise a garbage collection in between removes them.
+#[derive(Debug)]
+pub struct GcGuard(OwnedRwLockReadGuard<()>);
+
 impl fmt::Debug for InnerStore {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("InnerStore")
@@ -72,6 +121,8 @@ fn default_blob_opts() -> Options {
     let mut opts = Options::default();
     opts.set_enable_blob_files(true);
     opts.set_min_blob_size(5 * 1024);
+    // blobs removed by garbage collection are only reclaimed by blob gc during compaction
+    opts.set_enable_blob_gc(true);
 
     opts
 }
@@ -88,6 +139,21 @@ fn id_key(cid: &Cid) -> SmallVec<[u8; 64]> {
     key
 }
 
+/// Identity hashed blocks carry their data in the cid, so they are not stored unless they
+/// link to other blocks, which pins of them have to protect.
+fn is_inline_leaf(cid: &Cid, has_links: bool) -> bool {
+    inline_data(cid).is_some() && !has_links
+}
+
+/// Summary of the blocks removed by a garbage collection run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    /// Number of blocks removed.
+    pub blocks: u64,
+    /// Number of bytes of block data removed.
+    pub bytes: u64,
+}
+
 /// Struct used to iterate over all the ids for a multihash
 struct CodeAndId {
     // the ipld code of the id
@@ -123,18 +189,27 @@ impl Store {
                 let opts = Options::default();
                 db.create_cf(CF_ID_V0, &opts)?;
             }
+            {
+                let opts = Options::default();
+                db.create_cf(CF_PINS_V0, &opts)?;
+            }
 
             Ok(db)
         })
         .await??;
 
-        Ok(Store {
+        let store = Store {
             inner: Arc::new(InnerStore {
                 content: db,
                 next_id: 1.into(),
+                gc_lock: Default::default(),
                 _cache: cache,
             }),
-        })
+        };
+        if let Some(watermark) = config.gc.watermark {
+            store.spawn_gc_task(watermark, config.gc.interval());
+        }
+        Ok(store)
     }
 
     /// Opens an existing database.
@@ -142,6 +217,8 @@ impl Store {
     pub async fn open(config: Config) -> Result<Self> {
         let (mut options, cache) = default_options();
         options.create_if_missing(false);
+        // stores created before the pin set existed don't have all column families yet
+        options.create_missing_column_families(true);
         // TODO: find a way to read existing options
 
         let path = config.path.clone();
@@ -149,7 +226,13 @@ impl Store {
             let db = RocksDb::open_cf(
                 &options,
                 path,
-                [CF_BLOBS_V0, CF_METADATA_V0, CF_GRAPH_V0, CF_ID_V0],
+                [
+                    CF_BLOBS_V0,
+                    CF_METADATA_V0,
+                    CF_GRAPH_V0,
+                    CF_ID_V0,
+                    CF_PINS_V0,
+                ],
             )?;
 
             // read last inserted id
@@ -173,13 +256,18 @@ impl Store {
         })
         .await??;
 
-        Ok(Store {
+        let store = Store {
             inner: Arc::new(InnerStore {
                 content: db,
                 next_id: next_id.into(),
+                gc_lock: Default::default(),
                 _cache: cache,
             }),
-        })
+        };
+        if let Some(watermark) = config.gc.watermark {
+            store.spawn_gc_task(watermark, config.gc.interval());
+        }
+        Ok(store)
     }
 
     #[tracing::instrument(skip(self, links, blob))]
@@ -187,12 +275,38 @@ impl Store {
     where
         L: IntoIterator<Item = Cid>,
     {
+        let mut links = links.into_iter().peekable();
+        if is_inline_leaf(&cid, links.peek().is_some()) {
+            return Ok(());
+        }
         self.write_store()?.put(cid, blob, links)
     }
 
     #[tracing::instrument(skip(self, blocks))]
     pub fn put_many(&self, blocks: impl IntoIterator<Item = (Cid, Bytes, Vec<Cid>)>) -> Result<()> {
-        self.write_store()?.put_many(blocks)
+        self.write_store()?.put_many(
+            blocks
+                .into_iter()
+                .filter(|(cid, _, links)| !is_inline_leaf(cid, !links.is_empty())),
+        )
+    }
+
+    /// Deletes the block for the given cid.
+    ///
+    /// Returns the size of the deleted block, or `None` if it was not stored.
+    #[tracing::instrument(skip(self))]
+    pub fn delete(&self, cid: &Cid) -> Result<Option<u64>> {
+        let deleted = self.write_store()?.delete_many([*cid])?;
+        Ok(deleted.first().map(|(_, size)| *size))
+    }
+
+    /// Deletes the blocks for the given cids.
+    ///
+    /// Returns the cids and sizes of the blocks that were actually deleted.  Fails without
+    /// deleting anything if any of the blocks is pinned.
+    #[tracing::instrument(skip(self, cids))]
+    pub fn delete_many(&self, cids: impl IntoIterator<Item = Cid>) -> Result<Vec<(Cid, u64)>> {
+        self.write_store()?.delete_many(cids)
     }
 
     #[tracing::instrument(skip(self))]
@@ -205,18 +319,30 @@ impl Store {
         self.read_store()?.has_blob_for_hash(hash)
     }
 
+    /// Returns the data of the block for the given cid.
+    ///
+    /// The data of identity hashed cids is returned without looking at the database.
     #[tracing::instrument(skip(self))]
-    pub fn get(&self, cid: &Cid) -> Result<Option<DBPinnableSlice<'_>>> {
-        self.read_store()?.get(cid)
+    pub fn get(&self, cid: &Cid) -> Result<Option<BlockData<'_>>> {
+        if let Some(data) = inline_data(cid) {
+            return Ok(Some(BlockData::Inline(Bytes::copy_from_slice(data))));
+        }
+        Ok(self.read_store()?.get(cid)?.map(BlockData::Stored))
     }
 
     #[tracing::instrument(skip(self))]
     pub fn get_size(&self, cid: &Cid) -> Result<Option<usize>> {
+        if let Some(data) = inline_data(cid) {
+            return Ok(Some(data.len()));
+        }
         self.read_store()?.get_size(cid)
     }
 
     #[tracing::instrument(skip(self))]
     pub fn has(&self, cid: &Cid) -> Result<bool> {
+        if inline_data(cid).is_some() {
+            return Ok(true);
+        }
         self.read_store()?.has(cid)
     }
 
@@ -225,6 +351,79 @@ impl Store {
         self.read_store()?.get_links(cid)
     }
 
+    /// Lists up to `limit` stored blocks with their sizes, ordered by multihash.
+    ///
+    /// Blocks can be filtered by `codec` and by a prefix of the multihash bytes.  Listing
+    /// starts after the block `after`, which allows continuing from the last block of a
+    /// previous call.
+    #[tracing::instrument(skip(self))]
+    pub fn list_blocks(
+        &self,
+        codec: Option<u64>,
+        hash_prefix: Option<&[u8]>,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<Vec<(Cid, u64)>> {
+        self.read_store()?
+            .list_blocks(codec, hash_prefix, after, limit)
+    }
+
+    /// Pins the given cid, protecting it from garbage collection.
+    ///
+    /// A recursive pin also protects all blocks reachable from `cid`.
+    #[tracing::instrument(skip(self))]
+    pub fn pin(&self, cid: &Cid, recursive: bool) -> Result<()> {
+        self.write_store()?.pin(cid, recursive)
+    }
+
+    /// Removes the direct or recursive pin for the given cid.
+    #[tracing::instrument(skip(self))]
+    pub fn unpin(&self, cid: &Cid) -> Result<()> {
+        self.write_store()?.unpin(cid)
+    }
+
+    /// Returns how the given cid is pinned, if at all.
+    #[tracing::instrument(skip(self))]
+    pub fn pin_type(&self, cid: &Cid) -> Result<Option<PinType>> {
+        self.read_store()?.pin_type(cid)
+    }
+
+    /// Lists all pins, optionally only those of the given type.
+    #[tracing::instrument(skip(self))]
+    pub fn list_pins(&self, pin_type: Option<PinType>) -> Result<Vec<(Cid, PinType)>> {
+        self.read_store()?.list_pins(pin_type)
+    }
+
+    /// Removes all blocks that are not pinned, directly or indirectly.
+    ///
+    /// Waits until all [`GcGuard`]s are dropped.
+    #[tracing::instrument(skip(self))]
+    pub async fn gc(&self) -> Result<GcStats> {
+        let stats = {
+            let _lock = self.inner.gc_lock.write().await;
+            self.spawn_blocking(|store| store.write_store()?.gc())
+                .await?
+        };
+        if stats.blocks > 0 {
+            // compaction can take a while and needs neither lock, so pins and writes
+            // can go ahead meanwhile.
+            self.spawn_blocking(|store| store.compact()).await?;
+        }
+        Ok(stats)
+    }
+
+    /// Holds off garbage collection until the returned guard is dropped.
+    #[tracing::instrument(skip(self))]
+    pub async fn gc_guard(&self) -> GcGuard {
+        GcGuard(Arc::clone(&self.inner.gc_lock).read_owned().await)
+    }
+
+    /// Returns the approximate size in bytes of all data stored on disk.
+    #[tracing::instrument(skip(self))]
+    pub fn stored_size(&self) -> Result<u64> {
+        self.read_store()?.stored_size()
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn consistency_check(&self) -> Result<Vec<String>> {
         self.read_store()?.consistency_check()
@@ -238,6 +437,17 @@ impl Store {
         self.read_store()?.get_ids_for_hash(hash)
     }
 
+    /// Compacts the column families gc removes from, so the removed data is actually
+    /// freed on disk.
+    fn compact(&self) -> Result<()> {
+        let db = &self.inner.content;
+        let cf = ColumnFamilies::new(db)?;
+        for cf in [cf.id, cf.blobs, cf.metadata, cf.graph] {
+            db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+        Ok(())
+    }
+
     fn write_store(&self) -> Result<WriteStore> {
         let db = &self.inner.content;
         Ok(WriteStore {
@@ -256,6 +466,37 @@ impl Store {
         })
     }
 
+    /// Spawns a task which runs garbage collection whenever the stored data exceeds
+    /// `watermark` bytes.
+    ///
+    /// The task only holds a weak reference and stops once the store is dropped.
+    fn spawn_gc_task(&self, watermark: u64, interval: Duration) {
+        let inner = Arc::downgrade(&self.inner);
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let store = match Weak::upgrade(&inner) {
+                    Some(inner) => Store { inner },
+                    None => break,
+                };
+                let res = match store.spawn_blocking(|store| store.stored_size()).await {
+                    Ok(size) if size > watermark => store.gc().await.map(Some),
+                    Ok(_) => Ok(None),
+                    Err(err) => Err(err),
+                };
+                match res {
+                    Ok(Some(stats)) => info!(
+                        "automatic gc removed {} blocks, {} bytes",
+                        stats.blocks, stats.bytes
+                    ),
+                    Ok(None) => {}
+                    Err(err) => warn!("automatic gc failed: {:?}", err),
+                }
+            }
+        });
+    }
+
     pub(crate) async fn spawn_blocking<T: Send + Sync + 'static>(
         &self,
         f: impl FnOnce(Self) -> anyhow::Result<T> + Send + Sync + 'static,
@@ -292,6 +533,7 @@ struct ColumnFamilies<'a> {
     metadata: &'a ColumnFamily,
     graph: &'a ColumnFamily,
     blobs: &'a ColumnFamily,
+    pins: &'a ColumnFamily,
 }
 
 impl<'a> ColumnFamilies<'a> {
@@ -309,10 +551,86 @@ impl<'a> ColumnFamilies<'a> {
             blobs: db
                 .cf_handle(CF_BLOBS_V0)
                 .context("missing column family: blobs")?,
+            pins: db
+                .cf_handle(CF_PINS_V0)
+                .context("missing column family: pins")?,
         })
     }
 }
 
+/// Reads the ids of the direct children of the given id from the graph.
+///
+/// Returns an empty list for blobs that are not stored.
+fn get_child_ids(db: &RocksDb, cf: &ColumnFamilies, id: u64) -> Result<Vec<u64>> {
+    // FIXME: can't use pinned because otherwise this can trigger alignment issues :/
+    match db.get_cf(cf.graph, id.to_be_bytes())? {
+        Some(graph) => {
+            let graph =
+                rkyv::check_archived_root::<GraphV0>(&graph).map_err(|e| anyhow!("{:?}", e))?;
+            Ok(graph.children.iter().copied().collect())
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Reads the direct or recursive pin stored for the given id.
+fn get_stored_pin(db: &RocksDb, cf: &ColumnFamilies, id: u64) -> Result<Option<PinType>> {
+    match db.get_cf(cf.pins, id.to_be_bytes())? {
+        Some(pin) => Ok(Some(decode_pin(&pin)?)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the version of the cid the given id was pinned with directly or recursively,
+/// `V1` if it is only pinned indirectly.
+fn get_pinned_version(db: &RocksDb, cf: &ColumnFamilies, id: u64) -> Result<cid::Version> {
+    if let Some(pin) = db.get_cf(cf.pins, id.to_be_bytes())? {
+        let pin = rkyv::check_archived_root::<PinV0>(&pin).map_err(|e| anyhow!("{:?}", e))?;
+        if pin.v0 {
+            return Ok(cid::Version::V0);
+        }
+    }
+    Ok(cid::Version::V1)
+}
+
+fn decode_pin(bytes: &[u8]) -> Result<PinType> {
+    let pin = rkyv::check_archived_root::<PinV0>(bytes).map_err(|e| anyhow!("{:?}", e))?;
+    if pin.recursive {
+        Ok(PinType::Recursive)
+    } else {
+        Ok(PinType::Direct)
+    }
+}
+
+/// Computes the complete pin set, mapping ids to how they are pinned.
+///
+/// Indirect pins are found by walking the graph from all recursive pins.
+fn get_pin_set(db: &RocksDb, cf: &ColumnFamilies) -> Result<AHashMap<u64, PinType>> {
+    let mut pins = AHashMap::default();
+    let mut stack = Vec::new();
+    for elem in db.iterator_cf(cf.pins, IteratorMode::Start) {
+        let (k, v) = elem?;
+        let id = u64::from_be_bytes(k[..8].try_into()?);
+        let pin_type = decode_pin(&v)?;
+        if pin_type == PinType::Recursive {
+            stack.extend(get_child_ids(db, cf, id)?);
+        }
+        pins.insert(id, pin_type);
+    }
+
+    // a directly pinned block can still be part of a recursively pinned dag, so track the
+    // visited ids separately to make sure its children are visited as well
+    let mut visited = AHashSet::default();
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        pins.entry(id).or_insert(PinType::Indirect);
+        stack.extend(get_child_ids(db, cf, id)?);
+    }
+    Ok(pins)
+}
+
 impl<'a> WriteStore<'a> {
     fn put<T: AsRef<[u8]>, L>(&mut self, cid: Cid, blob: T, links: L) -> Result<()>
     where
@@ -324,7 +642,12 @@ impl<'a> WriteStore<'a> {
             return Ok(());
         }
 
-        let id = self.next_id();
+        // reuse the id if the cid is already known, because other blocks link to it or its
+        // blob was deleted, so that existing graph entries keep pointing to it
+        let id = match self.get_id(&cid)? {
+            Some(id) => id,
+            None => self.next_id(),
+        };
 
         let start = std::time::Instant::now();
 
@@ -371,7 +694,10 @@ impl<'a> WriteStore<'a> {
 
             cid_tracker.insert(cid);
 
-            let id = self.next_id();
+            let id = match self.get_id(&cid)? {
+                Some(id) => id,
+                None => self.next_id(),
+            };
 
             let id_bytes = id.to_be_bytes();
 
@@ -405,6 +731,47 @@ impl<'a> WriteStore<'a> {
         Ok(())
     }
 
+    /// Removes the blob and graph of each cid.
+    ///
+    /// The id and metadata are kept, as other blocks may still link to the cid. This keeps
+    /// the mapping between cids and ids bijective, and a later put of the same cid reuses
+    /// the id.  Ids which are no longer referenced are removed by garbage collection.
+    fn delete_many(&mut self, cids: impl IntoIterator<Item = Cid>) -> Result<Vec<(Cid, u64)>> {
+        let mut pins = None;
+        let mut deleted = Vec::new();
+        let mut seen = AHashSet::default();
+        let mut batch = WriteBatch::default();
+        for cid in cids {
+            let id = match self.get_id(&cid)? {
+                Some(id) => id,
+                None => continue,
+            };
+            if !seen.insert(id) {
+                continue;
+            }
+            let id_bytes = id.to_be_bytes();
+            let size = match self.db.get_pinned_cf(self.cf.blobs, id_bytes)? {
+                Some(blob) => blob.len() as u64,
+                None => continue,
+            };
+
+            // only compute the pin set if there actually is something to delete
+            if pins.is_none() {
+                pins = Some(get_pin_set(self.db, &self.cf)?);
+            }
+            if let Some(pin_type) = pins.as_ref().and_then(|pins| pins.get(&id)) {
+                bail!("cannot delete {}: pinned {}", cid, pin_type);
+            }
+
+            batch.delete_cf(self.cf.blobs, id_bytes);
+            batch.delete_cf(self.cf.graph, id_bytes);
+            deleted.push((cid, size));
+        }
+        self.db.write(batch)?;
+
+        Ok(deleted)
+    }
+
     /// Takes a list of cids and gives them ids, which are both stored and then returned.
     #[tracing::instrument(skip(self, cids))]
     fn ensure_id_many<I>(&mut self, cids: I) -> Result<Vec<u64>>
@@ -437,6 +804,95 @@ impl<'a> WriteStore<'a> {
         Ok(ids)
     }
 
+    fn pin(&mut self, cid: &Cid, recursive: bool) -> Result<()> {
+        let id = match self.get_id(cid)? {
+            Some(id) if self.has_id(id)? => id,
+            // not stored as it links to nothing, there is nothing to protect
+            _ if inline_data(cid).is_some() => return Ok(()),
+            _ => bail!("cannot pin {}: block not found", cid),
+        };
+        if !recursive && get_stored_pin(self.db, &self.cf, id)? == Some(PinType::Recursive) {
+            bail!("{} is already pinned recursively", cid);
+        }
+
+        let pin = PinV0 {
+            recursive,
+            v0: cid.version() == cid::Version::V0,
+        };
+        let pin_bytes = rkyv::to_bytes::<_, 64>(&pin)?;
+        self.db.put_cf(self.cf.pins, id.to_be_bytes(), pin_bytes)?;
+        Ok(())
+    }
+
+    fn unpin(&mut self, cid: &Cid) -> Result<()> {
+        let id = match self.get_id(cid)? {
+            Some(id) => id,
+            None if inline_data(cid).is_some() => return Ok(()),
+            None => bail!("{} is not pinned", cid),
+        };
+        if get_stored_pin(self.db, &self.cf, id)?.is_some() {
+            self.db.delete_cf(self.cf.pins, id.to_be_bytes())?;
+            return Ok(());
+        }
+        if get_pin_set(self.db, &self.cf)?.contains_key(&id) {
+            bail!("{} is pinned indirectly", cid);
+        }
+        bail!("{} is not pinned", cid);
+    }
+
+    /// Mark and sweep garbage collection.
+    ///
+    /// Marks everything reachable from the pin set and removes all other ids from all
+    /// column families.  Holding the write lock guarantees no blocks are added meanwhile.
+    fn gc(&mut self) -> Result<GcStats> {
+        inc!(StoreMetrics::GcRuns);
+        let start = std::time::Instant::now();
+        let live = get_pin_set(self.db, &self.cf)?;
+        // the children of direct pins are not protected, but the graph of the direct pin
+        // still refers to their ids, so the id and metadata must be kept.
+        let mut referenced = AHashSet::default();
+        for (id, pin_type) in &live {
+            if *pin_type == PinType::Direct {
+                referenced.extend(get_child_ids(self.db, &self.cf, *id)?);
+            }
+        }
+
+        let mut stats = GcStats::default();
+        let mut batch = WriteBatch::default();
+        for elem in self.db.iterator_cf(self.cf.metadata, IteratorMode::Start) {
+            let (id_bytes, meta) = elem?;
+            let id = u64::from_be_bytes(id_bytes[..8].try_into()?);
+            if live.contains_key(&id) {
+                continue;
+            }
+
+            let meta =
+                rkyv::check_archived_root::<MetadataV0>(&meta).map_err(|e| anyhow!("{:?}", e))?;
+            let mut id_key: SmallVec<[u8; 64]> = SmallVec::from_slice(&meta.multihash);
+            id_key.extend_from_slice(&meta.codec.to_be_bytes());
+
+            if let Some(blob) = self.db.get_pinned_cf(self.cf.blobs, &id_bytes)? {
+                stats.blocks += 1;
+                stats.bytes += blob.len() as u64;
+            }
+            batch.delete_cf(self.cf.blobs, &id_bytes);
+            batch.delete_cf(self.cf.graph, &id_bytes);
+            if !referenced.contains(&id) {
+                batch.delete_cf(self.cf.id, id_key);
+                batch.delete_cf(self.cf.metadata, &id_bytes);
+            }
+            if batch.len() >= GC_BATCH_SIZE {
+                self.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        self.db.write(batch)?;
+
+        observe!(StoreHistograms::Gc, start.elapsed().as_secs_f64());
+        record!(StoreMetrics::GcRemovedBlocks, stats.blocks);
+        record!(StoreMetrics::GcRemovedBytes, stats.bytes);
+        Ok(stats)
+    }
+
     #[tracing::instrument(skip(self))]
     fn next_id(&mut self) -> u64 {
         let id = *self.next_id;
@@ -463,16 +919,18 @@ impl<'a> WriteStore<'a> {
 
     fn has(&self, cid: &Cid) -> Result<bool> {
         match self.get_id(cid)? {
-            Some(id) => {
-                let exists = self
-                    .db
-                    .get_pinned_cf(self.cf.blobs, id.to_be_bytes())?
-                    .is_some();
-                Ok(exists)
-            }
+            Some(id) => self.has_id(id),
             None => Ok(false),
         }
     }
+
+    fn has_id(&self, id: u64) -> Result<bool> {
+        let exists = self
+            .db
+            .get_pinned_cf(self.cf.blobs, id.to_be_bytes())?
+            .is_some();
+        Ok(exists)
+    }
 }
 
 impl<'a> ReadStore<'a> {
@@ -657,6 +1115,104 @@ impl<'a> ReadStore<'a> {
         }
     }
 
+    fn list_blocks(
+        &self,
+        codec: Option<u64>,
+        hash_prefix: Option<&[u8]>,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<Vec<(Cid, u64)>> {
+        let hash_prefix = hash_prefix.unwrap_or_default();
+        let after = after.map(id_key);
+        let start = match after {
+            Some(ref after) if after.as_slice() > hash_prefix => after.as_slice(),
+            _ => hash_prefix,
+        };
+
+        let mut blocks = Vec::new();
+        let iter = self
+            .db
+            .iterator_cf(self.cf.id, IteratorMode::From(start, Direction::Forward));
+        for elem in iter {
+            if blocks.len() >= limit {
+                break;
+            }
+            let (k, v) = elem?;
+            if !k.starts_with(hash_prefix) {
+                break;
+            }
+            if Some(&*k) == after.as_deref() {
+                continue;
+            }
+            let (hash, code) = k.split_at(k.len() - 8);
+            let code = u64::from_be_bytes(code.try_into()?);
+            if matches!(codec, Some(codec) if codec != code) {
+                continue;
+            }
+            // ids without a blob are only known as links
+            let size = match self.db.get_pinned_cf(self.cf.blobs, &v[..8])? {
+                Some(blob) => blob.len() as u64,
+                None => continue,
+            };
+            let multihash = cid::multihash::Multihash::from_bytes(hash)?;
+            blocks.push((Cid::new_v1(code, multihash), size));
+        }
+        Ok(blocks)
+    }
+
+    fn pin_type(&self, cid: &Cid) -> Result<Option<PinType>> {
+        match self.get_id(cid)? {
+            Some(id) => Ok(get_pin_set(self.db, &self.cf)?.remove(&id)),
+            None => Ok(None),
+        }
+    }
+
+    fn list_pins(&self, pin_type: Option<PinType>) -> Result<Vec<(Cid, PinType)>> {
+        let mut pins = Vec::new();
+        for (id, typ) in get_pin_set(self.db, &self.cf)? {
+            if matches!(pin_type, Some(t) if t != typ) {
+                continue;
+            }
+            let version = get_pinned_version(self.db, &self.cf, id)?;
+            let cid = self
+                .get_cid_by_id(id, version)?
+                .ok_or_else(|| anyhow!("missing metadata for pinned id {}", id))?;
+            pins.push((cid, typ));
+        }
+        Ok(pins)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn get_cid_by_id(&self, id: u64, version: cid::Version) -> Result<Option<Cid>> {
+        // FIXME: can't use pinned because otherwise this can trigger alignment issues :/
+        match self.db.get_cf(self.cf.metadata, id.to_be_bytes())? {
+            Some(meta) => {
+                let meta = rkyv::check_archived_root::<MetadataV0>(&meta)
+                    .map_err(|e| anyhow!("{:?}", e))?;
+                let multihash = cid::multihash::Multihash::from_bytes(&meta.multihash)?;
+                Ok(Some(Cid::new(version, meta.codec, multihash)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn stored_size(&self) -> Result<u64> {
+        let cf = &self.cf;
+        let mut size = 0;
+        for cf in [cf.id, cf.metadata, cf.graph, cf.blobs, cf.pins] {
+            for property in [
+                "rocksdb.total-sst-files-size",
+                "rocksdb.total-blob-file-size",
+            ] {
+                size += self
+                    .db
+                    .property_int_value_cf(cf, property)?
+                    .unwrap_or_default();
+            }
+        }
+        Ok(size)
+    }
+
     /// Perform an internal consistency check on the store, and return all internal errors found.
     fn consistency_check(&self) -> anyhow::Result<Vec<String>> {
         let mut res = Vec::new();
@@ -797,6 +1353,40 @@ mod tests {
         Ok((store, dir))
     }
 
+    #[tokio::test]
+    async fn test_inline_cid() -> anyhow::Result<()> {
+        let hash = Multihash::wrap(iroh_util::IDENTITY_HASH_CODE, b"hello")?;
+        let cid = Cid::new_v1(RAW, hash);
+
+        // never stored, but still available
+        let (store, _dir) = test_store().await?;
+        assert!(store.has(&cid)?);
+        assert_eq!(store.get_size(&cid)?, Some(5));
+        assert_eq!(&store.get(&cid)?.unwrap()[..], b"hello");
+
+        // writing it does not store anything, pinning it is a no-op
+        store.put(cid, b"hello", [])?;
+        store.put_many([(cid, Bytes::from_static(b"hello"), Vec::new())])?;
+        assert!(store.list_blocks(None, None, None, 100)?.is_empty());
+        store.pin(&cid, true)?;
+        assert!(store.list_pins(None)?.is_empty());
+        store.unpin(&cid)?;
+
+        // unless it links to other blocks, which its pins have to protect
+        let data = b"linked".to_vec();
+        let link = Cid::new_v1(RAW, Code::Sha2_256.digest(&data));
+        store.put(link, &data, [])?;
+        let parent = Cid::new_v1(
+            DAG_CBOR,
+            Multihash::wrap(iroh_util::IDENTITY_HASH_CODE, b"parent")?,
+        );
+        store.put(parent, b"parent", [link])?;
+        store.pin(&parent, true)?;
+        store.gc().await?;
+        assert!(store.has(&link)?);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_multiple_cids_same_hash() -> anyhow::Result<()> {
         let link1 = Cid::from_str("bafybeib4tddkl4oalrhe7q66rrz5dcpz4qwv5lmpstuqrls3djikw566y4")?;
@@ -919,4 +1509,204 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_delete() -> anyhow::Result<()> {
+        let link1 = Cid::from_str("bafybeib4tddkl4oalrhe7q66rrz5dcpz4qwv5lmpstuqrls3djikw566y4")?;
+        let link2 = Cid::from_str("QmcBphfXUFUNLcfAm31WEqYjrjEh19G5x4iAQANSK151DD")?;
+        let data = libipld::ipld!({
+            "link1": link1,
+            "link2": link2,
+        });
+        let mut blob = Vec::new();
+        data.encode(IpldCodec::DagCbor, &mut blob)?;
+        let hash = Code::Sha2_256.digest(&blob);
+        let raw_cid = Cid::new_v1(IpldCodec::Raw.into(), hash);
+        let cbor_cid = Cid::new_v1(IpldCodec::DagCbor.into(), hash);
+        let parent = Cid::new_v1(DAG_CBOR, Code::Sha2_256.digest(b"parent"));
+
+        let (store, _dir) = test_store().await?;
+        store.put(raw_cid, &blob, vec![])?;
+        store.put(cbor_cid, &blob, vec![link1, link2])?;
+        store.put(parent, b"parent", vec![cbor_cid])?;
+
+        // deleting one cid keeps the other cid with the same multihash
+        assert_eq!(store.delete(&cbor_cid)?, Some(blob.len() as u64));
+        assert!(!store.has(&cbor_cid)?);
+        assert_eq!(store.get_links(&cbor_cid)?, None);
+        assert!(store.has(&raw_cid)?);
+        assert!(store.has_blob_for_hash(&hash)?);
+        assert_eq!(store.get_ids_for_hash(&hash)?.count(), 2);
+        // links to the deleted block stay intact
+        assert_eq!(store.get_links(&parent)?.unwrap(), vec![cbor_cid]);
+        assert_eq!(store.delete(&cbor_cid)?, None);
+
+        // putting the block again reuses its id
+        store.put(cbor_cid, &blob, vec![link1, link2])?;
+        assert_eq!(store.get_ids_for_hash(&hash)?.count(), 2);
+        assert_eq!(store.get_links(&cbor_cid)?.unwrap().len(), 2);
+        assert_eq!(Vec::<String>::new(), store.consistency_check()?);
+
+        // pinned blocks can't be deleted, and a failed batch deletes nothing
+        store.pin(&parent, true)?;
+        assert!(store.delete_many([raw_cid, cbor_cid]).is_err());
+        assert!(store.has(&raw_cid)?);
+        store.unpin(&parent)?;
+
+        let deleted = store.delete_many([raw_cid, cbor_cid, raw_cid, link1])?;
+        assert_eq!(
+            deleted,
+            vec![(raw_cid, blob.len() as u64), (cbor_cid, blob.len() as u64)]
+        );
+        assert!(!store.has_blob_for_hash(&hash)?);
+        assert_eq!(Vec::<String>::new(), store.consistency_check()?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_blocks() -> anyhow::Result<()> {
+        let (store, _dir) = test_store().await?;
+        let mut expected = Vec::new();
+        for i in 0..10u8 {
+            let data = vec![i; i as usize + 1];
+            let codec = if i % 2 == 0 { RAW } else { DAG_CBOR };
+            let cid = Cid::new_v1(codec, Code::Sha2_256.digest(&data));
+            // the link is never stored, so it must not be listed
+            let link = Cid::new_v1(RAW, Code::Sha2_256.digest(&[i, i]));
+            store.put(cid, &data, vec![link])?;
+            expected.push((cid, data.len() as u64));
+        }
+        expected.sort_by_key(|(cid, _)| cid.hash().to_bytes());
+
+        assert_eq!(store.list_blocks(None, None, None, 100)?, expected);
+
+        // paginate using the last listed block as cursor
+        let first = store.list_blocks(None, None, None, 4)?;
+        assert_eq!(first, expected[..4]);
+        let rest = store.list_blocks(None, None, Some(&first[3].0), 100)?;
+        assert_eq!(rest, expected[4..]);
+
+        let raw = store.list_blocks(Some(RAW), None, None, 100)?;
+        assert_eq!(raw.len(), 5);
+        assert!(raw.iter().all(|(cid, _)| cid.codec() == RAW));
+
+        let hash = expected[0].0.hash().to_bytes();
+        let prefixed = store.list_blocks(None, Some(&hash[..4]), None, 100)?;
+        assert!(prefixed.contains(&expected[0]));
+        assert!(prefixed
+            .iter()
+            .all(|(cid, _)| cid.hash().to_bytes().starts_with(&hash[..4])));
+        assert_eq!(
+            store.list_blocks(None, Some(&hash), None, 100)?,
+            expected[..1]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pins() -> anyhow::Result<()> {
+        let leaf = Cid::new_v1(RAW, Code::Sha2_256.digest(b"leaf"));
+        let branch = Cid::new_v1(DAG_CBOR, Code::Sha2_256.digest(b"branch"));
+        let root = Cid::new_v1(DAG_CBOR, Code::Sha2_256.digest(b"root"));
+        let other = Cid::new_v1(RAW, Code::Sha2_256.digest(b"other"));
+
+        let (store, _dir) = test_store().await?;
+        store.put(leaf, b"leaf", vec![])?;
+        store.put(branch, b"branch", vec![leaf])?;
+        store.put(root, b"root", vec![branch])?;
+        store.put(other, b"other", vec![])?;
+
+        // blocks that are not stored can't be pinned
+        let missing = Cid::new_v1(RAW, Code::Sha2_256.digest(b"missing"));
+        assert!(store.pin(&missing, false).is_err());
+
+        store.pin(&root, true)?;
+        store.pin(&other, false)?;
+        assert_eq!(store.pin_type(&root)?, Some(PinType::Recursive));
+        assert_eq!(store.pin_type(&branch)?, Some(PinType::Indirect));
+        assert_eq!(store.pin_type(&leaf)?, Some(PinType::Indirect));
+        assert_eq!(store.pin_type(&other)?, Some(PinType::Direct));
+
+        // a direct pin inside a recursive dag still protects its children indirectly
+        store.pin(&branch, false)?;
+        assert_eq!(store.pin_type(&branch)?, Some(PinType::Direct));
+        assert_eq!(store.pin_type(&leaf)?, Some(PinType::Indirect));
+        assert!(store.pin(&root, false).is_err());
+
+        let mut pins = store.list_pins(Some(PinType::Direct))?;
+        pins.sort();
+        let mut expected = vec![(branch, PinType::Direct), (other, PinType::Direct)];
+        expected.sort();
+        assert_eq!(pins, expected);
+        assert_eq!(store.list_pins(None)?.len(), 4);
+
+        assert!(store.unpin(&leaf).is_err());
+        store.unpin(&root)?;
+        store.unpin(&branch)?;
+        assert_eq!(store.pin_type(&root)?, None);
+        assert_eq!(store.pin_type(&leaf)?, None);
+        assert!(store.unpin(&root).is_err());
+        assert_eq!(store.list_pins(None)?, vec![(other, PinType::Direct)]);
+
+        // pins are listed with the cid version they were made with
+        store.unpin(&other)?;
+        let v0 = Cid::new_v0(Code::Sha2_256.digest(b"v0"))?;
+        store.put(v0, b"v0", vec![])?;
+        store.pin(&v0, false)?;
+        assert_eq!(store.list_pins(None)?, vec![(v0, PinType::Direct)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gc() -> anyhow::Result<()> {
+        let leaf = Cid::new_v1(RAW, Code::Sha2_256.digest(b"leaf"));
+        let root = Cid::new_v1(DAG_CBOR, Code::Sha2_256.digest(b"root"));
+        let direct = Cid::new_v1(RAW, Code::Sha2_256.digest(b"direct"));
+        let garbage_leaf = Cid::new_v1(RAW, Code::Sha2_256.digest(b"garbage leaf"));
+        let garbage = Cid::new_v1(DAG_CBOR, Code::Sha2_256.digest(b"garbage"));
+        // only referenced, never stored
+        let missing = Cid::new_v1(RAW, Code::Sha2_256.digest(b"missing"));
+
+        let (store, _dir) = test_store().await?;
+        store.put(leaf, b"leaf", vec![])?;
+        store.put(root, b"root", vec![leaf, missing])?;
+        store.put(direct, b"direct", vec![garbage_leaf])?;
+        store.put(garbage_leaf, b"garbage leaf", vec![])?;
+        store.put(garbage, b"garbage", vec![leaf, garbage_leaf])?;
+        store.pin(&root, true)?;
+        store.pin(&direct, false)?;
+
+        let stats = store.gc().await?;
+        assert_eq!(
+            stats,
+            GcStats {
+                blocks: 2,
+                bytes: (b"garbage leaf".len() + b"garbage".len()) as u64,
+            }
+        );
+        assert!(store.has(&root)?);
+        assert!(store.has(&leaf)?);
+        assert!(store.has(&direct)?);
+        assert!(!store.has(&garbage)?);
+        assert!(!store.has(&garbage_leaf)?);
+        assert_eq!(store.get_links(&root)?.unwrap(), vec![leaf, missing]);
+        assert_eq!(store.get_links(&direct)?.unwrap(), vec![garbage_leaf]);
+        assert_eq!(store.get_links(&garbage)?, None);
+        assert_eq!(Vec::<String>::new(), store.consistency_check()?);
+
+        // nothing left to collect
+        assert_eq!(store.gc().await?, GcStats::default());
+
+        store.unpin(&root)?;
+        store.unpin(&direct)?;
+        let stats = store.gc().await?;
+        assert_eq!(stats.blocks, 3);
+        assert!(!store.has(&root)?);
+        assert_eq!(Vec::<String>::new(), store.consistency_check()?);
+
+        Ok(())
+    }
 }
@@ -1,24 +1,88 @@
 use std::collections::VecDeque;
+use std::fmt::Display;
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Context, Result};
 use async_stream::try_stream;
 use bytes::Bytes;
 use cid::Cid;
-use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
+use futures::{future::Either, Stream, StreamExt, TryFutureExt, TryStreamExt};
 
 use crate::builder::encode_unixfs_pb;
+use crate::trickle_tree::stream_trickle_tree;
+pub use crate::trickle_tree::DEFAULT_DEPTH_REPEAT;
 use crate::types::Block;
-use crate::unixfs::{dag_pb, unixfs_pb, DataType, Node, UnixfsNode};
+use crate::unixfs::{dag_pb, unixfs_pb, CidBuilder, DataType, Node, UnixfsNode};
 
 /// Default degree number for balanced tree, taken from unixfs specs
 /// <https://github.com/ipfs/specs/blob/main/UNIXFS.md#layout>
 pub const DEFAULT_DEGREE: usize = 174;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TreeBuilder {
     /// TreeBuilder that builds a "balanced tree" with a max degree size of
     /// degree
     Balanced { degree: usize },
+    /// TreeBuilder that builds a "trickle dag" with at most `max_links` leaves per
+    /// node and `depth_repeat` subtrees of each depth, like `ipfs add --trickle`
+    Trickle {
+        max_links: usize,
+        depth_repeat: usize,
+    },
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        Self::balanced_tree()
+    }
+}
+
+impl Display for TreeBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Balanced { degree } => write!(f, "balanced-{degree}"),
+            Self::Trickle {
+                max_links,
+                depth_repeat,
+            } => write!(f, "trickle-{max_links}-{depth_repeat}"),
+        }
+    }
+}
+
+impl FromStr for TreeBuilder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("balanced") {
+            if rest.is_empty() {
+                return Ok(TreeBuilder::balanced_tree());
+            }
+
+            if let Some(rest) = rest.strip_prefix('-') {
+                let degree: usize = rest.parse().context("invalid degree")?;
+                ensure!(degree > 1, "degree must be larger than 1");
+                return Ok(TreeBuilder::balanced_tree_with_degree(degree));
+            }
+        }
+
+        if let Some(rest) = s.strip_prefix("trickle") {
+            if rest.is_empty() {
+                return Ok(TreeBuilder::trickle());
+            }
+
+            if let Some((max_links, depth_repeat)) =
+                rest.strip_prefix('-').and_then(|rest| rest.split_once('-'))
+            {
+                let max_links: usize = max_links.parse().context("invalid max links")?;
+                let depth_repeat: usize = depth_repeat.parse().context("invalid depth repeat")?;
+                ensure!(max_links > 0, "max links must be larger than 0");
+                ensure!(depth_repeat > 0, "depth repeat must be larger than 0");
+                return Ok(TreeBuilder::trickle_with_params(max_links, depth_repeat));
+            }
+        }
+
+        Err(anyhow!("unknown layout: {}", s))
+    }
 }
 
 impl TreeBuilder {
@@ -31,18 +95,53 @@ impl TreeBuilder {
         TreeBuilder::Balanced { degree }
     }
 
+    /// A trickle dag with the same parameters as kubo.
+    pub fn trickle() -> Self {
+        Self::trickle_with_params(DEFAULT_DEGREE, DEFAULT_DEPTH_REPEAT)
+    }
+
+    pub fn trickle_with_params(max_links: usize, depth_repeat: usize) -> Self {
+        assert!(max_links > 0);
+        assert!(depth_repeat > 0);
+        TreeBuilder::Trickle {
+            max_links,
+            depth_repeat,
+        }
+    }
+
+    /// Streams the blocks of the tree, the root comes last.
+    ///
+    /// Leaves are raw blocks if `raw_leaves` is set, and dag-pb file nodes otherwise, or
+    /// dag-pb raw nodes for the trickle layout.
     pub fn stream_tree(
         &self,
         chunks: impl Stream<Item = std::io::Result<Bytes>> + Send,
+        cid_builder: CidBuilder,
+        raw_leaves: bool,
     ) -> impl Stream<Item = Result<Block>> {
-        match self {
-            TreeBuilder::Balanced { degree } => stream_balanced_tree(chunks, *degree),
+        match *self {
+            TreeBuilder::Balanced { degree } => Either::Left(stream_balanced_tree(
+                chunks,
+                degree,
+                cid_builder,
+                raw_leaves,
+            )),
+            TreeBuilder::Trickle {
+                max_links,
+                depth_repeat,
+            } => Either::Right(stream_trickle_tree(
+                chunks,
+                max_links,
+                depth_repeat,
+                cid_builder,
+                raw_leaves,
+            )),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-struct LinkInfo {
+pub(crate) struct LinkInfo {
     raw_data_len: u64,
     encoded_len: u64,
 }
@@ -50,6 +149,8 @@ struct LinkInfo {
 fn stream_balanced_tree(
     in_stream: impl Stream<Item = std::io::Result<Bytes>> + Send,
     degree: usize,
+    cid_builder: CidBuilder,
+    raw_leaves: bool,
 ) -> impl Stream<Item = Result<Block>> {
     try_stream! {
         // degree = 8
@@ -80,8 +181,8 @@ fn stream_balanced_tree(
         let hash_par: usize = 8;
 
         let in_stream = in_stream.err_into::<anyhow::Error>().map(|chunk| {
-            tokio::task::spawn_blocking(|| {
-                chunk.and_then(|chunk| TreeNode::Leaf(chunk).encode())
+            tokio::task::spawn_blocking(move || {
+                chunk.and_then(|chunk| TreeNode::leaf(chunk, raw_leaves).encode(&cid_builder))
             }).err_into::<anyhow::Error>()
         }).buffered(hash_par).map(|x| x.and_then(|x| x));
 
@@ -108,7 +209,7 @@ fn stream_balanced_tree(
 
                     // create node, keeping the cid
                     let links = std::mem::replace(&mut tree[i], Vec::with_capacity(degree));
-                    let (block, link_info) = TreeNode::Stem(links).encode()?;
+                    let (block, link_info) = TreeNode::Stem(links).encode(&cid_builder)?;
                     let cid = *block.cid();
                     yield block;
 
@@ -133,11 +234,18 @@ fn stream_balanced_tree(
             return
         }
 
+        // our stream was empty, like kubo encode a single empty leaf
+        if tree.len() == 1 && tree[0].is_empty() {
+            let (block, _) = TreeNode::leaf(Bytes::new(), raw_leaves).encode(&cid_builder)?;
+            yield block;
+            return
+        }
+
         // clean up, aka yield the rest of the stem nodes
         // since all the stem nodes are able to recieve links
         // we don't have to worry about "overflow"
         while let Some(links) = tree.pop_front() {
-            let (block, link_info) = TreeNode::Stem(links).encode()?;
+            let (block, link_info) = TreeNode::Stem(links).encode(&cid_builder)?;
             let cid = *block.cid();
             yield block;
 
@@ -157,9 +265,9 @@ fn create_unixfs_node_from_links(links: Vec<(Cid, LinkInfo)>) -> Result<UnixfsNo
         .into_iter()
         .map(|(cid, l)| dag_pb::PbLink {
             hash: Some(cid.to_bytes()),
-            /// ALL "stem" nodes have `name: None`.
-            /// In kubo, nodes that have links to `leaf` nodes have `name: Some("".to_string())`
-            name: None,
+            /// Like in kubo, all links of "stem" nodes have an empty name, which is
+            /// encoded and so part of the cid.
+            name: Some(String::new()),
             /// tsize has no strict definition
             /// Iroh's definiton of `tsize` is "the cumulative size of the encoded tree
             /// pointed to by this link", so not just the size of the raw content, but including
@@ -190,19 +298,39 @@ fn create_unixfs_node_from_links(links: Vec<(Cid, LinkInfo)>) -> Result<UnixfsNo
 
 // Leaf and Stem nodes are the two types of nodes that can exist in the tree
 // Leaf nodes encode to `UnixfsNode::Raw`
+// PbLeaf nodes encode to `UnixfsNode::File` or `UnixfsNode::RawNode` without links, like
+// kubo without raw leaves
 // Stem nodes encode to `UnixfsNode::File`
-enum TreeNode {
+pub(crate) enum TreeNode {
     Leaf(Bytes),
+    PbLeaf(Bytes, DataType),
     Stem(Vec<(Cid, LinkInfo)>),
 }
 
 impl TreeNode {
-    fn encode(self) -> Result<(Block, LinkInfo)> {
+    pub(crate) fn leaf(bytes: Bytes, raw_leaves: bool) -> Self {
+        if raw_leaves {
+            TreeNode::Leaf(bytes)
+        } else {
+            TreeNode::PbLeaf(bytes, DataType::File)
+        }
+    }
+
+    /// Kubo uses the unixfs `Raw` type for the leaves of trickle dags, instead of `File`.
+    pub(crate) fn trickle_leaf(bytes: Bytes, raw_leaves: bool) -> Self {
+        if raw_leaves {
+            TreeNode::Leaf(bytes)
+        } else {
+            TreeNode::PbLeaf(bytes, DataType::Raw)
+        }
+    }
+
+    pub(crate) fn encode(self, cid_builder: &CidBuilder) -> Result<(Block, LinkInfo)> {
         match self {
             TreeNode::Leaf(bytes) => {
                 let len = bytes.len();
                 let node = UnixfsNode::Raw(bytes);
-                let block = node.encode()?;
+                let block = node.encode(cid_builder)?;
                 let link_info = LinkInfo {
                     // in a leaf the raw data len and encoded len are the same since our leaf
                     // nodes are raw unixfs nodes
@@ -211,10 +339,32 @@ impl TreeNode {
                 };
                 Ok((block, link_info))
             }
+            TreeNode::PbLeaf(bytes, typ) => {
+                let len = bytes.len() as u64;
+                let inner = unixfs_pb::Data {
+                    r#type: typ as i32,
+                    // empty data is left out, as in kubo
+                    data: if bytes.is_empty() { None } else { Some(bytes) },
+                    filesize: Some(len),
+                    ..Default::default()
+                };
+                let outer = encode_unixfs_pb(&inner, Vec::new())?;
+                let node = Node { inner, outer };
+                let node = match typ {
+                    DataType::Raw => UnixfsNode::RawNode(node),
+                    _ => UnixfsNode::File(node),
+                };
+                let block = node.encode(cid_builder)?;
+                let link_info = LinkInfo {
+                    raw_data_len: len,
+                    encoded_len: block.data().len() as u64,
+                };
+                Ok((block, link_info))
+            }
             TreeNode::Stem(links) => {
                 let mut encoded_len: u64 = links.iter().map(|(_, l)| l.encoded_len).sum();
                 let node = create_unixfs_node_from_links(links)?;
-                let block = node.encode()?;
+                let block = node.encode(cid_builder)?;
                 encoded_len += block.data().len() as u64;
                 let raw_data_len = node
                     .filesize()
@@ -253,7 +403,7 @@ mod tests {
         if num_chunks / degree == 0 {
             let chunk = chunks.next().await.unwrap().unwrap();
             let leaf = TreeNode::Leaf(chunk);
-            let (block, _) = leaf.encode().unwrap();
+            let (block, _) = leaf.encode(&CidBuilder::default()).unwrap();
             tree[0].push(block);
             return tree;
         }
@@ -261,7 +411,7 @@ mod tests {
         while let Some(chunk) = chunks.next().await {
             let chunk = chunk.unwrap();
             let leaf = TreeNode::Leaf(chunk);
-            let (block, link_info) = leaf.encode().unwrap();
+            let (block, link_info) = leaf.encode(&CidBuilder::default()).unwrap();
             links[0].push((*block.cid(), link_info));
             tree[0].push(block);
         }
@@ -273,7 +423,7 @@ mod tests {
             let mut links_layer = Vec::with_capacity(count);
             for links in prev_layer.chunks(degree) {
                 let stem = TreeNode::Stem(links.to_vec());
-                let (block, link_info) = stem.encode().unwrap();
+                let (block, link_info) = stem.encode(&CidBuilder::default()).unwrap();
                 links_layer.push((*block.cid(), link_info));
                 tree_layer.push(block);
             }
@@ -337,12 +487,14 @@ mod tests {
 
     fn make_leaf(data: usize) -> (Block, LinkInfo) {
         TreeNode::Leaf(BytesMut::from(&data.to_be_bytes()[..]).freeze())
-            .encode()
+            .encode(&CidBuilder::default())
             .unwrap()
     }
 
     fn make_stem(links: Vec<(Cid, LinkInfo)>) -> (Block, LinkInfo) {
-        TreeNode::Stem(links).encode().unwrap()
+        TreeNode::Stem(links)
+            .encode(&CidBuilder::default())
+            .unwrap()
     }
 
     #[tokio::test]
@@ -450,7 +602,7 @@ mod tests {
     async fn balanced_tree_test_leaf() {
         let num_chunks = 1;
         let expect = build_expect(num_chunks, 3).await;
-        let got = stream_balanced_tree(test_chunk_stream(1), 3);
+        let got = stream_balanced_tree(test_chunk_stream(1), 3, CidBuilder::default(), true);
         tokio::pin!(got);
         ensure_equal(expect, got, num_chunks as u64 * CHUNK_SIZE).await;
     }
@@ -460,7 +612,12 @@ mod tests {
         let num_chunks = 3;
         let degrees = 3;
         let expect = build_expect(num_chunks, degrees).await;
-        let got = stream_balanced_tree(test_chunk_stream(num_chunks), degrees);
+        let got = stream_balanced_tree(
+            test_chunk_stream(num_chunks),
+            degrees,
+            CidBuilder::default(),
+            true,
+        );
         tokio::pin!(got);
         ensure_equal(expect, got, num_chunks as u64 * CHUNK_SIZE).await;
     }
@@ -470,7 +627,12 @@ mod tests {
         let degrees = 3;
         let num_chunks = 9;
         let expect = build_expect(num_chunks, degrees).await;
-        let got = stream_balanced_tree(test_chunk_stream(num_chunks), degrees);
+        let got = stream_balanced_tree(
+            test_chunk_stream(num_chunks),
+            degrees,
+            CidBuilder::default(),
+            true,
+        );
         tokio::pin!(got);
         ensure_equal(expect, got, num_chunks as u64 * CHUNK_SIZE).await;
     }
@@ -480,7 +642,12 @@ mod tests {
         let degrees = 3;
         let num_chunks = 10;
         let expect = build_expect(num_chunks, degrees).await;
-        let got = stream_balanced_tree(test_chunk_stream(num_chunks), degrees);
+        let got = stream_balanced_tree(
+            test_chunk_stream(num_chunks),
+            degrees,
+            CidBuilder::default(),
+            true,
+        );
         tokio::pin!(got);
         ensure_equal(expect, got, num_chunks as u64 * CHUNK_SIZE).await;
     }
@@ -490,7 +657,12 @@ mod tests {
         let num_chunks = 125;
         let degrees = 5;
         let expect = build_expect(num_chunks, degrees).await;
-        let got = stream_balanced_tree(test_chunk_stream(num_chunks), degrees);
+        let got = stream_balanced_tree(
+            test_chunk_stream(num_chunks),
+            degrees,
+            CidBuilder::default(),
+            true,
+        );
         tokio::pin!(got);
         ensure_equal(expect, got, num_chunks as u64 * CHUNK_SIZE).await;
     }
@@ -500,8 +672,42 @@ mod tests {
         let num_chunks = 780;
         let degrees = 11;
         let expect = build_expect(num_chunks, degrees).await;
-        let got = stream_balanced_tree(test_chunk_stream(num_chunks), degrees);
+        let got = stream_balanced_tree(
+            test_chunk_stream(num_chunks),
+            degrees,
+            CidBuilder::default(),
+            true,
+        );
         tokio::pin!(got);
         ensure_equal(expect, got, num_chunks as u64 * CHUNK_SIZE).await;
     }
+
+    #[test]
+    fn test_tree_builder_from_str() {
+        assert_eq!(
+            "balanced".parse::<TreeBuilder>().unwrap(),
+            TreeBuilder::balanced_tree()
+        );
+        assert_eq!(
+            "balanced-11".parse::<TreeBuilder>().unwrap(),
+            TreeBuilder::balanced_tree_with_degree(11)
+        );
+        assert_eq!(
+            "trickle".parse::<TreeBuilder>().unwrap(),
+            TreeBuilder::trickle()
+        );
+        assert_eq!(
+            "trickle-10-2".parse::<TreeBuilder>().unwrap(),
+            TreeBuilder::trickle_with_params(10, 2)
+        );
+        for builder in [TreeBuilder::balanced_tree(), TreeBuilder::trickle()] {
+            assert_eq!(builder.to_string().parse::<TreeBuilder>().unwrap(), builder);
+        }
+
+        assert!("balanced-1".parse::<TreeBuilder>().is_err());
+        assert!("balanced-".parse::<TreeBuilder>().is_err());
+        assert!("trickle-10".parse::<TreeBuilder>().is_err());
+        assert!("trickle-10-0".parse::<TreeBuilder>().is_err());
+        assert!("foo".parse::<TreeBuilder>().is_err());
+    }
 }
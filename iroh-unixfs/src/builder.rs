@@ -1,43 +1,121 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     fmt::Debug,
     path::{Path, PathBuf},
     pin::Pin,
+    time::SystemTime,
 };
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use async_recursion::async_recursion;
 use bytes::Bytes;
+use cid::{multihash::Code, Version};
 use futures::{
     stream::{self, BoxStream},
-    Stream, StreamExt, TryFutureExt,
+    Stream, StreamExt,
 };
 use prost::Message;
 use tokio::io::AsyncRead;
 
 use crate::{
-    balanced_tree::{TreeBuilder, DEFAULT_DEGREE},
+    balanced_tree::TreeBuilder,
     chunker::{self, Chunker, ChunkerConfig, DEFAULT_CHUNK_SIZE_LIMIT},
     hamt::{bitfield::Bitfield, bits, hash_key},
-    types::Block,
-    unixfs::{dag_pb, unixfs_pb, DataType, HamtHashFunction, Node, UnixfsNode},
+    types::{Block, Link},
+    unixfs::{
+        dag_pb, to_unix_time, unixfs_pb, CidBuilder, DataType, HamtHashFunction, Node, UnixfsNode,
+    },
 };
 
-// The maximum number of links we allow in a directory
-// Any more links than this and we should switch to a hamt
-// calculation comes from:
-// (hash_length + max_file_name_len + tsize_len )/ block_size
-// (64 bytes + 256 bytes + 8 bytes) / 2 MB ≈ 6400
-// adding a generous buffer, we are using 6k as our link limit
-const DIRECTORY_LINK_LIMIT: usize = 6000;
+// Directories whose estimated size reaches this many bytes are sharded into a hamt.
+// The estimate is the sum of the entry name and cid lengths, which together with the
+// threshold matches kubo's default `Internal.UnixFSShardingSizeThreshold` of 256KiB.
+const HAMT_SHARDING_SIZE: usize = 256 * 1024;
+
+// Fanout of the hamt directories we write, the same as kubo.
+const HAMT_FANOUT: u32 = 256;
 
 #[derive(Debug, PartialEq)]
 enum DirectoryType {
+    /// A flat directory, which is sharded when it gets too large.
     Basic,
-    // TODO: writing hamt sharding not yet implemented
+    /// Always a hamt sharded directory.
     Hamt,
 }
 
+/// The optional UnixFS 1.5 metadata of a file, directory or symlink.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Attributes {
+    mode: Option<u32>,
+    mtime: Option<SystemTime>,
+}
+
+impl Attributes {
+    /// Reads the attributes of the file at `path`, only keeping the requested ones.
+    async fn from_path(path: &Path, preserve_mode: bool, preserve_mtime: bool) -> Result<Self> {
+        if !preserve_mode && !preserve_mtime {
+            return Ok(Attributes::default());
+        }
+        let metadata = tokio::fs::symlink_metadata(path).await?;
+        let mode = if preserve_mode && !metadata.is_symlink() {
+            file_mode(&metadata)
+        } else {
+            None
+        };
+        let mtime = if preserve_mtime {
+            Some(metadata.modified()?)
+        } else {
+            None
+        };
+        Ok(Attributes { mode, mtime })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.mtime.is_none()
+    }
+
+    fn apply(&self, inner: &mut unixfs_pb::Data) {
+        inner.mode = self.mode;
+        inner.mtime = self.mtime.map(to_unix_time);
+    }
+
+    /// Re-encodes the root block of a file with these attributes.
+    ///
+    /// A raw block can not carry any metadata, like kubo it is turned into a file node
+    /// holding the data.
+    fn apply_to_file_root(&self, root: Block, cid_builder: &CidBuilder) -> Result<Block> {
+        let (mut inner, links) = match UnixfsNode::decode(root.cid(), root.data().clone())? {
+            UnixfsNode::Raw(data) => {
+                let inner = unixfs_pb::Data {
+                    r#type: DataType::File as i32,
+                    filesize: Some(data.len() as u64),
+                    data: Some(data),
+                    ..Default::default()
+                };
+                (inner, Vec::new())
+            }
+            UnixfsNode::File(node) | UnixfsNode::RawNode(node) => (node.inner, node.outer.links),
+            _ => bail!("unexpected root block of a file: {}", root.cid()),
+        };
+        inner.r#type = DataType::File as i32;
+        self.apply(&mut inner);
+        let outer = encode_unixfs_pb(&inner, links)?;
+        UnixfsNode::File(Node { outer, inner }).encode(cid_builder)
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    // only the permission bits, including setuid, setgid and sticky
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
 /// Representation of a constructed Directory.
 #[derive(Debug, PartialEq)]
 pub enum Directory {
@@ -50,22 +128,37 @@ pub enum Directory {
 pub struct BasicDirectory {
     name: String,
     entries: Vec<Entry>,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
 }
 
 /// A hamt sharded directory
 #[derive(Debug, PartialEq)]
 pub struct HamtDirectory {
     name: String,
-    hamt: Box<HamtNode>,
+    entries: Vec<Entry>,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
 }
 
 impl Directory {
     fn single(name: String, entry: Entry) -> Self {
-        Directory::basic(name, vec![entry])
+        let cid_builder = entry.cid_builder();
+        Directory::Basic(BasicDirectory {
+            name,
+            entries: vec![entry],
+            attributes: Default::default(),
+            cid_builder,
+        })
     }
 
     pub fn basic(name: String, entries: Vec<Entry>) -> Self {
-        Directory::Basic(BasicDirectory { name, entries })
+        Directory::Basic(BasicDirectory {
+            name,
+            entries,
+            attributes: Default::default(),
+            cid_builder: Default::default(),
+        })
     }
 
     pub fn name(&self) -> &str {
@@ -75,6 +168,14 @@ impl Directory {
         }
     }
 
+    /// The cid version and hash function used for the directory node(s).
+    pub fn cid_builder(&self) -> CidBuilder {
+        match self {
+            Directory::Basic(BasicDirectory { cid_builder, .. }) => *cid_builder,
+            Directory::Hamt(HamtDirectory { cid_builder, .. }) => *cid_builder,
+        }
+    }
+
     pub fn set_name(&mut self, value: String) {
         match self {
             Directory::Basic(BasicDirectory { name, .. }) => {
@@ -113,44 +214,80 @@ impl Directory {
 }
 
 impl BasicDirectory {
+    /// Encodes the directory, sharding it into a hamt if it is too large.
     pub fn encode<'a>(self) -> BoxStream<'a, Result<Block>> {
-        async_stream::try_stream! {
-            let mut links = Vec::new();
-            for entry in self.entries {
-                let name = entry.name().to_string();
-                let parts = entry.encode().await?;
-                tokio::pin!(parts);
-                let mut root = None;
-                while let Some(part) = parts.next().await {
-                    let block = part?;
-                    root = Some(block.clone());
-                    yield block;
-                }
-                let root_block = root.expect("file must not be empty");
+        encode_directory(self.entries, self.attributes, self.cid_builder, false)
+    }
+}
+
+impl HamtDirectory {
+    pub fn encode<'a>(self) -> BoxStream<'a, Result<Block>> {
+        encode_directory(self.entries, self.attributes, self.cid_builder, true)
+    }
+}
+
+/// Encodes the entries of a directory, followed by the directory node(s) themselves.
+fn encode_directory<'a>(
+    entries: Vec<Entry>,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
+    hamt: bool,
+) -> BoxStream<'a, Result<Block>> {
+    async_stream::try_stream! {
+        let mut links = Vec::with_capacity(entries.len());
+        let mut estimated_size = 0;
+        for entry in entries {
+            let name = entry.name().to_string();
+            if let Entry::Link(link) = entry {
+                // already encoded and stored, only link to it
+                let root = link.cid.to_bytes();
+                estimated_size += name.len() + root.len();
                 links.push(dag_pb::PbLink {
-                    hash: Some(root_block.cid().to_bytes()),
+                    hash: Some(root),
                     name: Some(name),
-                    tsize: Some(root_block.data().len() as u64),
+                    tsize: link.tsize,
                 });
+                continue;
+            }
+            let parts = entry.encode().await?;
+            tokio::pin!(parts);
+            let mut root = None;
+            // the cumulative size of all blocks of the entry, like kubo
+            let mut tsize = 0;
+            while let Some(part) = parts.next().await {
+                let block = part?;
+                tsize += block.data().len() as u64;
+                root = Some(*block.cid());
+                yield block;
             }
+            let root = root.expect("file must not be empty").to_bytes();
+            estimated_size += name.len() + root.len();
+            links.push(dag_pb::PbLink {
+                hash: Some(root),
+                name: Some(name),
+                tsize: Some(tsize),
+            });
+        }
 
-            // directory itself comes last
-            let inner = unixfs_pb::Data {
+        // directory itself comes last
+        if hamt || estimated_size >= HAMT_SHARDING_SIZE {
+            let hamt = HamtNode::new(links)
+                .context("unable to build hamt. Probably a hash collision.")?;
+            for block in hamt.encode_root(attributes, &cid_builder)? {
+                yield block;
+            }
+        } else {
+            let mut inner = unixfs_pb::Data {
                 r#type: DataType::Directory as i32,
                 ..Default::default()
             };
+            attributes.apply(&mut inner);
             let outer = encode_unixfs_pb(&inner, links)?;
             let node = UnixfsNode::Directory(Node { outer, inner });
-            yield node.encode()?;
+            yield node.encode(&cid_builder)?;
         }
-        .boxed()
-    }
-}
-
-impl HamtDirectory {
-    pub fn encode<'a>(self) -> BoxStream<'a, Result<Block>> {
-        self.hamt.encode()
     }
+    .boxed()
 }
 
 enum Content {
@@ -184,6 +321,9 @@ pub struct File {
     content: Content,
     tree_builder: TreeBuilder,
     chunker: Chunker,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
+    raw_leaves: bool,
 }
 
 impl Debug for File {
@@ -193,6 +333,9 @@ impl Debug for File {
             .field("content", &self.content)
             .field("tree_builder", &self.tree_builder)
             .field("chunker", &self.chunker)
+            .field("attributes", &self.attributes)
+            .field("cid_builder", &self.cid_builder)
+            .field("raw_leaves", &self.raw_leaves)
             .finish()
     }
 }
@@ -228,7 +371,28 @@ impl File {
             Content::Reader(reader) => reader,
         };
         let chunks = self.chunker.chunks(reader);
-        Ok(self.tree_builder.stream_tree(chunks))
+        let cid_builder = self.cid_builder;
+        let blocks = self
+            .tree_builder
+            .stream_tree(chunks, cid_builder, self.raw_leaves);
+        let attributes = self.attributes;
+        Ok(async_stream::try_stream! {
+            tokio::pin!(blocks);
+            // hold back the last block, which is the root
+            let mut last = None;
+            while let Some(block) = blocks.next().await {
+                if let Some(block) = last.replace(block?) {
+                    yield block;
+                }
+            }
+            if let Some(root) = last {
+                if attributes.is_empty() {
+                    yield root;
+                } else {
+                    yield attributes.apply_to_file_root(root, &cid_builder)?;
+                }
+            }
+        })
     }
 }
 
@@ -237,6 +401,8 @@ impl File {
 pub struct Symlink {
     name: String,
     target: PathBuf,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
 }
 
 impl Symlink {
@@ -249,6 +415,8 @@ impl Symlink {
                 .unwrap_or_default()
                 .to_string(),
             target: target.into(),
+            attributes: Default::default(),
+            cid_builder: Default::default(),
         }
     }
 
@@ -266,14 +434,15 @@ impl Symlink {
             .to_str()
             .ok_or_else(|| anyhow::anyhow!("target path {:?} is not valid unicode", self.target))?;
         let target = String::from(target);
-        let inner = unixfs_pb::Data {
+        let mut inner = unixfs_pb::Data {
             r#type: DataType::Symlink as i32,
             data: Some(Bytes::from(target)),
             ..Default::default()
         };
+        self.attributes.apply(&mut inner);
         let outer = encode_unixfs_pb(&inner, Vec::new())?;
         let node = UnixfsNode::Symlink(Node { outer, inner });
-        node.encode()
+        node.encode(&self.cid_builder)
     }
 }
 
@@ -283,7 +452,10 @@ pub struct FileBuilder {
     path: Option<PathBuf>,
     reader: Option<Pin<Box<dyn AsyncRead + Send>>>,
     chunker: Chunker,
-    degree: usize,
+    tree_builder: TreeBuilder,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
+    raw_leaves: bool,
 }
 
 impl Default for FileBuilder {
@@ -293,7 +465,10 @@ impl Default for FileBuilder {
             path: None,
             reader: None,
             chunker: Chunker::Fixed(chunker::Fixed::default()),
-            degree: DEFAULT_DEGREE,
+            tree_builder: TreeBuilder::balanced_tree(),
+            attributes: Default::default(),
+            cid_builder: Default::default(),
+            raw_leaves: true,
         }
     }
 }
@@ -309,8 +484,11 @@ impl Debug for FileBuilder {
             .field("path", &self.path)
             .field("name", &self.name)
             .field("chunker", &self.chunker)
-            .field("degree", &self.degree)
+            .field("tree_builder", &self.tree_builder)
             .field("reader", &reader)
+            .field("attributes", &self.attributes)
+            .field("cid_builder", &self.cid_builder)
+            .field("raw_leaves", &self.raw_leaves)
             .finish()
     }
 }
@@ -349,7 +527,40 @@ impl FileBuilder {
     }
 
     pub fn degree(mut self, degree: usize) -> Self {
-        self.degree = degree;
+        self.tree_builder = TreeBuilder::balanced_tree_with_degree(degree);
+        self
+    }
+
+    /// Set the layout of the DAG, a balanced tree with the default degree by default.
+    pub fn tree_builder(mut self, tree_builder: TreeBuilder) -> Self {
+        self.tree_builder = tree_builder;
+        self
+    }
+
+    /// Set the unix permission bits of the file.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.attributes.mode = Some(mode);
+        self
+    }
+
+    /// Set the modification time of the file.
+    pub fn mtime(mut self, mtime: SystemTime) -> Self {
+        self.attributes.mtime = Some(mtime);
+        self
+    }
+
+    /// Set the cid version and hash function of the blocks, CIDv1 with sha2-256 by default.
+    pub fn cid_builder(mut self, cid_builder: CidBuilder) -> Self {
+        self.cid_builder = cid_builder;
+        self
+    }
+
+    /// Store the chunks as raw blocks, or wrap them in unixfs file nodes like kubo does
+    /// without `--raw-leaves`. Raw leaves are used by default.
+    ///
+    /// CIDv0 can only address dag-pb blocks, raw leaves get a CIDv1 like in kubo.
+    pub fn raw_leaves(mut self, raw_leaves: bool) -> Self {
+        self.raw_leaves = raw_leaves;
         self
     }
 
@@ -365,9 +576,11 @@ impl FileBuilder {
     }
 
     pub async fn build(self) -> Result<File> {
-        let degree = self.degree;
         let chunker = self.chunker;
-        let tree_builder = TreeBuilder::balanced_tree_with_degree(degree);
+        let attributes = self.attributes;
+        let tree_builder = self.tree_builder;
+        let cid_builder = self.cid_builder;
+        let raw_leaves = self.raw_leaves;
         if let Some(path) = self.path {
             let name = match self.name {
                 Some(n) => n,
@@ -382,6 +595,9 @@ impl FileBuilder {
                 name,
                 chunker,
                 tree_builder,
+                attributes,
+                cid_builder,
+                raw_leaves,
             });
         }
 
@@ -395,6 +611,9 @@ impl FileBuilder {
                 name,
                 chunker,
                 tree_builder,
+                attributes,
+                cid_builder,
+                raw_leaves,
             });
         }
         anyhow::bail!("must have a path to the content or a reader for the content");
@@ -408,6 +627,8 @@ pub enum Entry {
     File(File),
     Directory(Directory),
     Symlink(Symlink),
+    /// An existing file, directory or symlink, whose blocks are not encoded again.
+    Link(Link),
 }
 
 impl Entry {
@@ -416,6 +637,17 @@ impl Entry {
             Entry::File(f) => f.name(),
             Entry::Directory(d) => d.name(),
             Entry::Symlink(s) => s.name(),
+            Entry::Link(l) => l.name.as_deref().unwrap_or_default(),
+        }
+    }
+
+    /// The cid version and hash function used for the blocks of this entry.
+    pub fn cid_builder(&self) -> CidBuilder {
+        match self {
+            Entry::File(f) => f.cid_builder,
+            Entry::Directory(d) => d.cid_builder(),
+            Entry::Symlink(s) => s.cid_builder,
+            Entry::Link(_) => Default::default(),
         }
     }
 
@@ -424,16 +656,26 @@ impl Entry {
             Entry::File(f) => f.encode().await?.boxed(),
             Entry::Directory(d) => d.encode(),
             Entry::Symlink(s) => stream::iter(Some(s.encode())).boxed(),
+            Entry::Link(_) => stream::empty().boxed(),
         })
     }
 
     pub async fn from_path(path: &Path, config: Config) -> Result<Self> {
+        let mut cid_builder = CidBuilder::new(config.cid_version, config.hash)?;
+        if let Some(limit) = config.inline_limit {
+            cid_builder = cid_builder.inline(limit)?;
+        }
         let entry = if path.is_dir() {
             if let Some(chunker_config) = config.chunker {
                 let chunker = chunker_config.into();
                 let dir = DirectoryBuilder::new()
                     .chunker(chunker)
+                    .tree_builder(config.layout.unwrap_or_default())
+                    .cid_builder(cid_builder)
+                    .raw_leaves(config.raw_leaves)
                     .path(path)
+                    .preserve_mode(config.preserve_mode)
+                    .preserve_mtime(config.preserve_mtime)
                     .build()
                     .await?;
                 Entry::Directory(dir)
@@ -443,18 +685,26 @@ impl Entry {
         } else if path.is_file() {
             if let Some(chunker_config) = config.chunker {
                 let chunker = chunker_config.into();
-                let file = FileBuilder::new()
+                let mut file = FileBuilder::new()
                     .chunker(chunker)
-                    .path(path)
-                    .build()
-                    .await?;
-                Entry::File(file)
+                    .tree_builder(config.layout.unwrap_or_default())
+                    .cid_builder(cid_builder)
+                    .raw_leaves(config.raw_leaves)
+                    .path(path);
+                file.attributes =
+                    Attributes::from_path(path, config.preserve_mode, config.preserve_mtime)
+                        .await?;
+                Entry::File(file.build().await?)
             } else {
                 anyhow::bail!("expected a ChunkerConfig in the Config");
             }
         } else if path.is_symlink() {
-            let symlink = SymlinkBuilder::new(path).build().await?;
-            Entry::Symlink(symlink)
+            let mut symlink = SymlinkBuilder::new(path);
+            symlink.cid_builder(cid_builder);
+            if config.preserve_mtime {
+                symlink.mtime = Some(tokio::fs::symlink_metadata(path).await?.modified()?);
+            }
+            Entry::Symlink(symlink.build().await?)
         } else {
             anyhow::bail!("can only add files, directories, or symlinks");
         };
@@ -469,6 +719,7 @@ impl Entry {
             Entry::File(f) => f.wrap(),
             Entry::Directory(d) => d.wrap(),
             Entry::Symlink(s) => s.wrap(),
+            Entry::Link(l) => Directory::single("".into(), Entry::Link(l)),
         }
     }
 }
@@ -480,8 +731,13 @@ pub struct DirectoryBuilder {
     entries: Vec<Entry>,
     typ: DirectoryType,
     chunker: Chunker,
-    degree: usize,
+    tree_builder: TreeBuilder,
     path: Option<PathBuf>,
+    attributes: Attributes,
+    cid_builder: CidBuilder,
+    raw_leaves: bool,
+    preserve_mode: bool,
+    preserve_mtime: bool,
 }
 
 impl Default for DirectoryBuilder {
@@ -491,8 +747,13 @@ impl Default for DirectoryBuilder {
             entries: Default::default(),
             typ: DirectoryType::Basic,
             chunker: Chunker::Fixed(chunker::Fixed::default()),
-            degree: DEFAULT_DEGREE,
+            tree_builder: TreeBuilder::balanced_tree(),
             path: None,
+            attributes: Default::default(),
+            cid_builder: Default::default(),
+            raw_leaves: true,
+            preserve_mode: false,
+            preserve_mtime: false,
         }
     }
 }
@@ -523,7 +784,51 @@ impl DirectoryBuilder {
     }
 
     pub fn degree(mut self, degree: usize) -> Self {
-        self.degree = degree;
+        self.tree_builder = TreeBuilder::balanced_tree_with_degree(degree);
+        self
+    }
+
+    /// Set the layout of the DAGs of the files read from [`DirectoryBuilder::path`].
+    pub fn tree_builder(mut self, tree_builder: TreeBuilder) -> Self {
+        self.tree_builder = tree_builder;
+        self
+    }
+
+    /// Set the unix permission bits of the directory.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.attributes.mode = Some(mode);
+        self
+    }
+
+    /// Set the modification time of the directory.
+    pub fn mtime(mut self, mtime: SystemTime) -> Self {
+        self.attributes.mtime = Some(mtime);
+        self
+    }
+
+    /// Set the cid version and hash function of the directory, and of everything read from
+    /// [`DirectoryBuilder::path`].
+    pub fn cid_builder(mut self, cid_builder: CidBuilder) -> Self {
+        self.cid_builder = cid_builder;
+        self
+    }
+
+    /// Whether the files read from [`DirectoryBuilder::path`] use raw leaves, see
+    /// [`FileBuilder::raw_leaves`].
+    pub fn raw_leaves(mut self, raw_leaves: bool) -> Self {
+        self.raw_leaves = raw_leaves;
+        self
+    }
+
+    /// Store the permission bits of everything read from [`DirectoryBuilder::path`].
+    pub fn preserve_mode(mut self, preserve_mode: bool) -> Self {
+        self.preserve_mode = preserve_mode;
+        self
+    }
+
+    /// Store the modification times of everything read from [`DirectoryBuilder::path`].
+    pub fn preserve_mtime(mut self, preserve_mtime: bool) -> Self {
+        self.preserve_mtime = preserve_mtime;
         self
     }
 
@@ -539,10 +844,15 @@ impl DirectoryBuilder {
         self.entry(Entry::Symlink(symlink))
     }
 
+    /// Adds an existing entry by its [`Link`], the link must have a name.
+    ///
+    /// The blocks of the entry are not part of the encoded directory, they must already be
+    /// available.
+    pub fn add_link(self, link: Link) -> Self {
+        self.entry(Entry::Link(link))
+    }
+
     fn entry(mut self, entry: Entry) -> Self {
-        if self.typ == DirectoryType::Basic && self.entries.len() >= DIRECTORY_LINK_LIMIT {
-            self.typ = DirectoryType::Hamt
-        }
         self.entries.push(entry);
         self
     }
@@ -554,11 +864,24 @@ impl DirectoryBuilder {
             typ,
             path,
             chunker,
-            degree,
+            tree_builder,
+            attributes,
+            cid_builder,
+            raw_leaves,
+            preserve_mode,
+            preserve_mtime,
         } = self;
 
         Ok(if let Some(path) = path {
-            let mut dir = make_dir_from_path(path, chunker.clone(), degree).await?;
+            let options = ImportOptions {
+                chunker,
+                tree_builder,
+                cid_builder,
+                raw_leaves,
+                preserve_mode,
+                preserve_mtime,
+            };
+            let mut dir = make_dir_from_path(path, &options).await?;
             if let Some(name) = name {
                 dir.set_name(name);
             }
@@ -566,13 +889,23 @@ impl DirectoryBuilder {
         } else {
             let name = name.unwrap_or_default();
             match typ {
-                DirectoryType::Basic => Directory::Basic(BasicDirectory { name, entries }),
+                DirectoryType::Basic => Directory::Basic(BasicDirectory {
+                    name,
+                    entries,
+                    attributes,
+                    cid_builder,
+                }),
                 DirectoryType::Hamt => {
-                    let hamt = HamtNode::new(entries)
-                        .context("unable to build hamt. Probably a hash collision.")?;
+                    let mut names = HashSet::with_capacity(entries.len());
+                    ensure!(
+                        entries.iter().all(|entry| names.insert(entry.name())),
+                        "unable to build hamt: duplicate entry names"
+                    );
                     Directory::Hamt(HamtDirectory {
                         name,
-                        hamt: Box::new(hamt),
+                        entries,
+                        attributes,
+                        cid_builder,
                     })
                 }
             }
@@ -582,9 +915,9 @@ impl DirectoryBuilder {
 
 /// A leaf when building a hamt directory.
 ///
-/// Basically just an entry and the hash of its name.
+/// Basically just the link to an entry and the hash of its name.
 #[derive(Debug, PartialEq)]
-pub struct HamtLeaf([u8; 8], Entry);
+struct HamtLeaf([u8; 8], dag_pb::PbLink);
 
 /// A node when building a hamt directory.
 ///
@@ -597,17 +930,16 @@ enum HamtNode {
 }
 
 impl HamtNode {
-    fn new(entries: Vec<Entry>) -> anyhow::Result<HamtNode> {
+    fn new(links: Vec<dag_pb::PbLink>) -> anyhow::Result<HamtNode> {
         // add the hash
-        let entries = entries
+        let leafs = links
             .into_iter()
-            .map(|entry| {
-                let name = entry.name().to_string();
-                let hash = hash_key(name.as_bytes());
-                HamtLeaf(hash, entry)
+            .map(|link| {
+                let hash = hash_key(link.name.as_deref().unwrap_or_default().as_bytes());
+                HamtLeaf(hash, link)
             })
             .collect::<Vec<_>>();
-        Self::group(entries, 0, 8)
+        Self::group(leafs, 0, 8)
     }
 
     fn group(leafs: Vec<HamtLeaf>, pos: u32, len: u32) -> anyhow::Result<HamtNode> {
@@ -630,54 +962,58 @@ impl HamtNode {
         })
     }
 
-    fn name(&self) -> &str {
-        match self {
-            HamtNode::Branch(_) => "",
-            HamtNode::Leaf(HamtLeaf(_, entry)) => entry.name(),
-        }
+    /// Encodes all shards of the hamt, the root shard comes last and carries the
+    /// `attributes` of the directory.
+    fn encode_root(self, attributes: Attributes, cid_builder: &CidBuilder) -> Result<Vec<Block>> {
+        let mut blocks = Vec::new();
+        self.encode(0, attributes, cid_builder, &mut blocks)?;
+        Ok(blocks)
     }
 
-    pub fn encode<'a>(self) -> BoxStream<'a, Result<Block>> {
+    /// Encodes the shards of this node into `blocks` and returns the link to it from
+    /// its parent, which has it at position `prefix`.
+    fn encode(
+        self,
+        prefix: u32,
+        attributes: Attributes,
+        cid_builder: &CidBuilder,
+        blocks: &mut Vec<Block>,
+    ) -> Result<dag_pb::PbLink> {
         match self {
             Self::Branch(tree) => {
-                async_stream::try_stream! {
-                    let mut links = Vec::with_capacity(tree.len());
-                    let mut bitfield = Bitfield::default();
-                    for (prefix, node) in tree {
-                        let name = format!("{:02X}{}", prefix, node.name());
-                        bitfield.set_bit(prefix);
-                        let blocks = node.encode();
-                        let mut root = None;
-                        tokio::pin!(blocks);
-                        while let Some(block) = blocks.next().await {
-                            let block = block?;
-                            root = Some(*block.cid());
-                            yield block;
-                        }
-                        links.push(crate::unixfs::dag_pb::PbLink {
-                            name: Some(name),
-                            hash: root.map(|cid| cid.to_bytes()),
-                            tsize: None,
-                        });
-                    }
-                    let inner = unixfs_pb::Data {
-                        r#type: DataType::HamtShard as i32,
-                        hash_type: Some(HamtHashFunction::Murmur3 as u64),
-                        fanout: Some(256),
-                        data: Some(bitfield.as_bytes().to_vec().into()),
-                        ..Default::default()
-                    };
-                    let outer = encode_unixfs_pb(&inner, links).unwrap();
-                    // it does not really matter what enum variant we choose here as long as
-                    // it is not raw. The type of the node will be HamtShard from above.
-                    let node = UnixfsNode::Directory(crate::unixfs::Node { outer, inner });
-                    yield node.encode()?;
+                let mut links = Vec::with_capacity(tree.len());
+                let mut bitfield = Bitfield::default();
+                for (prefix, node) in tree {
+                    bitfield.set_bit(prefix);
+                    links.push(node.encode(prefix, Attributes::default(), cid_builder, blocks)?);
                 }
-                .boxed()
+                let tsize: u64 = links.iter().filter_map(|link| link.tsize).sum();
+                let mut inner = unixfs_pb::Data {
+                    r#type: DataType::HamtShard as i32,
+                    hash_type: Some(HamtHashFunction::Murmur3 as u64),
+                    fanout: Some(HAMT_FANOUT as u64),
+                    data: Some(bitfield.as_bytes().into()),
+                    ..Default::default()
+                };
+                attributes.apply(&mut inner);
+                let outer = encode_unixfs_pb(&inner, links)?;
+                // it does not really matter what enum variant we choose here as long as
+                // it is not raw. The type of the node will be HamtShard from above.
+                let node = UnixfsNode::Directory(crate::unixfs::Node { outer, inner });
+                let block = node.encode(cid_builder)?;
+                let link = dag_pb::PbLink {
+                    hash: Some(block.cid().to_bytes()),
+                    name: Some(format!("{:02X}", prefix)),
+                    tsize: Some(tsize + block.data().len() as u64),
+                };
+                blocks.push(block);
+                Ok(link)
+            }
+            Self::Leaf(HamtLeaf(_hash, mut link)) => {
+                let name = link.name.take().unwrap_or_default();
+                link.name = Some(format!("{:02X}{}", prefix, name));
+                Ok(link)
             }
-            Self::Leaf(HamtLeaf(_hash, entry)) => async move { entry.encode().await }
-                .try_flatten_stream()
-                .boxed(),
         }
     }
 }
@@ -687,6 +1023,8 @@ impl HamtNode {
 pub struct SymlinkBuilder {
     path: PathBuf,
     target: Option<PathBuf>,
+    mtime: Option<SystemTime>,
+    cid_builder: CidBuilder,
 }
 
 impl SymlinkBuilder {
@@ -694,6 +1032,8 @@ impl SymlinkBuilder {
         Self {
             path: path.into(),
             target: None,
+            mtime: None,
+            cid_builder: Default::default(),
         }
     }
 
@@ -702,6 +1042,18 @@ impl SymlinkBuilder {
         self
     }
 
+    /// Set the modification time of the symlink.
+    pub fn mtime(&mut self, mtime: SystemTime) -> &mut Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Set the cid version and hash function of the symlink node.
+    pub fn cid_builder(&mut self, cid_builder: CidBuilder) -> &mut Self {
+        self.cid_builder = cid_builder;
+        self
+    }
+
     pub async fn build(self) -> Result<Symlink> {
         let name = self
             .path
@@ -713,7 +1065,16 @@ impl SymlinkBuilder {
             Some(target) => target,
             None => tokio::fs::read_link(&self.path).await?,
         };
-        Ok(Symlink { name, target })
+        let attributes = Attributes {
+            mode: None,
+            mtime: self.mtime,
+        };
+        Ok(Symlink {
+            name,
+            target,
+            attributes,
+            cid_builder: self.cid_builder,
+        })
     }
 }
 
@@ -740,37 +1101,70 @@ pub struct Config {
     /// Should the outer object be wrapped in a directory?
     pub wrap: bool,
     pub chunker: Option<ChunkerConfig>,
+    /// The layout of the file DAGs, defaults to a balanced tree.
+    pub layout: Option<TreeBuilder>,
+    /// Store the unix permission bits of the added files and directories.
+    pub preserve_mode: bool,
+    /// Store the modification times of the added files, directories and symlinks.
+    pub preserve_mtime: bool,
+    /// The cid version of the added blocks.
+    pub cid_version: Version,
+    /// The hash function used for the cids of the added blocks.
+    pub hash: Code,
+    /// Store file chunks as raw blocks, which always get a CIDv1.
+    pub raw_leaves: bool,
+    /// Inline blocks of up to this many bytes into their cid.
+    pub inline_limit: Option<usize>,
+}
+
+/// The options of [`DirectoryBuilder`] that apply to everything read from a path.
+#[derive(Debug)]
+struct ImportOptions {
+    chunker: Chunker,
+    tree_builder: TreeBuilder,
+    cid_builder: CidBuilder,
+    raw_leaves: bool,
+    preserve_mode: bool,
+    preserve_mtime: bool,
 }
 
 #[async_recursion(?Send)]
 async fn make_dir_from_path<P: Into<PathBuf>>(
     path: P,
-    chunker: Chunker,
-    degree: usize,
+    options: &ImportOptions,
 ) -> Result<Directory> {
     let path = path.into();
-    let mut dir = DirectoryBuilder::new().name(
-        path.file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or_default(),
-    );
+    let mut dir = DirectoryBuilder::new()
+        .name(
+            path.file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default(),
+        )
+        .cid_builder(options.cid_builder);
+    dir.attributes =
+        Attributes::from_path(&path, options.preserve_mode, options.preserve_mtime).await?;
 
     let mut directory_reader = tokio::fs::read_dir(path.clone()).await?;
     while let Some(entry) = directory_reader.next_entry().await? {
         let path = entry.path();
+        let attributes =
+            Attributes::from_path(&path, options.preserve_mode, options.preserve_mtime).await?;
         if path.is_symlink() {
-            let s = SymlinkBuilder::new(path).build().await?;
-            dir = dir.add_symlink(s);
+            let mut s = SymlinkBuilder::new(path);
+            s.mtime = attributes.mtime;
+            s.cid_builder(options.cid_builder);
+            dir = dir.add_symlink(s.build().await?);
         } else if path.is_file() {
-            let f = FileBuilder::new()
-                .chunker(chunker.clone())
-                .degree(degree)
-                .path(path)
-                .build()
-                .await?;
-            dir = dir.add_file(f);
+            let mut f = FileBuilder::new()
+                .chunker(options.chunker.clone())
+                .tree_builder(options.tree_builder)
+                .cid_builder(options.cid_builder)
+                .raw_leaves(options.raw_leaves)
+                .path(path);
+            f.attributes = attributes;
+            dir = dir.add_file(f.build().await?);
         } else if path.is_dir() {
-            let d = make_dir_from_path(path, chunker.clone(), degree).await?;
+            let d = make_dir_from_path(path, options).await?;
             dir = dir.add_dir(d)?;
         } else {
             anyhow::bail!("directory entry is neither file nor directory")
@@ -783,7 +1177,10 @@ async fn make_dir_from_path<P: Into<PathBuf>>(
 mod tests {
     use super::*;
     use crate::chunker::DEFAULT_CHUNKS_SIZE;
+    use crate::unixfs::{DEFAULT_INLINE_LIMIT, MAX_INLINE_LIMIT};
+    use cid::Cid;
     use futures::TryStreamExt;
+    use std::collections::HashMap;
     use std::io::Write;
 
     #[tokio::test]
@@ -843,6 +1240,35 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_builder_links() -> Result<()> {
+        let bar = FileBuilder::new()
+            .name("bar.txt")
+            .content_bytes(b"bar".to_vec())
+            .build()
+            .await?
+            .encode_root()
+            .await?;
+        let link = Link {
+            cid: *bar.cid(),
+            name: Some("bar.txt".to_string()),
+            tsize: Some(3),
+        };
+
+        let dir = DirectoryBuilder::new().add_link(link).build().await?;
+        let blocks: Vec<_> = dir.encode().try_collect().await?;
+        // only the directory itself is encoded
+        assert_eq!(blocks.len(), 1);
+
+        let decoded_dir = UnixfsNode::decode(blocks[0].cid(), blocks[0].data().clone())?;
+        let links = decoded_dir.links().collect::<Result<Vec<_>>>()?;
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].name, Some("bar.txt"));
+        assert_eq!(links[0].cid, *bar.cid());
+        assert_eq!(links[0].tsize, Some(3));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_builder_stream_small() -> Result<()> {
         // Create a directory
@@ -983,35 +1409,87 @@ mod tests {
         Ok(())
     }
 
-    #[tokio::test]
-    async fn test_hamt_detection() -> Result<()> {
-        // allow hamt override
-        let builder = DirectoryBuilder::new().hamt();
-        assert_eq!(DirectoryType::Hamt, builder.typ);
-
-        let mut builder = DirectoryBuilder::new();
-
-        for _i in 0..DIRECTORY_LINK_LIMIT {
+    /// Builds a directory of `count` small files with 200 byte long names.
+    async fn long_names_dir(mut builder: DirectoryBuilder, count: usize) -> Result<Directory> {
+        for i in 0..count {
             let file = FileBuilder::new()
-                .name("foo.txt")
+                .name(format!("{:0>200}", i))
                 .content_bytes(Bytes::from("hello world"))
                 .build()
                 .await?;
             builder = builder.add_file(file);
         }
+        builder.build().await
+    }
+
+    /// Walks the hamt rooted at `cid`, returning the entry names and checking they are in
+    /// the right buckets.
+    fn hamt_entries(blocks: &HashMap<Cid, Bytes>, cid: &Cid, depth: u32) -> Vec<String> {
+        let node = UnixfsNode::decode(cid, blocks[cid].clone()).unwrap();
+        match node {
+            UnixfsNode::HamtShard(ref shard, _) => assert_eq!(shard.fanout(), Some(HAMT_FANOUT)),
+            _ => panic!("expected a hamt shard"),
+        }
+        let mut names = Vec::new();
+        for link in node.links() {
+            let link = link.unwrap();
+            let name = link.name.unwrap();
+            if name.len() == 2 {
+                names.extend(hamt_entries(blocks, &link.cid, depth + 1));
+            } else {
+                let (prefix, name) = name.split_at(2);
+                let bucket = bits(&hash_key(name.as_bytes()), depth * 8, 8).unwrap();
+                assert_eq!(prefix, format!("{:02X}", bucket));
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
 
-        // under DIRECTORY_LINK_LIMIT should still be a basic directory
-        assert_eq!(DirectoryType::Basic, builder.typ);
+    #[tokio::test]
+    async fn test_hamt_detection() -> Result<()> {
+        // allow hamt override
+        let builder = DirectoryBuilder::new().hamt();
+        assert_eq!(DirectoryType::Hamt, builder.typ);
 
         let file = FileBuilder::new()
             .name("foo.txt")
             .content_bytes(Bytes::from("hello world"))
             .build()
             .await?;
-        builder = builder.add_file(file);
-
-        // at directory link limit should be processed as a hamt
-        assert_eq!(DirectoryType::Hamt, builder.typ);
+        let cid_len = file.encode_root().await?.cid().to_bytes().len();
+        let limit = HAMT_SHARDING_SIZE / (200 + cid_len);
+
+        // under the sharding threshold should still be a basic directory
+        let dir = long_names_dir(DirectoryBuilder::new(), limit).await?;
+        assert!(matches!(dir, Directory::Basic(_)));
+        let root = dir.encode_root().await?;
+        let node = UnixfsNode::decode(root.cid(), root.data().clone())?;
+        assert_eq!(node.typ(), Some(DataType::Directory));
+        assert_eq!(node.links().count(), limit);
+
+        // at the sharding threshold should be encoded as a hamt
+        let dir = long_names_dir(DirectoryBuilder::new(), limit + 1).await?;
+        let blocks: Vec<_> = dir.encode().try_collect().await?;
+        let root = blocks.last().unwrap().clone();
+        let total_size: u64 = blocks[..blocks.len() - 1]
+            .iter()
+            .map(|block| block.data().len() as u64)
+            .sum();
+        let blocks: HashMap<_, _> = blocks
+            .into_iter()
+            .map(|block| (*block.cid(), block.data().clone()))
+            .collect();
+
+        let mut names = hamt_entries(&blocks, root.cid(), 0);
+        names.sort();
+        let expected: Vec<_> = (0..limit + 1).map(|i| format!("{:0>200}", i)).collect();
+        assert_eq!(names, expected);
+
+        // tsize is the cumulative size of everything below the root
+        let node = UnixfsNode::decode(root.cid(), root.data().clone())?;
+        let tsize: u64 = node.links().map(|l| l.unwrap().tsize.unwrap()).sum();
+        assert_eq!(tsize, total_size);
         Ok(())
     }
 
@@ -1031,6 +1509,180 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_cid_builder() -> Result<()> {
+        async fn add(content: &'static [u8], cid_builder: CidBuilder, raw_leaves: bool) -> String {
+            let file = FileBuilder::new()
+                .name("hello.txt")
+                .content_bytes(content)
+                .cid_builder(cid_builder)
+                .raw_leaves(raw_leaves)
+                .build()
+                .await
+                .unwrap();
+            file.encode_root().await.unwrap().cid().to_string()
+        }
+
+        // cids from kubo
+        assert_eq!(
+            add(b"hello world\n", CidBuilder::v0(), false).await,
+            "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+        );
+        // raw leaves are CIDv1 even for v0, a single chunk is the root itself
+        assert_eq!(
+            add(b"hello world\n", CidBuilder::v0(), true).await,
+            "bafkreifjjcie6lypi6ny7amxnfftagclbuxndqonfipmb64f2km2devei4"
+        );
+        assert_eq!(
+            add(b"hello world\n", CidBuilder::default(), true).await,
+            "bafkreifjjcie6lypi6ny7amxnfftagclbuxndqonfipmb64f2km2devei4"
+        );
+        assert_eq!(
+            add(b"", CidBuilder::v0(), false).await,
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"
+        );
+        assert_eq!(
+            add(b"", CidBuilder::default(), true).await,
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+
+        let dir = DirectoryBuilder::new()
+            .cid_builder(CidBuilder::v0())
+            .build()
+            .await?;
+        assert_eq!(
+            dir.encode_root().await?.cid().to_string(),
+            "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+        );
+        let dir = DirectoryBuilder::new().build().await?;
+        assert_eq!(
+            dir.encode_root().await?.cid().to_string(),
+            "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354"
+        );
+
+        let cid_builder = CidBuilder::new(Version::V1, Code::Blake3_256)?;
+        let block = FileBuilder::new()
+            .name("hello.txt")
+            .content_bytes(&b"hello world\n"[..])
+            .cid_builder(cid_builder)
+            .build()
+            .await?
+            .encode_root()
+            .await?;
+        assert_eq!(block.cid().hash().code(), u64::from(Code::Blake3_256));
+        block.validate()?;
+
+        assert!(CidBuilder::new(Version::V0, Code::Blake3_256).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline() -> Result<()> {
+        let cid_builder = CidBuilder::default().inline(DEFAULT_INLINE_LIMIT)?;
+        let small = FileBuilder::new()
+            .name("small.txt")
+            .content_bytes(&b"hello"[..])
+            .cid_builder(cid_builder)
+            .build()
+            .await?;
+        let large = FileBuilder::new()
+            .name("large.txt")
+            .content_bytes(vec![1u8; DEFAULT_INLINE_LIMIT + 1])
+            .cid_builder(cid_builder)
+            .build()
+            .await?;
+        let dir = DirectoryBuilder::new()
+            .cid_builder(cid_builder)
+            .add_file(small)
+            .add_file(large)
+            .build()
+            .await?;
+        let blocks: Vec<_> = dir.encode().try_collect().await?;
+        assert_eq!(blocks.len(), 3);
+
+        let small = &blocks[0];
+        assert_eq!(small.cid().hash().code(), iroh_util::IDENTITY_HASH_CODE);
+        assert_eq!(iroh_util::inline_data(small.cid()), Some(&b"hello"[..]));
+        small.validate()?;
+
+        let large = &blocks[1];
+        assert_eq!(large.cid().hash().code(), u64::from(Code::Sha2_256));
+
+        // the directory node is too large to be inlined
+        let root = &blocks[2];
+        assert_eq!(root.cid().hash().code(), u64::from(Code::Sha2_256));
+        assert_eq!(root.links(), &[*small.cid(), *large.cid()]);
+
+        // an empty directory is small enough to be inlined
+        let empty = DirectoryBuilder::new()
+            .cid_builder(cid_builder)
+            .build()
+            .await?
+            .encode_root()
+            .await?;
+        assert_eq!(empty.cid().hash().code(), iroh_util::IDENTITY_HASH_CODE);
+
+        assert!(CidBuilder::default().inline(MAX_INLINE_LIMIT + 1).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mode_mtime() -> Result<()> {
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::new(1_600_000_000, 42);
+        let decode = |block: &Block| UnixfsNode::decode(block.cid(), block.data().clone());
+
+        // a single raw leaf becomes a file node carrying the metadata
+        let file = FileBuilder::new()
+            .name("foo.txt")
+            .content_bytes(b"hello world".to_vec())
+            .mode(0o644)
+            .mtime(mtime)
+            .build()
+            .await?;
+        let file = file.encode_root().await?;
+        let node = decode(&file)?;
+        assert_eq!(node.typ(), Some(DataType::File));
+        assert_eq!(node.size(), Some(11));
+        assert_eq!(node.mode(), Some(0o644));
+        assert_eq!(node.mtime(), Some(mtime));
+
+        // without metadata nothing changes
+        let plain = FileBuilder::new()
+            .name("foo.txt")
+            .content_bytes(b"hello world".to_vec())
+            .build()
+            .await?;
+        let plain = decode(&plain.encode_root().await?)?;
+        assert!(matches!(plain, UnixfsNode::Raw(_)));
+        assert_eq!(plain.mode(), None);
+        assert_eq!(plain.mtime(), None);
+
+        let mut symlink = SymlinkBuilder::new("bar");
+        symlink.target("foo.txt").mtime(mtime);
+        let symlink = decode(&symlink.build().await?.encode()?)?;
+        assert_eq!(symlink.mode(), None);
+        assert_eq!(symlink.mtime(), Some(mtime));
+
+        for hamt in [false, true] {
+            let mut dir = DirectoryBuilder::new().name("dir").mode(0o755).mtime(mtime);
+            if hamt {
+                dir = dir.hamt();
+            }
+            let dir = dir.build().await?.encode_root().await?;
+            let node = decode(&dir)?;
+            assert_eq!(node.mode(), Some(0o755));
+            assert_eq!(node.mtime(), Some(mtime));
+        }
+
+        // times before the epoch
+        let before = std::time::UNIX_EPOCH - std::time::Duration::new(10, 1);
+        let time = to_unix_time(before);
+        assert_eq!(time.seconds, Some(-11));
+        assert_eq!(time.fractional_nanoseconds, Some(999_999_999));
+        assert_eq!(crate::unixfs::from_unix_time(&time), Some(before));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_make_dir_from_path() -> Result<()> {
         let temp_dir = std::env::temp_dir();
@@ -1076,12 +1728,15 @@ mod tests {
             vec![Entry::File(file), Entry::Directory(nested_dir)],
         );
 
-        let got = make_dir_from_path(
-            dir,
-            Chunker::Fixed(chunker::Fixed::default()),
-            DEFAULT_DEGREE,
-        )
-        .await?;
+        let options = ImportOptions {
+            chunker: Chunker::Fixed(chunker::Fixed::default()),
+            tree_builder: TreeBuilder::balanced_tree(),
+            cid_builder: Default::default(),
+            raw_leaves: true,
+            preserve_mode: false,
+            preserve_mtime: false,
+        };
+        let got = make_dir_from_path(dir, &options).await?;
 
         let basic_entries = |dir: Directory| match dir {
             Directory::Basic(basic) => basic.entries,
@@ -12,7 +12,8 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use cid::{multibase::Base, Cid};
 use futures::future::Either;
-use iroh_rpc_client::Client;
+use iroh_car::CarStore;
+use iroh_rpc_client::{store::GcGuard, Client};
 use rand::seq::SliceRandom;
 use reqwest::Url;
 use tracing::{debug, info, trace, warn};
@@ -20,10 +21,11 @@ use tracing::{debug, info, trace, warn};
 use crate::{
     indexer::{Indexer, IndexerUrl},
     parse_links,
-    types::{LoadedCid, Source},
+    types::{Block, LoadedCid, Source},
 };
 
 pub const IROH_STORE: &str = "iroh-store";
+pub const CAR_STORE: &str = "car-store";
 
 #[async_trait]
 pub trait ContentLoader: Sync + Send + std::fmt::Debug + Clone + 'static {
@@ -33,6 +35,26 @@ pub trait ContentLoader: Sync + Send + std::fmt::Debug + Clone + 'static {
     async fn stop_session(&self, ctx: ContextId) -> Result<()>;
     /// Checks if the given cid is present in the local storage.
     async fn has_cid(&self, cid: &Cid) -> Result<bool>;
+    /// Loads all records published in the DHT under the given key, without validating them.
+    async fn load_record(&self, _key: &[u8]) -> Result<Vec<Bytes>> {
+        bail!("record lookups are not supported")
+    }
+    /// Writes the given blocks to the local storage.
+    async fn store_blocks(&self, _blocks: Vec<Block>) -> Result<()> {
+        bail!("storing blocks is not supported")
+    }
+    /// Holds off garbage collection of the local storage until the guard is dropped.
+    async fn gc_guard(&self) -> Result<GcGuard> {
+        bail!("garbage collection is not supported by this loader")
+    }
+    /// Pins the given cid, and everything it links to, in the local storage.
+    async fn pin(&self, _cid: &Cid) -> Result<()> {
+        bail!("pinning is not supported by this loader")
+    }
+    /// Removes the pin of the given cid in the local storage.
+    async fn unpin(&self, _cid: &Cid) -> Result<()> {
+        bail!("unpinning is not supported by this loader")
+    }
 }
 
 #[async_trait]
@@ -48,6 +70,26 @@ impl<T: ContentLoader> ContentLoader for Arc<T> {
     async fn has_cid(&self, cid: &Cid) -> Result<bool> {
         self.as_ref().has_cid(cid).await
     }
+
+    async fn load_record(&self, key: &[u8]) -> Result<Vec<Bytes>> {
+        self.as_ref().load_record(key).await
+    }
+
+    async fn store_blocks(&self, blocks: Vec<Block>) -> Result<()> {
+        self.as_ref().store_blocks(blocks).await
+    }
+
+    async fn gc_guard(&self) -> Result<GcGuard> {
+        self.as_ref().gc_guard().await
+    }
+
+    async fn pin(&self, cid: &Cid) -> Result<()> {
+        self.as_ref().pin(cid).await
+    }
+
+    async fn unpin(&self, cid: &Cid) -> Result<()> {
+        self.as_ref().unpin(cid).await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +100,8 @@ pub struct FullLoader {
     indexer: Option<Indexer>,
     /// Gateway endpoints.
     http_gateways: Vec<GatewayUrl>,
+    /// CAR files which are served before looking at the store.
+    car_store: Option<CarStore>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -116,9 +160,16 @@ impl FullLoader {
             client,
             indexer,
             http_gateways: config.http_gateways,
+            car_store: None,
         })
     }
 
+    /// Serves the blocks of the given CAR files, without adding them to the store.
+    pub fn with_car_store(mut self, car_store: CarStore) -> Self {
+        self.car_store = Some(car_store);
+        self
+    }
+
     /// Fetch the next gateway url, if configured.
     async fn next_gateway(&self) -> Option<&GatewayUrl> {
         // TODO: maybe roundrobin?
@@ -129,6 +180,13 @@ impl FullLoader {
         Some(gw)
     }
 
+    async fn fetch_car_store(&self, cid: &Cid) -> Result<Option<LoadedCid>> {
+        match self.car_store {
+            Some(ref car_store) => load_from_car_store(car_store, cid).await,
+            None => Ok(None),
+        }
+    }
+
     async fn fetch_store(&self, cid: &Cid) -> Result<Option<LoadedCid>> {
         match self.client.try_store() {
             Ok(store) => Ok(store.get(*cid).await?.map(|data| LoadedCid {
@@ -236,6 +294,17 @@ impl ContentLoader for FullLoader {
     async fn load_cid(&self, cid: &Cid, ctx: &LoaderContext) -> Result<LoadedCid> {
         trace!("{:?} loading {}", ctx.id(), cid);
 
+        if let Some(data) = iroh_util::inline_data(cid) {
+            return Ok(LoadedCid {
+                data: Bytes::copy_from_slice(data),
+                source: Source::Inline,
+            });
+        }
+
+        if let Some(loaded) = self.fetch_car_store(cid).await? {
+            return Ok(loaded);
+        }
+
         if let Some(loaded) = self.fetch_store(cid).await? {
             return Ok(loaded);
         }
@@ -289,8 +358,145 @@ impl ContentLoader for FullLoader {
     }
 
     async fn has_cid(&self, cid: &Cid) -> Result<bool> {
+        if iroh_util::inline_data(cid).is_some() {
+            return Ok(true);
+        }
+        if let Some(ref car_store) = self.car_store {
+            if car_store.has(cid).await {
+                return Ok(true);
+            }
+        }
         self.client.try_store()?.has(*cid).await
     }
+
+    async fn load_record(&self, key: &[u8]) -> Result<Vec<Bytes>> {
+        self.client
+            .try_p2p()?
+            .get_record(Bytes::copy_from_slice(key))
+            .await
+    }
+
+    async fn store_blocks(&self, blocks: Vec<Block>) -> Result<()> {
+        self.client
+            .try_store()?
+            .put_many(blocks.into_iter().map(Block::into_parts).collect())
+            .await
+    }
+
+    async fn gc_guard(&self) -> Result<GcGuard> {
+        self.client.try_store()?.gc_guard().await
+    }
+
+    async fn pin(&self, cid: &Cid) -> Result<()> {
+        self.client.try_store()?.pin(*cid, true).await
+    }
+
+    async fn unpin(&self, cid: &Cid) -> Result<()> {
+        self.client.try_store()?.unpin(*cid).await
+    }
+}
+
+/// A loader that only looks at the local store, and never fetches from the network.
+#[derive(Debug, Clone)]
+pub struct StoreLoader {
+    client: Client,
+}
+
+impl StoreLoader {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ContentLoader for StoreLoader {
+    async fn load_cid(&self, cid: &Cid, _ctx: &LoaderContext) -> Result<LoadedCid> {
+        if let Some(data) = iroh_util::inline_data(cid) {
+            return Ok(LoadedCid {
+                data: Bytes::copy_from_slice(data),
+                source: Source::Inline,
+            });
+        }
+        match self.client.try_store()?.get(*cid).await? {
+            Some(data) => Ok(LoadedCid {
+                data,
+                source: Source::Store(IROH_STORE),
+            }),
+            None => bail!("{} is not available in the local store", cid),
+        }
+    }
+
+    async fn stop_session(&self, _ctx: ContextId) -> Result<()> {
+        // no sessions, nothing is fetched from the network
+        Ok(())
+    }
+
+    async fn has_cid(&self, cid: &Cid) -> Result<bool> {
+        if iroh_util::inline_data(cid).is_some() {
+            return Ok(true);
+        }
+        self.client.try_store()?.has(*cid).await
+    }
+
+    async fn store_blocks(&self, blocks: Vec<Block>) -> Result<()> {
+        self.client
+            .try_store()?
+            .put_many(blocks.into_iter().map(Block::into_parts).collect())
+            .await
+    }
+
+    async fn gc_guard(&self) -> Result<GcGuard> {
+        self.client.try_store()?.gc_guard().await
+    }
+
+    async fn pin(&self, cid: &Cid) -> Result<()> {
+        self.client.try_store()?.pin(*cid, true).await
+    }
+
+    async fn unpin(&self, cid: &Cid) -> Result<()> {
+        self.client.try_store()?.unpin(*cid).await
+    }
+}
+
+/// Loads blocks out of CAR files, see [`CarStore`].
+#[async_trait]
+impl ContentLoader for CarStore {
+    async fn load_cid(&self, cid: &Cid, _ctx: &LoaderContext) -> Result<LoadedCid> {
+        if let Some(data) = iroh_util::inline_data(cid) {
+            return Ok(LoadedCid {
+                data: Bytes::copy_from_slice(data),
+                source: Source::Inline,
+            });
+        }
+        match load_from_car_store(self, cid).await? {
+            Some(loaded) => Ok(loaded),
+            None => bail!("{} is not in any of the CAR files", cid),
+        }
+    }
+
+    async fn stop_session(&self, _ctx: ContextId) -> Result<()> {
+        // no session tracking
+        Ok(())
+    }
+
+    async fn has_cid(&self, cid: &Cid) -> Result<bool> {
+        Ok(iroh_util::inline_data(cid).is_some() || self.has(cid).await)
+    }
+}
+
+/// Loads and verifies a block, CAR files are not trusted to contain the right data.
+async fn load_from_car_store(car_store: &CarStore, cid: &Cid) -> Result<Option<LoadedCid>> {
+    let data = match car_store.get(cid).await? {
+        Some(data) => Bytes::from(data),
+        None => return Ok(None),
+    };
+    if iroh_util::verify_hash(cid, &data) != Some(true) {
+        bail!("invalid hash for {} in CAR file", cid);
+    }
+    Ok(Some(LoadedCid {
+        data,
+        source: Source::Store(CAR_STORE),
+    }))
 }
 
 #[derive(Debug, Clone)]
@@ -363,6 +569,12 @@ pub struct InnerLoaderContext {
 #[async_trait]
 impl<S: BuildHasher + Clone + Send + Sync + 'static> ContentLoader for HashMap<Cid, Bytes, S> {
     async fn load_cid(&self, cid: &Cid, _ctx: &LoaderContext) -> Result<LoadedCid> {
+        if let Some(data) = iroh_util::inline_data(cid) {
+            return Ok(LoadedCid {
+                data: Bytes::copy_from_slice(data),
+                source: Source::Inline,
+            });
+        }
         match self.get(cid) {
             Some(b) => Ok(LoadedCid {
                 data: b.clone(),
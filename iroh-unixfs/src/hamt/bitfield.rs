@@ -6,7 +6,15 @@ pub const BITWIDTH: usize = 256;
 pub struct Bitfield([u64; BITWIDTH / 64]);
 
 impl Bitfield {
-    pub fn as_bytes(&self) -> [u8; BITWIDTH / 8] {
+    /// Big endian bytes without leading zero bytes, the encoding go-bitfield uses in the
+    /// `Data` field of hamt shards.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let v = self.as_array();
+        let start = v.iter().position(|b| *b != 0).unwrap_or(v.len());
+        v[start..].to_vec()
+    }
+
+    fn as_array(&self) -> [u8; BITWIDTH / 8] {
         let mut v = [0u8; BITWIDTH / 8];
         // Big endian ordering, to match go
         v[..8].copy_from_slice(&self.0[3].to_be_bytes());
@@ -147,22 +155,44 @@ mod tests {
     #[test]
     fn test_serialization() {
         let mut b0 = Bitfield::zero();
-        let bz = b0.as_bytes();
+        let bz = b0.as_array();
         assert_eq!(&bz[..], &[0; 32]);
         assert_eq!(Bitfield::from_bytes(bz).unwrap(), b0);
 
         b0.set_bit(0);
-        let bz = b0.as_bytes();
+        let bz = b0.as_array();
         let mut expected = [0; 32];
         expected[31] = 0b0000_0001;
         assert_eq!(&bz[..], &expected);
         assert_eq!(Bitfield::from_bytes(bz).unwrap(), b0);
 
         b0.set_bit(64);
-        let bz = b0.as_bytes();
+        let bz = b0.as_array();
         expected[23] = 0b0000_0001;
 
         assert_eq!(&bz[..], &expected);
         assert_eq!(Bitfield::from_bytes(bz).unwrap(), b0);
     }
+
+    #[test]
+    fn test_trimmed_bytes() {
+        let mut b0 = Bitfield::zero();
+        assert!(b0.as_bytes().is_empty());
+        assert_eq!(Bitfield::from_slice(&b0.as_bytes()).unwrap(), b0);
+
+        b0.set_bit(0);
+        assert_eq!(b0.as_bytes(), vec![0b0000_0001]);
+        assert_eq!(Bitfield::from_slice(&b0.as_bytes()).unwrap(), b0);
+
+        b0.set_bit(64);
+        let mut expected = vec![0; 9];
+        expected[0] = 0b0000_0001;
+        expected[8] = 0b0000_0001;
+        assert_eq!(b0.as_bytes(), expected);
+        assert_eq!(Bitfield::from_slice(&b0.as_bytes()).unwrap(), b0);
+
+        b0.set_bit(255);
+        assert_eq!(b0.as_bytes().len(), 32);
+        assert_eq!(Bitfield::from_slice(&b0.as_bytes()).unwrap(), b0);
+    }
 }
@@ -5,6 +5,7 @@ pub mod codecs;
 pub mod content_loader;
 pub mod hamt;
 pub mod indexer;
+mod trickle_tree;
 mod types;
 pub mod unixfs;
 
@@ -0,0 +1,242 @@
+use anyhow::Result;
+use async_stream::try_stream;
+use bytes::Bytes;
+use cid::Cid;
+use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
+
+use crate::balanced_tree::{LinkInfo, TreeNode};
+use crate::types::Block;
+use crate::unixfs::CidBuilder;
+
+/// Default number of subtrees of the same depth in a trickle dag, taken from kubo
+/// <https://github.com/ipfs/go-unixfs/blob/master/importer/trickle/trickledag.go>
+pub const DEFAULT_DEPTH_REPEAT: usize = 4;
+
+/// A stem node of the trickle dag that is still receiving links.
+struct Layer {
+    links: Vec<(Cid, LinkInfo)>,
+    /// Subtrees of this node are at most this deep, `None` for the root node, which
+    /// grows without limit.
+    max_depth: Option<usize>,
+    /// Depth of the subtrees currently added to this node, `0` while filling it with
+    /// leaves.
+    depth: usize,
+    /// Number of subtrees of `depth` added so far.
+    repeat: usize,
+}
+
+impl Layer {
+    fn new(max_depth: Option<usize>, max_links: usize) -> Self {
+        Layer {
+            links: Vec::with_capacity(max_links),
+            max_depth,
+            depth: 0,
+            repeat: 0,
+        }
+    }
+}
+
+/// Streams a "trickle dag", in the same layout as kubo.
+///
+/// Every stem node first links up to `max_links` leaves, followed by `depth_repeat`
+/// subtrees of depth 1, `depth_repeat` subtrees of depth 2, and so on, up to its own
+/// depth. The root node has no depth limit. Data can be appended to such a dag by only
+/// rewriting the nodes along its right edge, and the beginning of the data is always
+/// close to the root.
+pub(crate) fn stream_trickle_tree(
+    in_stream: impl Stream<Item = std::io::Result<Bytes>> + Send,
+    max_links: usize,
+    depth_repeat: usize,
+    cid_builder: CidBuilder,
+    raw_leaves: bool,
+) -> impl Stream<Item = Result<Block>> {
+    try_stream! {
+        let hash_par: usize = 8;
+
+        let in_stream = in_stream.err_into::<anyhow::Error>().map(|chunk| {
+            tokio::task::spawn_blocking(move || {
+                chunk.and_then(|chunk| {
+                    TreeNode::trickle_leaf(chunk, raw_leaves).encode(&cid_builder)
+                })
+            }).err_into::<anyhow::Error>()
+        }).buffered(hash_par).map(|x| x.and_then(|x| x)).peekable();
+
+        tokio::pin!(in_stream);
+
+        // The path from the root to the node currently being filled, as nodes are only
+        // yielded once complete, only this path needs to be kept in memory.
+        let mut stack = vec![Layer::new(None, max_links)];
+
+        loop {
+            let layer = stack.last_mut().expect("the root is popped last");
+
+            // fill the node with leaves first
+            if layer.depth == 0 {
+                if layer.links.len() < max_links {
+                    if let Some(chunk) = in_stream.next().await {
+                        let (block, link_info) = chunk?;
+                        layer.links.push((*block.cid(), link_info));
+                        yield block;
+                        continue;
+                    }
+                }
+                layer.depth = 1;
+            }
+
+            let done = in_stream.as_mut().peek().await.is_none();
+            let full = matches!(layer.max_depth, Some(max_depth) if layer.depth >= max_depth);
+            if !done && !full {
+                // start the next subtree
+                let max_depth = layer.depth;
+                stack.push(Layer::new(Some(max_depth), max_links));
+                continue;
+            }
+
+            // the node is complete, link it from its parent
+            let layer = stack.pop().expect("checked above");
+            let (block, link_info) = TreeNode::Stem(layer.links).encode(&cid_builder)?;
+            let cid = *block.cid();
+            yield block;
+
+            match stack.last_mut() {
+                Some(parent) => {
+                    parent.links.push((cid, link_info));
+                    parent.repeat += 1;
+                    if parent.repeat == depth_repeat {
+                        parent.depth += 1;
+                        parent.repeat = 0;
+                    }
+                }
+                None => {
+                    // final root, nothing to do
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unixfs::{DataType, UnixfsNode};
+
+    fn test_chunk_stream(num_chunks: usize) -> impl Stream<Item = std::io::Result<Bytes>> {
+        futures::stream::iter((0..num_chunks).map(|n| Ok(n.to_be_bytes().to_vec().into())))
+    }
+
+    /// Returns the depth of the subtrees linked from `node`, leaves have depth 0.
+    fn depths(blocks: &[Block], node: &Block) -> Vec<usize> {
+        fn depth(blocks: &[Block], cid: &Cid) -> usize {
+            let block = blocks.iter().find(|b| b.cid() == cid).unwrap();
+            block
+                .links()
+                .iter()
+                .map(|cid| depth(blocks, cid) + 1)
+                .max()
+                .unwrap_or_default()
+        }
+        node.links().iter().map(|cid| depth(blocks, cid)).collect()
+    }
+
+    async fn build(num_chunks: usize, max_links: usize, depth_repeat: usize) -> Vec<Block> {
+        stream_trickle_tree(
+            test_chunk_stream(num_chunks),
+            max_links,
+            depth_repeat,
+            CidBuilder::default(),
+            true,
+        )
+        .try_collect()
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_trickle_layout() {
+        // 3 leaves, 2 subtrees of depth 1 with 3 leaves each, 2 subtrees of depth 2 with
+        // 3 leaves and 2 subtrees of depth 1 each, and a subtree for depth 3, which only
+        // got a single leaf
+        let num_chunks = 3 + 2 * 3 + 2 * (3 + 2 * 3) + 1;
+        let blocks = build(num_chunks, 3, 2).await;
+        let root = blocks.last().unwrap();
+        assert_eq!(depths(&blocks, root), vec![0, 0, 0, 1, 1, 2, 2, 1]);
+
+        // the depth 2 subtrees
+        for cid in &root.links()[5..7] {
+            let subtree = blocks.iter().find(|b| b.cid() == cid).unwrap();
+            assert_eq!(depths(&blocks, subtree), vec![0, 0, 0, 1, 1]);
+        }
+
+        // the last subtree only holds the last leaf
+        let partial = blocks.iter().find(|b| b.cid() == &root.links()[7]).unwrap();
+        assert_eq!(depths(&blocks, partial), vec![0]);
+
+        // all chunks are yielded in order, followed by the nodes linking to them
+        let leaves: Vec<_> = blocks
+            .iter()
+            .filter(|b| b.links().is_empty())
+            .map(|b| b.data().clone())
+            .collect();
+        let expected: Vec<Bytes> = (0..num_chunks)
+            .map(|n| n.to_be_bytes().to_vec().into())
+            .collect();
+        assert_eq!(leaves, expected);
+
+        let node = UnixfsNode::decode(root.cid(), root.data().clone()).unwrap();
+        assert_eq!(
+            node.filesize(),
+            Some((num_chunks * std::mem::size_of::<usize>()) as u64)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trickle_single_chunk() {
+        // unlike the balanced tree, the root always is a stem node
+        let blocks = build(1, 174, DEFAULT_DEPTH_REPEAT).await;
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].links(), &[*blocks[0].cid()]);
+    }
+
+    #[tokio::test]
+    async fn test_trickle_matches_kubo() {
+        // `ipfs add --trickle --cid-version=0 --chunker=size-256` of 1200 full chunks and
+        // a partial one: 174 leaves and 4 subtrees of depth 1 in the root, followed by
+        // a partial subtree of depth 2
+        let content: Bytes = (0..307300).map(|i| (i % 251) as u8).collect();
+        let chunks = content
+            .chunks(256)
+            .map(|chunk| Ok(content.slice_ref(chunk)))
+            .collect::<Vec<std::io::Result<Bytes>>>();
+        let blocks: Vec<Block> = stream_trickle_tree(
+            futures::stream::iter(chunks),
+            crate::balanced_tree::DEFAULT_DEGREE,
+            DEFAULT_DEPTH_REPEAT,
+            CidBuilder::v0(),
+            false,
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+        let root = blocks.last().unwrap();
+        assert_eq!(
+            root.cid().to_string(),
+            "QmWKPJ1qktZ5UTp7LWobHKvNjvyJvRAbzNu1sYhGxM3Hox"
+        );
+        assert_eq!(root.links().len(), 174 + 4 + 1);
+
+        // kubo uses the raw unixfs type for the leaves
+        let leaf = UnixfsNode::decode(blocks[0].cid(), blocks[0].data().clone()).unwrap();
+        assert_eq!(leaf.typ(), Some(DataType::Raw));
+        assert_eq!(leaf.filesize(), Some(256));
+    }
+
+    #[tokio::test]
+    async fn test_trickle_empty() {
+        let blocks = build(0, 174, DEFAULT_DEPTH_REPEAT).await;
+        assert_eq!(blocks.len(), 1);
+        let node = UnixfsNode::decode(blocks[0].cid(), blocks[0].data().clone()).unwrap();
+        assert_eq!(node.filesize(), Some(0));
+    }
+}
@@ -3,10 +3,16 @@ use std::io::Cursor;
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
 use cid::Cid;
+use iroh_util::IDENTITY_HASH_CODE;
 use libipld::error::{InvalidMultihash, UnsupportedMultihash};
-use multihash::{Code, MultihashDigest};
+use multihash::{Code, Multihash, MultihashDigest};
+use prost::Message;
 
-use crate::{codecs::Codec, parse_links, unixfs::dag_pb};
+use crate::{
+    codecs::Codec,
+    parse_links,
+    unixfs::{dag_pb, unixfs_pb, DataType},
+};
 
 #[derive(Debug)]
 pub struct LoadedCid {
@@ -19,6 +25,8 @@ pub enum Source {
     Bitswap,
     Http(String),
     Store(&'static str),
+    /// The data is inlined into an identity hashed cid.
+    Inline,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -49,6 +57,16 @@ impl Block {
         let codec = Codec::try_from(self.cid.codec()).unwrap();
         match codec {
             Codec::Raw => Some(self.data.len() as u64),
+            // a leaf of a file without raw leaves, trickle dags use the raw type for them
+            Codec::DagPb if self.links.is_empty() => {
+                let outer = dag_pb::PbNode::decode(self.data.clone()).ok()?;
+                let inner = unixfs_pb::Data::decode(outer.data?).ok()?;
+                if inner.r#type == DataType::File as i32 || inner.r#type == DataType::Raw as i32 {
+                    inner.data.map(|data| data.len() as u64)
+                } else {
+                    None
+                }
+            }
             _ => None,
         }
     }
@@ -57,9 +75,13 @@ impl Block {
     pub fn validate(&self) -> Result<()> {
         // check that the cid is supported
         let code = self.cid.hash().code();
-        let mh = Code::try_from(code)
-            .map_err(|_| UnsupportedMultihash(code))?
-            .digest(&self.data);
+        let mh = if code == IDENTITY_HASH_CODE {
+            Multihash::wrap(code, &self.data)?
+        } else {
+            Code::try_from(code)
+                .map_err(|_| UnsupportedMultihash(code))?
+                .digest(&self.data)
+        };
         // check that the hash matches the data
         if mh.digest() != self.cid.hash().digest() {
             return Err(InvalidMultihash(mh.to_bytes()).into());
@@ -3,13 +3,18 @@ use std::{
     fmt::Debug,
     pin::Pin,
     task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, bail, ensure, Result};
 use bytes::{Buf, Bytes};
-use cid::{multihash::MultihashDigest, Cid};
+use cid::{
+    multihash::{Code, Multihash, MultihashDigest},
+    Cid, Version,
+};
 use futures::{future::BoxFuture, stream::BoxStream, FutureExt, Stream, StreamExt};
 use iroh_metrics::resolver::OutMetrics;
+use iroh_util::IDENTITY_HASH_CODE;
 use prost::Message;
 use tokio::io::{AsyncRead, AsyncSeek};
 
@@ -65,6 +70,113 @@ impl Unixfs {
     }
 }
 
+/// Default maximum size of blocks that are inlined into their cid, the same as kubo.
+pub const DEFAULT_INLINE_LIMIT: usize = 32;
+
+/// Blocks up to this size can be inlined, the maximum digest size of a cid.
+pub const MAX_INLINE_LIMIT: usize = 64;
+
+/// Builds the CIDs of encoded nodes, with a configurable CID version and multihash.
+///
+/// The default are CIDv1 with sha2-256 hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CidBuilder {
+    version: Version,
+    hash: Code,
+    inline_limit: Option<usize>,
+}
+
+impl Default for CidBuilder {
+    fn default() -> Self {
+        CidBuilder {
+            version: Version::V1,
+            hash: Code::Sha2_256,
+            inline_limit: None,
+        }
+    }
+}
+
+impl CidBuilder {
+    /// CIDv0 only supports sha2-256 hashes.
+    pub fn new(version: Version, hash: Code) -> Result<Self> {
+        ensure!(
+            version == Version::V1 || hash == Code::Sha2_256,
+            "CIDv0 only supports sha2-256 hashes"
+        );
+        Ok(CidBuilder {
+            version,
+            hash,
+            inline_limit: None,
+        })
+    }
+
+    /// CIDv0 with sha2-256 hashes, the default of kubo.
+    pub fn v0() -> Self {
+        CidBuilder {
+            version: Version::V0,
+            hash: Code::Sha2_256,
+            inline_limit: None,
+        }
+    }
+
+    /// Inline blocks of up to `limit` bytes into their cid, using the identity hash
+    /// function. Such cids are always CIDv1.
+    pub fn inline(mut self, limit: usize) -> Result<Self> {
+        ensure!(
+            limit <= MAX_INLINE_LIMIT,
+            "inline limit must be at most {} bytes",
+            MAX_INLINE_LIMIT
+        );
+        self.inline_limit = Some(limit);
+        Ok(self)
+    }
+
+    pub fn inline_limit(&self) -> Option<usize> {
+        self.inline_limit
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn hash(&self) -> Code {
+        self.hash
+    }
+
+    /// Hashes `data` into a CID with the given codec.
+    ///
+    /// CIDv0 can only refer to dag-pb, all other codecs get a CIDv1, like raw leaves in kubo.
+    pub fn build(&self, codec: Codec, data: &[u8]) -> Cid {
+        if matches!(self.inline_limit, Some(limit) if data.len() <= limit) {
+            let digest =
+                Multihash::wrap(IDENTITY_HASH_CODE, data).expect("checked against the limit");
+            return Cid::new_v1(codec as _, digest);
+        }
+        let digest = self.hash.digest(data);
+        match self.version {
+            Version::V0 if codec == Codec::DagPb => {
+                Cid::new_v0(digest).expect("checked to be sha2-256")
+            }
+            _ => Cid::new_v1(codec as _, digest),
+        }
+    }
+}
+
+/// Parses the name of a multihash function, as used by the `--hash` flag of kubo.
+pub fn parse_hash(name: &str) -> Result<Code> {
+    let code = match name {
+        "sha2-256" => Code::Sha2_256,
+        "sha2-512" => Code::Sha2_512,
+        "sha3-256" => Code::Sha3_256,
+        "sha3-512" => Code::Sha3_512,
+        "blake2b-256" => Code::Blake2b256,
+        "blake2b-512" => Code::Blake2b512,
+        "blake3" => Code::Blake3_256,
+        _ => bail!("unsupported hash function: {}", name),
+    };
+    Ok(code)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum UnixfsNode {
     Raw(Bytes),
@@ -146,6 +258,55 @@ impl Node {
     pub fn fanout(&self) -> Option<u32> {
         self.inner.fanout.and_then(|f| u32::try_from(f).ok())
     }
+
+    /// Returns the unix permission bits, if stored.
+    pub fn mode(&self) -> Option<u32> {
+        self.inner.mode.map(|mode| mode & 0o7777)
+    }
+
+    /// Returns the modification time, if stored.
+    pub fn mtime(&self) -> Option<SystemTime> {
+        self.inner.mtime.as_ref().and_then(from_unix_time)
+    }
+}
+
+/// Converts a `SystemTime` into the UnixFS 1.5 representation.
+///
+/// Times before the epoch are stored as negative seconds with positive nanoseconds.
+pub(crate) fn to_unix_time(time: SystemTime) -> unixfs_pb::UnixTime {
+    let (seconds, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(err) => {
+            let d = err.duration();
+            match d.subsec_nanos() {
+                0 => (-(d.as_secs() as i64), 0),
+                nanos => (-(d.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    unixfs_pb::UnixTime {
+        seconds: Some(seconds),
+        fractional_nanoseconds: if nanos == 0 { None } else { Some(nanos) },
+    }
+}
+
+/// Converts the UnixFS 1.5 representation of a time into a `SystemTime`.
+///
+/// Returns `None` for invalid or unrepresentable times.
+pub(crate) fn from_unix_time(time: &unixfs_pb::UnixTime) -> Option<SystemTime> {
+    let seconds = time.seconds?;
+    let nanos = time.fractional_nanoseconds.unwrap_or_default();
+    if nanos >= 1_000_000_000 {
+        return None;
+    }
+    let nanos = Duration::from_nanos(nanos as u64);
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64) + nanos)
+    } else {
+        UNIX_EPOCH
+            .checked_sub(Duration::from_secs(seconds.unsigned_abs()))?
+            .checked_add(nanos)
+    }
 }
 
 impl UnixfsNode {
@@ -165,7 +326,7 @@ impl UnixfsNode {
 
                 // ensure correct unixfs type
                 match typ {
-                    DataType::Raw => todo!(),
+                    DataType::Raw => Ok(UnixfsNode::RawNode(node)),
                     DataType::Directory => Ok(UnixfsNode::Directory(node)),
                     DataType::File => Ok(UnixfsNode::File(node)),
                     DataType::Symlink => Ok(UnixfsNode::Symlink(node)),
@@ -179,12 +340,13 @@ impl UnixfsNode {
         }
     }
 
-    pub fn encode(&self) -> Result<Block> {
+    /// Encodes the node into a block, with a CID built by `cid_builder`.
+    pub fn encode(&self, cid_builder: &CidBuilder) -> Result<Block> {
         let res = match self {
             UnixfsNode::Raw(data) => {
                 let out = data.clone();
                 let links = vec![];
-                let cid = Cid::new_v1(Codec::Raw as _, cid::multihash::Code::Sha2_256.digest(&out));
+                let cid = cid_builder.build(Codec::Raw, &out);
                 Block::new(cid, out, links)
             }
             UnixfsNode::RawNode(node)
@@ -197,10 +359,7 @@ impl UnixfsNode {
                     .links()
                     .map(|x| Ok(x?.cid))
                     .collect::<Result<Vec<_>>>()?;
-                let cid = Cid::new_v1(
-                    Codec::DagPb as _,
-                    cid::multihash::Code::Sha2_256.digest(&out),
-                );
+                let cid = cid_builder.build(Codec::DagPb, &out);
                 Block::new(cid, out, links)
             }
         };
@@ -251,6 +410,32 @@ impl UnixfsNode {
         }
     }
 
+    /// Returns the unix permission bits, if stored.
+    /// Raw blocks never carry any metadata.
+    pub fn mode(&self) -> Option<u32> {
+        match self {
+            UnixfsNode::Raw(_) => None,
+            UnixfsNode::Directory(node)
+            | UnixfsNode::RawNode(node)
+            | UnixfsNode::File(node)
+            | UnixfsNode::Symlink(node)
+            | UnixfsNode::HamtShard(node, _) => node.mode(),
+        }
+    }
+
+    /// Returns the modification time, if stored.
+    /// Raw blocks never carry any metadata.
+    pub fn mtime(&self) -> Option<SystemTime> {
+        match self {
+            UnixfsNode::Raw(_) => None,
+            UnixfsNode::Directory(node)
+            | UnixfsNode::RawNode(node)
+            | UnixfsNode::File(node)
+            | UnixfsNode::Symlink(node)
+            | UnixfsNode::HamtShard(node, _) => node.mtime(),
+        }
+    }
+
     /// Returns the blocksizes of the links
     /// Should only be set for File
     pub fn blocksizes(&self) -> &[u64] {
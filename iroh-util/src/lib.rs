@@ -230,8 +230,25 @@ where
     Ok(cfg)
 }
 
+/// Multihash code of the identity "hash" function, whose digest is the data itself.
+pub const IDENTITY_HASH_CODE: u64 = 0x00;
+
+/// Returns the data inlined into the given cid, if it uses the identity hash function.
+///
+/// Such blocks never need to be stored or fetched from the network.
+pub fn inline_data(cid: &Cid) -> Option<&[u8]> {
+    if cid.hash().code() == IDENTITY_HASH_CODE {
+        Some(cid.hash().digest())
+    } else {
+        None
+    }
+}
+
 /// Verifies that the provided bytes hash to the given multihash.
 pub fn verify_hash(cid: &Cid, bytes: &[u8]) -> Option<bool> {
+    if let Some(data) = inline_data(cid) {
+        return Some(data == bytes);
+    }
     Code::try_from(cid.hash().code()).ok().map(|code| {
         let calculated_hash = code.digest(bytes);
         &calculated_hash == cid.hash()
@@ -261,6 +278,18 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_inline_data() {
+        let hash = cid::multihash::Multihash::wrap(IDENTITY_HASH_CODE, b"hello").unwrap();
+        let cid = Cid::new_v1(0x55, hash);
+        assert_eq!(inline_data(&cid), Some(&b"hello"[..]));
+        assert_eq!(verify_hash(&cid, b"hello"), Some(true));
+        assert_eq!(verify_hash(&cid, b"world"), Some(false));
+
+        let cid = Cid::new_v1(0x55, Code::Sha2_256.digest(b"hello"));
+        assert_eq!(inline_data(&cid), None);
+    }
+
     #[test]
     fn test_iroh_directory_paths() {
         let got = iroh_config_path("foo.bar").unwrap();
@@ -0,0 +1,80 @@
+use crate::doc;
+use crate::services::require_services;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand, ValueEnum};
+use futures::StreamExt;
+use iroh_api::{Api, Cid};
+use iroh_unixfs::codecs::Codec;
+use std::collections::BTreeSet;
+
+#[derive(Args, Debug, Clone)]
+#[clap(about = "Inspect the blocks in the store")]
+#[clap(after_help = doc::BLOCK_LONG_DESCRIPTION)]
+pub struct Block {
+    #[clap(subcommand)]
+    command: BlockCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BlockCommands {
+    #[clap(about = "List the blocks in the store")]
+    #[clap(after_help = doc::BLOCK_LS_LONG_DESCRIPTION)]
+    Ls {
+        /// Only list blocks with this codec
+        #[clap(long, value_enum)]
+        codec: Option<CodecArg>,
+        /// Only list blocks whose multihash starts with these hex encoded bytes
+        #[clap(long)]
+        hash_prefix: Option<String>,
+        /// Start listing after this CID
+        #[clap(long)]
+        after: Option<Cid>,
+        /// Maximum number of blocks to list
+        #[clap(long)]
+        limit: Option<u64>,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum CodecArg {
+    Raw,
+    DagPb,
+    DagCbor,
+    DagJson,
+}
+
+impl From<CodecArg> for Codec {
+    fn from(arg: CodecArg) -> Self {
+        match arg {
+            CodecArg::Raw => Codec::Raw,
+            CodecArg::DagPb => Codec::DagPb,
+            CodecArg::DagCbor => Codec::DagCbor,
+            CodecArg::DagJson => Codec::DagJson,
+        }
+    }
+}
+
+pub async fn run_command(api: &Api, cmd: &Block) -> Result<()> {
+    require_services(api, BTreeSet::from(["store"])).await?;
+    match &cmd.command {
+        BlockCommands::Ls {
+            codec,
+            hash_prefix,
+            after,
+            limit,
+        } => {
+            let codec = codec.map(|c| Codec::from(c) as u64);
+            let hash_prefix = hash_prefix
+                .as_ref()
+                .map(hex::decode)
+                .transpose()
+                .context("invalid hash prefix, expected hex")?;
+            let mut blocks = api.list_blocks(codec, hash_prefix, *after, *limit).await?;
+            while let Some(block) = blocks.next().await {
+                let (cid, size) = block?;
+                println!("{cid} {size}");
+            }
+        }
+    };
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use crate::doc;
+use crate::services::require_services;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use iroh_api::{Api, CarExportOptions, CarImportEvent, IpfsPath};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+#[derive(Args, Debug, Clone)]
+#[clap(about = "Import and export DAGs")]
+#[clap(after_help = doc::DAG_LONG_DESCRIPTION)]
+pub struct Dag {
+    #[clap(subcommand)]
+    command: DagCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DagCommands {
+    #[clap(about = "Import the blocks of a CAR file into the store")]
+    #[clap(after_help = doc::DAG_IMPORT_LONG_DESCRIPTION)]
+    Import {
+        /// Path to the CAR file
+        path: PathBuf,
+        /// Don't pin the roots of the CAR file
+        #[clap(long)]
+        no_pin: bool,
+    },
+    #[clap(about = "Export a DAG as a CAR file")]
+    #[clap(after_help = doc::DAG_EXPORT_LONG_DESCRIPTION)]
+    Export {
+        /// CID or path of the root of the DAG
+        path: IpfsPath,
+        /// Write the CAR file to this path instead of stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+        /// Write the blocks in depth-first order, each block only once
+        #[clap(long)]
+        deterministic: bool,
+        /// Only use blocks from the local store
+        #[clap(long)]
+        offline: bool,
+    },
+}
+
+pub async fn run_command(api: &Api, cmd: &Dag) -> Result<()> {
+    require_services(api, BTreeSet::from(["store"])).await?;
+    match &cmd.command {
+        DagCommands::Import { path, no_pin } => {
+            let file = tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("failed to open {}", path.display()))?;
+            let reader = tokio::io::BufReader::new(file);
+            let mut events = api.import_car(reader, !*no_pin).await?;
+
+            let pb = ProgressBar::new_spinner();
+            let mut blocks = 0;
+            let mut roots = Vec::new();
+            while let Some(event) = events.next().await {
+                match event? {
+                    CarImportEvent::Block(_) => {
+                        blocks += 1;
+                        pb.set_message(format!("Imported {blocks} blocks"));
+                        pb.inc(1);
+                    }
+                    CarImportEvent::Pinned(root) => roots.push(root),
+                }
+            }
+            pb.finish_and_clear();
+
+            println!("Imported {blocks} blocks");
+            for root in roots {
+                println!("Pinned root {root}");
+            }
+        }
+        DagCommands::Export {
+            path,
+            output,
+            deterministic,
+            offline,
+        } => {
+            let options = CarExportOptions {
+                deterministic: *deterministic,
+                offline: *offline,
+            };
+            match output {
+                Some(output) => {
+                    let file = tokio::fs::File::create(output)
+                        .await
+                        .with_context(|| format!("failed to create {}", output.display()))?;
+                    let blocks = api.export_car(path, file, options).await?;
+                    println!("Exported {blocks} blocks to {}", output.display());
+                }
+                None => {
+                    api.export_car(path, tokio::io::stdout(), options).await?;
+                }
+            }
+        }
+    };
+    Ok(())
+}
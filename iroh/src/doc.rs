@@ -10,9 +10,6 @@ of the protocol.
 For more info see https://iroh.computer/docs";
 
 pub const ADD_LONG_DESCRIPTION: &str = "
-WARNING 1: IROH CURRENTLY PROVIDES NO WAY TO REMOVE CONTENT ONCE ADDED. 
-This will be addressed in a future release.
-
 Add copies the file or directory specified by <PATH> into the iroh store,
 splitting the input file into a tree of immutable blocks. Each block is labeled
 by the hash of its content. The final output of the add command is the hash of
@@ -49,13 +46,101 @@ hosted at https://gateway.lol to fetch the content from our local node:
 
   > curl https://gateway.lol/ipfs/bafybeihjgu5w6wbbxqevdgccj5xm453dbzpkwmkyoepvs3vh6wft4uvf2q/cat.jpg
 
+Added content is pinned recursively, which protects it from being removed by
+'iroh gc'. Use the --no-pin flag to skip pinning, and 'iroh pin rm' to unpin
+content later on.
+
+File permissions and modification times are not stored by default, so the same
+content always results in the same CID. Use the --preserve-mode and
+--preserve-mtime flags to store them, 'iroh get' restores them when present.
+
+Files are stored as balanced trees of blocks by default. Use '--layout trickle'
+for the trickle layout, which matches 'ipfs add --trickle' in kubo and suits
+content that is appended to or streamed, like logs and media.
+
+The --cid-version and --hash flags select the version and hash function of the
+CIDs, CIDv1 with sha2-256 by default. File chunks are stored as raw blocks, unless
+--raw-leaves=false is given or the CID version is 0 and --raw-leaves is not set.
+Raw leaves always get a CIDv1, as CIDv0 can only address dag-pb blocks.
+
+With --inline, blocks of up to --inline-limit bytes (32 by default) are inlined
+into their CIDs using the identity hash function. Tiny files and directories
+then need no block of their own, their content is read directly from the CID.
+
 Implementation Interop:
-Iroh does *not* produce the same hashes as other IPFS implementations when given
-the same data. Iroh & other valid implementations can read each other's data,
-but if given the same file, Iroh will almost certianly produce a different hash
-value.
+Iroh chunks files the same way kubo does, but the defaults differ: iroh wraps
+added content in a directory and uses CIDv1 with raw leaves, while kubo adds
+content unwrapped with CIDv0. To get the CIDs of 'ipfs add' use:
+
+  > iroh add --no-wrap --cid-version 0 file.txt
+
+and to get the CIDs of the iroh defaults from kubo use:
+
+  > ipfs add --wrap-with-directory --cid-version 1 file.txt
+
+The same flags apply to directories added with -r, including directories large
+enough to be sharded into a HAMT.
 ";
 
+pub const BLOCK_LONG_DESCRIPTION: &str = "
+block commands inspect the raw blocks held by the iroh store. Every piece of
+content in iroh is split into blocks, each labeled by the CID of its content.";
+
+pub const BLOCK_LS_LONG_DESCRIPTION: &str = "
+Lists the blocks in the iroh store together with their size in bytes, ordered
+by the multihash of each block. Blocks can be filtered by their codec or by a
+hex encoded prefix of their multihash:
+
+  > iroh block ls --codec raw --limit 2
+  bafkreiaa2q3zw3jnl7oi7nttzmu5jvlhl5n7ppwfjdnzbmgubqbkmjxq3u 262144
+  bafkreiabdiieeuzoklwuzuw7ftwvsy3tqrq6ygcbmsifwwijcowm7qpyqy 262144
+
+Pass the last listed CID to --after to continue listing from there.";
+
+pub const DAG_LONG_DESCRIPTION: &str = "
+dag commands move whole DAGs in and out of the iroh store as CAR (Content
+Addressable aRchive) files, the format kubo uses for 'ipfs dag import' and
+'ipfs dag export'.";
+
+pub const DAG_IMPORT_LONG_DESCRIPTION: &str = "
+Imports all blocks of a CARv1 or CARv2 file into the iroh store. The file is
+read as a stream, so it can be larger than the available memory. The hash of
+every block is checked against its CID, and the import fails on the first block
+that does not match.
+
+The roots listed in the header of the CAR file are pinned recursively once all
+blocks are imported, unless --no-pin is given:
+
+  > iroh dag import wikipedia.car
+  Imported 2456 blocks
+  Pinned root bafybeiaysi4s6lnjev27ln5icwm6tueaw2vdykrtjkwiphwekaywqhcjze";
+
+pub const DAG_EXPORT_LONG_DESCRIPTION: &str = "
+Exports the DAG below a CID or path as a CAR file, with the resolved CID as its
+only root. The CAR file is written to stdout, unless --output is given:
+
+  > iroh dag export bafybeiaysi4s6lnjev27ln5icwm6tueaw2vdykrtjkwiphwekaywqhcjze > wikipedia.car
+
+Missing blocks are fetched from the network, just like with 'iroh get'. With
+--offline only blocks from the local store are used, and the export fails if the
+DAG is not complete locally.
+
+By default blocks are written in the order they are fetched, which can differ
+between runs. --deterministic walks the DAG depth-first and writes every block
+only once, so exporting the same DAG always yields the same file.";
+
+pub const GC_LONG_DESCRIPTION: &str = "
+gc runs garbage collection on the iroh store, removing every block which is not
+pinned. Content is pinned when it was added with 'iroh add' or 'iroh pin add',
+or when it is part of a recursively pinned DAG. Everything else, e.g. content
+fetched from the network by 'iroh get' or through the gateway, is removed:
+
+  > iroh gc
+  Removed 1024 blocks, freeing 262.14 MB
+
+The store can also run garbage collection automatically once the stored data
+grows beyond a watermark, see the 'gc' section of the store configuration.";
+
 pub const START_LONG_DESCRIPTION: &str = "
 Iroh start kicks off 'daemons' on your local machine: long-running processes 
 that make iroh work. Iroh requires a running daemon to do anything meaningful 
@@ -146,6 +231,26 @@ to the given path's CID.
 If <ipfs-path> is already present in the iroh store, no network call will
 be made.";
 
+pub const NAME_LONG_DESCRIPTION: &str = "
+name commands manage IPNS names. An IPNS name is a mutable pointer to content,
+named after the public key which signs its records. Publishing under the same
+name again updates the pointer, while the name itself stays the same. IPNS names
+can be used anywhere an IPFS path is accepted:
+
+  > iroh get /ipns/12D3KooWJHxkQKX8C5KAyqEPhn2ssT2in4TExyG9SXxi519tycL9";
+
+pub const NAME_PUBLISH_LONG_DESCRIPTION: &str = "
+Publishes <CID> under an IPNS name by signing a record with a key from the
+node's keychain and putting it into the distributed hash table (DHT). By default
+the node's identity key is used, so the name is the node's peer ID. Use --key to
+publish with another key from the keychain, e.g. 'id_ed25519_1':
+
+  > iroh name publish bafybeihjgu5w6wbbxqevdgccj5xm453dbzpkwmkyoepvs3vh6wft4uvf2q
+  published to /ipns/12D3KooWJHxkQKX8C5KAyqEPhn2ssT2in4TExyG9SXxi519tycL9: /ipfs/bafybeihjgu5w6wbbxqevdgccj5xm453dbzpkwmkyoepvs3vh6wft4uvf2q
+
+Records expire after 48 hours. The p2p service republishes all names it
+published every 4 hours for as long as it is running.";
+
 pub const P2P_CONNECT_LONG_DESCRIPTION: &str = "
 Attempts to open a new direct connection to a peer address. By default p2p
 continulously maintains an open set of peer connections based on requests &
@@ -199,3 +304,43 @@ for additional details about the peer. For example:
 
 For more info on multiaddrs see https://iroh.computer/docs/concepts#multiaddr.
 ";
+
+pub const PIN_LONG_DESCRIPTION: &str = "
+pin commands manage the pin set of the iroh store. Pinned content is protected
+from being removed from the store. There are three kinds of pins:
+
+  direct     - only the pinned block itself is protected
+  recursive  - the pinned block and every block it links to are protected
+  indirect   - the block is protected because it is part of a recursively
+               pinned DAG
+
+Only direct and recursive pins can be added & removed, indirect pins follow
+from the recursive pins.";
+
+pub const PIN_ADD_LONG_DESCRIPTION: &str = "
+Pins the content identified by <CID> in the iroh store. By default the pin is
+recursive: the whole DAG below <CID> is fetched, from the network if necessary,
+and protected. Use the --direct flag to only fetch & pin the root block:
+
+  > iroh pin add bafybeihjgu5w6wbbxqevdgccj5xm453dbzpkwmkyoepvs3vh6wft4uvf2q
+  pinned bafybeihjgu5w6wbbxqevdgccj5xm453dbzpkwmkyoepvs3vh6wft4uvf2q recursively
+
+Pinning an already pinned block recursively turns a direct pin into a
+recursive one. A recursive pin can not be turned into a direct pin, remove it
+first with 'iroh pin rm'.";
+
+pub const PIN_RM_LONG_DESCRIPTION: &str = "
+Removes the direct or recursive pin for <CID>. Removing a pin does not remove
+any content from the store right away, but the content is no longer protected.
+Indirect pins can not be removed, remove the recursive pin they belong to
+instead.";
+
+pub const PIN_LS_LONG_DESCRIPTION: &str = "
+Lists all pinned CIDs together with the type of their pin. Use the --type flag
+to only list direct, recursive or indirect pins:
+
+  > iroh pin ls --type recursive
+  bafybeihjgu5w6wbbxqevdgccj5xm453dbzpkwmkyoepvs3vh6wft4uvf2q recursive
+
+Listing indirect pins walks all recursively pinned DAGs and can take a while
+for large pin sets.";
@@ -1,7 +1,11 @@
+pub mod block;
 mod config;
+pub mod dag;
 pub mod doc;
 pub mod metrics;
+pub mod name;
 pub mod p2p;
+pub mod pin;
 pub mod run;
 pub mod services;
 mod size;
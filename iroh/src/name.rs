@@ -0,0 +1,38 @@
+use crate::doc;
+use crate::services::require_services;
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use iroh_api::{Api, Cid};
+use std::collections::BTreeSet;
+
+#[derive(Args, Debug, Clone)]
+#[clap(about = "Publish IPNS names")]
+#[clap(after_help = doc::NAME_LONG_DESCRIPTION)]
+pub struct Name {
+    #[clap(subcommand)]
+    command: NameCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum NameCommands {
+    #[clap(about = "Publish content under an IPNS name")]
+    #[clap(after_help = doc::NAME_PUBLISH_LONG_DESCRIPTION)]
+    Publish {
+        /// CID of the content to publish
+        cid: Cid,
+        /// Name of the keychain key to publish with, defaults to the node's identity
+        #[clap(long)]
+        key: Option<String>,
+    },
+}
+
+pub async fn run_command(api: &Api, cmd: &Name) -> Result<()> {
+    require_services(api, BTreeSet::from(["p2p"])).await?;
+    match &cmd.command {
+        NameCommands::Publish { cid, key } => {
+            let name = api.p2p()?.publish_name(*cid, key.clone()).await?;
+            println!("published to /ipns/{name}: /ipfs/{cid}");
+        }
+    };
+    Ok(())
+}
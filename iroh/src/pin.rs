@@ -0,0 +1,80 @@
+use crate::doc;
+use crate::services::require_services;
+use anyhow::Result;
+use clap::{Args, Subcommand, ValueEnum};
+use iroh_api::{Api, Cid, PinType};
+use std::collections::BTreeSet;
+
+#[derive(Args, Debug, Clone)]
+#[clap(about = "Manage the pin set of the store")]
+#[clap(after_help = doc::PIN_LONG_DESCRIPTION)]
+pub struct Pin {
+    #[clap(subcommand)]
+    command: PinCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PinCommands {
+    #[clap(about = "Pin content in the local store")]
+    #[clap(after_help = doc::PIN_ADD_LONG_DESCRIPTION)]
+    Add {
+        /// CID of the content to pin
+        cid: Cid,
+        /// Only pin the block itself, not the blocks it links to
+        #[clap(long)]
+        direct: bool,
+    },
+    #[clap(about = "Remove a pin from the local store")]
+    #[clap(after_help = doc::PIN_RM_LONG_DESCRIPTION)]
+    Rm {
+        /// CID of the pinned content
+        cid: Cid,
+    },
+    #[clap(about = "List pinned content")]
+    #[clap(after_help = doc::PIN_LS_LONG_DESCRIPTION)]
+    Ls {
+        /// Only list pins of this type
+        #[clap(long = "type", value_enum)]
+        pin_type: Option<PinTypeArg>,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum PinTypeArg {
+    Direct,
+    Recursive,
+    Indirect,
+}
+
+impl From<PinTypeArg> for PinType {
+    fn from(arg: PinTypeArg) -> Self {
+        match arg {
+            PinTypeArg::Direct => PinType::Direct,
+            PinTypeArg::Recursive => PinType::Recursive,
+            PinTypeArg::Indirect => PinType::Indirect,
+        }
+    }
+}
+
+pub async fn run_command(api: &Api, cmd: &Pin) -> Result<()> {
+    require_services(api, BTreeSet::from(["store"])).await?;
+    match &cmd.command {
+        PinCommands::Add { cid, direct } => {
+            api.pin(*cid, !*direct).await?;
+            let mode = if *direct { "directly" } else { "recursively" };
+            println!("pinned {cid} {mode}");
+        }
+        PinCommands::Rm { cid } => {
+            api.unpin(*cid).await?;
+            println!("unpinned {cid}");
+        }
+        PinCommands::Ls { pin_type } => {
+            let mut pins = api.pins(pin_type.map(Into::into)).await?;
+            pins.sort();
+            for (cid, pin_type) in pins {
+                println!("{cid} {pin_type}");
+            }
+        }
+    };
+    Ok(())
+}
@@ -8,16 +8,21 @@ use crossterm::style::Stylize;
 use futures::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use iroh_api::{
-    Api, ChunkerConfig, IpfsPath, StatusType, UnixfsConfig, UnixfsEntry, DEFAULT_CHUNKS_SIZE,
+    parse_hash, Api, ChunkerConfig, CidVersion, IpfsPath, MultihashCode, StatusType, TreeBuilder,
+    UnixfsConfig, UnixfsEntry, DEFAULT_CHUNKS_SIZE, DEFAULT_INLINE_LIMIT,
 };
 use iroh_metrics::config::Config as MetricsConfig;
 use iroh_util::{human, iroh_config_path, make_config};
 
+use crate::block::{run_command as run_block_command, Block};
 use crate::config::{Config, CONFIG_FILE_NAME, ENV_PREFIX};
+use crate::dag::{run_command as run_dag_command, Dag};
 use crate::doc;
 #[cfg(feature = "testing")]
 use crate::fixture::get_fixture_api;
+use crate::name::{run_command as run_name_command, Name};
 use crate::p2p::{run_command as run_p2p_command, P2p};
+use crate::pin::{run_command as run_pin_command, Pin};
 use crate::services::require_services;
 use crate::size::size_stream;
 
@@ -37,7 +42,11 @@ pub struct Cli {
 
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
+    Block(Block),
+    Dag(Dag),
+    Name(Name),
     P2p(P2p),
+    Pin(Pin),
     #[clap(about = "Add a file or directory to iroh & make it available on IPFS")]
     #[clap(after_help = doc::ADD_LONG_DESCRIPTION )]
     Add {
@@ -52,10 +61,40 @@ enum Commands {
         /// Don't provide added content to the network
         #[clap(long)]
         offline: bool,
+        /// Don't pin added content, allowing garbage collection to remove it
+        #[clap(long)]
+        no_pin: bool,
         /// Select the chunker to use, when chunking data. Available chunkers are currently "fixed" and "rabin".
         #[clap(long, default_value_t = ChunkerConfig::Fixed(DEFAULT_CHUNKS_SIZE))]
         chunker: ChunkerConfig,
+        /// Select the layout of the DAG. Available layouts are currently "balanced" and "trickle".
+        #[clap(long, default_value_t = TreeBuilder::balanced_tree())]
+        layout: TreeBuilder,
+        /// Store the unix permission bits of the added files and directories
+        #[clap(long)]
+        preserve_mode: bool,
+        /// Store the modification times of the added files, directories and symlinks
+        #[clap(long)]
+        preserve_mtime: bool,
+        /// The CID version of the added blocks, 0 or 1
+        #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(0..=1))]
+        cid_version: u64,
+        /// The hash function used for the CIDs, e.g. "sha2-256", "sha2-512", "blake2b-256" or "blake3"
+        #[clap(long, default_value = "sha2-256", value_parser = parse_hash)]
+        hash: MultihashCode,
+        /// Store file chunks as raw blocks, with CIDv1 even for CID version 0. Defaults to true, unless the CID version is 0
+        #[clap(long, num_args = 0..=1, default_missing_value = "true")]
+        raw_leaves: Option<bool>,
+        /// Inline small blocks into their CIDs, using the identity hash function
+        #[clap(long)]
+        inline: bool,
+        /// Maximum size in bytes of the blocks to inline, at most 64
+        #[clap(long, default_value_t = DEFAULT_INLINE_LIMIT, requires = "inline")]
+        inline_limit: usize,
     },
+    #[clap(about = "Remove all unpinned content from the store")]
+    #[clap(after_help = doc::GC_LONG_DESCRIPTION )]
+    Gc {},
     #[clap(about = "Fetch IPFS content and write it to disk")]
     #[clap(after_help = doc::GET_LONG_DESCRIPTION )]
     Get {
@@ -134,9 +173,41 @@ impl Cli {
                 recursive,
                 no_wrap,
                 offline,
+                no_pin,
                 chunker,
+                layout,
+                preserve_mode,
+                preserve_mtime,
+                cid_version,
+                hash,
+                raw_leaves,
+                inline,
+                inline_limit,
             } => {
-                add(api, path, *no_wrap, *recursive, *chunker, !*offline).await?;
+                let cid_version = CidVersion::try_from(*cid_version)?;
+                let config = UnixfsConfig {
+                    wrap: !*no_wrap,
+                    chunker: Some(*chunker),
+                    layout: Some(*layout),
+                    preserve_mode: *preserve_mode,
+                    preserve_mtime: *preserve_mtime,
+                    cid_version,
+                    hash: *hash,
+                    raw_leaves: raw_leaves.unwrap_or(cid_version != CidVersion::V0),
+                    inline_limit: inline.then_some(*inline_limit),
+                };
+                add(api, path, *recursive, config, !*offline, !*no_pin).await?;
+            }
+            Commands::Block(block) => run_block_command(api, block).await?,
+            Commands::Dag(dag) => run_dag_command(api, dag).await?,
+            Commands::Gc {} => {
+                require_services(api, BTreeSet::from(["store"])).await?;
+                let stats = api.gc().await?;
+                println!(
+                    "Removed {} blocks, freeing {}",
+                    stats.blocks,
+                    human::format_bytes(stats.bytes)
+                );
             }
             Commands::Get {
                 ipfs_path: path,
@@ -147,7 +218,9 @@ impl Cli {
                     iroh_api::fs::write_get_stream(path, blocks, output.as_deref()).await?;
                 println!("Saving file(s) to {}", root_path.to_str().unwrap());
             }
+            Commands::Name(name) => run_name_command(api, name).await?,
             Commands::P2p(p2p) => run_p2p_command(&api.p2p()?, p2p).await?,
+            Commands::Pin(pin) => run_pin_command(api, pin).await?,
             Commands::Start { service, all } => {
                 let svc = match *all {
                     true => vec![
@@ -177,10 +250,10 @@ impl Cli {
 async fn add(
     api: &Api,
     path: &Path,
-    no_wrap: bool,
     recursive: bool,
-    chunker: ChunkerConfig,
+    config: UnixfsConfig,
     provide: bool,
+    pin: bool,
 ) -> Result<()> {
     if !path.exists() {
         anyhow::bail!("Path does not exist");
@@ -255,15 +328,14 @@ async fn add(
     // a while before it starts ending progress reports
     pb.inc(0);
 
-    let entry = UnixfsEntry::from_path(
-        path,
-        UnixfsConfig {
-            wrap: !no_wrap,
-            chunker: Some(chunker),
-        },
-    )
-    .await?;
-    let mut progress = api.add_stream(entry).await?;
+    let entry = UnixfsEntry::from_path(path, config).await?;
+    // pinning while adding keeps garbage collection from removing blocks before the root
+    // is pinned
+    let mut progress = if pin {
+        api.add_stream_pinned(entry).await?
+    } else {
+        api.add_stream(entry).await?
+    };
     let mut cids = Vec::new();
     while let Some(prog) = progress.next().await {
         let (cid, size) = prog?;